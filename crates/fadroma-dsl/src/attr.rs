@@ -1,4 +1,4 @@
-use syn::{Attribute, Meta, NestedMeta, MetaList, Ident, parse_quote};
+use syn::{Attribute, Meta, NestedMeta, MetaList, Ident, Lit, LitStr, Path, parse_quote};
 use proc_macro2::Span;
 
 use crate::err::ErrorSink;
@@ -15,14 +15,32 @@ pub const CONTRACT_ERR_VARIANT: &str = "Base";
 /// The [`ERROR_ENUM`] enum variant case that represents an error
 /// when trying to convert a query response to binary.
 pub const BINARY_SERIALIZE_ERR_VARIANT: &str = "QueryResponseSerialize";
+/// The [`ERROR_ENUM`] enum variant case that represents an error when
+/// the funds sent along with an execute message are not accepted by the method.
+pub const INVALID_FUNDS_ERR_VARIANT: &str = "InvalidFunds";
 
 pub const INIT_MSG: &str = "InstantiateMsg";
 pub const EXECUTE_MSG: &str = "ExecuteMsg";
 pub const QUERY_MSG: &str = "QueryMsg";
+pub const MIGRATE_MSG: &str = "MigrateMsg";
+pub const SUDO_MSG: &str = "SudoMsg";
 
 pub const INIT_FN: &str = "instantiate";
 pub const EXECUTE_FN: &str = "execute";
 pub const QUERY_FN: &str = "query";
+pub const MIGRATE_FN: &str = "migrate";
+pub const SUDO_FN: &str = "sudo";
+/// Name of the auto-generated function which returns the schema of the contract API.
+pub const API_SCHEMA_FN: &str = "api_schema";
+
+/// Name of the auto-generated struct used to call contracts
+/// that implement an interface.
+pub const CLIENT: &str = "Client";
+
+/// Name of the auto-generated struct used to call a contract
+/// instance in the ensemble testing environment.
+#[cfg(feature = "ensemble")]
+pub const ENSEMBLE_HANDLE: &str = "EnsembleHandle";
 
 /// Name of the associated type that represents the error type in an interface.
 pub const ERROR_TYPE: &str = "Error";
@@ -32,10 +50,25 @@ pub enum MsgAttr {
     Init { entry: Option<Entry> },
     Execute,
     Query,
+    Migrate,
+    Sudo,
     Reply,
     ExecuteGuard
 }
 
+/// Checks that the generated `execute` function performs
+/// before calling the method that they are declared on.
+#[derive(Clone, Debug)]
+pub enum ExecuteCheck {
+    /// `#[nonpayable]` - no funds can be sent.
+    NonPayable,
+    /// `#[payable]` or `#[payable(denom = "uscrt")]` - funds can be
+    /// sent but only in the given denomination, if one is specified.
+    Payable { denom: Option<LitStr> },
+    /// `#[guard(fn_name)]` - calls the given function which can return an error.
+    Guard(Path)
+}
+
 #[derive(Clone, Copy, Debug)]
 pub enum Entry {
     Functions,
@@ -51,10 +84,14 @@ impl MsgAttr {
     pub const INIT: &str = "init";
     pub const EXECUTE: &str = "execute";
     pub const QUERY: &str = "query";
+    pub const MIGRATE: &str = "migrate";
+    pub const SUDO: &str = "sudo";
     pub const REPLY: &str = "reply";
     pub const EXECUTE_GUARD: &str = "execute_guard";
 
     pub fn parse(sink: &mut ErrorSink, attrs: &[Attribute]) -> Option<Self> {
+        let mut result: Option<Self> = None;
+
         for attr in attrs {
             if let Some(ident) = attr.path.get_ident() {
                 let meta = match attr.parse_meta() {
@@ -88,6 +125,16 @@ impl MsgAttr {
 
                         Some(Self::Query)
                     },
+                    Self::MIGRATE => {
+                        assert_is_path_ident(sink, &meta);
+
+                        Some(Self::Migrate)
+                    },
+                    Self::SUDO => {
+                        assert_is_path_ident(sink, &meta);
+
+                        Some(Self::Sudo)
+                    },
                     Self::EXECUTE_GUARD => {
                         assert_is_path_ident(sink, &meta);
 
@@ -101,13 +148,26 @@ impl MsgAttr {
                     _ => None
                 };
 
-                if instance.is_some() {
-                    return instance;
+                let Some(instance) = instance else {
+                    continue;
+                };
+
+                if let Some(first) = result {
+                    sink.push_spanned(
+                        attr,
+                        format!(
+                            "Only one of \"#[{}]\" or \"#[{}]\" can be used per method.",
+                            first.as_str(),
+                            instance.as_str()
+                        )
+                    );
+                } else {
+                    result = Some(instance);
                 }
             }
         }
     
-        None
+        result
     }
 
     #[inline]
@@ -116,12 +176,137 @@ impl MsgAttr {
             MsgAttr::Init { .. } => Self::INIT,
             MsgAttr::Execute => Self::EXECUTE,
             MsgAttr::Query => Self::QUERY,
+            MsgAttr::Migrate => Self::MIGRATE,
+            MsgAttr::Sudo => Self::SUDO,
             MsgAttr::Reply => Self::REPLY,
             MsgAttr::ExecuteGuard => Self::EXECUTE_GUARD
         }
     }
 }
 
+impl ExecuteCheck {
+    pub const NONPAYABLE: &str = "nonpayable";
+    pub const PAYABLE: &str = "payable";
+    pub const GUARD: &str = "guard";
+    /// Used as a meta tag in the `#[payable(denom = "uscrt")]` attribute.
+    pub const DENOM_META: &str = "denom";
+
+    pub fn parse(sink: &mut ErrorSink, attrs: &[Attribute]) -> Vec<Self> {
+        let mut result = vec![];
+
+        for attr in attrs.iter().filter(|x| Self::is_check_attr(x)) {
+            let meta = match attr.parse_meta() {
+                Ok(meta) => meta,
+                Err(err) => {
+                    sink.push_err(err);
+
+                    continue;
+                }
+            };
+
+            let check = match attr.path.get_ident().unwrap().to_string().as_str() {
+                Self::NONPAYABLE => {
+                    assert_is_path_ident(sink, &meta);
+
+                    Self::NonPayable
+                }
+                Self::PAYABLE => match meta {
+                    Meta::List(list) => Self::Payable {
+                        denom: validate_denom_meta(sink, &list)
+                    },
+                    meta => {
+                        assert_is_path_ident(sink, &meta);
+
+                        Self::Payable { denom: None }
+                    }
+                }
+                Self::GUARD => match meta {
+                    Meta::List(list) if list.nested.len() == 1 => {
+                        if let NestedMeta::Meta(Meta::Path(path)) = &list.nested[0] {
+                            Self::Guard(path.clone())
+                        } else {
+                            sink.push_spanned(list, "Expecting the path to a guard function.");
+
+                            continue;
+                        }
+                    }
+                    meta => {
+                        sink.push_spanned(meta, "Expecting the path to a guard function.");
+
+                        continue;
+                    }
+                }
+                _ => unreachable!()
+            };
+
+            let is_payment_check = check.is_payment_check();
+
+            if is_payment_check && result.iter().any(Self::is_payment_check) {
+                sink.push_spanned(
+                    attr,
+                    format!(
+                        "Only one of \"{}\" or \"{}\" can be used per method.",
+                        Self::NONPAYABLE,
+                        Self::PAYABLE
+                    )
+                );
+
+                continue;
+            }
+
+            result.push(check);
+        }
+
+        result
+    }
+
+    /// Whether the attribute is one of the execute checks. These are
+    /// removed from the methods by the `#[contract]` macro after parsing.
+    #[inline]
+    pub fn is_check_attr(attr: &Attribute) -> bool {
+        matches!(
+            attr.path.get_ident(),
+            Some(ident) if [Self::NONPAYABLE, Self::PAYABLE, Self::GUARD]
+                .contains(&ident.to_string().as_str())
+        )
+    }
+
+    /// Whether the check concerns the funds sent along with the message.
+    #[inline]
+    pub fn is_payment_check(&self) -> bool {
+        matches!(self, Self::NonPayable | Self::Payable { .. })
+    }
+
+    /// Whether the check can fail with the
+    /// [`INVALID_FUNDS_ERR_VARIANT`] error.
+    #[inline]
+    pub fn rejects_funds(&self) -> bool {
+        matches!(self, Self::NonPayable | Self::Payable { denom: Some(_) })
+    }
+}
+
+fn validate_denom_meta(sink: &mut ErrorSink, list: &MetaList) -> Option<LitStr> {
+    if list.nested.len() == 1 {
+        if let NestedMeta::Meta(Meta::NameValue(name_value)) = &list.nested[0] {
+            if let Lit::Str(denom) = &name_value.lit {
+                if name_value.path.is_ident(ExecuteCheck::DENOM_META) {
+                    return Some(denom.clone());
+                }
+            }
+        }
+    }
+
+    sink.push_spanned(
+        list,
+        format!(
+            "Expecting a single \"{} = \"...\"\" nested meta.",
+            ExecuteCheck::DENOM_META
+        )
+    );
+
+    None
+}
+
 fn validate_entry_meta(sink: &mut ErrorSink, list: &MetaList) -> Option<Entry> {
     if list.nested.len() == 1 {
         let entry = Ident::new(MsgAttr::ENTRY_META, Span::call_site());
@@ -57,7 +57,7 @@ impl AutoImpl {
                     let stmt = match attr {
                         MsgAttr::Init { .. } | MsgAttr::Execute => 
                             parse_quote!(<#impl_path as #trait_>::#fn_name(deps, env, info, #args)),
-                        MsgAttr::Query =>
+                        MsgAttr::Query | MsgAttr::Migrate | MsgAttr::Sudo =>
                             parse_quote!(<#impl_path as #trait_>::#fn_name(deps, env, #args)),
                         _ => unreachable!("{} should not be supported in interfaces.", attr.as_str())
                     };
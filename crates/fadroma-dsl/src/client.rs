@@ -0,0 +1,147 @@
+use syn::{ItemImpl, ItemStruct, Ident, ImplItemMethod, parse_quote};
+use proc_macro2::Span;
+
+use crate::{
+    err::ErrorSink,
+    attr::{CLIENT, EXECUTE_MSG, QUERY_MSG},
+    method::{Method, msg_args},
+    utils::to_pascal
+};
+
+/// Parameter names that the generated query methods have and
+/// as such cannot be used as names for message arguments.
+const RESERVED_ARGS: [&str; 1] = ["querier"];
+
+pub struct Client {
+    pub client_struct: ItemStruct,
+    pub client_impl: ItemImpl,
+    pub from_impl: ItemImpl
+}
+
+/// Creates a struct which wraps a `ContractLink<Addr>` and has a method
+/// for each execute message that returns a `CosmosMsg` and a method for
+/// each query message that returns the typed response.
+pub fn client(
+    sink: &mut ErrorSink,
+    execute: &[Method<'_>],
+    query: &[Method<'_>]
+) -> Client {
+    let client = Ident::new(CLIENT, Span::call_site());
+
+    let client_struct: ItemStruct = parse_quote! {
+        /// Calls a contract that implements the interface from another contract.
+        #[derive(Clone, Debug)]
+        pub struct #client {
+            pub link: fadroma::prelude::ContractLink<cosmwasm_std::Addr>,
+            /// Funds sent with each execute message.
+            pub funds: Vec<cosmwasm_std::Coin>,
+            /// The block size that execute messages are padded to.
+            /// No padding is applied if set to `None`.
+            pub block_size: Option<usize>
+        }
+    };
+
+    let mut client_impl: ItemImpl = parse_quote! {
+        impl #client {
+            #[inline]
+            pub fn new(link: fadroma::prelude::ContractLink<cosmwasm_std::Addr>) -> Self {
+                Self {
+                    link,
+                    funds: vec![],
+                    block_size: Some(fadroma::prelude::BLOCK_SIZE)
+                }
+            }
+
+            #[inline]
+            pub fn with_funds(mut self, funds: Vec<cosmwasm_std::Coin>) -> Self {
+                self.funds = funds;
+
+                self
+            }
+
+            #[inline]
+            pub fn with_block_size(mut self, block_size: Option<usize>) -> Self {
+                self.block_size = block_size;
+
+                self
+            }
+
+            fn cosmos_msg(
+                &self,
+                msg: &impl serde::Serialize
+            ) -> cosmwasm_std::StdResult<cosmwasm_std::CosmosMsg> {
+                let mut msg = cosmwasm_std::to_binary(msg)?;
+
+                if let Some(block_size) = self.block_size {
+                    fadroma::prelude::space_pad(&mut msg.0, block_size);
+                }
+
+                Ok(cosmwasm_std::WasmMsg::Execute {
+                    contract_addr: self.link.address.to_string(),
+                    code_hash: self.link.code_hash.clone(),
+                    msg,
+                    funds: self.funds.clone()
+                }.into())
+            }
+        }
+    };
+
+    let execute_msg = Ident::new(EXECUTE_MSG, Span::call_site());
+
+    for method in execute {
+        let (params, fields) = msg_args(sink, method, &RESERVED_ARGS, CLIENT);
+        let name = &method.sig().ident;
+        let variant = Ident::new(&to_pascal(&name.to_string()), Span::call_site());
+
+        let item: ImplItemMethod = parse_quote! {
+            pub fn #name(
+                &self,
+                #params
+            ) -> cosmwasm_std::StdResult<cosmwasm_std::CosmosMsg> {
+                self.cosmos_msg(&#execute_msg::#variant { #fields })
+            }
+        };
+
+        client_impl.items.push(item.into());
+    }
+
+    let query_msg = Ident::new(QUERY_MSG, Span::call_site());
+
+    for method in query {
+        let (params, fields) = msg_args(sink, method, &RESERVED_ARGS, CLIENT);
+        let name = &method.sig().ident;
+        let variant = Ident::new(&to_pascal(&name.to_string()), Span::call_site());
+        let response = method.return_ty().value;
+
+        let item: ImplItemMethod = parse_quote! {
+            pub fn #name(
+                &self,
+                querier: cosmwasm_std::QuerierWrapper,
+                #params
+            ) -> cosmwasm_std::StdResult<#response> {
+                querier.query_wasm_smart(
+                    self.link.code_hash.clone(),
+                    self.link.address.to_string(),
+                    &#query_msg::#variant { #fields }
+                )
+            }
+        };
+
+        client_impl.items.push(item.into());
+    }
+
+    let from_impl: ItemImpl = parse_quote! {
+        impl From<fadroma::prelude::ContractLink<cosmwasm_std::Addr>> for #client {
+            #[inline]
+            fn from(link: fadroma::prelude::ContractLink<cosmwasm_std::Addr>) -> Self {
+                Self::new(link)
+            }
+        }
+    };
+
+    Client {
+        client_struct,
+        client_impl,
+        from_impl
+    }
+}
@@ -1,5 +1,5 @@
 use syn::{
-    Item, ItemMod, ItemImpl, Type, TypePath,
+    Item, ItemMod, ItemImpl, ImplItem, Type, TypePath,
     Ident, ItemStruct, ItemEnum, ItemFn,
     GenericArgument, parse_quote
 };
@@ -7,11 +7,14 @@ use quote::quote;
 use proc_macro2::Span;
 
 use crate::{
-    attr::{MsgAttr, Entry, CONTRACT},
+    attr::{MsgAttr, Entry, ExecuteCheck, CONTRACT},
     err::{ErrorSink, CompileErrors},
     generate::{self, MsgType, ErrorEnum},
+    schema,
     method::{Method, item_impl_methods}
 };
+#[cfg(feature = "ensemble")]
+use crate::ensemble;
 
 pub fn derive(mut item_mod: ItemMod) -> Result<proc_macro2::TokenStream, CompileErrors> {
     let Some((_, items)) = &mut item_mod.content else {
@@ -37,14 +40,57 @@ pub fn derive(mut item_mod: ItemMod) -> Result<proc_macro2::TokenStream, Compile
         items.push(Item::Struct(i.init_msg));
         items.push(Item::Enum(i.execute_msg));
         items.push(Item::Enum(i.query_msg));
+
+        if let Some(migrate_msg) = i.migrate_msg {
+            items.push(Item::Enum(migrate_msg));
+        }
+
+        if let Some(sudo_msg) = i.sudo_msg {
+            items.push(Item::Enum(sudo_msg));
+        }
     
         items.push(Item::Fn(i.entry.init));
         items.push(Item::Fn(i.entry.execute));
         items.push(Item::Fn(i.entry.query));
 
+        if let Some(migrate) = i.entry.migrate {
+            items.push(Item::Fn(migrate));
+        }
+
+        if let Some(sudo) = i.entry.sudo {
+            items.push(Item::Fn(sudo));
+        }
+
         if let Some(wasm) = i.entry.wasm_ffi {
             items.push(Item::Mod(wasm));
         }
+
+        items.push(Item::Fn(i.api_schema));
+
+        #[cfg(feature = "ensemble")]
+        {
+            items.push(Item::Impl(i.ensemble.harness));
+            items.push(Item::Struct(i.ensemble.handle_struct));
+            items.push(Item::Impl(i.ensemble.handle_impl));
+        }
+    }
+
+    // The execute check attributes are only markers for this
+    // macro and so they are removed once they have been parsed.
+    for item in items.iter_mut() {
+        let Item::Impl(item) = item else {
+            continue;
+        };
+
+        if !is_contract_impl(item) {
+            continue;
+        }
+
+        for item in item.items.iter_mut() {
+            if let ImplItem::Method(method) = item {
+                method.attrs.retain(|x| !ExecuteCheck::is_check_attr(x));
+            }
+        }
     }
 
     sink.check()?;
@@ -66,16 +112,30 @@ struct Interfaces {
     init_msg: ItemStruct,
     execute_msg: ItemEnum,
     query_msg: ItemEnum,
-    entry: Entrypoints
+    migrate_msg: Option<ItemEnum>,
+    sudo_msg: Option<ItemEnum>,
+    entry: Entrypoints,
+    api_schema: ItemFn,
+    #[cfg(feature = "ensemble")]
+    ensemble: Ensemble
 }
 
 struct Entrypoints {
     init: ItemFn,
     execute: ItemFn,
     query: ItemFn,
+    migrate: Option<ItemFn>,
+    sudo: Option<ItemFn>,
     wasm_ffi: Option<ItemMod>
 }
 
+#[cfg(feature = "ensemble")]
+struct Ensemble {
+    harness: ItemImpl,
+    handle_struct: ItemStruct,
+    handle_impl: ItemImpl
+}
+
 struct Boilerplate {
     contract_struct: ItemStruct,
     error_enum: ErrorEnum
@@ -115,6 +175,8 @@ impl<'a> Contract<'a> {
         let mut init: Option<Method> = None;
         let mut execute: Vec<Method> = vec![];
         let mut query: Vec<Method> = vec![];
+        let mut migrate: Vec<Method> = vec![];
+        let mut sudo: Vec<Method> = vec![];
         let mut reply: Option<Method> = None;
         let mut execute_guard: Option<Method> = None;
         let mut contract_err_ty: Option<GenericArgument> = None;
@@ -156,6 +218,8 @@ impl<'a> Contract<'a> {
                     }
                     MsgAttr::Execute => execute.push(method),
                     MsgAttr::Query => query.push(method),
+                    MsgAttr::Migrate => migrate.push(method),
+                    MsgAttr::Sudo => sudo.push(method),
                     MsgAttr::Reply => {
                         if reply.is_some() {
                             sink.duplicate_annotation(&contract_impl.self_ty, ty);
@@ -195,6 +259,8 @@ impl<'a> Contract<'a> {
                     }
                     MsgAttr::Execute => execute.push(method),
                     MsgAttr::Query => query.push(method),
+                    MsgAttr::Migrate => migrate.push(method),
+                    MsgAttr::Sudo => sudo.push(method),
                     unsupported => sink.unsupported_interface_attr(
                         &method.sig().ident,
                         unsupported
@@ -203,6 +269,10 @@ impl<'a> Contract<'a> {
             }
         }
 
+        let has_invalid_funds = init.is_some() && execute.iter().any(|x|
+            x.checks().iter().any(ExecuteCheck::rejects_funds)
+        );
+
         let interfaces = if let Some(init) = init {
             let entry = Entrypoints {
                 init: generate::init_fn(
@@ -218,15 +288,41 @@ impl<'a> Contract<'a> {
                     sink,
                     &query
                 ),
+                migrate: (!migrate.is_empty()).then(||
+                    generate::migrate_fn(sink, &migrate)
+                ),
+                sudo: (!sudo.is_empty()).then(||
+                    generate::sudo_fn(sink, &sudo)
+                ),
                 wasm_ffi: if matches!(
                     init.ty(),
                     MsgAttr::Init { entry } if matches!(entry, Some(Entry::Wasm))
                 ) {
-                    Some(generate::wasm_entry(&reply))
+                    Some(generate::wasm_entry(
+                        &reply,
+                        !migrate.is_empty(),
+                        !sudo.is_empty()
+                    ))
                 } else {
                     None
                 }
             };
+
+            #[cfg(feature = "ensemble")]
+            let ensemble = {
+                let (handle_struct, handle_impl) = ensemble::handle(
+                    sink,
+                    &init,
+                    &execute,
+                    &query
+                );
+
+                Ensemble {
+                    harness: ensemble::harness_impl(&reply, !migrate.is_empty()),
+                    handle_struct,
+                    handle_impl
+                }
+            };
     
             Some(Interfaces {
                 init_msg: generate::init_msg(sink, &init),
@@ -240,7 +336,20 @@ impl<'a> Contract<'a> {
                     MsgType::Query,
                     &query
                 ),
-                entry
+                migrate_msg: (!migrate.is_empty()).then(||
+                    generate::messages(sink, MsgType::Migrate, &migrate)
+                ),
+                sudo_msg: (!sudo.is_empty()).then(||
+                    generate::messages(sink, MsgType::Sudo, &sudo)
+                ),
+                entry,
+                api_schema: schema::api_schema_fn(
+                    &query,
+                    !migrate.is_empty(),
+                    !sudo.is_empty()
+                ),
+                #[cfg(feature = "ensemble")]
+                ensemble
             })
         } else {
             if let Some(guard) = execute_guard {
@@ -251,12 +360,24 @@ impl<'a> Contract<'a> {
                 sink.attr_no_effect(reply.sig(), reply.ty());
             }
 
+            for method in execute.iter().filter(|x| !x.checks().is_empty()) {
+                sink.push_spanned(
+                    method.sig(),
+                    "Execute checks have no effect when no entry point is defined. Either remove them or set an entry point for the contract."
+                );
+            }
+
             None
         };
 
         let boilerplate = Boilerplate {
             contract_struct: create_contract_struct(),
-            error_enum: generate::error_enum(sink, contract_err_ty, &self.interfaces)
+            error_enum: generate::error_enum(
+                sink,
+                contract_err_ty,
+                &self.interfaces,
+                has_invalid_funds
+            )
         };
 
         Generated {
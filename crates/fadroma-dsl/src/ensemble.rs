@@ -0,0 +1,198 @@
+use syn::{ItemImpl, ItemStruct, Ident, ImplItemMethod, parse_quote};
+use proc_macro2::Span;
+
+use crate::{
+    err::ErrorSink,
+    attr::{
+        CONTRACT, ENSEMBLE_HANDLE, INIT_FN, EXECUTE_FN,
+        QUERY_FN, MIGRATE_FN, INIT_MSG, EXECUTE_MSG, QUERY_MSG
+    },
+    method::{Method, msg_args},
+    utils::to_pascal
+};
+
+/// Parameter names that the generated handle methods of each message
+/// kind have and as such cannot be used as names for message arguments.
+const RESERVED_INIT_ARGS: [&str; 3] = ["ensemble", "code_id", "env"];
+const RESERVED_EXECUTE_ARGS: [&str; 3] = ["ensemble", "sender", "funds"];
+const RESERVED_QUERY_ARGS: [&str; 1] = ["ensemble"];
+
+/// Implements `fadroma_ensemble::ContractHarness` for the `Contract`
+/// struct using the generated entry point functions.
+///
+/// Like the handle, the impl is only compiled when the crate which uses
+/// `#[contract]` enables its own `ensemble` feature, so that only crates
+/// which opt in have to depend on `fadroma_ensemble`.
+pub fn harness_impl(
+    reply: &Option<Method<'_>>,
+    has_migrate: bool
+) -> ItemImpl {
+    let contract = Ident::new(CONTRACT, Span::call_site());
+    let init_fn = Ident::new(INIT_FN, Span::call_site());
+    let execute_fn = Ident::new(EXECUTE_FN, Span::call_site());
+    let query_fn = Ident::new(QUERY_FN, Span::call_site());
+
+    let mut result: ItemImpl = parse_quote! {
+        #[cfg(all(feature = "ensemble", not(target_arch = "wasm32")))]
+        impl fadroma_ensemble::ContractHarness for #contract {
+            fn instantiate(
+                &self,
+                deps: cosmwasm_std::DepsMut,
+                env: cosmwasm_std::Env,
+                info: cosmwasm_std::MessageInfo,
+                msg: cosmwasm_std::Binary
+            ) -> fadroma_ensemble::AnyResult<cosmwasm_std::Response> {
+                Ok(#init_fn(deps, env, info, cosmwasm_std::from_binary(&msg)?)?)
+            }
+
+            fn execute(
+                &self,
+                deps: cosmwasm_std::DepsMut,
+                env: cosmwasm_std::Env,
+                info: cosmwasm_std::MessageInfo,
+                msg: cosmwasm_std::Binary
+            ) -> fadroma_ensemble::AnyResult<cosmwasm_std::Response> {
+                Ok(#execute_fn(deps, env, info, cosmwasm_std::from_binary(&msg)?)?)
+            }
+
+            fn query(
+                &self,
+                deps: cosmwasm_std::Deps,
+                env: cosmwasm_std::Env,
+                msg: cosmwasm_std::Binary
+            ) -> fadroma_ensemble::AnyResult<cosmwasm_std::Binary> {
+                Ok(#query_fn(deps, env, cosmwasm_std::from_binary(&msg)?)?)
+            }
+        }
+    };
+
+    if let Some(reply) = reply {
+        let reply_fn = &reply.sig().ident;
+
+        result.items.push(parse_quote! {
+            fn reply(
+                &self,
+                deps: cosmwasm_std::DepsMut,
+                env: cosmwasm_std::Env,
+                reply: cosmwasm_std::Reply
+            ) -> fadroma_ensemble::AnyResult<cosmwasm_std::Response> {
+                Ok(#contract::#reply_fn(deps, env, reply)?)
+            }
+        });
+    }
+
+    if has_migrate {
+        let migrate_fn = Ident::new(MIGRATE_FN, Span::call_site());
+
+        result.items.push(parse_quote! {
+            fn migrate(
+                &self,
+                deps: cosmwasm_std::DepsMut,
+                env: cosmwasm_std::Env,
+                msg: cosmwasm_std::Binary
+            ) -> fadroma_ensemble::AnyResult<cosmwasm_std::Response> {
+                Ok(#migrate_fn(deps, env, cosmwasm_std::from_binary(&msg)?)?)
+            }
+        });
+    }
+
+    result
+}
+
+/// Creates the handle struct which is bound to the address of a contract
+/// instance and has a method for each execute and query message.
+pub fn handle(
+    sink: &mut ErrorSink,
+    init: &Method<'_>,
+    execute: &[Method<'_>],
+    query: &[Method<'_>]
+) -> (ItemStruct, ItemImpl) {
+    let handle = Ident::new(ENSEMBLE_HANDLE, Span::call_site());
+
+    let def: ItemStruct = parse_quote! {
+        /// Calls the contract instance at `address` in a
+        /// `fadroma_ensemble::ContractEnsemble` using typed methods.
+        #[cfg(all(feature = "ensemble", not(target_arch = "wasm32")))]
+        #[derive(Clone, PartialEq, Debug)]
+        pub struct #handle {
+            pub address: String
+        }
+    };
+
+    let init_msg = Ident::new(INIT_MSG, Span::call_site());
+    let (params, fields) = msg_args(sink, init, &RESERVED_INIT_ARGS, ENSEMBLE_HANDLE);
+
+    let mut result: ItemImpl = parse_quote! {
+        #[cfg(all(feature = "ensemble", not(target_arch = "wasm32")))]
+        impl #handle {
+            pub fn new(address: impl Into<String>) -> Self {
+                Self { address: address.into() }
+            }
+
+            /// Instantiates the contract with the given code id and returns
+            /// a handle to the new instance at `env.contract()`.
+            pub fn instantiate(
+                ensemble: &mut fadroma_ensemble::ContractEnsemble,
+                code_id: u64,
+                env: fadroma_ensemble::MockEnv,
+                #params
+            ) -> fadroma_ensemble::EnsembleResult<Self> {
+                let msg = #init_msg { #fields };
+                let resp = ensemble.instantiate(code_id, &msg, env)?;
+
+                Ok(Self::new(resp.instance.address))
+            }
+        }
+    };
+
+    let execute_msg = Ident::new(EXECUTE_MSG, Span::call_site());
+
+    for method in execute {
+        let (params, fields) = msg_args(sink, method, &RESERVED_EXECUTE_ARGS, ENSEMBLE_HANDLE);
+        let name = &method.sig().ident;
+        let variant = Ident::new(&to_pascal(&name.to_string()), Span::call_site());
+
+        let item: ImplItemMethod = parse_quote! {
+            pub fn #name(
+                &self,
+                ensemble: &mut fadroma_ensemble::ContractEnsemble,
+                sender: impl Into<String>,
+                funds: Vec<cosmwasm_std::Coin>,
+                #params
+            ) -> fadroma_ensemble::EnsembleResult<fadroma_ensemble::ExecuteResponse> {
+                let msg = #execute_msg::#variant { #fields };
+                let env = fadroma_ensemble::MockEnv::new(sender, self.address.clone())
+                    .sent_funds(funds);
+
+                ensemble.execute(&msg, env)
+            }
+        };
+
+        result.items.push(item.into());
+    }
+
+    let query_msg = Ident::new(QUERY_MSG, Span::call_site());
+
+    for method in query {
+        let (params, fields) = msg_args(sink, method, &RESERVED_QUERY_ARGS, ENSEMBLE_HANDLE);
+        let name = &method.sig().ident;
+        let variant = Ident::new(&to_pascal(&name.to_string()), Span::call_site());
+        let response = method.return_ty().value;
+
+        let item: ImplItemMethod = parse_quote! {
+            pub fn #name(
+                &self,
+                ensemble: &fadroma_ensemble::ContractEnsemble,
+                #params
+            ) -> fadroma_ensemble::EnsembleResult<#response> {
+                let msg = #query_msg::#variant { #fields };
+
+                ensemble.query(&self.address, &msg)
+            }
+        };
+
+        result.items.push(item.into());
+    }
+
+    (def, result)
+}
@@ -11,9 +11,11 @@ use crate::{
     err::ErrorSink,
     attr::{
         MsgAttr, CONTRACT, INIT_MSG, EXECUTE_MSG,
-        QUERY_MSG, INIT_FN, EXECUTE_FN, QUERY_FN,
+        QUERY_MSG, MIGRATE_MSG, SUDO_MSG, INIT_FN,
+        EXECUTE_FN, QUERY_FN, MIGRATE_FN, SUDO_FN,
         ERROR_ENUM, ERROR_TYPE, CONTRACT_ERR_VARIANT,
-        BINARY_SERIALIZE_ERR_VARIANT
+        BINARY_SERIALIZE_ERR_VARIANT, INVALID_FUNDS_ERR_VARIANT,
+        ExecuteCheck
     },
     method::{Method, fn_args_to_idents, fn_arg_ident, pat_ident},
     utils::to_pascal
@@ -22,7 +24,9 @@ use crate::{
 #[derive(Clone, Copy)]
 pub enum MsgType {
     Execute,
-    Query
+    Query,
+    Migrate,
+    Sudo
 }
 
 pub struct ErrorEnum {
@@ -209,7 +213,54 @@ pub fn query_fn(
     result
 }
 
-pub fn wasm_entry(reply: &Option<Method<'_>>) -> ItemMod {
+pub fn migrate_fn(
+    sink: &mut ErrorSink,
+    methods: &[Method<'_>]
+) -> ItemFn {
+    privileged_fn(sink, methods, MsgType::Migrate, MIGRATE_FN)
+}
+
+pub fn sudo_fn(
+    sink: &mut ErrorSink,
+    methods: &[Method<'_>]
+) -> ItemFn {
+    privileged_fn(sink, methods, MsgType::Sudo, SUDO_FN)
+}
+
+/// Creates an entry point that doesn't receive a `MessageInfo`
+/// because it's not called by an account but by the chain itself.
+fn privileged_fn(
+    sink: &mut ErrorSink,
+    methods: &[Method<'_>],
+    msg_type: MsgType,
+    fn_name: &str
+) -> ItemFn {
+    assert!(!methods.is_empty());
+
+    let fn_name = Ident::new(fn_name, Span::call_site());
+    let msg: Ident = msg_type.into();
+    let error_enum = Ident::new(ERROR_ENUM, Span::call_site());
+
+    let mut result: ItemFn = parse_quote! {
+        pub fn #fn_name(
+            deps: cosmwasm_std::DepsMut,
+            env: cosmwasm_std::Env,
+            msg: #msg
+        ) -> std::result::Result<cosmwasm_std::Response, #error_enum> { }
+    };
+
+    if let Some(match_expr) = create_match_expr(sink, methods, msg_type) {
+        result.block.stmts.push(Stmt::Expr(match_expr));
+    }
+
+    result
+}
+
+pub fn wasm_entry(
+    reply: &Option<Method<'_>>,
+    has_migrate: bool,
+    has_sudo: bool
+) -> ItemMod {
     let init_fn = Ident::new(INIT_FN, Span::call_site());
     let execute_fn = Ident::new(EXECUTE_FN, Span::call_site());
     let query_fn = Ident::new(QUERY_FN, Span::call_site());
@@ -250,13 +301,40 @@ pub fn wasm_entry(reply: &Option<Method<'_>>) -> ItemMod {
         result.content.as_mut().unwrap().1.push(Item::Fn(entry));
     }
 
+    if has_migrate {
+        let migrate_fn = Ident::new(MIGRATE_FN, Span::call_site());
+
+        let entry = parse_quote! {
+            #[no_mangle]
+            extern "C" fn migrate(env_ptr: u32, msg_ptr: u32) -> u32 {
+                super::cosmwasm_std::do_migrate(&super::#migrate_fn, env_ptr, msg_ptr)
+            }
+        };
+
+        result.content.as_mut().unwrap().1.push(Item::Fn(entry));
+    }
+
+    if has_sudo {
+        let sudo_fn = Ident::new(SUDO_FN, Span::call_site());
+
+        let entry = parse_quote! {
+            #[no_mangle]
+            extern "C" fn sudo(env_ptr: u32, msg_ptr: u32) -> u32 {
+                super::cosmwasm_std::do_sudo(&super::#sudo_fn, env_ptr, msg_ptr)
+            }
+        };
+
+        result.content.as_mut().unwrap().1.push(Item::Fn(entry));
+    }
+
     result
 }
 
 pub fn error_enum(
     sink: &mut ErrorSink,
     contract: Option<GenericArgument>,
-    interfaces: &[&ItemImpl]
+    interfaces: &[&ItemImpl],
+    has_invalid_funds: bool
 ) -> ErrorEnum {
     let name = Ident::new(ERROR_ENUM, Span::call_site());
     let serialize_err_variant = Ident::new(BINARY_SERIALIZE_ERR_VARIANT, Span::call_site());
@@ -279,6 +357,16 @@ pub fn error_enum(
         }
     );
 
+    // Only added when a method can reject funds so that
+    // the enum doesn't change for all other contracts.
+    if has_invalid_funds {
+        let variant = Ident::new(INVALID_FUNDS_ERR_VARIANT, Span::call_site());
+        enum_def.variants.push(parse_quote!(#variant(String)));
+
+        let arm = parse_quote!(Self::#variant(msg) => #fmt_arg.write_str(msg));
+        match_expr.arms.push(arm);
+    }
+
     if let Some(contract) = contract {
         if let GenericArgument::Type(ty) = contract {
             let contract_variant = Ident::new(CONTRACT_ERR_VARIANT, Span::call_site());
@@ -344,6 +432,15 @@ pub fn cw_arguments(sig: &mut Signature, attr: MsgAttr, has_block: bool) {
             sig.inputs.insert(1, parse_quote!(env: cosmwasm_std::Env));
             sig.inputs.insert(2, parse_quote!(info: cosmwasm_std::MessageInfo));
         },
+        MsgAttr::Migrate | MsgAttr::Sudo => {
+            if has_block {
+                sig.inputs.insert(0, parse_quote!(mut deps: cosmwasm_std::DepsMut));
+            } else {
+                sig.inputs.insert(0, parse_quote!(deps: cosmwasm_std::DepsMut));
+            }
+
+            sig.inputs.insert(1, parse_quote!(env: cosmwasm_std::Env));
+        },
         MsgAttr::Reply => {
             sig.inputs.insert(0, parse_quote!(mut deps: cosmwasm_std::DepsMut));
             sig.inputs.insert(1, parse_quote!(env: cosmwasm_std::Env));
@@ -394,11 +491,15 @@ fn create_match_expr(
 
         match msg_type {
             MsgType::Execute => {
-                match_expr.arms.push(
-                    parse_quote!(#enum_name::#variant { #args } =>
+                let checks = execute_checks(method.checks(), &map_err);
+
+                match_expr.arms.push(parse_quote! {
+                    #enum_name::#variant { #args } => {
+                        #(#checks)*
+
                         #contract_ident::#method_name(deps, env, info, #args).#map_err
-                    )
-                );
+                    }
+                });
             }
             MsgType::Query => {
                 match_expr.arms.push(parse_quote! {
@@ -409,12 +510,53 @@ fn create_match_expr(
                     }
                 });
             }
+            MsgType::Migrate | MsgType::Sudo => {
+                match_expr.arms.push(
+                    parse_quote!(#enum_name::#variant { #args } =>
+                        #contract_ident::#method_name(deps, env, #args).#map_err
+                    )
+                );
+            }
         }
     }
 
     Some(Expr::Match(match_expr))
 }
 
+fn execute_checks(checks: &[ExecuteCheck], map_err: &Expr) -> Vec<Stmt> {
+    let error_enum = Ident::new(ERROR_ENUM, Span::call_site());
+    let funds_err_variant = Ident::new(INVALID_FUNDS_ERR_VARIANT, Span::call_site());
+
+    let mut result = vec![];
+
+    for check in checks {
+        match check {
+            ExecuteCheck::NonPayable => result.push(parse_quote! {
+                if !info.funds.is_empty() {
+                    return Err(#error_enum::#funds_err_variant(
+                        String::from("This method doesn't accept any funds.")
+                    ));
+                }
+            }),
+            ExecuteCheck::Payable { denom: Some(denom) } => result.push(parse_quote! {
+                if let Some(coin) = info.funds.iter().find(|x| x.denom != #denom) {
+                    return Err(#error_enum::#funds_err_variant(format!(
+                        "This method only accepts funds in \"{}\" but \"{}\" was sent.",
+                        #denom,
+                        coin.denom
+                    )));
+                }
+            }),
+            ExecuteCheck::Payable { denom: None } => { }
+            ExecuteCheck::Guard(guard) => result.push(parse_quote! {
+                #guard(deps.as_ref(), &env, &info).#map_err?;
+            })
+        }
+    }
+
+    result
+}
+
 fn extract_fields(
     sink: &mut ErrorSink,
     sig: &Signature,
@@ -455,7 +597,9 @@ impl From<MsgType> for Ident {
     fn from(msg: MsgType) -> Self {
         match msg {
             MsgType::Execute => Self::new(EXECUTE_MSG, Span::call_site()),
-            MsgType::Query => Self::new(QUERY_MSG, Span::call_site())
+            MsgType::Query => Self::new(QUERY_MSG, Span::call_site()),
+            MsgType::Migrate => Self::new(MIGRATE_MSG, Span::call_site()),
+            MsgType::Sudo => Self::new(SUDO_MSG, Span::call_site())
         }
     }
 }
@@ -8,13 +8,16 @@ use crate::{
     attr::{MsgAttr, ERROR_TYPE},
     err::{ErrorSink, CompileErrors},
     generate::{self, MsgType},
+    client,
     method::{Method, trait_methods},
     validate
 };
 
-pub const SUPPORTED_ATTRS: [&'static str; 3] = [
+pub const SUPPORTED_ATTRS: [&'static str; 5] = [
     MsgAttr::EXECUTE,
     MsgAttr::QUERY,
+    MsgAttr::MIGRATE,
+    MsgAttr::SUDO,
     MsgAttr::INIT
 ];
 
@@ -39,12 +42,32 @@ pub fn derive(r#trait: ItemTrait) -> Result<proc_macro2::TokenStream, CompileErr
         &interface.query
     );
 
+    // Unlike execute and query, these are only generated when
+    // used since most contracts don't have migrate or sudo methods.
+    let migrate_msg = (!interface.migrate.is_empty()).then(||
+        generate::messages(&mut sink, MsgType::Migrate, &interface.migrate)
+    );
+    let sudo_msg = (!interface.sudo.is_empty()).then(||
+        generate::messages(&mut sink, MsgType::Sudo, &interface.sudo)
+    );
+
+    let client::Client {
+        client_struct,
+        client_impl,
+        from_impl
+    } = client::client(&mut sink, &interface.execute, &interface.query);
+
     sink.check()?;
 
     Ok(quote! {
         #init_msg
         #execute_msg
         #query_msg
+        #migrate_msg
+        #sudo_msg
+        #client_struct
+        #client_impl
+        #from_impl
     })
 }
 
@@ -57,7 +80,9 @@ struct Interface<'a> {
     /// Optional because an interface might not want to have an init method.
     init: Option<Method<'a>>,
     execute: Vec<Method<'a>>,
-    query: Vec<Method<'a>>
+    query: Vec<Method<'a>>,
+    migrate: Vec<Method<'a>>,
+    sudo: Vec<Method<'a>>
 }
 
 impl<'a> Interface<'a> {
@@ -66,6 +91,8 @@ impl<'a> Interface<'a> {
         let mut init: Option<Method> = None;
         let mut execute: Vec<Method> = vec![];
         let mut query: Vec<Method> = vec![];
+        let mut migrate: Vec<Method> = vec![];
+        let mut sudo: Vec<Method> = vec![];
 
         // We forbid generic traits because they will complicate the error type on contracts.
         if validate::has_generics(&r#trait.generics) {
@@ -121,6 +148,8 @@ impl<'a> Interface<'a> {
                 }
                 MsgAttr::Execute => execute.push(Method::Interface(method)),
                 MsgAttr::Query => query.push(Method::Interface(method)),
+                MsgAttr::Migrate => migrate.push(Method::Interface(method)),
+                MsgAttr::Sudo => sudo.push(Method::Interface(method)),
                 unsupported => sink.unsupported_interface_attr(
                     &method.sig.ident,
                     unsupported
@@ -131,7 +160,9 @@ impl<'a> Interface<'a> {
         Self {
             init,
             execute,
-            query
+            query,
+            migrate,
+            sudo
         }
     }
 }
@@ -11,6 +11,10 @@ mod auto_impl;
 mod execute_guard;
 mod reply;
 mod utils;
+mod client;
+mod schema;
+#[cfg(feature = "ensemble")]
+mod ensemble;
 
 use syn::{
     AttributeArgs, Item, ItemTrait, TraitItemMethod, ItemFn,
@@ -20,7 +24,7 @@ use proc_macro2::Span;
 use quote::quote;
 
 use auto_impl::AutoImpl;
-use attr::MsgAttr;
+use attr::{MsgAttr, ExecuteCheck};
 
 #[proc_macro_attribute]
 pub fn interface(
@@ -112,6 +116,28 @@ pub fn query(
     proc_macro::TokenStream::from(result)
 }
 
+#[proc_macro_attribute]
+pub fn migrate(
+    _args: proc_macro::TokenStream,
+    item: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+    let item = parse_macro_input!(item as Item);
+    let result = add_fn_args(item, MsgAttr::Migrate);
+
+    proc_macro::TokenStream::from(result)
+}
+
+#[proc_macro_attribute]
+pub fn sudo(
+    _args: proc_macro::TokenStream,
+    item: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+    let item = parse_macro_input!(item as Item);
+    let result = add_fn_args(item, MsgAttr::Sudo);
+
+    proc_macro::TokenStream::from(result)
+}
+
 #[proc_macro_attribute]
 pub fn execute_guard(
     _args: proc_macro::TokenStream,
@@ -142,6 +168,49 @@ pub fn reply(
     proc_macro::TokenStream::from(result)
 }
 
+#[proc_macro_attribute]
+pub fn nonpayable(
+    _args: proc_macro::TokenStream,
+    item: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+    execute_check_misuse(item, ExecuteCheck::NONPAYABLE)
+}
+
+#[proc_macro_attribute]
+pub fn payable(
+    _args: proc_macro::TokenStream,
+    item: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+    execute_check_misuse(item, ExecuteCheck::PAYABLE)
+}
+
+#[proc_macro_attribute]
+pub fn guard(
+    _args: proc_macro::TokenStream,
+    item: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+    execute_check_misuse(item, ExecuteCheck::GUARD)
+}
+
+/// Execute checks are parsed and removed by the `#[contract]` macro
+/// so they only ever expand on their own when used elsewhere.
+fn execute_check_misuse(
+    item: proc_macro::TokenStream,
+    attr: &str
+) -> proc_macro::TokenStream {
+    let item = proc_macro2::TokenStream::from(item);
+    let err = syn::Error::new(
+        Span::call_site(),
+        format!(
+            "#[{}] is only valid for #[{}] methods inside a #[contract] module.",
+            attr,
+            MsgAttr::EXECUTE
+        )
+    ).to_compile_error();
+
+    proc_macro::TokenStream::from(quote!(#err #item))
+}
+
 fn add_fn_args(mut item: Item, attr: MsgAttr) -> proc_macro2::TokenStream {
     match &mut item {
         Item::Fn(item) => {
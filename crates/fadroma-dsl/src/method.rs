@@ -9,7 +9,7 @@ use proc_macro2::Span;
 
 use crate::{
     validate::{self, ResultType},
-    attr::{MsgAttr, ERROR_TYPE},
+    attr::{MsgAttr, ExecuteCheck, ERROR_TYPE},
     err::ErrorSink
 };
 
@@ -21,14 +21,18 @@ pub enum Method<'a> {
 pub struct ContractMethod<'a> {
     ty: MsgAttr,
     sig: &'a Signature,
-    return_ty: ResultType<'a>
+    return_ty: ResultType<'a>,
+    checks: Vec<ExecuteCheck>
 }
 
 pub struct InterfaceMethod<'a> {
     pub ty: MsgAttr,
     pub sig: &'a Signature,
     pub return_ty: ResultType<'a>,
-    trait_: Path
+    trait_: Path,
+    /// Always empty for methods declared in the interface trait
+    /// since the checks are specific to the implementation.
+    checks: Vec<ExecuteCheck>
 }
 
 pub fn trait_methods<'a>(
@@ -67,7 +71,8 @@ pub fn trait_methods<'a>(
                 ty,
                 sig: &method.sig,
                 trait_,
-                return_ty
+                return_ty,
+                checks: vec![]
             });
         }
     }
@@ -86,7 +91,19 @@ pub fn item_impl_methods<'a>(
             continue;
         };
 
+        let checks = ExecuteCheck::parse(sink, &method.attrs);
+
         let Some(ty) = MsgAttr::parse(sink, &method.attrs) else {
+            if !checks.is_empty() {
+                sink.push_spanned(
+                    &method.sig.ident,
+                    format!(
+                        "Methods with execute checks must have the \"#[{}]\" attribute.",
+                        MsgAttr::EXECUTE
+                    )
+                );
+            }
+
             // Require an attribute for trait impls only.
             // The "Contract" struct methods don't all
             // have to be part of its interface.
@@ -97,6 +114,16 @@ pub fn item_impl_methods<'a>(
             continue;
         };
 
+        if !checks.is_empty() && !matches!(ty, MsgAttr::Execute) {
+            sink.push_spanned(
+                &method.sig.ident,
+                format!(
+                    "Execute checks are only valid for \"#[{}]\" methods.",
+                    MsgAttr::EXECUTE
+                )
+            );
+        }
+
         if let Some((_, trait_, _)) = &item_impl.trait_ {
             if let Some(return_ty) = interface_method_return_ty(
                 sink,
@@ -108,7 +135,8 @@ pub fn item_impl_methods<'a>(
                     ty,
                     sig: &method.sig,
                     trait_: trait_.clone(),
-                    return_ty
+                    return_ty,
+                    checks
                 }));
             }
         } else {
@@ -124,7 +152,8 @@ pub fn item_impl_methods<'a>(
                 methods.push(Method::Contract(ContractMethod {
                     ty,
                     sig: &method.sig,
-                    return_ty
+                    return_ty,
+                    checks
                 }));
             }
         }
@@ -181,6 +210,52 @@ pub fn pat_ident(sink: &mut ErrorSink, pat: Pat) -> Option<Ident> {
     }
 }
 
+/// Returns the message arguments of the method as parameters of a generated
+/// wrapper method and the fields of the message that they are moved into.
+/// Arguments that clash with the `reserved` parameters of `item` are reported.
+pub fn msg_args(
+    sink: &mut ErrorSink,
+    method: &Method<'_>,
+    reserved: &[&str],
+    item: &str
+) -> (Punctuated<FnArg, Comma>, Punctuated<Ident, Comma>) {
+    let mut params = Punctuated::<FnArg, Comma>::new();
+    let mut fields = Punctuated::<Ident, Comma>::new();
+
+    for input in &method.sig().inputs {
+        // Invalid arguments are already reported when generating the message.
+        let FnArg::Typed(pat_type) = input else {
+            continue;
+        };
+
+        if !matches!(*pat_type.pat, Pat::Ident(_)) {
+            continue;
+        }
+
+        let Some(ident) = pat_ident(sink, *pat_type.pat.to_owned()) else {
+            continue;
+        };
+
+        if reserved.contains(&ident.to_string().as_str()) {
+            sink.push_spanned(
+                &pat_type.pat,
+                format!(
+                    "\"{}\" is reserved by the generated {} and cannot be used as an argument name.",
+                    ident,
+                    item
+                )
+            );
+        }
+
+        let ty = &pat_type.ty;
+
+        params.push(parse_quote!(#ident: #ty));
+        fields.push(ident);
+    }
+
+    (params, fields)
+}
+
 fn interface_method_return_ty<'a>(
     sink: &mut ErrorSink,
     ty: MsgAttr,
@@ -222,7 +297,8 @@ fn contract_method_return_ty<'a>(
 #[inline]
 fn expected_value_type(ty: MsgAttr) -> Option<GenericArgument> {
     match ty {
-        MsgAttr::Init { .. } | MsgAttr::Execute | MsgAttr::Reply =>
+        MsgAttr::Init { .. } | MsgAttr::Execute | MsgAttr::Migrate |
+        MsgAttr::Sudo | MsgAttr::Reply =>
             Some(parse_quote!(Response)),
         MsgAttr::Query => None,
         MsgAttr::ExecuteGuard => Some(parse_quote!(()))
@@ -253,6 +329,14 @@ impl<'a> Method<'a> {
             Method::Interface(x) => &x.return_ty
         }
     }
+
+    #[inline]
+    pub fn checks(&self) -> &[ExecuteCheck] {
+        match self {
+            Method::Contract(x) => &x.checks,
+            Method::Interface(x) => &x.checks
+        }
+    }
 }
 
 impl<'a> InterfaceMethod<'a> {
@@ -0,0 +1,70 @@
+use syn::{ItemFn, Ident, Expr, LitStr, parse_quote, punctuated::Punctuated, token::Comma};
+use proc_macro2::Span;
+
+use crate::{
+    attr::{API_SCHEMA_FN, INIT_MSG, EXECUTE_MSG, QUERY_MSG, MIGRATE_MSG, SUDO_MSG},
+    method::Method,
+    utils::{to_pascal, to_snake}
+};
+
+/// Creates a function which returns the combined schema of the contract messages
+/// and query responses as a `fadroma::schema::ContractApi`.
+pub fn api_schema_fn(
+    query: &[Method<'_>],
+    has_migrate: bool,
+    has_sudo: bool
+) -> ItemFn {
+    let fn_name = Ident::new(API_SCHEMA_FN, Span::call_site());
+    let init_msg = Ident::new(INIT_MSG, Span::call_site());
+    let execute_msg = Ident::new(EXECUTE_MSG, Span::call_site());
+    let query_msg = Ident::new(QUERY_MSG, Span::call_site());
+
+    let migrate: Expr = if has_migrate {
+        let msg = Ident::new(MIGRATE_MSG, Span::call_site());
+
+        parse_quote!(Some(schemars::schema_for!(#msg)))
+    } else {
+        parse_quote!(None)
+    };
+
+    let sudo: Expr = if has_sudo {
+        let msg = Ident::new(SUDO_MSG, Span::call_site());
+
+        parse_quote!(Some(schemars::schema_for!(#msg)))
+    } else {
+        parse_quote!(None)
+    };
+
+    // The keys must match how the variants of the query message are
+    // serialized, which is the snake_case form of the variant name.
+    let mut responses = Punctuated::<Expr, Comma>::new();
+
+    for method in query {
+        let variant = to_pascal(&method.sig().ident.to_string());
+        let name = LitStr::new(&to_snake(&variant), Span::call_site());
+        let response = method.return_ty().value;
+
+        responses.push(parse_quote!(
+            (String::from(#name), schemars::schema_for!(#response))
+        ));
+    }
+
+    parse_quote! {
+        /// Returns the schema of all messages that the contract accepts and
+        /// the responses to its queries in the `cosmwasm-schema` IDL format.
+        #[cfg(not(target_arch = "wasm32"))]
+        pub fn #fn_name() -> fadroma::schema::ContractApi {
+            fadroma::schema::ContractApi {
+                contract_name: env!("CARGO_PKG_NAME").into(),
+                contract_version: env!("CARGO_PKG_VERSION").into(),
+                idl_version: fadroma::schema::IDL_VERSION.into(),
+                instantiate: schemars::schema_for!(#init_msg),
+                execute: Some(schemars::schema_for!(#execute_msg)),
+                query: Some(schemars::schema_for!(#query_msg)),
+                migrate: #migrate,
+                sudo: #sudo,
+                responses: Some(std::collections::BTreeMap::from([#responses]))
+            }
+        }
+    }
+}
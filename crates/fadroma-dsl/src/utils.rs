@@ -22,6 +22,22 @@ pub fn to_pascal(s: &str) -> String {
     result
 }
 
+/// Converts a PascalCase name to snake_case the same way that
+/// `#[serde(rename_all = "snake_case")]` renames enum variants.
+pub fn to_snake(s: &str) -> String {
+    let mut result = String::with_capacity(s.len() + 4);
+
+    for (i, c) in s.char_indices() {
+        if i > 0 && c.is_uppercase() {
+            result.push('_');
+        }
+
+        result.push(c.to_ascii_lowercase());
+    }
+
+    result
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -32,4 +48,12 @@ mod tests {
         assert_eq!(to_pascal(&"__to_pascal"), String::from("ToPascal"));
         assert_eq!(to_pascal(&"Very_Long_string"), String::from("VeryLongString"));
     }
+
+    #[test]
+    fn test_to_snake() {
+        assert_eq!(to_snake("ToSnake"), String::from("to_snake"));
+        assert_eq!(to_snake(&to_pascal("__to_snake")), String::from("to_snake"));
+        assert_eq!(to_snake(&to_pascal("foo__bar")), String::from("foo_bar"));
+        assert_eq!(to_snake(&to_pascal("get_2fa")), String::from("get2fa"));
+    }
 }
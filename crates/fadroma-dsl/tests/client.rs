@@ -0,0 +1,135 @@
+use fadroma::{dsl::*, prelude::*};
+#[cfg(feature = "ensemble")]
+use fadroma_ensemble::{ContractEnsemble, MockEnv};
+
+pub mod counter_api {
+    use fadroma::{dsl::*, prelude::*};
+
+    #[interface]
+    pub trait Counter {
+        type Error: std::fmt::Display;
+
+        #[execute]
+        fn add(amount: u64) -> Result<Response, Self::Error>;
+
+        #[query]
+        fn count() -> Result<u64, Self::Error>;
+    }
+}
+
+#[contract]
+pub mod counter {
+    use fadroma::{dsl::*, prelude::*};
+    use super::counter_api::Counter;
+
+    pub const COUNT: &[u8] = b"count";
+
+    impl Contract {
+        #[init(entry)]
+        pub fn init() -> Result<Response, StdError> {
+            Ok(Response::default())
+        }
+    }
+
+    impl Counter for Contract {
+        type Error = StdError;
+
+        #[execute]
+        fn add(amount: u64) -> Result<Response, Self::Error> {
+            let count = Self::count(deps.as_ref(), env)? + amount;
+            deps.storage.set(COUNT, &count.to_be_bytes());
+
+            Ok(Response::default())
+        }
+
+        #[query]
+        fn count() -> Result<u64, Self::Error> {
+            Ok(deps.storage.get(COUNT).map_or(0, |x| u64::from_be_bytes(x.try_into().unwrap())))
+        }
+    }
+}
+
+#[contract]
+pub mod caller {
+    use fadroma::{dsl::*, prelude::*};
+    use super::counter_api;
+
+    pub const TARGET: &[u8] = b"target";
+
+    impl Contract {
+        #[init(entry)]
+        pub fn init(target: ContractLink<Addr>) -> Result<Response, StdError> {
+            deps.storage.set(TARGET, &to_vec(&target)?);
+
+            Ok(Response::default())
+        }
+
+        #[execute]
+        pub fn forward(amount: u64) -> Result<Response, StdError> {
+            let client = target(deps.as_ref())?.with_funds(info.funds);
+
+            Ok(Response::default().add_message(client.add(amount)?))
+        }
+
+        #[query]
+        pub fn read() -> Result<u64, StdError> {
+            target(deps)?.count(deps.querier)
+        }
+    }
+
+    fn target(deps: Deps) -> StdResult<counter_api::Client> {
+        let link: ContractLink<Addr> = from_slice(&deps.storage.get(TARGET).unwrap())?;
+
+        Ok(link.into())
+    }
+}
+
+#[test]
+fn client_creates_padded_messages() {
+    let link = ContractLink { address: Addr::unchecked("counter"), code_hash: "hash".into() };
+    let client = counter_api::Client::new(link);
+
+    let CosmosMsg::Wasm(WasmMsg::Execute { contract_addr, code_hash, msg, funds }) = client.add(1).unwrap() else {
+        panic!("Expecting WasmMsg::Execute");
+    };
+    assert_eq!(contract_addr, "counter");
+    assert_eq!(code_hash, "hash");
+    assert_eq!(msg.len(), BLOCK_SIZE);
+    assert!(funds.is_empty());
+
+    let client = client.with_block_size(None).with_funds(vec![coin(1, "uscrt")]);
+
+    let CosmosMsg::Wasm(WasmMsg::Execute { msg, funds, .. }) = client.add(1).unwrap() else {
+        panic!("Expecting WasmMsg::Execute");
+    };
+    assert_eq!(msg.as_slice(), br#"{"add":{"amount":1}}"#);
+    assert_eq!(funds, vec![coin(1, "uscrt")]);
+}
+
+#[test]
+#[cfg(feature = "ensemble")]
+fn client_calls_other_contracts() {
+    let mut ensemble = ContractEnsemble::new();
+    let counter_code = ensemble.register(Box::new(counter::Contract));
+    let caller_code = ensemble.register(Box::new(caller::Contract));
+
+    let counter = ensemble.instantiate(
+        counter_code.id,
+        &counter::InstantiateMsg {},
+        MockEnv::new("admin", "counter")
+    ).unwrap().instance;
+
+    let caller = caller::EnsembleHandle::instantiate(
+        &mut ensemble,
+        caller_code.id,
+        MockEnv::new("admin", "caller"),
+        counter
+    ).unwrap();
+
+    ensemble.add_funds("alice", vec![coin(5, "uscrt")]);
+    caller.forward(&mut ensemble, "alice", vec![coin(5, "uscrt")], 3).unwrap();
+    caller.forward(&mut ensemble, "alice", vec![], 4).unwrap();
+
+    assert_eq!(caller.read(&ensemble).unwrap(), 7);
+    assert_eq!(ensemble.balances("counter").unwrap()["uscrt"].u128(), 5);
+}
@@ -0,0 +1,130 @@
+#![cfg(feature = "ensemble")]
+
+use fadroma::{dsl::*, prelude::*};
+use fadroma_ensemble::{ContractEnsemble, ContractHarness, MockEnv};
+
+#[interface]
+pub trait Counter {
+    type Error: std::fmt::Display;
+
+    #[execute]
+    fn add(amount: u64) -> Result<Response, Self::Error>;
+
+    #[query]
+    fn count() -> Result<u64, Self::Error>;
+}
+
+#[contract]
+pub mod counter {
+    use fadroma::{dsl::*, prelude::*};
+    use super::Counter;
+
+    pub const COUNT: &[u8] = b"count";
+    pub const LAST_REPLY: &[u8] = b"last_reply";
+
+    impl Contract {
+        #[init(entry)]
+        pub fn init(start: u64) -> Result<Response, StdError> {
+            deps.storage.set(COUNT, &start.to_be_bytes());
+
+            Ok(Response::default())
+        }
+
+        #[execute]
+        pub fn forward(recipient: String) -> Result<Response, StdError> {
+            let send = BankMsg::Send { to_address: recipient, amount: info.funds };
+
+            Ok(Response::default().add_submessage(SubMsg::reply_on_success(send, 7)))
+        }
+
+        #[query]
+        pub fn last_reply() -> Result<Option<u64>, StdError> {
+            Ok(deps.storage.get(LAST_REPLY).map(|x| u64::from_be_bytes(x.try_into().unwrap())))
+        }
+
+        #[reply]
+        pub fn reply(reply: Reply) -> Result<Response, StdError> {
+            deps.storage.set(LAST_REPLY, &reply.id.to_be_bytes());
+
+            Ok(Response::default())
+        }
+    }
+
+    impl Counter for Contract {
+        type Error = StdError;
+
+        #[execute]
+        fn add(amount: u64) -> Result<Response, Self::Error> {
+            if amount == 0 {
+                return Err(StdError::generic_err("Nothing to add."));
+            }
+
+            let count = Self::count(deps.as_ref(), env)? + amount;
+            deps.storage.set(COUNT, &count.to_be_bytes());
+
+            Ok(Response::default())
+        }
+
+        #[query]
+        fn count() -> Result<u64, Self::Error> {
+            Ok(deps.storage.get(COUNT).map_or(0, |x| u64::from_be_bytes(x.try_into().unwrap())))
+        }
+    }
+}
+
+#[test]
+fn handle_calls_the_contract() {
+    let mut ensemble = ContractEnsemble::new();
+    let code = ensemble.register(Box::new(counter::Contract));
+
+    let counter = counter::EnsembleHandle::instantiate(
+        &mut ensemble,
+        code.id,
+        MockEnv::new("admin", "counter"),
+        3
+    ).unwrap();
+    assert_eq!(counter, counter::EnsembleHandle::new("counter"));
+
+    counter.add(&mut ensemble, "alice", vec![], 4).unwrap();
+    assert_eq!(counter.count(&ensemble).unwrap(), 7);
+
+    let err = counter.add(&mut ensemble, "alice", vec![], 0).unwrap_err();
+    assert_eq!(err.unwrap_contract_error().to_string(), "Generic error: Nothing to add.");
+    assert_eq!(counter.count(&ensemble).unwrap(), 7);
+}
+
+#[test]
+fn harness_dispatches_funds_and_replies() {
+    let mut ensemble = ContractEnsemble::new();
+    let code = ensemble.register(Box::new(counter::Contract));
+
+    let counter = counter::EnsembleHandle::instantiate(
+        &mut ensemble,
+        code.id,
+        MockEnv::new("admin", "counter"),
+        0
+    ).unwrap();
+    assert_eq!(counter.last_reply(&ensemble).unwrap(), None);
+
+    ensemble.add_funds("alice", vec![coin(100, "uscrt")]);
+    counter.forward(&mut ensemble, "alice", vec![coin(100, "uscrt")], "bob".into()).unwrap();
+
+    assert_eq!(ensemble.balances("bob").unwrap()["uscrt"].u128(), 100);
+    assert_eq!(counter.last_reply(&ensemble).unwrap(), Some(7));
+}
+
+#[test]
+fn harness_rejects_invalid_messages() {
+    use fadroma::cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+
+    let mut deps = mock_dependencies();
+    let msg = to_binary(&counter::ExecuteMsg::Add { amount: 1 }).unwrap();
+
+    let err = counter::Contract.instantiate(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("admin", &[]),
+        msg
+    ).unwrap_err();
+    assert!(err.to_string().contains("InstantiateMsg"));
+}
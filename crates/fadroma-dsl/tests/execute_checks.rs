@@ -0,0 +1,167 @@
+use fadroma::{
+    dsl::*,
+    prelude::*,
+    cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info}
+};
+#[cfg(feature = "ensemble")]
+use fadroma_ensemble::{ContractEnsemble, EnsembleError, MockEnv};
+
+pub mod vault_api {
+    use fadroma::{dsl::*, prelude::*};
+
+    #[interface]
+    pub trait Vault {
+        type Error: std::fmt::Display;
+
+        #[execute]
+        fn deposit() -> Result<Response, Self::Error>;
+    }
+}
+
+#[contract]
+pub mod vault {
+    use fadroma::{dsl::*, prelude::*};
+    use super::vault_api::Vault;
+
+    pub const DEPOSITS: &[u8] = b"deposits";
+    pub const SWEPT: &[u8] = b"swept";
+
+    impl Contract {
+        #[init(entry)]
+        pub fn init() -> Result<Response, StdError> {
+            Ok(Response::default())
+        }
+
+        #[execute]
+        #[nonpayable]
+        pub fn ping() -> Result<Response, StdError> {
+            Ok(Response::default())
+        }
+
+        #[execute]
+        #[guard(only_admin)]
+        #[payable]
+        pub fn sweep() -> Result<Response, StdError> {
+            deps.storage.set(SWEPT, info.sender.as_bytes());
+
+            Ok(Response::default())
+        }
+
+        #[query]
+        pub fn swept_by() -> Result<Option<String>, StdError> {
+            Ok(deps.storage.get(SWEPT).map(|x| String::from_utf8(x).unwrap()))
+        }
+
+        #[query]
+        pub fn deposits() -> Result<u64, StdError> {
+            Ok(deps.storage.get(DEPOSITS).map_or(0, |x| u64::from_be_bytes(x.try_into().unwrap())))
+        }
+    }
+
+    impl Vault for Contract {
+        type Error = StdError;
+
+        #[execute]
+        #[payable(denom = "uscrt")]
+        fn deposit() -> Result<Response, Self::Error> {
+            let deposits = Self::deposits(deps.as_ref(), env)? + 1;
+            deps.storage.set(DEPOSITS, &deposits.to_be_bytes());
+
+            Ok(Response::default())
+        }
+    }
+
+    fn only_admin(_deps: Deps, _env: &Env, info: &MessageInfo) -> StdResult<()> {
+        if info.sender != "admin" {
+            return Err(StdError::generic_err("Unauthorized."));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "ensemble")]
+fn contract_error(err: EnsembleError) -> String {
+    err.unwrap_contract_error().to_string()
+}
+
+#[test]
+fn payable_checks_return_invalid_funds() {
+    let mut deps = mock_dependencies();
+
+    let err = vault::execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("alice", &[coin(1, "uatom")]),
+        vault::ExecuteMsg::Deposit {}
+    ).unwrap_err();
+    assert!(matches!(err, vault::Error::InvalidFunds(_)));
+
+    let err = vault::execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("alice", &[coin(1, "uscrt")]),
+        vault::ExecuteMsg::Ping {}
+    ).unwrap_err();
+    assert!(matches!(err, vault::Error::InvalidFunds(_)));
+
+    let deposits = vault::query(deps.as_ref(), mock_env(), vault::QueryMsg::Deposits {}).unwrap();
+    assert_eq!(from_binary::<u64>(&deposits).unwrap(), 0);
+}
+
+#[test]
+#[cfg(feature = "ensemble")]
+fn payable_checks_through_the_ensemble() {
+    let mut ensemble = ContractEnsemble::new();
+    let code = ensemble.register(Box::new(vault::Contract));
+
+    let vault = vault::EnsembleHandle::instantiate(
+        &mut ensemble,
+        code.id,
+        MockEnv::new("admin", "vault")
+    ).unwrap();
+
+    ensemble.add_funds("alice", vec![coin(10, "uscrt"), coin(10, "uatom")]);
+
+    vault.ping(&mut ensemble, "alice", vec![]).unwrap();
+    assert_eq!(
+        contract_error(vault.ping(&mut ensemble, "alice", vec![coin(1, "uscrt")]).unwrap_err()),
+        "This method doesn't accept any funds."
+    );
+
+    vault.deposit(&mut ensemble, "alice", vec![coin(1, "uscrt")]).unwrap();
+    vault.deposit(&mut ensemble, "alice", vec![]).unwrap();
+    assert_eq!(
+        contract_error(
+            vault.deposit(&mut ensemble, "alice", vec![coin(1, "uscrt"), coin(1, "uatom")]).unwrap_err()
+        ),
+        "This method only accepts funds in \"uscrt\" but \"uatom\" was sent."
+    );
+
+    assert_eq!(vault.deposits(&ensemble).unwrap(), 2);
+    assert_eq!(ensemble.balances("vault").unwrap()["uscrt"].u128(), 1);
+    assert_eq!(ensemble.balances("alice").unwrap()["uatom"].u128(), 10);
+}
+
+#[test]
+#[cfg(feature = "ensemble")]
+fn guard_short_circuits() {
+    let mut ensemble = ContractEnsemble::new();
+    let code = ensemble.register(Box::new(vault::Contract));
+
+    let vault = vault::EnsembleHandle::instantiate(
+        &mut ensemble,
+        code.id,
+        MockEnv::new("admin", "vault")
+    ).unwrap();
+
+    ensemble.add_funds("alice", vec![coin(10, "uatom")]);
+
+    let err = vault.sweep(&mut ensemble, "alice", vec![coin(10, "uatom")]).unwrap_err();
+    assert_eq!(contract_error(err), "Generic error: Unauthorized.");
+    assert_eq!(vault.swept_by(&ensemble).unwrap(), None);
+    assert_eq!(ensemble.balances("alice").unwrap()["uatom"].u128(), 10);
+
+    vault.sweep(&mut ensemble, "admin", vec![]).unwrap();
+    assert_eq!(vault.swept_by(&ensemble).unwrap(), Some("admin".into()));
+}
@@ -0,0 +1,160 @@
+use fadroma::{
+    dsl::*,
+    prelude::*,
+    cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info}
+};
+#[cfg(feature = "ensemble")]
+use fadroma_ensemble::{ContractEnsemble, MockEnv};
+
+pub mod upgradable {
+    use fadroma::{dsl::*, prelude::*};
+
+    #[interface]
+    pub trait Upgradable {
+        type Error: std::fmt::Display;
+
+        #[migrate]
+        fn upgrade(version: u8) -> Result<Response, Self::Error>;
+    }
+}
+
+#[contract]
+pub mod counter {
+    use fadroma::{dsl::*, prelude::*};
+    use super::upgradable::Upgradable;
+
+    pub const VALUE: &[u8] = b"value";
+    pub const VERSION: &[u8] = b"version";
+    pub const FROZEN: &[u8] = b"frozen";
+
+    impl Contract {
+        #[init(entry)]
+        pub fn init(value: u64) -> Result<Response, StdError> {
+            deps.storage.set(VALUE, &value.to_be_bytes());
+
+            Ok(Response::default())
+        }
+
+        #[migrate]
+        pub fn reset(value: u64) -> Result<Response, StdError> {
+            deps.storage.set(VALUE, &value.to_be_bytes());
+
+            Ok(Response::default().add_attribute("height", env.block.height.to_string()))
+        }
+
+        #[sudo]
+        pub fn freeze(reason: String) -> Result<Response, StdError> {
+            deps.storage.set(FROZEN, reason.as_bytes());
+
+            Ok(Response::default())
+        }
+
+        #[execute]
+        pub fn add(value: u64) -> Result<Response, StdError> {
+            if deps.storage.get(FROZEN).is_some() {
+                return Err(StdError::generic_err("Frozen."));
+            }
+
+            let current = Self::value(deps.as_ref(), env)?;
+            deps.storage.set(VALUE, &(current + value).to_be_bytes());
+
+            Ok(Response::default())
+        }
+
+        #[query]
+        pub fn value() -> Result<u64, StdError> {
+            let value = deps.storage.get(VALUE)
+                .ok_or_else(|| StdError::not_found("value"))?;
+
+            Ok(u64::from_be_bytes(value.try_into().unwrap()))
+        }
+
+        #[query]
+        pub fn version() -> Result<u8, StdError> {
+            Ok(deps.storage.get(VERSION).map_or(0, |x| x[0]))
+        }
+    }
+
+    impl Upgradable for Contract {
+        type Error = StdError;
+
+        #[migrate]
+        fn upgrade(version: u8) -> Result<Response, Self::Error> {
+            deps.storage.set(VERSION, &[version]);
+
+            Ok(Response::default())
+        }
+    }
+}
+
+#[test]
+fn migrate_and_sudo_messages_are_generated() {
+    assert_eq!(
+        to_vec(&counter::MigrateMsg::Upgrade { version: 2 }).unwrap(),
+        br#"{"upgrade":{"version":2}}"#
+    );
+    assert_eq!(
+        to_vec(&counter::SudoMsg::Freeze { reason: "audit".into() }).unwrap(),
+        br#"{"freeze":{"reason":"audit"}}"#
+    );
+}
+
+#[test]
+fn migrate_and_sudo_entry_points_dispatch() {
+    let mut deps = mock_dependencies();
+
+    counter::instantiate(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("admin", &[]),
+        counter::InstantiateMsg { value: 1 }
+    ).unwrap();
+
+    let resp = counter::migrate(
+        deps.as_mut(),
+        mock_env(),
+        counter::MigrateMsg::Reset { value: 5 }
+    ).unwrap();
+    assert_eq!(resp.attributes[0].value, mock_env().block.height.to_string());
+
+    counter::migrate(deps.as_mut(), mock_env(), counter::MigrateMsg::Upgrade { version: 2 }).unwrap();
+
+    let value = counter::query(deps.as_ref(), mock_env(), counter::QueryMsg::Value {}).unwrap();
+    assert_eq!(from_binary::<u64>(&value).unwrap(), 5);
+
+    let version = counter::query(deps.as_ref(), mock_env(), counter::QueryMsg::Version {}).unwrap();
+    assert_eq!(from_binary::<u8>(&version).unwrap(), 2);
+
+    counter::sudo(deps.as_mut(), mock_env(), counter::SudoMsg::Freeze { reason: "audit".into() }).unwrap();
+
+    let err = counter::execute(
+        deps.as_mut(),
+        mock_env(),
+        mock_info("user", &[]),
+        counter::ExecuteMsg::Add { value: 1 }
+    ).unwrap_err();
+    assert_eq!(err.to_string(), "Generic error: Frozen.");
+}
+
+#[test]
+#[cfg(feature = "ensemble")]
+fn migrates_through_the_ensemble() {
+    let mut ensemble = ContractEnsemble::new();
+    let code = ensemble.register(Box::new(counter::Contract));
+
+    ensemble.instantiate_with_admin(
+        code.id,
+        &counter::InstantiateMsg { value: 1 },
+        MockEnv::new("admin", "counter"),
+        "admin"
+    ).unwrap();
+
+    ensemble.migrate(
+        code.id,
+        &counter::MigrateMsg::Upgrade { version: 3 },
+        MockEnv::new("admin", "counter")
+    ).unwrap();
+
+    let version: u8 = ensemble.query("counter", &counter::QueryMsg::Version {}).unwrap();
+    assert_eq!(version, 3);
+}
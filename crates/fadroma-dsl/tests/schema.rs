@@ -0,0 +1,84 @@
+use fadroma::{dsl::*, prelude::*};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct Owner {
+    pub address: String,
+    pub since: u64
+}
+
+#[contract]
+pub mod registry {
+    use fadroma::{dsl::*, prelude::*};
+    use super::Owner;
+
+    impl Contract {
+        #[init(entry)]
+        pub fn init(start: u64) -> Result<Response, StdError> {
+            Ok(Response::default().add_attribute("start", start.to_string()))
+        }
+
+        #[execute]
+        pub fn register(id: u64) -> Result<Response, StdError> {
+            deps.storage.set(&id.to_be_bytes(), info.sender.as_bytes());
+
+            Ok(Response::default())
+        }
+
+        #[query]
+        pub fn owner_of(id: u64) -> Result<Owner, StdError> {
+            let address = deps.storage.get(&id.to_be_bytes())
+                .ok_or_else(|| StdError::not_found("owner"))?;
+
+            Ok(Owner { address: String::from_utf8(address)?, since: env.block.height })
+        }
+
+        #[query]
+        pub fn count() -> Result<u64, StdError> {
+            Ok(deps.storage.range(None, None, Order::Ascending).count() as u64)
+        }
+
+        #[query]
+        pub fn get_2fa() -> Result<bool, StdError> {
+            Ok(false)
+        }
+
+        #[migrate]
+        pub fn reset() -> Result<Response, StdError> {
+            Ok(Response::default())
+        }
+    }
+}
+
+#[test]
+fn api_schema_describes_the_contract() {
+    let api = serde_json::to_value(registry::api_schema()).unwrap();
+
+    assert_eq!(api["contract_name"], env!("CARGO_PKG_NAME"));
+    assert_eq!(api["contract_version"], env!("CARGO_PKG_VERSION"));
+    assert_eq!(api["idl_version"], fadroma::schema::IDL_VERSION);
+
+    assert_eq!(api["instantiate"]["title"], "InstantiateMsg");
+    assert_eq!(api["instantiate"]["properties"]["start"]["type"], "integer");
+    assert_eq!(api["execute"]["title"], "ExecuteMsg");
+    assert_eq!(api["migrate"]["title"], "MigrateMsg");
+    assert!(api["sudo"].is_null());
+
+    let queries: Vec<_> = api["query"]["oneOf"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|x| x["required"][0].as_str().unwrap())
+        .collect();
+    assert_eq!(queries, ["owner_of", "count", "get2fa"]);
+
+    let responses = api["responses"].as_object().unwrap();
+    assert_eq!(responses.len(), 3);
+    assert_eq!(responses["count"]["type"], "integer");
+    assert_eq!(responses["owner_of"]["title"], "Owner");
+    assert_eq!(responses["owner_of"]["properties"]["since"]["type"], "integer");
+    assert_eq!(responses["get2fa"]["type"], "boolean");
+
+    let msg = serde_json::to_value(registry::QueryMsg::Get2fa {}).unwrap();
+    assert!(responses.contains_key(msg.as_object().unwrap().keys().next().unwrap()));
+}
@@ -0,0 +1,19 @@
+#[fadroma::dsl::contract]
+pub mod contract {
+    use fadroma::{dsl::*, prelude::*};
+
+    impl Contract {
+        #[init(entry)]
+        pub fn init() -> Result<Response, StdError> {
+            Ok(Response::default())
+        }
+
+        #[query]
+        #[nonpayable]
+        pub fn balance() -> Result<u64, StdError> {
+            Ok(0)
+        }
+    }
+}
+
+fn main() { }
@@ -0,0 +1,18 @@
+#[fadroma::dsl::contract]
+pub mod contract {
+    use fadroma::{dsl::*, prelude::*};
+
+    impl Contract {
+        #[init(entry)]
+        pub fn init() -> Result<Response, StdError> {
+            Ok(Response::default())
+        }
+
+        #[payable(denom = "uscrt")]
+        pub fn deposit() -> Result<Response, StdError> {
+            Ok(Response::default())
+        }
+    }
+}
+
+fn main() { }
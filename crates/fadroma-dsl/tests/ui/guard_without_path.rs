@@ -0,0 +1,19 @@
+#[fadroma::dsl::contract]
+pub mod contract {
+    use fadroma::{dsl::*, prelude::*};
+
+    impl Contract {
+        #[init(entry)]
+        pub fn init() -> Result<Response, StdError> {
+            Ok(Response::default())
+        }
+
+        #[execute]
+        #[guard]
+        pub fn withdraw() -> Result<Response, StdError> {
+            Ok(Response::default())
+        }
+    }
+}
+
+fn main() { }
@@ -0,0 +1,12 @@
+use fadroma::{dsl::*, prelude::*};
+
+#[interface]
+pub trait Freezable {
+    type Error: std::fmt::Display;
+
+    #[sudo]
+    #[query]
+    fn freeze(reason: String) -> Result<Response, Self::Error>;
+}
+
+fn main() {}
@@ -0,0 +1,21 @@
+use fadroma::dsl::*;
+
+#[contract]
+pub mod contract {
+    use fadroma::{dsl::*, prelude::*};
+
+    impl Contract {
+        #[init(entry)]
+        pub fn new() -> Result<Response, StdError> {
+            Ok(Response::default())
+        }
+
+        #[migrate]
+        #[execute]
+        pub fn upgrade() -> Result<Response, StdError> {
+            Ok(Response::default())
+        }
+    }
+}
+
+fn main() {}
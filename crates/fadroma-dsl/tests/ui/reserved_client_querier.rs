@@ -0,0 +1,11 @@
+use fadroma::{dsl::*, prelude::*};
+
+#[interface]
+pub trait Balances {
+    type Error: std::fmt::Display;
+
+    #[query]
+    fn balance(querier: String) -> Result<u64, Self::Error>;
+}
+
+fn main() {}
@@ -0,0 +1,20 @@
+use fadroma::dsl::*;
+
+#[contract]
+pub mod contract {
+    use fadroma::{dsl::*, prelude::*};
+
+    impl Contract {
+        #[init(entry)]
+        pub fn init() -> Result<Response, StdError> {
+            Ok(Response::default())
+        }
+
+        #[execute]
+        pub fn transfer(sender: String) -> Result<Response, StdError> {
+            Ok(Response::default())
+        }
+    }
+}
+
+fn main() {}
@@ -0,0 +1,15 @@
+use fadroma::dsl::*;
+
+#[contract]
+pub mod contract {
+    use fadroma::{dsl::*, prelude::*};
+
+    impl Contract {
+        #[init(entry)]
+        pub fn init(code_id: u64) -> Result<Response, StdError> {
+            Ok(Response::default())
+        }
+    }
+}
+
+fn main() {}
@@ -0,0 +1,20 @@
+use fadroma::dsl::*;
+
+#[contract]
+pub mod contract {
+    use fadroma::{dsl::*, prelude::*};
+
+    impl Contract {
+        #[init(entry)]
+        pub fn init() -> Result<Response, StdError> {
+            Ok(Response::default())
+        }
+
+        #[query]
+        pub fn balance(ensemble: String) -> Result<u64, StdError> {
+            Ok(0)
+        }
+    }
+}
+
+fn main() {}
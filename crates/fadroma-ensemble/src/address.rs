@@ -0,0 +1,180 @@
+use bech32::{FromBase32, ToBase32, Variant};
+use sha2::{Sha256, Digest};
+use fadroma::cosmwasm_std::{Addr, CanonicalAddr, StdResult, StdError};
+
+use super::env::MockEnv;
+
+/// Generates and validates bech32 addresses with a fixed human readable prefix
+/// i.e `secret` for `secret1...` addresses. Enabled with
+/// [`crate::ContractEnsemble::set_bech32_prefix`], after which new contract
+/// instances get an address that is derived the same way as on chain
+/// instead of using their label as the address.
+///
+/// This only changes the addresses that the ensemble assigns and accepts.
+/// Contracts still get `cosmwasm_std::testing::MockApi` as their `deps.api`,
+/// so addresses that a contract validates itself are checked as leniently
+/// as in unit tests. The `addr_*` methods of this type behave like the `Api`
+/// of a real chain and are what the ensemble uses to validate the recipients
+/// of funds and contract admins.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Bech32Addresses {
+    prefix: String
+}
+
+impl Bech32Addresses {
+    /// The length in bytes of the generated addresses. Secret Network uses
+    /// 20 byte addresses for both accounts and contracts.
+    pub const ADDRESS_LEN: usize = 20;
+
+    /// Creates a new instance that uses the given prefix.
+    ///
+    /// # Panics
+    ///
+    /// If the prefix is not a valid lower case bech32 prefix or is too long
+    /// for the resulting addresses to fit into [`MockEnv::MAX_ADDRESS_LEN`].
+    pub fn new(prefix: impl Into<String>) -> Self {
+        let prefix = prefix.into();
+
+        assert!(
+            prefix.to_lowercase() == prefix &&
+                bech32::encode(&prefix, Vec::new(), Variant::Bech32).is_ok(),
+            "Ensemble: \"{}\" is not a valid bech32 prefix.",
+            prefix
+        );
+
+        let result = Self { prefix };
+
+        assert!(
+            result.encode(&[0; Self::ADDRESS_LEN]).len() <= MockEnv::MAX_ADDRESS_LEN,
+            "Ensemble: the bech32 prefix \"{}\" is too long.",
+            result.prefix
+        );
+
+        result
+    }
+
+    /// The human readable prefix of the addresses.
+    #[inline]
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    /// Derives the address of a user account from an arbitrary name.
+    /// The same name always results in the same address, which is
+    /// useful for creating senders in tests.
+    pub fn account(&self, name: impl AsRef<str>) -> String {
+        let hash = Sha256::digest(name.as_ref().as_bytes());
+
+        self.encode(&hash[..Self::ADDRESS_LEN])
+    }
+
+    /// Derives the address of the contract with the given code id and
+    /// global instance sequence number, starting from 1, the same way
+    /// that `wasmd` does for regular instantiations.
+    pub fn contract_address(&self, code_id: u64, instance_id: u64) -> String {
+        let mut key = Vec::with_capacity(16);
+        key.extend_from_slice(&code_id.to_be_bytes());
+        key.extend_from_slice(&instance_id.to_be_bytes());
+
+        self.encode(&module_address("wasm", &key))
+    }
+
+    /// Derives the address of the contract instantiated by `creator` from the
+    /// code with the given `checksum` using `salt`, the same way that `wasmd`
+    /// does for `MsgInstantiateContract2`. The address doesn't depend on the
+    /// number of instances, so it can be known ahead of time.
+    ///
+    /// Returns an error if the creator is not a valid address or the salt
+    /// is not between 1 and 64 bytes long.
+    pub fn predictable_address(
+        &self,
+        checksum: &[u8],
+        creator: &str,
+        salt: &[u8]
+    ) -> StdResult<String> {
+        if salt.is_empty() || salt.len() > 64 {
+            return Err(StdError::generic_err("Salt must be between 1 and 64 bytes long."));
+        }
+
+        let creator = self.addr_canonicalize(creator)?;
+
+        let mut key = Vec::new();
+
+        for component in [checksum, creator.as_slice(), salt, &[]] {
+            key.extend_from_slice(&(component.len() as u64).to_be_bytes());
+            key.extend_from_slice(component);
+        }
+
+        Ok(self.encode(&module_address("wasm", &key)))
+    }
+
+    /// Same as `Api::addr_validate`. Only normalized (all lower case)
+    /// addresses with the configured prefix are valid.
+    pub fn addr_validate(&self, input: &str) -> StdResult<Addr> {
+        let canonical = self.addr_canonicalize(input)?;
+
+        if self.addr_humanize(&canonical)? != input {
+            return Err(StdError::generic_err(format!(
+                "Invalid address {}: address not normalized",
+                input
+            )));
+        }
+
+        Ok(Addr::unchecked(input))
+    }
+
+    /// Same as `Api::addr_canonicalize`. Decodes the bech32 address into
+    /// its raw bytes after checking its prefix and checksum.
+    pub fn addr_canonicalize(&self, input: &str) -> StdResult<CanonicalAddr> {
+        let invalid = |reason: &dyn std::fmt::Display|
+            StdError::generic_err(format!("Invalid address {}: {}", input, reason));
+
+        let (prefix, data, variant) = bech32::decode(input).map_err(|err| invalid(&err))?;
+
+        if prefix != self.prefix {
+            return Err(invalid(&format!("expected prefix \"{}\", got \"{}\"", self.prefix, prefix)));
+        }
+
+        if variant != Variant::Bech32 {
+            return Err(invalid(&"expected bech32 encoding, got bech32m"));
+        }
+
+        let bytes = Vec::<u8>::from_base32(&data).map_err(|err| invalid(&err))?;
+
+        if bytes.is_empty() || bytes.len() > 255 {
+            return Err(invalid(&"address must be between 1 and 255 bytes long"));
+        }
+
+        Ok(bytes.into())
+    }
+
+    /// Same as `Api::addr_humanize`. Encodes the raw bytes
+    /// as a bech32 address with the configured prefix.
+    pub fn addr_humanize(&self, canonical: &CanonicalAddr) -> StdResult<Addr> {
+        if canonical.is_empty() || canonical.len() > 255 {
+            return Err(StdError::generic_err(
+                "Invalid canonical address: must be between 1 and 255 bytes long"
+            ));
+        }
+
+        Ok(Addr::unchecked(self.encode(canonical.as_slice())))
+    }
+
+    #[inline]
+    fn encode(&self, data: &[u8]) -> String {
+        // Only fails if the prefix is invalid which we check in Self::new()
+        bech32::encode(&self.prefix, data.to_base32(), Variant::Bech32).unwrap()
+    }
+}
+
+/// The address of the given key under a module account, same as the Cosmos SDK
+/// `address.Module` function, truncated to [`Bech32Addresses::ADDRESS_LEN`] bytes.
+fn module_address(module: &str, key: &[u8]) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update(Sha256::digest(b"module"));
+    hasher.update(module.as_bytes());
+    hasher.update([0]);
+    hasher.update(key);
+
+    hasher.finalize()[..Bech32Addresses::ADDRESS_LEN].to_vec()
+}
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, BTreeMap};
 
 use fadroma::cosmwasm_std::{Uint128, Coin, coin};
 use super::{
@@ -7,29 +7,101 @@ use super::{
 
 pub type Balances = HashMap<String, Uint128>;
 
+/// The prefix of denoms created with [`Bank::create_denom`].
+pub(crate) const FACTORY_PREFIX: &str = "factory";
+const MAX_SUBDENOM_LEN: usize = 44;
+
 #[derive(Clone, Default, Debug)]
-pub(crate) struct Bank(pub HashMap<String, Balances>);
+pub(crate) struct Bank {
+    pub accounts: HashMap<String, Balances>,
+    /// The admins of token factory denoms by denom.
+    pub denoms: BTreeMap<String, String>,
+    /// The total supply of each denom. Only minting, burning
+    /// and slashing change it, moving funds around doesn't.
+    pub supply: BTreeMap<String, Uint128>
+}
 
 impl Bank {
     pub fn add_funds(&mut self, address: &str, coin: Coin) {
         self.assert_account_exists(address);
 
-        let account = self.0.get_mut(address).unwrap();
+        let account = self.accounts.get_mut(address).unwrap();
         add_balance(account, coin);
     }
 
+    /// Adds the given funds to `address` and increases the total supply.
+    pub fn mint(&mut self, address: &str, coin: Coin) {
+        *self.supply.entry(coin.denom.clone()).or_default() += coin.amount;
+        self.add_funds(address, coin);
+    }
+
+    /// Removes the given funds from `address` and decreases the total supply.
+    pub fn burn(&mut self, address: &str, coin: Coin) -> EnsembleResult<()> {
+        self.remove_funds(address, coin.clone())?;
+        self.reduce_supply(coin);
+
+        Ok(())
+    }
+
+    /// Decreases the total supply without touching any balances.
+    /// Used for funds that are destroyed while not being held
+    /// in an account, such as slashed delegations.
+    pub fn reduce_supply(&mut self, coin: Coin) {
+        if let Some(supply) = self.supply.get_mut(&coin.denom) {
+            *supply = supply.saturating_sub(coin.amount);
+        }
+    }
+
+    /// The total supply of the given denom.
+    pub fn supply(&self, denom: &str) -> Uint128 {
+        self.supply.get(denom).copied().unwrap_or_default()
+    }
+
+    /// Creates a token factory denom in the form of `factory/{creator}/{subdenom}`
+    /// and makes `creator` its admin.
+    pub fn create_denom(&mut self, creator: &str, subdenom: &str) -> EnsembleResult<String> {
+        let valid = !subdenom.is_empty() &&
+            subdenom.len() <= MAX_SUBDENOM_LEN &&
+            subdenom.chars().all(|x| x.is_ascii_alphanumeric() || matches!(x, '.' | '_' | '-'));
+
+        if !valid {
+            return Err(EnsembleError::Bank(format!("Invalid subdenom: \"{}\"", subdenom)));
+        }
+
+        let denom = format!("{}/{}/{}", FACTORY_PREFIX, creator, subdenom);
+
+        if self.denoms.contains_key(&denom) {
+            return Err(EnsembleError::Bank(format!("Denom {} already exists", denom)));
+        }
+
+        self.denoms.insert(denom.clone(), creator.into());
+
+        Ok(denom)
+    }
+
+    /// Returns an error if `denom` is a token factory denom that hasn't been created.
+    pub fn assert_denom_exists(&self, denom: &str) -> EnsembleResult<()> {
+        let is_factory = denom.split('/').next() == Some(FACTORY_PREFIX);
+
+        if is_factory && !self.denoms.contains_key(denom) {
+            return Err(EnsembleError::Bank(format!("Denom {} does not exist", denom)));
+        }
+
+        Ok(())
+    }
+
     pub fn remove_funds(
         &mut self, 
         address: &str, 
         coin: Coin
     ) -> EnsembleResult<()> {
-        if !self.0.contains_key(address) {
+        if !self.accounts.contains_key(address) {
             return Err(EnsembleError::Bank(
                 format!("Account {} does not exist for remove balance", address)
             ))
         }
 
-        let account = self.0.get_mut(address).unwrap();
+        let account = self.accounts.get_mut(address).unwrap();
         let balance = account.get_mut(&coin.denom);
     
         match balance {
@@ -70,7 +142,7 @@ impl Bank {
         self.assert_account_exists(to);
 
         let amount = self
-            .0
+            .accounts
             .get_mut(from)
             .unwrap()
             .get_mut(&coin.denom)
@@ -91,13 +163,13 @@ impl Bank {
             ))
         })?;
 
-        add_balance(self.0.get_mut(to).unwrap(), coin);
+        add_balance(self.accounts.get_mut(to).unwrap(), coin);
 
         Ok(())
     }
 
     pub fn query_balances(&self, address: &str, denom: Option<String>) -> Vec<Coin> {
-        let account = self.0.get(address);
+        let account = self.accounts.get(address);
 
         match account {
             Some(account) => match denom {
@@ -124,8 +196,8 @@ impl Bank {
     }
 
     fn assert_account_exists(&mut self, address: &str) {
-        if !self.0.contains_key(address) {
-            self.0.insert(address.to_string(), Default::default());
+        if !self.accounts.contains_key(address) {
+            self.accounts.insert(address.to_string(), Default::default());
         }
     }
 }
@@ -1,7 +1,11 @@
-use std::ops::Range;
+use std::ops::{Range, Deref, DerefMut};
 use oorandom::Rand64;
+use serde::{Serialize, Deserialize};
+use fadroma::cosmwasm_std::Timestamp;
 
-#[derive(Clone, Debug)]
+use super::ensemble::Context;
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct Block {
     pub height: u64,
     pub time: u64,
@@ -9,7 +13,7 @@ pub struct Block {
     is_frozen: bool
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Serialize, Deserialize, Debug)]
 enum BlockIncrement {
     Random {
         height: Range<u64>,
@@ -57,6 +61,31 @@ impl Block {
         self.is_frozen = false;
     }
 
+    /// Returns `true` if blocks are not being incremented.
+    #[inline]
+    pub fn is_frozen(&self) -> bool {
+        self.is_frozen
+    }
+
+    /// Sets the block time. Unlike on a real chain, the time can also be
+    /// moved back. To advance the time block by block and run any scheduled
+    /// hooks, use [`crate::ContractEnsemble::fast_forward_to_time`] instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fadroma_ensemble::{Block, cosmwasm_std::Timestamp};
+    ///
+    /// let mut block = Block::default();
+    /// block.set_time(Timestamp::from_seconds(1700000000));
+    ///
+    /// assert_eq!(block.time, 1700000000);
+    /// ```
+    #[inline]
+    pub fn set_time(&mut self, time: Timestamp) {
+        self.time = time.seconds();
+    }
+
     /// Increments the block height and time by the amount configured - once.
     ///  
     /// # Examples
@@ -105,6 +134,11 @@ impl Block {
             return;
         }
 
+        self.advance(times);
+    }
+
+    /// Same as [`Block::increment`] but also advances frozen blocks.
+    pub(crate) fn advance(&mut self, times: u64) {
         match self.incr.clone() {
             BlockIncrement::Exact { height, time } => {
                 let height = height * times;
@@ -128,6 +162,37 @@ impl Block {
     }
 }
 
+/// A mutable reference to the current block, returned by
+/// [`crate::ContractEnsemble::block_mut`]. Any state that depends on the
+/// block time, such as unbondings that have completed, is brought up to
+/// date with the block once it's dropped.
+pub struct BlockMut<'a> {
+    pub(crate) ctx: &'a mut Context
+}
+
+impl Deref for BlockMut<'_> {
+    type Target = Block;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.ctx.block
+    }
+}
+
+impl DerefMut for BlockMut<'_> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.ctx.block
+    }
+}
+
+impl Drop for BlockMut<'_> {
+    fn drop(&mut self) {
+        #[cfg(feature = "staking")]
+        self.ctx.complete_unbondings();
+    }
+}
+
 impl Default for Block {
     fn default() -> Self {
         Self {
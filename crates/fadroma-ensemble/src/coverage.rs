@@ -0,0 +1,379 @@
+use std::{
+    fs,
+    path::Path,
+    cell::RefCell,
+    collections::{HashMap, BTreeMap},
+    fmt::{Display, Write}
+};
+
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+use fadroma::{
+    schemars::{JsonSchema, gen::SchemaGenerator},
+    cosmwasm_std::Binary
+};
+
+use super::{EnsembleResult, EnsembleError};
+
+/// Which message variants of the tracked contracts were executed or
+/// queried and which errors they returned. Created by
+/// [`crate::ContractEnsemble::coverage`].
+///
+/// Each test usually creates its own ensemble, so reports can be
+/// combined with [`CoverageReport::merge`] or written to a file that
+/// is shared by the whole test suite with [`CoverageReport::merge_into_file`].
+/// Contracts are identified by the name that they were tracked with.
+///
+/// It's printed as a text summary and can be serialized as JSON
+/// with [`CoverageReport::to_json`].
+///
+/// # Examples
+///
+/// ```
+/// # use fadroma_ensemble::{ContractEnsemble, ContractHarness, MockEnv, AnyResult, anyhow::bail};
+/// # use fadroma::{prelude::*, schemars};
+/// # use serde::{Serialize, Deserialize};
+/// #[derive(Serialize, Deserialize, JsonSchema)]
+/// #[serde(rename_all = "snake_case")]
+/// enum ExecuteMsg {
+///     Increment {},
+///     Reset {}
+/// }
+///
+/// #[derive(Serialize, Deserialize, JsonSchema)]
+/// #[serde(rename_all = "snake_case")]
+/// enum QueryMsg {
+///     Count {}
+/// }
+///
+/// struct Counter;
+///
+/// impl ContractHarness for Counter {
+///     // ...
+/// #   fn instantiate(&self, _deps: DepsMut, _env: Env, _info: MessageInfo, _msg: Binary) -> AnyResult<Response> {
+/// #       Ok(Response::default())
+/// #   }
+/// #
+/// #   fn execute(&self, _deps: DepsMut, _env: Env, _info: MessageInfo, msg: Binary) -> AnyResult<Response> {
+/// #       match from_binary(&msg)? {
+/// #           ExecuteMsg::Increment {} => bail!("Unauthorized"),
+/// #           ExecuteMsg::Reset {} => Ok(Response::default())
+/// #       }
+/// #   }
+/// #
+/// #   fn query(&self, _deps: Deps, _env: Env, _msg: Binary) -> AnyResult<Binary> {
+/// #       Ok(to_binary(&0u64)?)
+/// #   }
+/// }
+///
+/// let mut ensemble = ContractEnsemble::new();
+/// let counter = ensemble.register(Box::new(Counter));
+/// ensemble.track_coverage::<ExecuteMsg, QueryMsg>("counter", counter.id);
+///
+/// ensemble.instantiate(counter.id, &(), MockEnv::new("admin", "counter")).unwrap();
+/// ensemble.execute(&ExecuteMsg::Increment {}, MockEnv::new("admin", "counter")).unwrap_err();
+///
+/// let report = ensemble.coverage();
+/// let counter = &report.contracts["counter"];
+///
+/// assert_eq!(counter.execute["increment"].errors["Unauthorized"], 1);
+/// assert_eq!(counter.uncovered_execute().collect::<Vec<_>>(), ["reset"]);
+/// assert_eq!(counter.uncovered_query().collect::<Vec<_>>(), ["count"]);
+///
+/// println!("{}", report);
+/// ```
+#[derive(Serialize, Deserialize, Clone, PartialEq, Default, Debug)]
+pub struct CoverageReport {
+    pub contracts: BTreeMap<String, ContractCoverage>
+}
+
+/// The coverage of the execute and query messages of a single contract.
+/// The variants are keyed by their name in the JSON encoding of the message.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Default, Debug)]
+pub struct ContractCoverage {
+    pub execute: BTreeMap<String, VariantCoverage>,
+    pub query: BTreeMap<String, VariantCoverage>
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Default, Debug)]
+pub struct VariantCoverage {
+    /// The number of times that the variant was executed or queried,
+    /// including the calls that returned an error.
+    pub calls: u64,
+    /// The number of times that each error was returned.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub errors: BTreeMap<String, u64>
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum EntryPoint {
+    Execute,
+    Query
+}
+
+/// Records the calls to the tracked contracts.
+#[derive(Default, Debug)]
+pub(crate) struct CoverageTracker {
+    /// The names of the tracked contracts by code id.
+    names: HashMap<usize, String>,
+    /// Query is called with a shared reference to the context.
+    report: RefCell<CoverageReport>
+}
+
+impl CoverageReport {
+    /// Adds the calls and errors recorded in `other` to this report.
+    /// Variants that are only declared in one of the reports are kept.
+    pub fn merge(&mut self, other: &CoverageReport) {
+        for (name, contract) in &other.contracts {
+            let entry = self.contracts.entry(name.clone()).or_default();
+
+            merge_variants(&mut entry.execute, &contract.execute);
+            merge_variants(&mut entry.query, &contract.query);
+        }
+    }
+
+    /// Serializes the report as pretty printed JSON.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    /// Parses a report that was serialized with [`CoverageReport::to_json`].
+    pub fn from_json(json: &str) -> EnsembleResult<Self> {
+        serde_json::from_str(json).map_err(|err|
+            EnsembleError::Coverage(format!("Invalid report: {}", err))
+        )
+    }
+
+    /// Writes the report as JSON to the file at `path`, replacing it if it exists.
+    pub fn save(&self, path: impl AsRef<Path>) -> EnsembleResult<()> {
+        let path = path.as_ref();
+
+        fs::write(path, self.to_json()).map_err(|err|
+            EnsembleError::Coverage(format!("Couldn't write {}: {}", path.display(), err))
+        )
+    }
+
+    /// Reads a report from the file at `path` that was created
+    /// using [`CoverageReport::save`].
+    pub fn load(path: impl AsRef<Path>) -> EnsembleResult<Self> {
+        let path = path.as_ref();
+
+        let json = fs::read_to_string(path).map_err(|err|
+            EnsembleError::Coverage(format!("Couldn't read {}: {}", path.display(), err))
+        )?;
+
+        Self::from_json(&json)
+    }
+
+    /// Merges the report with the one in the file at `path`, if it exists,
+    /// and writes the result back to it. Returns the merged report.
+    ///
+    /// Useful for collecting the coverage of a whole test suite. Since the
+    /// file isn't locked, tests that call this must not run in parallel.
+    pub fn merge_into_file(&self, path: impl AsRef<Path>) -> EnsembleResult<Self> {
+        let path = path.as_ref();
+
+        let mut report = if path.exists() {
+            Self::load(path)?
+        } else {
+            Self::default()
+        };
+
+        report.merge(self);
+        report.save(path)?;
+
+        Ok(report)
+    }
+}
+
+impl ContractCoverage {
+    /// Returns the execute variants that were never called.
+    pub fn uncovered_execute(&self) -> impl Iterator<Item = &str> {
+        uncovered(&self.execute)
+    }
+
+    /// Returns the query variants that were never called.
+    pub fn uncovered_query(&self) -> impl Iterator<Item = &str> {
+        uncovered(&self.query)
+    }
+
+    /// Returns the percentage of execute and query variants that were called.
+    pub fn percentage(&self) -> f64 {
+        let total = self.execute.len() + self.query.len();
+
+        if total == 0 {
+            return 100.;
+        }
+
+        let covered = self.execute.values()
+            .chain(self.query.values())
+            .filter(|x| x.calls > 0)
+            .count();
+
+        covered as f64 * 100. / total as f64
+    }
+}
+
+impl CoverageTracker {
+    pub fn track<E: JsonSchema, Q: JsonSchema>(&mut self, name: String, code_id: usize) {
+        let mut report = self.report.borrow_mut();
+        let contract = report.contracts.entry(name.clone()).or_default();
+
+        for variant in variants_of::<E>() {
+            contract.execute.entry(variant).or_default();
+        }
+
+        for variant in variants_of::<Q>() {
+            contract.query.entry(variant).or_default();
+        }
+
+        self.names.insert(code_id, name);
+    }
+
+    pub fn record<T>(
+        &self,
+        code_id: usize,
+        entry: EntryPoint,
+        msg: &Binary,
+        result: &anyhow::Result<T>
+    ) {
+        let Some(name) = self.names.get(&code_id) else {
+            return;
+        };
+
+        let variant = variant_of(msg);
+
+        let mut report = self.report.borrow_mut();
+        let contract = report.contracts.get_mut(name).unwrap();
+
+        let coverage = match entry {
+            EntryPoint::Execute => contract.execute.entry(variant).or_default(),
+            EntryPoint::Query => contract.query.entry(variant).or_default()
+        };
+
+        coverage.calls += 1;
+
+        if let Err(err) = result {
+            *coverage.errors.entry(err.to_string()).or_default() += 1;
+        }
+    }
+
+    #[inline]
+    pub fn report(&self) -> CoverageReport {
+        self.report.borrow().clone()
+    }
+}
+
+impl Display for CoverageReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.contracts.is_empty() {
+            return f.write_str("No contracts are tracked.\n");
+        }
+
+        for (name, contract) in &self.contracts {
+            writeln!(f, "{} ({:.1}%)", name, contract.percentage())?;
+
+            write_variants(f, "execute", &contract.execute)?;
+            write_variants(f, "query", &contract.query)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn write_variants(
+    f: &mut std::fmt::Formatter<'_>,
+    entry: &str,
+    variants: &BTreeMap<String, VariantCoverage>
+) -> std::fmt::Result {
+    if variants.is_empty() {
+        return Ok(());
+    }
+
+    let covered = variants.values().filter(|x| x.calls > 0).count();
+    writeln!(f, "  {}: {}/{} variants", entry, covered, variants.len())?;
+
+    let width = variants.keys().map(|x| x.len()).max().unwrap_or(0);
+
+    for (variant, coverage) in variants {
+        let mut line = String::new();
+
+        if coverage.calls == 0 {
+            write!(line, "    [ ] {:width$}  never called", variant)?;
+        } else {
+            let errors: u64 = coverage.errors.values().sum();
+            write!(line, "    [x] {:width$}  {} call(s)", variant, coverage.calls)?;
+
+            if errors > 0 {
+                write!(line, ", {} error(s)", errors)?;
+            }
+        }
+
+        writeln!(f, "{}", line)?;
+
+        for (error, count) in &coverage.errors {
+            writeln!(f, "          {} x{}", error, count)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn merge_variants(
+    into: &mut BTreeMap<String, VariantCoverage>,
+    from: &BTreeMap<String, VariantCoverage>
+) {
+    for (variant, coverage) in from {
+        let entry = into.entry(variant.clone()).or_default();
+        entry.calls += coverage.calls;
+
+        for (error, count) in &coverage.errors {
+            *entry.errors.entry(error.clone()).or_default() += count;
+        }
+    }
+}
+
+fn uncovered(variants: &BTreeMap<String, VariantCoverage>) -> impl Iterator<Item = &str> {
+    variants.iter()
+        .filter(|(_, x)| x.calls == 0)
+        .map(|(variant, _)| variant.as_str())
+}
+
+/// Unit variants are encoded as a string and the rest
+/// as an object with the variant name as its only key.
+fn variant_of(msg: &Binary) -> String {
+    match serde_json::from_slice(msg.as_slice()) {
+        Ok(Value::String(variant)) => variant,
+        Ok(Value::Object(map)) if map.len() == 1 => map.keys().next().unwrap().clone(),
+        _ => "(invalid)".into()
+    }
+}
+
+fn variants_of<T: JsonSchema>() -> Vec<String> {
+    let schema = SchemaGenerator::default().into_root_schema_for::<T>();
+    let schema = serde_json::to_value(schema.schema).unwrap();
+
+    let mut result = vec![];
+    collect_variants(&schema, &mut result);
+
+    result
+}
+
+fn collect_variants(schema: &Value, result: &mut Vec<String>) {
+    if let Some(values) = schema.get("enum").and_then(|x| x.as_array()) {
+        result.extend(values.iter().filter_map(|x| x.as_str()).map(String::from));
+    } else if let Some(schemas) = schema.get("oneOf").or_else(|| schema.get("anyOf")) {
+        for schema in schemas.as_array().into_iter().flatten() {
+            collect_variants(schema, result);
+        }
+    } else if let Some(properties) = schema.get("properties").and_then(|x| x.as_object()) {
+        // Variants with fields are objects with the variant name as their only
+        // property. Unlike structs, they never allow additional properties.
+        let is_variant = properties.len() == 1 &&
+            schema.get("additionalProperties") == Some(&Value::Bool(false));
+
+        if is_variant {
+            result.extend(properties.keys().cloned());
+        }
+    }
+}
@@ -8,38 +8,56 @@ use serde::{
 };
 use fadroma::{
     prelude::{ContractCode, ContractLink},
+    schemars::JsonSchema,
     cosmwasm_std::{
         SubMsg, Deps, DepsMut, Env, Response, MessageInfo, Binary, Coin, Empty,
         CosmosMsg, WasmMsg, BlockInfo, ContractInfo, BankMsg, Timestamp, Addr,
-        SubMsgResponse, SubMsgResult, Reply, Storage, Api, Querier, QuerierWrapper,
-        from_binary, to_binary, testing::MockApi
+        SubMsgResponse, SubMsgResult, Reply, Storage, Api, Querier, QuerierWrapper, QueryRequest,
+        Uint128, from_binary, to_binary, testing::MockApi
     }
 };
 
 use oorandom::Rand64;
+use sha2::{Sha256, Digest};
 
-#[cfg(feature = "ensemble-staking")]
+#[cfg(feature = "staking")]
 use fadroma::cosmwasm_std::{
-    Uint128, FullDelegation, Validator, Delegation, StakingMsg, DistributionMsg
+    FullDelegation, Validator, Delegation, StakingMsg, DistributionMsg, Decimal
 };
 
 use super::{
     bank::Balances,
-    block::Block,
+    block::{Block, BlockMut},
     env::MockEnv,
     querier::EnsembleQuerier,
     response::{
-        ResponseVariants, ExecuteResponse,
-        InstantiateResponse, ReplyResponse
+        ResponseVariants, ExecuteResponse, InstantiateResponse,
+        ReplyResponse, MigrateResponse, AdminResponse, ModuleMsgResponse,
+        BurnResponse
     },
     state::State,
+    snapshot::Snapshot,
+    module::{Module, ModuleDeps, ModuleDepsMut},
+    gas::{GasMeter, GasCosts, MeteredStorage, MeteredStorageRef},
+    trace::Trace,
+    address::Bech32Addresses,
+    mock::{ContractMock, MockId, MockCall, MockCallKind},
+    schedule::{Schedule, HookId, Hook},
+    failure::{Failure, FailureId},
+    coverage::{CoverageTracker, CoverageReport, EntryPoint},
     execution_state::{ExecutionState, MessageType},
     error::{EnsembleError, RegistryError},
     event::ProcessedEvents
 };
 
-#[cfg(feature = "ensemble-staking")]
-use super::staking::Delegations;
+#[cfg(feature = "staking")]
+use super::staking::{Delegations, Unbonding};
+
+#[cfg(feature = "ibc")]
+use fadroma::cosmwasm_std::{
+    IbcChannelOpenMsg, Ibc3ChannelOpenResponse, IbcChannelConnectMsg, IbcChannelCloseMsg,
+    IbcPacketReceiveMsg, IbcPacketAckMsg, IbcPacketTimeoutMsg, IbcBasicResponse, IbcReceiveResponse
+};
 
 pub type AnyResult<T> = anyhow::Result<T>;
 pub type EnsembleResult<T> = core::result::Result<T, EnsembleError>;
@@ -61,6 +79,45 @@ pub trait ContractHarness {
     fn reply(&self, _deps: DepsMut, _env: Env, _reply: Reply) -> AnyResult<Response> {
         panic!("Reply entry point not implemented.")
     }
+
+    fn migrate(&self, _deps: DepsMut, _env: Env, _msg: Binary) -> AnyResult<Response> {
+        panic!("Migrate entry point not implemented.")
+    }
+
+    #[cfg(feature = "ibc")]
+    fn ibc_channel_open(
+        &self,
+        _deps: DepsMut,
+        _env: Env,
+        _msg: IbcChannelOpenMsg
+    ) -> AnyResult<Option<Ibc3ChannelOpenResponse>> {
+        panic!("IBC channel open entry point not implemented.")
+    }
+
+    #[cfg(feature = "ibc")]
+    fn ibc_channel_connect(&self, _deps: DepsMut, _env: Env, _msg: IbcChannelConnectMsg) -> AnyResult<IbcBasicResponse> {
+        panic!("IBC channel connect entry point not implemented.")
+    }
+
+    #[cfg(feature = "ibc")]
+    fn ibc_channel_close(&self, _deps: DepsMut, _env: Env, _msg: IbcChannelCloseMsg) -> AnyResult<IbcBasicResponse> {
+        panic!("IBC channel close entry point not implemented.")
+    }
+
+    #[cfg(feature = "ibc")]
+    fn ibc_packet_receive(&self, _deps: DepsMut, _env: Env, _msg: IbcPacketReceiveMsg) -> AnyResult<IbcReceiveResponse> {
+        panic!("IBC packet receive entry point not implemented.")
+    }
+
+    #[cfg(feature = "ibc")]
+    fn ibc_packet_ack(&self, _deps: DepsMut, _env: Env, _msg: IbcPacketAckMsg) -> AnyResult<IbcBasicResponse> {
+        panic!("IBC packet ack entry point not implemented.")
+    }
+
+    #[cfg(feature = "ibc")]
+    fn ibc_packet_timeout(&self, _deps: DepsMut, _env: Env, _msg: IbcPacketTimeoutMsg) -> AnyResult<IbcBasicResponse> {
+        panic!("IBC packet timeout entry point not implemented.")
+    }
 }
 
 /// This the main type in the system that takes care of registering and executing contracts,
@@ -151,16 +208,32 @@ pub struct ContractEnsemble {
 
 pub(crate) struct Context {
     pub contracts: Vec<ContractUpload>,
-    #[cfg(feature = "ensemble-staking")]
+    pub modules: Vec<(String, Box<dyn Module>)>,
+    /// Removed mocks are set to [`None`] so that the ids of the rest stay valid.
+    pub mocks: Vec<Option<ContractMock>>,
+    /// Hooks that have finished or were unscheduled are set to [`None`].
+    pub hooks: Vec<Option<Hook>>,
+    /// Removed failures are set to [`None`] so that the ids of the rest stay valid.
+    pub failures: Vec<Option<Failure>>,
+    pub coverage: CoverageTracker,
+    #[cfg(feature = "staking")]
     pub delegations: Delegations,
     pub state: State,
-    block: Block,
-    chain_id: String
+    pub gas: GasMeter,
+    /// [`None`] if tracing is disabled.
+    pub trace: Option<Trace>,
+    pub block: Block,
+    pub chain_id: String,
+    /// [`None`] if contracts use their label as the address.
+    pub addresses: Option<Bech32Addresses>,
+    /// The salt of the next instantiation if it was started
+    /// with [`ContractEnsemble::instantiate2`].
+    pub salt: Option<Binary>
 }
 
 pub(crate) struct ContractUpload {
-    code_hash: String,
-    code: Box<dyn ContractHarness>
+    pub code_hash: String,
+    pub code: Box<dyn ContractHarness>
 }
 
 impl ContractEnsemble {
@@ -181,7 +254,7 @@ impl ContractEnsemble {
 
     /// Creates a new instance of the ensemble that will use
     /// the provided denomination as the native coin.
-    #[cfg(feature = "ensemble-staking")]
+    #[cfg(feature = "staking")]
     pub fn new_with_denom(native_denom: impl Into<String>) -> Self {
         Self {
             ctx: Box::new(Context::new(native_denom.into()))
@@ -193,10 +266,72 @@ impl ContractEnsemble {
     /// upload step of the real chain.
     /// 
     /// Returns the code id that must be use to create an instance of it
-    /// and its unique code hash.
+    /// and its unique code hash. The code hash is a placeholder in the form
+    /// of `test_contract_{id}`. Use [`ContractEnsemble::register_named`] or
+    /// [`ContractEnsemble::register_with_code_hash`] instead if the contracts
+    /// expect a real code hash.
     pub fn register(&mut self, code: Box<dyn ContractHarness>) -> ContractCode {
+        let code_hash = format!("test_contract_{}", self.ctx.contracts.len());
+
+        self.register_impl(code, code_hash)
+    }
+
+    /// Same as [`ContractEnsemble::register`] but the code hash is the
+    /// hex encoded SHA-256 hash of `name`, as returned by [`code_hash_of`].
+    /// The same name always results in the same code hash.
+    #[inline]
+    pub fn register_named(
+        &mut self,
+        code: Box<dyn ContractHarness>,
+        name: impl AsRef<str>
+    ) -> ContractCode {
+        self.register_impl(code, code_hash_of(name.as_ref()))
+    }
+
+    /// Same as [`ContractEnsemble::register`] but uses the given code hash.
+    /// Like on chain, the same code hash can be registered more than once
+    /// and it is case insensitive.
+    ///
+    /// # Panics
+    ///
+    /// If the code hash is not 64 hex characters long.
+    pub fn register_with_code_hash(
+        &mut self,
+        code: Box<dyn ContractHarness>,
+        code_hash: impl Into<String>
+    ) -> ContractCode {
+        let code_hash = code_hash.into();
+
+        assert!(
+            is_code_hash(&code_hash),
+            "Ensemble: \"{}\" is not a valid code hash. It must be 64 hex characters long.",
+            code_hash
+        );
+
+        self.register_impl(code, code_hash.to_lowercase())
+    }
+
+    /// Returns the id and code hash of the code with the given code hash.
+    /// If the same code hash was registered more than once, the one with
+    /// the lowest code id is returned.
+    pub fn code_by_hash(&self, code_hash: impl AsRef<str>) -> Option<ContractCode> {
+        let code_hash = code_hash.as_ref();
+
+        self.ctx.contracts
+            .iter()
+            .position(|x| x.code_hash.eq_ignore_ascii_case(code_hash))
+            .map(|id| ContractCode {
+                id: id as u64,
+                code_hash: self.ctx.contracts[id].code_hash.clone()
+            })
+    }
+
+    pub(crate) fn register_impl(
+        &mut self,
+        code: Box<dyn ContractHarness>,
+        code_hash: String
+    ) -> ContractCode {
         let id = self.ctx.contracts.len() as u64;
-        let code_hash = format!("test_contract_{}", id);
 
         self.ctx.contracts.push(ContractUpload {
             code_hash: code_hash.clone(),
@@ -209,6 +344,124 @@ impl ContractEnsemble {
         }
     }
 
+    /// Adds a [`Module`] which will handle any messages and queries
+    /// that the ensemble doesn't support natively. Modules are tried
+    /// in the order that they were added.
+    /// 
+    /// # Panics
+    /// 
+    /// If a module with the same `name` has already been added.
+    pub fn add_module(&mut self, name: impl Into<String>, module: Box<dyn Module>) {
+        let name = name.into();
+
+        if self.ctx.modules.iter().any(|(x, _)| *x == name) {
+            panic!("Ensemble: A module with the name \"{}\" already exists.", name);
+        }
+
+        self.ctx.state.add_module(&name);
+        self.ctx.modules.push((name, module));
+    }
+
+    /// Adds a [`ContractMock`] which will intercept calls to its target instead
+    /// of the contract. When more than one mock intercepts the same call, the
+    /// one that was added first is used.
+    pub fn add_mock(&mut self, mock: ContractMock) -> MockId {
+        self.ctx.mocks.push(Some(mock));
+
+        MockId(self.ctx.mocks.len() - 1)
+    }
+
+    /// Removes the mock with the given id so that calls go
+    /// through to the contract again.
+    ///
+    /// # Panics
+    ///
+    /// If the mock has already been removed.
+    pub fn remove_mock(&mut self, id: MockId) -> ContractMock {
+        self.ctx.mocks[id.0].take().expect("Ensemble: The mock has already been removed.")
+    }
+
+    /// Returns all calls that the mock with the given id intercepted so far,
+    /// in the order that they were made. This includes calls made as part of
+    /// transactions that failed afterwards.
+    ///
+    /// # Panics
+    ///
+    /// If the mock has been removed.
+    pub fn mock_calls(&self, id: MockId) -> Vec<MockCall> {
+        let mock = self.ctx.mocks[id.0]
+            .as_ref()
+            .expect("Ensemble: The mock has been removed.");
+
+        mock.calls.borrow().clone()
+    }
+
+    /// Adds a [`Failure`] which will make the matching operations fail
+    /// with a contract error until it is removed.
+    pub fn inject_failure(&mut self, failure: Failure) -> FailureId {
+        self.ctx.failures.push(Some(failure));
+
+        FailureId(self.ctx.failures.len() - 1)
+    }
+
+    /// Removes the failure with the given id so that
+    /// the operations that it matched succeed again.
+    ///
+    /// # Panics
+    ///
+    /// If the failure has already been removed.
+    pub fn remove_failure(&mut self, id: FailureId) -> Failure {
+        self.ctx.failures[id.0].take().expect("Ensemble: The failure has already been removed.")
+    }
+
+    /// Returns the number of times that the failure with the given id made
+    /// an operation fail, including operations in transactions that were
+    /// reverted afterwards.
+    ///
+    /// # Panics
+    ///
+    /// If the failure has been removed.
+    pub fn failure_count(&self, id: FailureId) -> u64 {
+        let failure = self.ctx.failures[id.0]
+            .as_ref()
+            .expect("Ensemble: The failure has been removed.");
+
+        failure.triggered.get()
+    }
+
+    /// Records which variants of `E` and `Q` are executed and queried on
+    /// the instances of the contract with the given code id and which
+    /// errors they return. The contract is listed under `name` in the
+    /// [`CoverageReport`] returned by [`ContractEnsemble::coverage`].
+    ///
+    /// The variants are read from the JSON schema of the messages, such
+    /// as the `ExecuteMsg` and `QueryMsg` generated by `#[contract]`.
+    ///
+    /// # Panics
+    ///
+    /// If there is no contract with the given code id.
+    pub fn track_coverage<E: JsonSchema, Q: JsonSchema>(
+        &mut self,
+        name: impl Into<String>,
+        code_id: u64
+    ) {
+        assert!(
+            (code_id as usize) < self.ctx.contracts.len(),
+            "Ensemble: Contract with id {} not found.",
+            code_id
+        );
+
+        self.ctx.coverage.track::<E, Q>(name.into(), code_id as usize);
+    }
+
+    /// Returns the coverage of the contracts tracked with
+    /// [`ContractEnsemble::track_coverage`] so far. Calls in
+    /// transactions that were reverted are included.
+    #[inline]
+    pub fn coverage(&self) -> CoverageReport {
+        self.ctx.coverage.report()
+    }
+
     /// Returns a reference to the current block state.
     #[inline]
     pub fn block(&self) -> &Block {
@@ -219,9 +472,147 @@ impl ContractEnsemble {
     /// Can be used to manually advance the block time and height
     /// or configure the auto advancement strategy. Auto advancement
     /// occurs on successful message execution.
+    ///
+    /// Unbondings that have completed by the new block time are paid
+    /// out when the returned [`BlockMut`] is dropped. Scheduled hooks
+    /// that became due run after the next transaction or fast forward.
     #[inline]
-    pub fn block_mut(&mut self) -> &mut Block {
-        &mut self.ctx.block
+    pub fn block_mut(&mut self) -> BlockMut<'_> {
+        BlockMut { ctx: &mut self.ctx }
+    }
+
+    /// Schedules `action` to run when the block reaches the given height or
+    /// time while advancing it with [`ContractEnsemble::fast_forward`],
+    /// [`ContractEnsemble::fast_forward_to_height`] or
+    /// [`ContractEnsemble::fast_forward_to_time`]. Hooks also run after
+    /// a transaction advances the block, in which case any error that
+    /// a hook returns is returned instead of the transaction's response,
+    /// even though the transaction itself has already been committed.
+    ///
+    /// Hooks that are due in the same block run in the order that they
+    /// were scheduled. The block is frozen while a hook runs so any
+    /// messages that it executes are included in the same block.
+    ///
+    /// # Panics
+    ///
+    /// If the interval of a repeating schedule is 0.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use fadroma_ensemble::{ContractEnsemble, Schedule};
+    /// let mut ensemble = ContractEnsemble::new();
+    /// ensemble.block_mut().exact_increments(1, 5);
+    ///
+    /// let start = ensemble.block().height;
+    ///
+    /// ensemble.schedule(Schedule::EveryBlocks(10), |ensemble| {
+    ///     ensemble.add_funds("keeper", vec![fadroma::cosmwasm_std::coin(1, "uscrt")]);
+    ///
+    ///     Ok(())
+    /// });
+    ///
+    /// ensemble.fast_forward(35).unwrap();
+    ///
+    /// assert_eq!(ensemble.block().height, start + 35);
+    /// assert_eq!(ensemble.balances("keeper").unwrap()["uscrt"].u128(), 3);
+    /// ```
+    pub fn schedule(
+        &mut self,
+        schedule: Schedule,
+        action: impl FnMut(&mut ContractEnsemble) -> EnsembleResult<()> + 'static
+    ) -> HookId {
+        let hook = Hook::new(schedule, &self.ctx.block, Box::new(action));
+        self.ctx.hooks.push(Some(hook));
+
+        HookId(self.ctx.hooks.len() - 1)
+    }
+
+    /// Removes the hook with the given id so that it doesn't run anymore.
+    /// Returns `false` if the hook has already run or was removed before.
+    pub fn unschedule(&mut self, id: HookId) -> bool {
+        self.ctx.hooks[id.0].take().is_some()
+    }
+
+    /// Advances the block by the given number of increments, running
+    /// any hooks that are due after each one. Also advances frozen blocks.
+    ///
+    /// Stops at the block where a hook failed and returns its error.
+    pub fn fast_forward(&mut self, blocks: u64) -> EnsembleResult<()> {
+        for _ in 0..blocks {
+            self.ctx.block.advance(1);
+
+            #[cfg(feature = "staking")]
+            self.ctx.complete_unbondings();
+
+            self.run_hooks()?;
+        }
+
+        Ok(())
+    }
+
+    /// Advances the block until it reaches the given height, running
+    /// any hooks that are due after each one. The last block is shortened
+    /// if needed, so that it ends up exactly at `height`.
+    /// Also advances frozen blocks.
+    ///
+    /// Stops at the block where a hook failed and returns its error.
+    ///
+    /// # Panics
+    ///
+    /// If `height` is lower than the current block height.
+    pub fn fast_forward_to_height(&mut self, height: u64) -> EnsembleResult<()> {
+        assert!(
+            height >= self.ctx.block.height,
+            "Ensemble: Can't fast forward to height {} which is lower than the current height {}.",
+            height,
+            self.ctx.block.height
+        );
+
+        while self.ctx.block.height < height {
+            self.ctx.block.advance(1);
+            self.ctx.block.height = self.ctx.block.height.min(height);
+
+            #[cfg(feature = "staking")]
+            self.ctx.complete_unbondings();
+
+            self.run_hooks()?;
+        }
+
+        Ok(())
+    }
+
+    /// Advances the block until it reaches the given time, running
+    /// any hooks that are due after each one. The last block is shortened
+    /// if needed, so that it ends up exactly at `time`.
+    /// Also advances frozen blocks.
+    ///
+    /// Stops at the block where a hook failed and returns its error.
+    ///
+    /// # Panics
+    ///
+    /// If `time` is before the current block time.
+    pub fn fast_forward_to_time(&mut self, time: Timestamp) -> EnsembleResult<()> {
+        let time = time.seconds();
+
+        assert!(
+            time >= self.ctx.block.time,
+            "Ensemble: Can't fast forward to time {} which is before the current time {}.",
+            time,
+            self.ctx.block.time
+        );
+
+        while self.ctx.block.time < time {
+            self.ctx.block.advance(1);
+            self.ctx.block.time = self.ctx.block.time.min(time);
+
+            #[cfg(feature = "staking")]
+            self.ctx.complete_unbondings();
+
+            self.run_hooks()?;
+        }
+
+        Ok(())
     }
 
     /// Sets that chain id string i.e `env.block.chain_id`.
@@ -230,24 +621,148 @@ impl ContractEnsemble {
         self.ctx.chain_id = id.into();
     }
 
+    /// Enables gas metering using the given costs or disables it if [`None`].
+    /// When enabled, each instantiate, execute, reply and migrate response
+    /// will contain a report of the gas used by that message.
+    #[inline]
+    pub fn set_gas_costs(&mut self, costs: Option<GasCosts>) {
+        self.ctx.gas.costs = costs;
+    }
+
+    /// Sets the maximum amount of gas that a single transaction can use,
+    /// including all of the messages that it initiates. Exceeding it fails
+    /// the transaction with [`EnsembleError::OutOfGas`] and reverts all of
+    /// its state changes. A contract that exceeds it is aborted right away
+    /// instead of running to completion. Has no effect unless gas metering is enabled
+    /// with [`ContractEnsemble::set_gas_costs`].
+    #[inline]
+    pub fn set_gas_limit(&mut self, limit: Option<u64>) {
+        self.ctx.gas.limit = limit;
+    }
+
+    /// Makes new contract instances get bech32 addresses with the given
+    /// prefix i.e `secret`, derived from their code id and the number of
+    /// instances the same way as on chain, instead of using their label as
+    /// the address. Recipients of funds and contract admins must then also
+    /// be valid addresses with that prefix. Passing [`None`] switches back
+    /// to using labels. Existing instances keep their addresses.
+    ///
+    /// Use [`Bech32Addresses::account`] to create addresses for senders.
+    ///
+    /// # Panics
+    ///
+    /// If the prefix is invalid. See [`Bech32Addresses::new`].
+    #[inline]
+    pub fn set_bech32_prefix(&mut self, prefix: Option<&str>) {
+        self.ctx.addresses = prefix.map(Bech32Addresses::new);
+    }
+
+    /// Returns the bech32 address generator if it was enabled
+    /// with [`ContractEnsemble::set_bech32_prefix`].
+    #[inline]
+    pub fn addresses(&self) -> Option<&Bech32Addresses> {
+        self.ctx.addresses.as_ref()
+    }
+
+    /// Enables or disables recording an execution [`Trace`] for each transaction.
+    /// Disabled by default.
+    #[inline]
+    pub fn set_tracing(&mut self, enabled: bool) {
+        self.ctx.trace = if enabled { Some(Trace::default()) } else { None };
+    }
+
+    /// Returns the trace of the last transaction that was executed,
+    /// whether it succeeded or not. [`None`] if tracing is disabled.
+    #[inline]
+    pub fn trace(&self) -> Option<&Trace> {
+        self.ctx.trace.as_ref()
+    }
+
     /// Adds the given funds that will be associated with the
     /// provided account's address. Can either be a contract or
     /// a mock user's address. You need to use this method first
     /// if you want to send a contract funds when using [`MockEnv::sent_funds`].
+    /// The funds are minted, so the total supply increases.
     #[inline]
     pub fn add_funds(&mut self, address: impl AsRef<str>, coins: Vec<Coin>) {
         for coin in coins {
-            self.ctx.state.bank.add_funds(address.as_ref(), coin);
+            self.ctx.state.bank.mint(address.as_ref(), coin);
         }
     }
 
+    /// Mints the given funds to the provided account's address, increasing
+    /// the total supply. Unlike [`ContractEnsemble::add_funds`], it returns
+    /// an error for token factory denoms that haven't been created with
+    /// [`ContractEnsemble::create_denom`].
+    pub fn mint(&mut self, address: impl AsRef<str>, coins: Vec<Coin>) -> EnsembleResult<()> {
+        for coin in &coins {
+            self.ctx.state.bank.assert_denom_exists(&coin.denom)?;
+        }
+
+        self.add_funds(address, coins);
+
+        Ok(())
+    }
+
+    /// Creates a token factory denom in the form of `factory/{creator}/{subdenom}`
+    /// and returns it. The subdenom can be up to 44 characters long and can
+    /// contain alphanumeric characters, `.`, `_` and `-`.
+    ///
+    /// This is only available to tests. Token factory messages sent by
+    /// contracts must be handled by a [`crate::Module`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fadroma::cosmwasm_std::coin;
+    /// use fadroma_ensemble::ContractEnsemble;
+    ///
+    /// let mut ensemble = ContractEnsemble::new();
+    /// let denom = ensemble.create_denom("creator", "ufoo").unwrap();
+    ///
+    /// assert_eq!(denom, "factory/creator/ufoo");
+    /// assert_eq!(ensemble.denom_admin(&denom), Some("creator"));
+    ///
+    /// ensemble.mint("wallet", vec![coin(100, &denom)]).unwrap();
+    /// assert_eq!(ensemble.supply(&denom).u128(), 100);
+    ///
+    /// assert!(ensemble.mint("wallet", vec![coin(100, "factory/creator/ubar")]).is_err());
+    /// ```
+    pub fn create_denom(
+        &mut self,
+        creator: impl AsRef<str>,
+        subdenom: impl AsRef<str>
+    ) -> EnsembleResult<String> {
+        self.ctx.validate_address(creator.as_ref())?;
+
+        self.ctx.state.bank.create_denom(creator.as_ref(), subdenom.as_ref())
+    }
+
+    /// Returns the admin of the given token factory denom
+    /// or [`None`] if it hasn't been created.
+    #[inline]
+    pub fn denom_admin(&self, denom: impl AsRef<str>) -> Option<&str> {
+        self.ctx.state.bank.denoms.get(denom.as_ref()).map(|x| x.as_str())
+    }
+
+    /// Returns the total supply of the given denom. Funds added with
+    /// [`ContractEnsemble::add_funds`] and [`ContractEnsemble::mint`] or
+    /// withdrawn as staking rewards increase it, while burned and slashed
+    /// funds decrease it. Delegating or transferring funds doesn't change it
+    /// and neither do changes made through [`ContractEnsemble::balances_mut`].
+    #[inline]
+    pub fn supply(&self, denom: impl AsRef<str>) -> Uint128 {
+        self.ctx.state.bank.supply(denom.as_ref())
+    }
+
     /// Removes the given funds from the provided account's
     /// address. Can either be a contract or a mock user's address.
     /// The account must already exist and have at least the given amount
-    /// in order for this to be a success.
+    /// in order for this to be a success. The funds are burned, so the
+    /// total supply decreases.
     #[inline]
     pub fn remove_funds(&mut self, address: impl AsRef<str>, coin: Coin) -> EnsembleResult<()> {
-        self.ctx.state.bank.remove_funds(address.as_ref(), coin)
+        self.ctx.state.bank.burn(address.as_ref(), coin)
     }
 
     /// Transfers funds from one account to another. The `from` address
@@ -286,12 +801,13 @@ impl ContractEnsemble {
     /// ```
     #[inline]
     pub fn balances(&self, address: impl AsRef<str>) -> Option<&Balances> {
-        self.ctx.state.bank.0.get(address.as_ref())
+        self.ctx.state.bank.accounts.get(address.as_ref())
     }
 
     /// Returns a mutable reference to all the balances associated with the
     /// given account. Returns [`None`] if the account doesn't exist or hasn't
-    /// received any funds before.
+    /// received any funds before. Changing the balances this way doesn't
+    /// update the total supply.
     /// 
     /// # Examples
     /// 
@@ -312,19 +828,19 @@ impl ContractEnsemble {
     /// assert!(ensemble.balances("absent").is_none());
     #[inline]
     pub fn balances_mut(&mut self, address: impl AsRef<str>) -> Option<&mut Balances> {
-        self.ctx.state.bank.0.get_mut(address.as_ref())
+        self.ctx.state.bank.accounts.get_mut(address.as_ref())
     }
 
     /// Returns all active delegations associated with the given address.
     #[inline]
-    #[cfg(feature = "ensemble-staking")]
+    #[cfg(feature = "staking")]
     pub fn delegations(&self, address: impl AsRef<str>) -> Vec<Delegation> {
         self.ctx.delegations.all_delegations(address.as_ref())
     }
 
     /// Creates a new delegation for the given address using the given validator.
     #[inline]
-    #[cfg(feature = "ensemble-staking")]
+    #[cfg(feature = "staking")]
     pub fn delegation(
         &self,
         delegator: impl AsRef<str>,
@@ -337,21 +853,90 @@ impl ContractEnsemble {
 
     /// Adds the validator to the validator list.
     #[inline]
-    #[cfg(feature = "ensemble-staking")]
+    #[cfg(feature = "staking")]
     pub fn add_validator(&mut self, validator: Validator) {
         self.ctx.delegations.add_validator(validator);
     }
 
     /// Distributes the given amount as rewards.
     #[inline]
-    #[cfg(feature = "ensemble-staking")]
+    #[cfg(feature = "staking")]
     pub fn add_rewards(&mut self, amount: impl Into<Uint128>) {
         self.ctx.delegations.distribute_rewards(amount.into());
     }
 
-    /// Re-allow redelegating and deposit unbondings.
+    /// Distributes the given amount as rewards across all delegations
+    /// to validators that aren't jailed, proportionally to their stake.
+    /// Each validator keeps its commission rate of the rewards, which can
+    /// be read with [`ContractEnsemble::validator_commission`].
+    #[inline]
+    #[cfg(feature = "staking")]
+    pub fn distribute_rewards(&mut self, amount: impl Into<Uint128>) {
+        self.ctx.delegations.distribute_weighted_rewards(amount.into());
+    }
+
+    /// Returns the total commission earned by the validator from
+    /// [`ContractEnsemble::distribute_rewards`].
     #[inline]
-    #[cfg(feature = "ensemble-staking")]
+    #[cfg(feature = "staking")]
+    pub fn validator_commission(&self, validator: impl AsRef<str>) -> Uint128 {
+        self.ctx.delegations.validator_commission(validator.as_ref())
+    }
+
+    /// Sets the commission rate of the validator.
+    /// Returns `Err` if it exceeds the validator's `max_commission`.
+    #[inline]
+    #[cfg(feature = "staking")]
+    pub fn set_validator_commission(
+        &mut self,
+        validator: impl AsRef<str>,
+        rate: Decimal
+    ) -> EnsembleResult<()> {
+        self.ctx.delegations.set_commission(validator.as_ref(), rate)
+    }
+
+    /// Slashes the delegations to the validator, including those
+    /// that are still unbonding, by the given fraction. The slashed
+    /// funds are removed from the total supply.
+    /// Returns the total amount that was slashed.
+    #[cfg(feature = "staking")]
+    pub fn slash_validator(
+        &mut self,
+        validator: impl AsRef<str>,
+        fraction: Decimal
+    ) -> EnsembleResult<Uint128> {
+        let slashed = self.ctx.delegations.slash(validator.as_ref(), fraction)?;
+        let denom = self.ctx.delegations.bonded_denom();
+
+        self.ctx.state.bank.reduce_supply(Coin::new(slashed.u128(), denom));
+
+        Ok(slashed)
+    }
+
+    /// Jails the validator. Jailed validators can't receive new delegations,
+    /// don't earn rewards and are excluded from the `AllValidators` query.
+    #[inline]
+    #[cfg(feature = "staking")]
+    pub fn jail_validator(&mut self, validator: impl AsRef<str>) -> EnsembleResult<()> {
+        self.ctx.delegations.jail(validator.as_ref())
+    }
+
+    #[inline]
+    #[cfg(feature = "staking")]
+    pub fn unjail_validator(&mut self, validator: impl AsRef<str>) -> EnsembleResult<()> {
+        self.ctx.delegations.unjail(validator.as_ref())
+    }
+
+    #[inline]
+    #[cfg(feature = "staking")]
+    pub fn is_jailed(&self, validator: impl AsRef<str>) -> bool {
+        self.ctx.delegations.is_jailed(validator.as_ref())
+    }
+
+    /// Re-allow redelegating and complete all pending unbondings,
+    /// regardless of their completion time.
+    #[inline]
+    #[cfg(feature = "staking")]
     pub fn fast_forward_delegation_waits(&mut self) {
         let unbondings = self.ctx.delegations.fast_forward_waits();
 
@@ -363,6 +948,31 @@ impl ContractEnsemble {
         }
     }
 
+    /// Sets the time in seconds that it takes for undelegated funds to be
+    /// returned to the delegator. Defaults to [`crate::DEFAULT_UNBONDING_PERIOD`]
+    /// (21 days). Only affects unbondings that are created afterwards.
+    #[inline]
+    #[cfg(feature = "staking")]
+    pub fn set_unbonding_period(&mut self, seconds: u64) {
+        self.ctx.delegations.set_unbonding_period(seconds);
+    }
+
+    #[inline]
+    #[cfg(feature = "staking")]
+    pub fn unbonding_period(&self) -> u64 {
+        self.ctx.delegations.unbonding_period()
+    }
+
+    /// Returns the pending unbondings of the given address in the order
+    /// that they were created. Unbondings complete and their funds are
+    /// returned once the block time reaches their completion time, when
+    /// the block is advanced by a transaction or by fast forwarding.
+    #[inline]
+    #[cfg(feature = "staking")]
+    pub fn unbondings(&self, delegator: impl AsRef<str>) -> Vec<Unbonding> {
+        self.ctx.delegations.unbondings(delegator.as_ref())
+    }
+
     /// Provides read access to the storage associated with the given contract address.
     /// 
     /// Returns `Err` if a contract with `address` wasn't found.
@@ -376,38 +986,249 @@ impl ContractEnsemble {
         Ok(())
     }
 
-    /// Provides write access to the storage associated with the given contract address.
-    /// 
-    /// Returns an `Err` if a contract with `address` wasn't found. In case an error
-    /// is returned from the closure, the updates to that storage are discarded.
-    pub fn contract_storage_mut<F>(&mut self, address: impl AsRef<str>, mutate: F) -> EnsembleResult<()>
-        where F: FnOnce(&mut dyn Storage) -> EnsembleResult<()>
-    {
-        self.ctx.state.push_scope();
-        let result = self.ctx.state.borrow_storage_mut(address.as_ref(), mutate);
+    /// Provides read access to the storage of the module with the given name.
+    /// 
+    /// Returns `Err` if a module with `name` wasn't added.
+    pub fn module_storage<F>(&self, name: impl AsRef<str>, borrow: F) -> EnsembleResult<()>
+        where F: FnOnce(&dyn Storage)
+    {
+        let name = name.as_ref();
+
+        if !self.ctx.modules.iter().any(|(x, _)| x == name) {
+            return Err(EnsembleError::Module(format!("{} not found", name)));
+        }
+
+        borrow(self.ctx.state.module_storage(name) as &dyn Storage);
+
+        Ok(())
+    }
+
+    /// Provides write access to the storage associated with the given contract address.
+    /// 
+    /// Returns an `Err` if a contract with `address` wasn't found. In case an error
+    /// is returned from the closure, the updates to that storage are discarded.
+    pub fn contract_storage_mut<F>(&mut self, address: impl AsRef<str>, mutate: F) -> EnsembleResult<()>
+        where F: FnOnce(&mut dyn Storage) -> EnsembleResult<()>
+    {
+        self.ctx.state.push_scope();
+        let result = self.ctx.state.borrow_storage_mut(address.as_ref(), mutate);
+
+        if result.is_ok() {
+            self.ctx.state.commit();
+        } else {
+            self.ctx.state.revert();
+        }
+
+        result
+    }
+
+    /// Takes a snapshot of the current chain state which can later be
+    /// restored with [`ContractEnsemble::restore`]. Useful for sharing
+    /// an expensive setup between multiple test scenarios.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use fadroma::cosmwasm_std::coin;
+    /// use fadroma_ensemble::ContractEnsemble;
+    ///
+    /// let mut ensemble = ContractEnsemble::new();
+    /// ensemble.add_funds("wallet", vec![coin(100, "uscrt")]);
+    ///
+    /// let snapshot = ensemble.snapshot();
+    ///
+    /// ensemble.add_funds("wallet", vec![coin(100, "uscrt")]);
+    /// assert_eq!(ensemble.balances("wallet").unwrap().get("uscrt").unwrap().u128(), 200);
+    ///
+    /// ensemble.restore(&snapshot);
+    /// assert_eq!(ensemble.balances("wallet").unwrap().get("uscrt").unwrap().u128(), 100);
+    /// ```
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            instances: self.ctx.state.instances.clone(),
+            bank: self.ctx.state.bank.clone(),
+            modules: self.ctx.state.modules.clone(),
+            #[cfg(feature = "staking")]
+            delegations: Some(self.ctx.delegations.clone()),
+            block: self.ctx.block.clone(),
+            chain_id: self.ctx.chain_id.clone(),
+            code_count: self.ctx.contracts.len()
+        }
+    }
+
+    /// Restores the chain state to the one captured in the given `snapshot`.
+    /// Any contract instances created after the snapshot was taken are removed.
+    ///
+    /// Panics if the snapshot was taken from an ensemble that has more
+    /// contracts registered than this one.
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        assert!(
+            snapshot.code_count <= self.ctx.contracts.len(),
+            "Snapshot references contracts that are not registered in this ensemble."
+        );
+
+        self.ctx.state.replace(
+            snapshot.instances.clone(),
+            snapshot.bank.clone(),
+            snapshot.modules.clone()
+        );
+
+        // Modules that were added after the snapshot was taken start with empty storage.
+        for (name, _) in &self.ctx.modules {
+            self.ctx.state.add_module(name);
+        }
+
+        self.ctx.block = snapshot.block.clone();
+        self.ctx.chain_id = snapshot.chain_id.clone();
+
+        // Snapshots that were saved without the staking feature
+        // don't have any delegations.
+        #[cfg(feature = "staking")]
+        {
+            self.ctx.delegations = match &snapshot.delegations {
+                Some(delegations) => delegations.clone(),
+                None => Delegations::new(self.ctx.delegations.bonded_denom().into())
+            };
+        }
+    }
+
+    /// Creates a new contract instance using the given code id. The code id
+    /// must be obtained by calling the [`ContractEnsemble::register`] method first.
+    /// 
+    /// The contract will be assigned the address the was provided with
+    /// the `env.contract` parameter.
+    /// 
+    /// The `instance` field of the response will contain this address and
+    /// the code hash associated with this instance.
+    pub fn instantiate<T: Serialize>(
+        &mut self,
+        code_id: u64,
+        msg: &T,
+        env: MockEnv
+    ) -> EnsembleResult<InstantiateResponse> {
+        self.instantiate_impl(code_id, msg, env, None, None)
+    }
+
+    /// Same as [`ContractEnsemble::instantiate`] but also sets the given
+    /// `admin` on the new instance. Only the admin is allowed to migrate
+    /// the contract or to update/clear its admin.
+    pub fn instantiate_with_admin<T: Serialize>(
+        &mut self,
+        code_id: u64,
+        msg: &T,
+        env: MockEnv,
+        admin: impl Into<String>
+    ) -> EnsembleResult<InstantiateResponse> {
+        self.instantiate_impl(code_id, msg, env, Some(admin.into()), None)
+    }
+
+    /// Same as [`ContractEnsemble::instantiate`] but the address of the new
+    /// instance is derived from the code, the sender and the given `salt`
+    /// like `MsgInstantiateContract2` does, so it can be computed ahead of
+    /// time with [`Bech32Addresses::predictable_address`]. The `env.contract`
+    /// is used as the label of the instance.
+    ///
+    /// # Panics
+    ///
+    /// If bech32 addresses weren't enabled with [`ContractEnsemble::set_bech32_prefix`].
+    pub fn instantiate2<T: Serialize>(
+        &mut self,
+        code_id: u64,
+        msg: &T,
+        env: MockEnv,
+        salt: impl Into<Binary>
+    ) -> EnsembleResult<InstantiateResponse> {
+        assert!(
+            self.ctx.addresses.is_some(),
+            "Ensemble: instantiate2 requires bech32 addresses to be enabled."
+        );
+
+        self.instantiate_impl(code_id, msg, env, None, Some(salt.into()))
+    }
+
+    /// Returns the checksum of the code with the given id as used
+    /// by [`Bech32Addresses::predictable_address`].
+    pub fn code_checksum(&self, code_id: u64) -> EnsembleResult<Vec<u8>> {
+        self.ctx.code_checksum(code_id)
+    }
+
+    /// Migrates the contract with the address provided in `env.contract`
+    /// to the code with the given `code_id` by calling its migrate entry point.
+    /// The `env.sender` must be the current admin of the contract.
+    pub fn migrate<T: Serialize + ?Sized>(
+        &mut self,
+        code_id: u64,
+        msg: &T,
+        env: MockEnv
+    ) -> EnsembleResult<MigrateResponse> {
+        let contract = self
+            .ctx
+            .contracts
+            .get(code_id as usize)
+            .ok_or_else(|| EnsembleError::registry(RegistryError::IdNotFound(code_id)))?;
+
+        let sub_msg = SubMsg::new(WasmMsg::Migrate {
+            contract_addr: env.contract.into_string(),
+            code_hash: contract.code_hash.clone(),
+            code_id,
+            msg: to_binary(msg)?
+        });
+
+        match self.transact(sub_msg, env.sender.into_string())? {
+            ResponseVariants::Migrate(resp) => Ok(resp),
+            _ => unreachable!()
+        }
+    }
+
+    /// Sets the admin of the contract with the address provided in `env.contract`.
+    /// The `env.sender` must be the current admin of the contract.
+    pub fn update_admin(
+        &mut self,
+        admin: impl Into<String>,
+        env: MockEnv
+    ) -> EnsembleResult<AdminResponse> {
+        let sub_msg = SubMsg::new(WasmMsg::UpdateAdmin {
+            contract_addr: env.contract.into_string(),
+            admin: admin.into()
+        });
+
+        match self.transact(sub_msg, env.sender.into_string())? {
+            ResponseVariants::Admin(resp) => Ok(resp),
+            _ => unreachable!()
+        }
+    }
+
+    /// Clears the admin of the contract with the address provided in `env.contract`
+    /// making it impossible to migrate it anymore. The `env.sender` must be the
+    /// current admin of the contract.
+    pub fn clear_admin(&mut self, env: MockEnv) -> EnsembleResult<AdminResponse> {
+        let sub_msg = SubMsg::new(WasmMsg::ClearAdmin {
+            contract_addr: env.contract.into_string()
+        });
 
-        if result.is_ok() {
-            self.ctx.state.commit();
-        } else {
-            self.ctx.state.revert();
+        match self.transact(sub_msg, env.sender.into_string())? {
+            ResponseVariants::Admin(resp) => Ok(resp),
+            _ => unreachable!()
         }
-
-        result
     }
 
-    /// Creates a new contract instance using the given code id. The code id
-    /// must be obtained by calling the [`ContractEnsemble::register`] method first.
-    /// 
-    /// The contract will be assigned the address the was provided with
-    /// the `env.contract` parameter.
+    /// Returns the admin of the contract with the given address, if it has one.
     /// 
-    /// The `instance` field of the response will contain this address and
-    /// the code hash associated with this instance.
-    pub fn instantiate<T: Serialize>(
+    /// Returns `Err` if a contract with `address` wasn't found.
+    #[inline]
+    pub fn contract_admin(&self, address: impl AsRef<str>) -> EnsembleResult<Option<&str>> {
+        let instance = self.ctx.state.instance(address.as_ref())?;
+
+        Ok(instance.admin.as_deref())
+    }
+
+    fn instantiate_impl<T: Serialize>(
         &mut self,
         code_id: u64,
         msg: &T,
-        env: MockEnv
+        env: MockEnv,
+        admin: Option<String>,
+        salt: Option<Binary>
     ) -> EnsembleResult<InstantiateResponse> {
         let contract = self
             .ctx
@@ -421,10 +1242,14 @@ impl ContractEnsemble {
             msg: to_binary(msg)?,
             funds: env.sent_funds,
             label: env.contract.into_string(),
-            admin: None
+            admin
         });
 
-        match self.ctx.execute_messages(sub_msg, env.sender.into_string())? {
+        self.ctx.salt = salt;
+        let result = self.transact(sub_msg, env.sender.into_string());
+        self.ctx.salt = None;
+
+        match result? {
             ResponseVariants::Instantiate(resp) => Ok(resp),
             _ => unreachable!()
         }
@@ -435,20 +1260,32 @@ impl ContractEnsemble {
         &mut self,
         msg: &T,
         env: MockEnv
+    ) -> EnsembleResult<ExecuteResponse> {
+        self.execute_binary(to_binary(msg)?, env)
+    }
+
+    pub(crate) fn execute_binary(
+        &mut self,
+        msg: Binary,
+        env: MockEnv
     ) -> EnsembleResult<ExecuteResponse> {
         let address = env.contract.into_string();
 
-        let instance = self.ctx.state.instance(&address)?;
-        let code_hash = self.ctx.contracts[instance.index].code_hash.clone();
+        let code_hash = match self.ctx.state.instance(&address) {
+            Ok(instance) => self.ctx.contracts[instance.index].code_hash.clone(),
+            // Mocked addresses don't need to be contract instances.
+            Err(_) if self.ctx.find_mock(MockCallKind::Execute, &address).is_some() => String::new(),
+            Err(err) => return Err(err)
+        };
 
         let sub_msg = SubMsg::new(WasmMsg::Execute {
             contract_addr: address,
             code_hash,
-            msg: to_binary(msg)?,
+            msg,
             funds: env.sent_funds
         });
 
-        match self.ctx.execute_messages(sub_msg, env.sender.into_string())? {
+        match self.transact(sub_msg, env.sender.into_string())? {
             ResponseVariants::Execute(resp) => Ok(resp),
             _ => unreachable!()
         }
@@ -478,27 +1315,116 @@ impl ContractEnsemble {
     ) -> EnsembleResult<Binary> {
         self.ctx.query(address.as_ref(), to_binary(msg)?)
     }
+
+    /// Executes `sub_msg` as a single transaction and runs
+    /// any hooks that are due if it advanced the block.
+    fn transact(&mut self, sub_msg: SubMsg, sender: String) -> EnsembleResult<ResponseVariants> {
+        let height = self.ctx.block.height;
+        let time = self.ctx.block.time;
+
+        let resp = self.ctx.execute_messages(sub_msg, sender)?;
+
+        // Blocks are frozen while hooks run, so transactions
+        // executed by hooks don't run them recursively.
+        if self.ctx.block.height != height || self.ctx.block.time != time {
+            self.run_hooks()?;
+        }
+
+        Ok(resp)
+    }
+
+    /// Runs the hooks that are due in the current block.
+    fn run_hooks(&mut self) -> EnsembleResult<()> {
+        // Hooks scheduled by other hooks are added to the end and
+        // run in this block as well if they are already due.
+        let mut i = 0;
+
+        while i < self.ctx.hooks.len() {
+            let mut action = match &mut self.ctx.hooks[i] {
+                Some(hook) if hook.is_due(&self.ctx.block) => {
+                    std::mem::replace(&mut hook.action, Box::new(|_| Ok(())))
+                },
+                _ => {
+                    i += 1;
+
+                    continue;
+                }
+            };
+
+            let is_frozen = self.ctx.block.is_frozen();
+            self.ctx.block.freeze();
+
+            let result = action(self);
+
+            if !is_frozen {
+                self.ctx.block.unfreeze();
+            }
+
+            // The hook may have unscheduled itself.
+            if let Some(hook) = &mut self.ctx.hooks[i] {
+                if hook.reschedule(&self.ctx.block) {
+                    hook.action = action;
+                } else {
+                    self.ctx.hooks[i] = None;
+                }
+            }
+
+            result?;
+            i += 1;
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the hex encoded SHA-256 hash of `data`, which is how
+/// Secret Network derives the code hash from the contract's wasm code.
+pub fn code_hash_of(data: impl AsRef<[u8]>) -> String {
+    Sha256::digest(data.as_ref()).iter().map(|x| format!("{:02x}", x)).collect()
+}
+
+#[inline]
+fn is_code_hash(code_hash: &str) -> bool {
+    code_hash.len() == 64 && code_hash.chars().all(|x| x.is_ascii_hexdigit())
 }
 
 impl Context {
-    #[cfg(not(feature = "ensemble-staking"))]
+    #[cfg(not(feature = "staking"))]
     fn new(_native_denom: String) -> Self {
         Self {
             contracts: vec![],
+            modules: vec![],
+            mocks: vec![],
+            hooks: vec![],
+            failures: vec![],
+            coverage: CoverageTracker::default(),
             state: State::new(),
+            gas: GasMeter::new(),
+            trace: None,
             block: Block::default(),
-            chain_id: "fadroma-ensemble-testnet".into()
+            chain_id: "fadroma-ensemble-testnet".into(),
+            addresses: None,
+            salt: None
         }
     }
 
-    #[cfg(feature = "ensemble-staking")]
+    #[cfg(feature = "staking")]
     fn new(native_denom: String) -> Self {
         Self {
             contracts: vec![],
+            modules: vec![],
+            mocks: vec![],
+            hooks: vec![],
+            failures: vec![],
+            coverage: CoverageTracker::default(),
             state: State::new(),
+            gas: GasMeter::new(),
+            trace: None,
             delegations: Delegations::new(native_denom),
             block: Block::default(),
-            chain_id: "fadroma-ensemble-testnet".into()
+            chain_id: "fadroma-ensemble-testnet".into(),
+            addresses: None,
+            salt: None
         }
     }
 
@@ -507,6 +1433,8 @@ impl Context {
         id: u64,
         msg: Binary,
         env: MockEnv,
+        label: String,
+        admin: Option<String>
     ) -> EnsembleResult<InstantiateResponse> {
         // We check for validity in execute_sub_msg()
         let contract = &self.contracts[id as usize];
@@ -515,7 +1443,13 @@ impl Context {
         let address = env.contract.to_string();
         let code_hash = contract.code_hash.clone();
 
-        self.state.create_contract_instance(address.clone(), id as usize)?;
+        self.state.create_contract_instance(
+            address.clone(),
+            id as usize,
+            sender.clone(),
+            admin,
+            label
+        )?;
 
         let (env, msg_info) = self.create_msg_deps(
             env,
@@ -523,9 +1457,11 @@ impl Context {
         );
 
         let querier = EnsembleQuerier::new(&self);
-        let response = self.state.borrow_storage_mut(&address, |storage| {
+        let gas = &self.gas;
+
+        let response = self.state.borrow_storage_mut(&address, |storage| gas.call(|| {
             let deps = DepsMut::<Empty> {
-                storage,
+                storage: &mut MeteredStorage::new(storage, gas),
                 api: &MockApi::default() as &dyn Api,
                 querier: QuerierWrapper::new(&querier as &dyn Querier)
             };
@@ -533,7 +1469,7 @@ impl Context {
             let result = contract.code.instantiate(deps, env, msg_info, msg.clone())?;
 
             Ok(result)
-        })?;
+        }))?;
 
         Ok(InstantiateResponse {
             sent: Vec::with_capacity(response.messages.len()),
@@ -544,7 +1480,9 @@ impl Context {
             },
             code_id: id,
             msg,
-            response
+            response,
+            gas: self.gas.report(),
+            events: vec![]
         })
     }
 
@@ -563,16 +1501,46 @@ impl Context {
         let contract = &self.contracts[index];
 
         let querier = EnsembleQuerier::new(&self);
-        let response = self.state.borrow_storage_mut(&address, |storage| {
+        let gas = &self.gas;
+        let coverage = &self.coverage;
+
+        let response = self.state.borrow_storage_mut(&address, |storage| gas.call(|| {
             let deps = DepsMut::<Empty> {
-                storage,
+                storage: &mut MeteredStorage::new(storage, gas),
                 api: &MockApi::default() as &dyn Api,
                 querier: QuerierWrapper::new(&querier as &dyn Querier)
             };
 
-            let result = contract.code.execute(deps, env, msg_info, msg.clone())?;
+            let result = contract.code.execute(deps, env, msg_info, msg.clone());
+            coverage.record(index, EntryPoint::Execute, &msg, &result);
 
-            Ok(result)
+            Ok(result?)
+        }))?;
+
+        Ok(ExecuteResponse {
+            sent: Vec::with_capacity(response.messages.len()),
+            sender,
+            address,
+            msg,
+            response,
+            gas: self.gas.report(),
+            events: vec![]
+        })
+    }
+
+    fn execute_mock(&mut self, msg: Binary, env: MockEnv) -> EnsembleResult<ExecuteResponse> {
+        let sender = env.sender.into_string();
+        let address = env.contract.into_string();
+
+        // We check that it exists in execute_sub_msg()
+        let mock = self.find_mock(MockCallKind::Execute, &address).unwrap();
+
+        let response = mock.execute(MockCall {
+            kind: MockCallKind::Execute,
+            sender: Some(sender.clone()),
+            address: address.clone(),
+            msg: msg.clone(),
+            funds: env.sent_funds
         })?;
 
         Ok(ExecuteResponse {
@@ -580,11 +1548,27 @@ impl Context {
             sender,
             address,
             msg,
-            response
+            response,
+            gas: self.gas.report(),
+            events: vec![]
         })
     }
 
     pub(crate) fn query(&self, address: &str, msg: Binary) -> EnsembleResult<Binary> {
+        self.check_failures(|x| x.check_query(address))?;
+
+        if let Some(mock) = self.find_mock(MockCallKind::Query, address) {
+            let result = mock.query(MockCall {
+                kind: MockCallKind::Query,
+                sender: None,
+                address: address.into(),
+                msg,
+                funds: vec![]
+            })?;
+
+            return Ok(result);
+        }
+
         let instance = self.state.instance(address)?;
         let contract = &self.contracts[instance.index];
 
@@ -594,15 +1578,81 @@ impl Context {
         });
 
         let querier = EnsembleQuerier::new(&self);
+        let storage = MeteredStorageRef::new(&instance.storage, &self.gas);
+
         let deps = Deps::<Empty> {
-            storage: &instance.storage as &dyn Storage,
+            storage: &storage as &dyn Storage,
             api: &MockApi::default() as &dyn Api,
             querier: QuerierWrapper::new(&querier as &dyn Querier)
         };
 
-        let result = contract.code.query(deps, env, msg)?;
+        let result = contract.code.query(deps, env, msg.clone());
+        self.coverage.record(instance.index, EntryPoint::Query, &msg, &result);
 
-        Ok(result)
+        Ok(result?)
+    }
+
+    fn migrate(
+        &mut self,
+        address: String,
+        id: u64,
+        msg: Binary,
+        sender: String
+    ) -> EnsembleResult<MigrateResponse> {
+        // We check for validity in execute_sub_msg()
+        let code_hash = self.contracts[id as usize].code_hash.clone();
+
+        self.assert_admin(&address, &sender)?;
+        self.state.migrate_instance(&address, id as usize)?;
+
+        let env = self.create_env(ContractLink {
+            address: Addr::unchecked(address.clone()),
+            code_hash
+        });
+
+        let contract = &self.contracts[id as usize];
+
+        let querier = EnsembleQuerier::new(&self);
+        let gas = &self.gas;
+
+        let response = self.state.borrow_storage_mut(&address, |storage| gas.call(|| {
+            let deps = DepsMut::<Empty> {
+                storage: &mut MeteredStorage::new(storage, gas),
+                api: &MockApi::default() as &dyn Api,
+                querier: QuerierWrapper::new(&querier as &dyn Querier)
+            };
+
+            let result = contract.code.migrate(deps, env, msg.clone())?;
+
+            Ok(result)
+        }))?;
+
+        Ok(MigrateResponse {
+            sent: Vec::with_capacity(response.messages.len()),
+            sender,
+            address,
+            code_id: id,
+            msg,
+            response,
+            gas: self.gas.report(),
+            events: vec![]
+        })
+    }
+
+    fn update_admin(
+        &mut self,
+        address: String,
+        admin: Option<String>,
+        sender: String
+    ) -> EnsembleResult<AdminResponse> {
+        self.assert_admin(&address, &sender)?;
+        self.state.set_admin(&address, admin.clone())?;
+
+        Ok(AdminResponse {
+            sender,
+            address,
+            admin
+        })
     }
 
     fn reply(&mut self, address: String, reply: Reply) -> EnsembleResult<ReplyResponse> {
@@ -621,9 +1671,11 @@ impl Context {
         let contract = &self.contracts[index];
 
         let querier = EnsembleQuerier::new(&self);
-        let response = self.state.borrow_storage_mut(&address, |storage| {
+        let gas = &self.gas;
+
+        let response = self.state.borrow_storage_mut(&address, |storage| gas.call(|| {
             let deps = DepsMut::<Empty> {
-                storage,
+                storage: &mut MeteredStorage::new(storage, gas),
                 api: &MockApi::default() as &dyn Api,
                 querier: QuerierWrapper::new(&querier as &dyn Querier)
             };
@@ -631,25 +1683,49 @@ impl Context {
             let result = contract.code.reply(deps, env, reply.clone())?;
 
             Ok(result)
-        })?;
+        }))?;
 
         Ok(ReplyResponse {
             sent: Vec::with_capacity(response.messages.len()),
             address,
             reply,
-            response
+            response,
+            gas: self.gas.report(),
+            events: vec![]
         })
     }
 
+    #[inline]
     fn execute_messages(
         &mut self,
         msg: SubMsg,
         initial_sender: String
     ) -> EnsembleResult<ResponseVariants> {
-        let mut state = ExecutionState::new(msg, initial_sender);
+        self.execute_state(ExecutionState::new(msg, initial_sender))
+    }
+
+    /// Executes all messages of the given state as a single transaction.
+    pub(crate) fn execute_state(
+        &mut self,
+        state: ExecutionState
+    ) -> EnsembleResult<ResponseVariants> {
+        self.gas.start_tx();
+        let result = self.execute_tx(state);
+        self.gas.end_tx();
+
+        result
+    }
+
+    fn execute_tx(&mut self, mut state: ExecutionState) -> EnsembleResult<ResponseVariants> {
+        if let Some(trace) = &mut self.trace {
+            trace.steps.clear();
+        }
 
         while let Some(msg_ty) = state.next() {
             self.state.push_scope();
+            self.gas.start_msg();
+
+            let step = self.trace.as_mut().map(|x| x.start_step(state.depth(), &msg_ty));
 
             let result = match msg_ty {
                 MessageType::SubMsg { msg, sender } => {
@@ -687,8 +1763,29 @@ impl Context {
                         Err(err) => Err(err)
                     }
                 }
+                #[cfg(feature = "ibc")]
+                MessageType::Ibc { address, msg } => {
+                    self.ibc_entry_point(address, msg)
+                }
             };
 
+            let result = result.map(|(mut resp, events)| {
+                resp.set_events(events.events());
+
+                (resp, events)
+            });
+
+            if let (Some(trace), Some(step)) = (&mut self.trace, step) {
+                trace.finish_step(step, &result, &self.state);
+            }
+
+            // Running out of gas fails the whole transaction and can't be handled by a reply.
+            if let Err(err) = self.gas.check_limit() {
+                self.state.revert();
+
+                return Err(err);
+            }
+
             match state.process_result(result) {
                 Ok(mut msgs_reverted) => {
                     while msgs_reverted > 0 {
@@ -707,14 +1804,30 @@ impl Context {
         self.block.next();
         self.state.commit();
 
+        #[cfg(feature = "staking")]
+        self.complete_unbondings();
+
         Ok(state.finalize())
     }
 
+    /// Returns the funds of the unbondings that have completed
+    /// at the current block time to their delegators.
+    #[cfg(feature = "staking")]
+    pub(crate) fn complete_unbondings(&mut self) {
+        let time = Timestamp::from_seconds(self.block.time);
+
+        for unbonding in self.delegations.complete_unbondings(time) {
+            self.state.bank.add_funds(unbonding.delegator.as_str(), unbonding.amount);
+        }
+    }
+
     fn execute_sub_msg(
         &mut self,
         sub_msg: SubMsg,
         sender: String,
     ) -> SubMsgExecuteResult {
+        self.check_failures(|x| x.check_sub_msg(&sub_msg.msg, &sender))?;
+
         match sub_msg.msg {
             CosmosMsg::Wasm(msg) => match msg {
                 WasmMsg::Execute {
@@ -723,15 +1836,23 @@ impl Context {
                     funds,
                     code_hash,
                 } => {
-                    let index = self.state.instance(&contract_addr)?.index;
+                    let mocked = self.find_mock(MockCallKind::Execute, &contract_addr).is_some();
 
-                    if self.contracts[index].code_hash != code_hash {
-                        return Err(EnsembleError::registry(RegistryError::InvalidCodeHash(code_hash)));
+                    match self.state.instance(&contract_addr) {
+                        Ok(instance) => {
+                            if !self.contracts[instance.index].code_hash.eq_ignore_ascii_case(&code_hash) {
+                                return Err(EnsembleError::registry(RegistryError::InvalidCodeHash(code_hash)));
+                            }
+                        },
+                        Err(err) if !mocked => return Err(err),
+                        Err(_) => { }
                     }
 
                     let mut events = if funds.is_empty() {
                         ProcessedEvents::empty()
                     } else {
+                        self.check_failures(|x| x.check_transfer(&contract_addr))?;
+
                         let transfer_resp = self.state.transfer_funds(
                             &sender,
                             &contract_addr,
@@ -746,7 +1867,12 @@ impl Context {
                         contract_addr.clone()
                     ).sent_funds(funds);
 
-                    let execute_resp = self.execute(msg, env)?;
+                    let execute_resp = if mocked {
+                        self.execute_mock(msg, env)?
+                    } else {
+                        self.execute(msg, env)?
+                    };
+
                     events.extend(&execute_resp)?;
 
                     Ok((execute_resp.into(), events))
@@ -757,25 +1883,49 @@ impl Context {
                     funds,
                     label,
                     code_hash,
-                    ..
+                    admin
                 } => {
                     let contract = self
                         .contracts
                         .get(code_id as usize)
                         .ok_or_else(|| EnsembleError::registry(RegistryError::IdNotFound(code_id)))?;
 
-                    if contract.code_hash != code_hash {
+                    if !contract.code_hash.eq_ignore_ascii_case(&code_hash) {
                         return Err(EnsembleError::registry(RegistryError::InvalidCodeHash(code_hash)));
                     }
 
+                    if let Some(admin) = &admin {
+                        self.validate_address(admin)?;
+                    }
+
+                    // Only the message that started the transaction can have a salt.
+                    let salt = self.salt.take();
+
+                    let address = match &self.addresses {
+                        Some(addresses) => match salt {
+                            Some(salt) => addresses.predictable_address(
+                                &self.code_checksum(code_id)?,
+                                &sender,
+                                &salt
+                            )?,
+                            None => addresses.contract_address(
+                                code_id,
+                                self.state.instances.len() as u64 + 1
+                            )
+                        },
+                        None => label.clone()
+                    };
+
                     let env = MockEnv::new_sanitized(
                         sender,
-                        label
+                        address
                     ).sent_funds(funds);
 
                     let mut events = if env.sent_funds.is_empty() {
                         ProcessedEvents::empty()
                     } else {
+                        self.check_failures(|x| x.check_transfer(env.contract()))?;
+
                         let transfer_resp = self.state.transfer_funds(
                             env.sender(),
                             env.contract(),
@@ -788,20 +1938,65 @@ impl Context {
                     let instantiate_resp = self.instantiate(
                         code_id,
                         msg,
-                        env
+                        env,
+                        label,
+                        admin
                     )?;
 
                     events.extend(&instantiate_resp)?;
 
                     Ok((instantiate_resp.into(), events))
                 }
-                _ => panic!("Ensemble: Unsupported message: {:?}", msg)
+                WasmMsg::Migrate {
+                    contract_addr,
+                    code_hash,
+                    code_id,
+                    msg
+                } => {
+                    let contract = self
+                        .contracts
+                        .get(code_id as usize)
+                        .ok_or_else(|| EnsembleError::registry(RegistryError::IdNotFound(code_id)))?;
+
+                    if !contract.code_hash.eq_ignore_ascii_case(&code_hash) {
+                        return Err(EnsembleError::registry(RegistryError::InvalidCodeHash(code_hash)));
+                    }
+
+                    let migrate_resp = self.migrate(
+                        contract_addr,
+                        code_id,
+                        msg,
+                        sender
+                    )?;
+
+                    let events = ProcessedEvents::try_from(&migrate_resp)?;
+
+                    Ok((migrate_resp.into(), events))
+                }
+                WasmMsg::UpdateAdmin { contract_addr, admin } => {
+                    self.validate_address(&admin)?;
+
+                    let resp = self.update_admin(contract_addr, Some(admin), sender)?;
+                    let events = ProcessedEvents::from(&resp);
+
+                    Ok((resp.into(), events))
+                }
+                WasmMsg::ClearAdmin { contract_addr } => {
+                    let resp = self.update_admin(contract_addr, None, sender)?;
+                    let events = ProcessedEvents::from(&resp);
+
+                    Ok((resp.into(), events))
+                }
+                msg => self.execute_module_msg(CosmosMsg::Wasm(msg), sender)
             }
             CosmosMsg::Bank(msg) => match msg {
                 BankMsg::Send {
                     to_address,
                     amount,
                 } => {
+                    self.validate_address(&to_address)?;
+                    self.check_failures(|x| x.check_transfer(&to_address))?;
+
                     let resp = self.state.transfer_funds(
                         &sender,
                         &to_address,
@@ -812,9 +2007,20 @@ impl Context {
 
                     Ok((resp.into(), events))
                 },
-                _ => panic!("Ensemble: Unsupported message: {:?}", msg)
+                BankMsg::Burn { amount } => {
+                    self.state.burn(&sender, amount.clone())?;
+
+                    let resp = BurnResponse {
+                        sender,
+                        coins: amount
+                    };
+                    let events = ProcessedEvents::from(&resp);
+
+                    Ok((resp.into(), events))
+                },
+                msg => self.execute_module_msg(CosmosMsg::Bank(msg), sender)
             }
-            #[cfg(feature = "ensemble-staking")]
+            #[cfg(feature = "staking")]
             CosmosMsg::Staking(msg) => match msg {
                 StakingMsg::Delegate { validator, amount } => {
                     self.state.remove_funds(&sender, vec![amount.clone()])?;
@@ -834,6 +2040,7 @@ impl Context {
                         sender.clone(),
                         validator,
                         amount.clone(),
+                        Timestamp::from_seconds(self.block.time)
                     )?;
 
                     let events = ProcessedEvents::from(&resp);
@@ -856,9 +2063,9 @@ impl Context {
 
                     Ok((resp.into(), events))
                 },
-                _ => panic!("Ensemble: Unsupported message: {:?}", msg)
+                msg => self.execute_module_msg(CosmosMsg::Staking(msg), sender)
             },
-            #[cfg(feature = "ensemble-staking")]
+            #[cfg(feature = "staking")]
             CosmosMsg::Distribution(msg) => match msg {
                 DistributionMsg::WithdrawDelegatorReward { validator } => {
                     // Query accumulated rewards so bank transaction can take place first
@@ -867,16 +2074,140 @@ impl Context {
                         None => return Err(EnsembleError::Staking("Delegation not found".into())),
                     };
 
-                    self.state.add_funds(sender.clone(), withdraw_amount);
+                    self.state.mint(sender.clone(), withdraw_amount);
 
                     let resp = self.delegations.withdraw(sender, validator)?;
                     let events = ProcessedEvents::from(&resp);
 
                     Ok((resp.into(), events))
                 },
-                _ => unimplemented!()
+                msg => self.execute_module_msg(CosmosMsg::Distribution(msg), sender)
+            }
+            #[cfg(feature = "ibc")]
+            CosmosMsg::Ibc(msg) => self.execute_ibc_msg(msg, sender),
+            msg => self.execute_module_msg(msg, sender)
+        }
+    }
+
+    fn execute_module_msg(&mut self, msg: CosmosMsg, sender: String) -> SubMsgExecuteResult {
+        for (name, module) in &self.modules {
+            let deps = ModuleDepsMut {
+                state: &mut self.state,
+                block: &self.block,
+                chain_id: &self.chain_id,
+                name
+            };
+
+            let result = module.execute(deps, &sender, &msg);
+            self.state.flush_module_storage(name);
+
+            if let Some(response) = result? {
+                let resp = ModuleMsgResponse {
+                    module: name.clone(),
+                    sender,
+                    msg,
+                    response
+                };
+                let events = ProcessedEvents::from(&resp);
+
+                return Ok((resp.into(), events));
+            }
+        }
+
+        panic!("Ensemble: Unsupported message: {:?}", msg)
+    }
+
+    pub(crate) fn query_modules(&self, request: &QueryRequest<Empty>) -> Option<AnyResult<Binary>> {
+        for (name, module) in &self.modules {
+            let deps = ModuleDeps {
+                state: &self.state,
+                block: &self.block,
+                chain_id: &self.chain_id,
+                name
+            };
+
+            match module.query(deps, request) {
+                Ok(Some(result)) => return Some(Ok(result)),
+                Ok(None) => continue,
+                Err(err) => return Some(Err(err))
+            }
+        }
+
+        None
+    }
+
+    pub(crate) fn find_mock(&self, kind: MockCallKind, address: &str) -> Option<&ContractMock> {
+        if self.mocks.is_empty() {
+            return None;
+        }
+
+        let code_id = self.state.instance(address).ok().map(|x| x.index as u64);
+
+        self.mocks
+            .iter()
+            .flatten()
+            .find(|x| x.intercepts(kind, address, code_id))
+    }
+
+    /// Returns the first error if any of the failures match. All of them are
+    /// checked so that the ones that count operations stay consistent.
+    fn check_failures(
+        &self,
+        check: impl Fn(&Failure) -> EnsembleResult<()>
+    ) -> EnsembleResult<()> {
+        let mut result = Ok(());
+
+        for failure in self.failures.iter().flatten() {
+            let checked = check(failure);
+
+            if result.is_ok() {
+                result = checked;
             }
-            _ => panic!("Ensemble: Unsupported message: {:?}", sub_msg)
+        }
+
+        result
+    }
+
+    /// Always charges gas for validating the address
+    /// but only checks it if bech32 addresses are enabled.
+    #[inline]
+    fn validate_address(&self, address: &str) -> EnsembleResult<()> {
+        self.gas.charge_addr_validate();
+
+        if let Some(addresses) = &self.addresses {
+            addresses.addr_validate(address)?;
+        }
+
+        Ok(())
+    }
+
+    fn code_checksum(&self, code_id: u64) -> EnsembleResult<Vec<u8>> {
+        let contract = self
+            .contracts
+            .get(code_id as usize)
+            .ok_or_else(|| EnsembleError::registry(RegistryError::IdNotFound(code_id)))?;
+
+        // Placeholder code hashes from ContractEnsemble::register() are not hex.
+        let checksum = if is_code_hash(&contract.code_hash) {
+            (0..contract.code_hash.len())
+                .step_by(2)
+                .map(|i| u8::from_str_radix(&contract.code_hash[i..i + 2], 16).unwrap())
+                .collect()
+        } else {
+            Sha256::digest(contract.code_hash.as_bytes()).to_vec()
+        };
+
+        Ok(checksum)
+    }
+
+    fn assert_admin(&self, address: &str, sender: &str) -> EnsembleResult<()> {
+        match &self.state.instance(address)?.admin {
+            Some(admin) if admin == sender => Ok(()),
+            Some(_) => Err(EnsembleError::registry(RegistryError::Unauthorized {
+                address: address.to_string(),
+                sender: sender.to_string()
+            })),
+            None => Err(EnsembleError::registry(RegistryError::NoAdmin(address.to_string())))
         }
     }
 
@@ -895,7 +2226,7 @@ impl Context {
     }
 
     #[inline]
-    fn create_env(&self, contract: ContractLink<Addr>) -> Env {
+    pub(crate) fn create_env(&self, contract: ContractLink<Addr>) -> Env {
         let seed = 94759574359011638572u128.wrapping_mul(self.block.height as u128);
         
         let mut rng = Rand64::new(seed);
@@ -921,6 +2252,10 @@ impl Debug for Context {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Context")
             .field("contracts_len", &self.contracts.len())
+            .field("mocks", &self.mocks)
+            .field("hooks", &self.hooks)
+            .field("failures", &self.failures)
+            .field("coverage", &self.coverage)
             .field("block", &self.block)
             .field("chain_id", &self.chain_id)
             .finish()
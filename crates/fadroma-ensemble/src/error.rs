@@ -9,6 +9,14 @@ pub enum EnsembleError {
     AttributeValidation(String),
     Bank(String),
     Staking(String),
+    Snapshot(String),
+    Ibc(String),
+    Module(String),
+    Coverage(String),
+    OutOfGas {
+        limit: u64,
+        used: u64
+    },
     Std(StdError)
 }
 
@@ -18,6 +26,11 @@ pub enum RegistryError {
     IdNotFound(u64),
     DuplicateAddress(String),
     InvalidCodeHash(String),
+    NoAdmin(String),
+    Unauthorized {
+        address: String,
+        sender: String
+    }
 }
 
 #[derive(Clone, PartialEq, Debug)]
@@ -71,12 +84,19 @@ impl Display for EnsembleError {
             Self::Staking(msg) => f.write_fmt(format_args!("Ensemble error - Staking: {}", msg)),
             Self::ContractRegistry(err) => f.write_fmt(format_args!("Ensemble error - Contract registry: {}", err.to_string())),
             Self::AttributeValidation(msg) => f.write_fmt(format_args!("Ensemble error - Event attribute validation: {}", msg)),
+            Self::Snapshot(msg) => f.write_fmt(format_args!("Ensemble error - Snapshot: {}", msg)),
+            Self::Ibc(msg) => f.write_fmt(format_args!("Ensemble error - IBC: {}", msg)),
+            Self::Module(msg) => f.write_fmt(format_args!("Ensemble error - Module: {}", msg)),
+            Self::Coverage(msg) => f.write_fmt(format_args!("Ensemble error - Coverage: {}", msg)),
+            Self::OutOfGas { limit, used } => f.write_fmt(format_args!("Ensemble error - Out of gas: used {} with a limit of {}", used, limit)),
             Self::Std(err) => Display::fmt(err, f),
             Self::ContractError(err) => Display::fmt(err, f)
         }
     }
 }
 
+impl std::error::Error for EnsembleError { }
+
 impl Display for RegistryError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -84,6 +104,8 @@ impl Display for RegistryError {
             Self::DuplicateAddress(address) => f.write_fmt(format_args!("Contract instance with address {} already exists", address)),
             Self::IdNotFound(id) => f.write_fmt(format_args!("Contract with id {} not found", id)),
             Self::InvalidCodeHash(hash) => f.write_fmt(format_args!("Contract code hash {} is invalid", hash)),
+            Self::NoAdmin(address) => f.write_fmt(format_args!("Contract {} has no admin", address)),
+            Self::Unauthorized { address, sender } => f.write_fmt(format_args!("{} is not the admin of contract {}", sender, address)),
         }
     }
 }
@@ -1,16 +1,21 @@
 use std::convert::{TryFrom, TryInto};
 
-#[cfg(feature = "ensemble-staking")]
+#[cfg(feature = "staking")]
 use time::{OffsetDateTime, format_description::well_known::Rfc3339};
 
 use fadroma::cosmwasm_std::{Response, Attribute, Event};
 use super::{
     EnsembleResult, EnsembleError,
     response::{
-        InstantiateResponse, ExecuteResponse, BankResponse, ReplyResponse
+        InstantiateResponse, ExecuteResponse, BankResponse, BurnResponse,
+        ReplyResponse, MigrateResponse, AdminResponse, ModuleMsgResponse
     }
 };
-#[cfg(feature = "ensemble-staking")]
+#[cfg(feature = "ibc")]
+use fadroma::cosmwasm_std::IbcMsg;
+#[cfg(feature = "ibc")]
+use super::response::{IbcResponse, IbcMsgResponse};
+#[cfg(feature = "staking")]
 use super::response::{StakingResponse, StakingOp, DistributionResponse, DistributionOp};
 
 const CONTRACT_ATTR: &str = "contract_address";
@@ -33,6 +38,11 @@ impl ProcessedEvents {
         Ok(())
     }
 
+    #[inline]
+    pub fn events(&self) -> &[Event] {
+        &self.0
+    }
+
     #[inline]
     pub fn take(self) -> Vec<Event> {
         self.0
@@ -94,6 +104,39 @@ impl TryFrom<&ReplyResponse> for ProcessedEvents {
     }
 }
 
+impl TryFrom<&MigrateResponse> for ProcessedEvents {
+    type Error = EnsembleError;
+
+    fn try_from(resp: &MigrateResponse) -> Result<Self, Self::Error> {
+        validate_response(&resp.response)?;
+
+        let address = resp.address.as_str();
+        let event = Event::new("migrate")
+            .add_attribute(CONTRACT_ATTR, address)
+            .add_attribute("code_id", resp.code_id.to_string());
+
+        Ok(process_wasm_response(
+            &resp.response,
+            address.into(),
+            event
+        ))
+    }
+}
+
+impl From<&AdminResponse> for ProcessedEvents {
+    fn from(resp: &AdminResponse) -> Self {
+        let event = match &resp.admin {
+            Some(admin) => Event::new("update_contract_admin")
+                .add_attribute(CONTRACT_ATTR, &resp.address)
+                .add_attribute("new_admin_address", admin),
+            None => Event::new("clear_contract_admin")
+                .add_attribute(CONTRACT_ATTR, &resp.address)
+        };
+
+        Self(vec![event])
+    }
+}
+
 impl From<&BankResponse> for ProcessedEvents {
     fn from(resp: &BankResponse) -> Self {
         let coins: String = resp.coins.iter()
@@ -118,7 +161,33 @@ impl From<&BankResponse> for ProcessedEvents {
     }
 }
 
-#[cfg(feature = "ensemble-staking")]
+impl From<&BurnResponse> for ProcessedEvents {
+    fn from(resp: &BurnResponse) -> Self {
+        let coins: String = resp.coins.iter()
+            .map(|x| format!("{}{}", x.amount, x.denom))
+            .collect::<Vec<String>>()
+            .join(",");
+
+        Self(vec![
+            Event::new("coin_spent")
+                .add_attribute("amount", coins.clone())
+                .add_attribute("spender", &resp.sender),
+
+            Event::new("burn")
+                .add_attribute("amount", coins)
+                .add_attribute("burner", &resp.sender)
+        ])
+    }
+}
+
+impl From<&ModuleMsgResponse> for ProcessedEvents {
+    #[inline]
+    fn from(resp: &ModuleMsgResponse) -> Self {
+        Self(resp.response.events.clone())
+    }
+}
+
+#[cfg(feature = "staking")]
 impl From<&StakingResponse> for ProcessedEvents {
     fn from(resp: &StakingResponse) -> Self {
         let amount_value = format!("{}{}", resp.amount.amount, resp.amount.denom);
@@ -129,8 +198,11 @@ impl From<&StakingResponse> for ProcessedEvents {
                     .add_attribute("validator", validator)
                     .add_attribute("amount", amount_value)
                     .add_attribute("new_shares", resp.amount.amount.to_string()),
-            StakingOp::Undelegate { validator } => {
-                let date = OffsetDateTime::now_utc().format(&Rfc3339).unwrap();
+            StakingOp::Undelegate { validator, completion_time } => {
+                let date = OffsetDateTime::from_unix_timestamp(completion_time.seconds() as i64)
+                    .unwrap()
+                    .format(&Rfc3339)
+                    .unwrap();
 
                 Event::new("unbond")
                     .add_attribute("validator", validator)
@@ -148,7 +220,7 @@ impl From<&StakingResponse> for ProcessedEvents {
     }
 }
 
-#[cfg(feature = "ensemble-staking")]
+#[cfg(feature = "staking")]
 impl From<&DistributionResponse> for ProcessedEvents {
     fn from(resp: &DistributionResponse) -> Self {
         let event = match &resp.kind {
@@ -167,6 +239,44 @@ impl From<&DistributionResponse> for ProcessedEvents {
     }
 }
 
+#[cfg(feature = "ibc")]
+impl TryFrom<&IbcResponse> for ProcessedEvents {
+    type Error = EnsembleError;
+
+    fn try_from(resp: &IbcResponse) -> Result<Self, Self::Error> {
+        validate_response(&resp.response)?;
+
+        let address = resp.address.as_str();
+        let event = Event::new(resp.msg.name())
+            .add_attribute(CONTRACT_ATTR, address);
+
+        Ok(process_wasm_response(
+            &resp.response,
+            address.into(),
+            event
+        ))
+    }
+}
+
+#[cfg(feature = "ibc")]
+impl From<&IbcMsgResponse> for ProcessedEvents {
+    fn from(resp: &IbcMsgResponse) -> Self {
+        let event = match (&resp.packet, &resp.msg) {
+            (Some(packet), _) => Event::new("send_packet")
+                .add_attribute("packet_src_port", &packet.src.port_id)
+                .add_attribute("packet_src_channel", &packet.src.channel_id)
+                .add_attribute("packet_dst_port", &packet.dest.port_id)
+                .add_attribute("packet_dst_channel", &packet.dest.channel_id)
+                .add_attribute("packet_sequence", packet.sequence.to_string()),
+            (None, IbcMsg::CloseChannel { channel_id }) => Event::new("channel_close_init")
+                .add_attribute("channel_id", channel_id),
+            _ => unreachable!()
+        };
+
+        Self(vec![event])
+    }
+}
+
 fn process_wasm_response(
     response: &Response,
     address: String,
@@ -0,0 +1,409 @@
+use std::{
+    fmt::{Debug, Display, Write},
+    ops::Deref,
+    rc::Rc
+};
+
+use regex::Regex;
+use fadroma::cosmwasm_std::Event;
+
+const CONTRACT_ATTR: &str = "contract_address";
+
+/// All events emitted by a message and the messages that it initiated,
+/// in the order that the chain emits them. Returned by the `event_log`
+/// method of the responses.
+///
+/// Derefs to a slice of the events. Use [`EventMatcher`] to find events
+/// or to assert that they were emitted. The `assert_*` methods panic
+/// with a description of the closest events when the assertion fails.
+///
+/// # Examples
+///
+/// ```
+/// # use fadroma_ensemble::{EventLog, EventMatcher, cosmwasm_std::Event};
+/// let log = EventLog::new(vec![
+///     Event::new("wasm")
+///         .add_attribute("action", "transfer")
+///         .add_attribute("amount", "100")
+///         .add_attribute("contract_address", "token"),
+///     Event::new("wasm-payout")
+///         .add_attribute("contract_address", "vault")
+///         .add_attribute("recipient", "alice")
+/// ]);
+///
+/// log.assert(EventMatcher::wasm()
+///     .contract("token")
+///     .attr("action", "transfer")
+///     .attr_regex("amount", r"^\d+$"));
+///
+/// log.assert_order([
+///     EventMatcher::wasm().contract("token"),
+///     EventMatcher::custom("payout").attr_with("recipient", |x| x.starts_with('a'))
+/// ]);
+///
+/// log.assert_none(EventMatcher::custom("payout").contract("token"));
+/// ```
+#[derive(Clone, PartialEq, Default, Debug)]
+pub struct EventLog(Vec<Event>);
+
+/// Matches events by type and attributes. All conditions
+/// must be met for an event to match.
+#[derive(Clone, Debug)]
+pub struct EventMatcher {
+    ty: Option<String>,
+    attrs: Vec<(String, ValueMatcher)>
+}
+
+#[derive(Clone)]
+enum ValueMatcher {
+    Exact(String),
+    Regex(Regex),
+    Predicate(Rc<dyn Fn(&str) -> bool>),
+    Any
+}
+
+impl EventLog {
+    #[inline]
+    pub fn new(events: Vec<Event>) -> Self {
+        Self(events)
+    }
+
+    /// Returns the first event that matches.
+    #[inline]
+    pub fn find(&self, matcher: &EventMatcher) -> Option<&Event> {
+        self.0.iter().find(|x| matcher.matches(x))
+    }
+
+    /// Returns all events that match.
+    pub fn filter<'a>(&'a self, matcher: &'a EventMatcher) -> impl Iterator<Item = &'a Event> {
+        self.0.iter().filter(move |x| matcher.matches(x))
+    }
+
+    /// Returns the first event that matches.
+    ///
+    /// # Panics
+    ///
+    /// If no event matches.
+    #[track_caller]
+    pub fn assert(&self, matcher: EventMatcher) -> &Event {
+        match self.find(&matcher) {
+            Some(event) => event,
+            None => panic!("{}", self.failure("No event matched", &matcher, 0))
+        }
+    }
+
+    /// # Panics
+    ///
+    /// If any event matches.
+    #[track_caller]
+    pub fn assert_none(&self, matcher: EventMatcher) {
+        if let Some(index) = self.0.iter().position(|x| matcher.matches(x)) {
+            panic!(
+                "Expected no event to match {}, but found:\n  #{} {}",
+                matcher,
+                index,
+                DisplayEvent(&self.0[index])
+            );
+        }
+    }
+
+    /// # Panics
+    ///
+    /// If the number of events that match is not `count`.
+    #[track_caller]
+    pub fn assert_count(&self, matcher: EventMatcher, count: usize) {
+        let found = self.filter(&matcher).count();
+
+        if found != count {
+            let msg = format!("Expected {} events to match, found {}", count, found);
+            panic!("{}", self.failure(&msg, &matcher, 0));
+        }
+    }
+
+    /// Asserts that the events were emitted in the given order. Other
+    /// events can be emitted in between. Each matcher must match a
+    /// different event which comes after the one matched by the
+    /// previous matcher.
+    ///
+    /// # Panics
+    ///
+    /// If there is no such sequence of events.
+    #[track_caller]
+    pub fn assert_order(&self, matchers: impl IntoIterator<Item = EventMatcher>) {
+        let mut start = 0;
+
+        for (i, matcher) in matchers.into_iter().enumerate() {
+            match self.0[start..].iter().position(|x| matcher.matches(x)) {
+                Some(index) => start += index + 1,
+                None => {
+                    let mut msg = if i == 0 {
+                        "No event matched the first matcher".to_string()
+                    } else {
+                        format!("No event after #{} matched matcher #{}", start - 1, i)
+                    };
+
+                    if let Some(index) = self.0[..start].iter().position(|x| matcher.matches(x)) {
+                        let _ = write!(msg, " (it matched #{}, which comes before)", index);
+                    }
+
+                    panic!("{}", self.failure(&msg, &matcher, start));
+                }
+            }
+        }
+    }
+
+    fn failure(&self, msg: &str, matcher: &EventMatcher, start: usize) -> String {
+        let mut result = format!("{}: {}\n", msg, matcher);
+
+        let candidates: Vec<_> = self.0.iter()
+            .enumerate()
+            .skip(start)
+            .filter(|(_, x)| matcher.ty.as_ref().map(|ty| *ty == x.ty).unwrap_or(true))
+            .collect();
+
+        if !candidates.is_empty() {
+            result.push_str("Events of the same type:\n");
+
+            for (index, event) in candidates {
+                let _ = writeln!(result, "  #{} {}", index, DisplayEvent(event));
+
+                for diff in matcher.diff(event) {
+                    let _ = writeln!(result, "     - {}", diff);
+                }
+            }
+        }
+
+        result.push_str("All events:\n");
+
+        if self.0.is_empty() {
+            result.push_str("  (none)\n");
+        }
+
+        for (index, event) in self.0.iter().enumerate() {
+            let _ = writeln!(result, "  #{} {}", index, DisplayEvent(event));
+        }
+
+        result
+    }
+}
+
+impl Deref for EventLog {
+    type Target = [Event];
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl From<Vec<Event>> for EventLog {
+    #[inline]
+    fn from(events: Vec<Event>) -> Self {
+        Self(events)
+    }
+}
+
+impl From<EventLog> for Vec<Event> {
+    #[inline]
+    fn from(log: EventLog) -> Self {
+        log.0
+    }
+}
+
+impl EventMatcher {
+    /// Matches events of the given type.
+    #[inline]
+    pub fn new(ty: impl Into<String>) -> Self {
+        Self {
+            ty: Some(ty.into()),
+            attrs: vec![]
+        }
+    }
+
+    /// Matches events of any type.
+    #[inline]
+    pub fn any() -> Self {
+        Self {
+            ty: None,
+            attrs: vec![]
+        }
+    }
+
+    /// Matches the `wasm` events that contain the attributes
+    /// added with `Response::add_attribute`.
+    #[inline]
+    pub fn wasm() -> Self {
+        Self::new("wasm")
+    }
+
+    /// Matches events added with `Response::add_event`,
+    /// whose type is prefixed with `wasm-` by the chain.
+    #[inline]
+    pub fn custom(ty: impl AsRef<str>) -> Self {
+        Self::new(format!("wasm-{}", ty.as_ref()))
+    }
+
+    /// Matches events emitted by the contract with the given address.
+    #[inline]
+    pub fn contract(self, address: impl Into<String>) -> Self {
+        self.attr(CONTRACT_ATTR, address)
+    }
+
+    /// Matches events that have the attribute with the given value.
+    #[inline]
+    pub fn attr(self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.with(key, ValueMatcher::Exact(value.into()))
+    }
+
+    /// Matches events that have the attribute with a value
+    /// that matches the given regular expression.
+    ///
+    /// # Panics
+    ///
+    /// If the regular expression is not valid.
+    pub fn attr_regex(self, key: impl Into<String>, regex: &str) -> Self {
+        let regex = Regex::new(regex).unwrap_or_else(|err|
+            panic!("Ensemble: Invalid regular expression \"{}\": {}", regex, err)
+        );
+
+        self.with(key, ValueMatcher::Regex(regex))
+    }
+
+    /// Matches events that have the attribute with a value
+    /// for which the given function returns `true`.
+    #[inline]
+    pub fn attr_with(
+        self,
+        key: impl Into<String>,
+        predicate: impl Fn(&str) -> bool + 'static
+    ) -> Self {
+        self.with(key, ValueMatcher::Predicate(Rc::new(predicate)))
+    }
+
+    /// Matches events that have the attribute with any value.
+    #[inline]
+    pub fn has_attr(self, key: impl Into<String>) -> Self {
+        self.with(key, ValueMatcher::Any)
+    }
+
+    /// Returns `true` if the event meets all conditions.
+    pub fn matches(&self, event: &Event) -> bool {
+        if let Some(ty) = &self.ty {
+            if *ty != event.ty {
+                return false;
+            }
+        }
+
+        self.attrs.iter().all(|(key, value)|
+            event.attributes.iter().any(|x| x.key == *key && value.matches(&x.value))
+        )
+    }
+
+    fn with(mut self, key: impl Into<String>, value: ValueMatcher) -> Self {
+        self.attrs.push((key.into(), value));
+
+        self
+    }
+
+    /// Describes the conditions that the event doesn't meet.
+    fn diff(&self, event: &Event) -> Vec<String> {
+        let mut result = vec![];
+
+        if let Some(ty) = &self.ty {
+            if *ty != event.ty {
+                result.push(format!("type: expected \"{}\", got \"{}\"", ty, event.ty));
+            }
+        }
+
+        for (key, value) in &self.attrs {
+            let values: Vec<&str> = event.attributes.iter()
+                .filter(|x| x.key == *key)
+                .map(|x| x.value.as_str())
+                .collect();
+
+            if values.iter().any(|x| value.matches(x)) {
+                continue;
+            }
+
+            if values.is_empty() {
+                result.push(format!("{}: the attribute is missing", key));
+            } else {
+                result.push(format!("{}: expected {}, got \"{}\"", key, value, values.join("\", \"")));
+            }
+        }
+
+        result
+    }
+}
+
+impl Display for EventMatcher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.ty {
+            Some(ty) => write!(f, "{}", ty)?,
+            None => f.write_str("any")?
+        }
+
+        if self.attrs.is_empty() {
+            return Ok(());
+        }
+
+        f.write_str(" [")?;
+
+        for (i, (key, value)) in self.attrs.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+
+            write!(f, "{} {}", key, value)?;
+        }
+
+        f.write_str("]")
+    }
+}
+
+impl ValueMatcher {
+    fn matches(&self, value: &str) -> bool {
+        match self {
+            Self::Exact(expected) => expected == value,
+            Self::Regex(regex) => regex.is_match(value),
+            Self::Predicate(predicate) => predicate(value),
+            Self::Any => true
+        }
+    }
+}
+
+impl Display for ValueMatcher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Exact(value) => write!(f, "= \"{}\"", value),
+            Self::Regex(regex) => write!(f, "=~ /{}/", regex),
+            Self::Predicate(_) => f.write_str("matching a predicate"),
+            Self::Any => f.write_str("with any value")
+        }
+    }
+}
+
+impl Debug for ValueMatcher {
+    #[inline]
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+struct DisplayEvent<'a>(&'a Event);
+
+impl Display for DisplayEvent<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} [", self.0.ty)?;
+
+        for (i, attr) in self.0.attributes.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+
+            write!(f, "{} = \"{}\"", attr.key, attr.value)?;
+        }
+
+        f.write_str("]")
+    }
+}
@@ -1,9 +1,13 @@
 use fadroma::{
     cosmwasm_std::{SubMsg, ReplyOn, Event, Binary},
 };
+#[cfg(feature = "ibc")]
+use fadroma::cosmwasm_std::{CosmosMsg, Empty};
 use crate::{
     ResponseVariants, EnsembleResult, SubMsgExecuteResult
 };
+#[cfg(feature = "ibc")]
+use crate::ibc::IbcEntryPoint;
 
 pub struct ExecutionState {
     states: Vec<ExecutionLevel>,
@@ -19,6 +23,11 @@ pub enum MessageType {
         id: u64,
         error: Option<String>,
         target: String
+    },
+    #[cfg(feature = "ibc")]
+    Ibc {
+        address: String,
+        msg: IbcEntryPoint
     }
 }
 
@@ -60,6 +69,20 @@ impl ExecutionState {
         }
     }
 
+    /// Starts the execution from one of the IBC entry points of the contract at `address`.
+    #[cfg(feature = "ibc")]
+    pub fn new_ibc(address: String, msg: IbcEntryPoint) -> Self {
+        // The root node is never executed. It only serves as
+        // the parent of the messages that the contract returns.
+        let mut level = ExecutionLevel::new(vec![SubMsg::new(CosmosMsg::Custom(Empty {}))]);
+        level.current_mut().state = SubMsgState::Done;
+
+        Self {
+            states: vec![level],
+            next: Some(MessageType::Ibc { address, msg })
+        }
+    }
+
     pub fn process_result(
         &mut self,
         result: SubMsgExecuteResult
@@ -75,6 +98,8 @@ impl ExecutionState {
                     } else {
                         self.current_level_mut().data = cw_resp.data.clone();
                     }
+                } else if let ResponseVariants::Module(resp) = &response {
+                    self.current_level_mut().data = resp.response.data.clone();
                 }
 
                 let level = self.current_level_mut();
@@ -118,6 +143,12 @@ impl ExecutionState {
         self.next.take()
     }
 
+    /// The depth in the call tree of the message returned by the last call to `next`.
+    #[inline]
+    pub fn depth(&self) -> usize {
+        self.states.len() - 1
+    }
+
     #[inline]
     pub fn events(&self) -> &[Event] {
         &self.current_level().current().events
@@ -354,10 +385,18 @@ fn contract_address(resp: &ResponseVariants) -> &str {
         ResponseVariants::Instantiate(resp) => resp.instance.address.as_str(),
         ResponseVariants::Execute(resp) => &resp.address,
         ResponseVariants::Reply(resp) => &resp.address,
+        ResponseVariants::Migrate(resp) => &resp.address,
+        ResponseVariants::Admin(_) => unreachable!(),
         ResponseVariants::Bank(_) => unreachable!(),
-        #[cfg(feature = "ensemble-staking")]
+        ResponseVariants::Burn(_) => unreachable!(),
+        ResponseVariants::Module(_) => unreachable!(),
+        #[cfg(feature = "staking")]
         ResponseVariants::Staking(_) => unreachable!(),
-        #[cfg(feature = "ensemble-staking")]
-        ResponseVariants::Distribution(_) => unreachable!()
+        #[cfg(feature = "staking")]
+        ResponseVariants::Distribution(_) => unreachable!(),
+        #[cfg(feature = "ibc")]
+        ResponseVariants::Ibc(resp) => &resp.address,
+        #[cfg(feature = "ibc")]
+        ResponseVariants::IbcMsg(_) => unreachable!()
     }
 }
@@ -0,0 +1,178 @@
+use std::{cell::Cell, fmt::Debug};
+
+use fadroma::cosmwasm_std::CosmosMsg;
+
+use super::{
+    ensemble::EnsembleResult,
+    error::EnsembleError,
+    anyhow::anyhow
+};
+
+type SubMsgPredicate = Box<dyn Fn(&CosmosMsg, &str) -> bool>;
+
+/// Makes an operation fail deterministically with a contract error, so
+/// that it can be handled by a sub-message with `reply_on_error` just like
+/// an error returned by a contract. Added with
+/// [`crate::ContractEnsemble::inject_failure`].
+///
+/// # Examples
+///
+/// ```
+/// # use fadroma_ensemble::{ContractEnsemble, Failure};
+/// # use fadroma::cosmwasm_std::{CosmosMsg, BankMsg};
+/// let mut ensemble = ContractEnsemble::new();
+///
+/// // Fails the second query to the oracle.
+/// ensemble.inject_failure(Failure::nth_query("oracle", 2));
+///
+/// // Fails all bank transfers to the treasury.
+/// ensemble.inject_failure(Failure::bank_transfer_to("treasury").with_error("Frozen"));
+///
+/// // Fails all bank messages sent by the vault.
+/// ensemble.inject_failure(Failure::sub_msg(|msg, sender|
+///     sender == "vault" && matches!(msg, CosmosMsg::Bank(_))
+/// ));
+/// ```
+pub struct Failure {
+    kind: FailureKind,
+    error: Option<String>,
+    pub(crate) triggered: Cell<u64>
+}
+
+enum FailureKind {
+    BankTransfer {
+        recipient: String
+    },
+    Query {
+        address: String,
+        /// [`None`] if all queries fail.
+        nth: Option<u64>,
+        count: Cell<u64>
+    },
+    SubMsg(SubMsgPredicate)
+}
+
+/// Identifies a [`Failure`] that was added to the ensemble.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct FailureId(pub(crate) usize);
+
+impl Failure {
+    /// Fails all bank transfers to the given address. This includes
+    /// `BankMsg::Send` as well as funds sent with execute and
+    /// instantiate messages.
+    #[inline]
+    pub fn bank_transfer_to(address: impl Into<String>) -> Self {
+        Self::new(FailureKind::BankTransfer { recipient: address.into() })
+    }
+
+    /// Fails all smart queries to the contract with the given address.
+    #[inline]
+    pub fn query(address: impl Into<String>) -> Self {
+        Self::new(FailureKind::Query {
+            address: address.into(),
+            nth: None,
+            count: Cell::new(0)
+        })
+    }
+
+    /// Fails only the `n`th smart query to the contract with the given
+    /// address, counting from 1 and from when the failure was added.
+    ///
+    /// # Panics
+    ///
+    /// If `n` is 0.
+    pub fn nth_query(address: impl Into<String>, n: u64) -> Self {
+        assert!(n > 0, "Ensemble: Queries are counted from 1.");
+
+        Self::new(FailureKind::Query {
+            address: address.into(),
+            nth: Some(n),
+            count: Cell::new(0)
+        })
+    }
+
+    /// Fails all messages for which the given function returns `true`.
+    /// It's called with the message and its sender, before the message
+    /// is executed. This includes the messages sent by
+    /// [`crate::ContractEnsemble::execute`] and the other top level
+    /// methods, whose sender is the sender of their `MockEnv`.
+    #[inline]
+    pub fn sub_msg(predicate: impl Fn(&CosmosMsg, &str) -> bool + 'static) -> Self {
+        Self::new(FailureKind::SubMsg(Box::new(predicate)))
+    }
+
+    /// Sets the error that the operation fails with.
+    /// Otherwise, the error describes the failure.
+    #[inline]
+    pub fn with_error(mut self, error: impl Into<String>) -> Self {
+        self.error = Some(error.into());
+
+        self
+    }
+
+    fn new(kind: FailureKind) -> Self {
+        Self {
+            kind,
+            error: None,
+            triggered: Cell::new(0)
+        }
+    }
+
+    pub(crate) fn check_transfer(&self, recipient: &str) -> EnsembleResult<()> {
+        match &self.kind {
+            FailureKind::BankTransfer { recipient: x } if x == recipient =>
+                Err(self.fail(|| format!("bank transfer to {}", recipient))),
+            _ => Ok(())
+        }
+    }
+
+    pub(crate) fn check_query(&self, address: &str) -> EnsembleResult<()> {
+        match &self.kind {
+            FailureKind::Query { address: x, nth, count } if x == address => {
+                count.set(count.get() + 1);
+
+                match nth {
+                    Some(n) if *n != count.get() => Ok(()),
+                    _ => Err(self.fail(|| format!("query #{} to {}", count.get(), address)))
+                }
+            },
+            _ => Ok(())
+        }
+    }
+
+    pub(crate) fn check_sub_msg(&self, msg: &CosmosMsg, sender: &str) -> EnsembleResult<()> {
+        match &self.kind {
+            FailureKind::SubMsg(predicate) if predicate(msg, sender) =>
+                Err(self.fail(|| format!("message sent by {}", sender))),
+            _ => Ok(())
+        }
+    }
+
+    fn fail(&self, describe: impl FnOnce() -> String) -> EnsembleError {
+        self.triggered.set(self.triggered.get() + 1);
+
+        let error = match &self.error {
+            Some(error) => error.clone(),
+            None => format!("Injected failure: {}", describe())
+        };
+
+        EnsembleError::ContractError(anyhow!(error))
+    }
+}
+
+impl Debug for Failure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = match &self.kind {
+            FailureKind::BankTransfer { recipient } => format!("BankTransfer({})", recipient),
+            FailureKind::Query { address, nth: Some(n), .. } => format!("Query({}, #{})", address, n),
+            FailureKind::Query { address, nth: None, .. } => format!("Query({})", address),
+            FailureKind::SubMsg(_) => "SubMsg".into()
+        };
+
+        f.debug_struct("Failure")
+            .field("kind", &kind)
+            .field("error", &self.error)
+            .field("triggered", &self.triggered.get())
+            .finish()
+    }
+}
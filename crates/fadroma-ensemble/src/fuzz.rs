@@ -0,0 +1,352 @@
+use std::{
+    cell::RefCell,
+    fmt::{self, Display, Debug},
+    ops::Range,
+    panic::{self, AssertUnwindSafe}
+};
+
+use serde::Serialize;
+use proptest::{
+    collection::vec,
+    sample::select,
+    strategy::{Strategy, BoxedStrategy, Union},
+    test_runner::{Config, TestRunner, TestCaseError, TestError}
+};
+use fadroma::cosmwasm_std::{Binary, Coin, to_binary};
+
+use super::{
+    ContractEnsemble, AnyResult,
+    env::MockEnv,
+    trace::{decode, format_coins}
+};
+
+type Invariant = Box<dyn Fn(&ContractEnsemble) -> AnyResult<()>>;
+
+/// Runs random sequences of execute messages against a [`ContractEnsemble`]
+/// and checks a set of invariants after each one. When an invariant is
+/// violated or a contract panics, the sequence is shrunk down to a minimal
+/// reproduction which is returned as a [`FuzzFailure`].
+///
+/// The ensemble passed to [`Fuzzer::new`] should already contain all of the
+/// contracts and funds that the test needs. Its state is captured as a
+/// [`crate::Snapshot`] and restored before each sequence, so the setup only
+/// runs once. Messages that return an error are not failures by themselves,
+/// since the ensemble reverts their state changes anyway.
+///
+/// # Examples
+///
+/// ```ignore
+/// let mut fuzzer = Fuzzer::new(ensemble)
+///     .actors(["alice", "bob"])
+///     .message("token", any_transfer())
+///     .invariant("total supply", |ensemble| {
+///         let supply: Uint128 = ensemble.query("token", &QueryMsg::TotalSupply {})?;
+///         ensure!(supply == sum_of_balances(ensemble)?, "{} != sum of balances", supply);
+///
+///         Ok(())
+///     });
+///
+/// fuzzer.check();
+/// ```
+pub struct Fuzzer {
+    ensemble: ContractEnsemble,
+    actors: Vec<String>,
+    generators: Vec<BoxedStrategy<Call>>,
+    invariants: Vec<(String, Invariant)>,
+    steps: Range<usize>,
+    config: Config
+}
+
+/// A single execute message in a sequence generated by a [`Fuzzer`].
+#[derive(Clone, PartialEq, Debug)]
+pub struct FuzzStep {
+    pub sender: String,
+    pub contract: String,
+    pub msg: Binary,
+    pub funds: Vec<Coin>,
+    /// The error that the message returned, if any. Only
+    /// populated in the steps of a [`FuzzFailure`].
+    pub error: Option<String>
+}
+
+/// The minimal sequence of steps that reproduces a failure.
+/// Its [`Display`] implementation prints the sequence.
+#[derive(Clone, PartialEq, Debug)]
+pub struct FuzzFailure {
+    /// Why the sequence failed i.e the violated invariant or a panic message.
+    pub reason: String,
+    pub steps: Vec<FuzzStep>
+}
+
+#[derive(Clone, Debug)]
+struct Call {
+    contract: String,
+    msg: Binary,
+    funds: Vec<Coin>
+}
+
+impl Fuzzer {
+    /// Creates a fuzzer that generates sequences of 1 to 32 steps and runs
+    /// 256 of them. Failures are not persisted to disk, so pass a custom
+    /// [`Config`] to [`Fuzzer::config`] in order to change that.
+    pub fn new(ensemble: ContractEnsemble) -> Self {
+        Self {
+            ensemble,
+            actors: vec![],
+            generators: vec![],
+            invariants: vec![],
+            steps: 1..33,
+            config: Config {
+                failure_persistence: None,
+                ..Config::default()
+            }
+        }
+    }
+
+    /// The addresses that will be used as the sender of messages.
+    pub fn actors<T: Into<String>>(mut self, actors: impl IntoIterator<Item = T>) -> Self {
+        self.actors.extend(actors.into_iter().map(Into::into));
+
+        self
+    }
+
+    /// Adds a generator of execute messages for the contract at `address`.
+    /// Each step picks one of the added generators at random.
+    pub fn message<M, S>(self, address: impl Into<String>, strategy: S) -> Self
+        where
+            M: Serialize + Debug,
+            S: Strategy<Value = M> + 'static
+    {
+        self.message_with_funds(address, strategy.prop_map(|msg| (msg, vec![])))
+    }
+
+    /// Same as [`Fuzzer::message`] but the strategy also generates the funds
+    /// sent with the message. The sender must have enough funds, otherwise the
+    /// message fails.
+    pub fn message_with_funds<M, S>(mut self, address: impl Into<String>, strategy: S) -> Self
+        where
+            M: Serialize + Debug,
+            S: Strategy<Value = (M, Vec<Coin>)> + 'static
+    {
+        let contract = address.into();
+
+        self.generators.push(strategy.prop_map(move |(msg, funds)| Call {
+            contract: contract.clone(),
+            msg: to_binary(&msg).expect("Fuzzer: message must serialize to JSON."),
+            funds
+        }).boxed());
+
+        self
+    }
+
+    /// Adds an invariant that must hold before the first and after each step.
+    pub fn invariant(
+        mut self,
+        name: impl Into<String>,
+        check: impl Fn(&ContractEnsemble) -> AnyResult<()> + 'static
+    ) -> Self {
+        self.invariants.push((name.into(), Box::new(check)));
+
+        self
+    }
+
+    /// Sets the range of the number of steps in each generated sequence.
+    #[inline]
+    pub fn steps(mut self, range: Range<usize>) -> Self {
+        self.steps = range;
+
+        self
+    }
+
+    /// Sets the proptest configuration, such as the number of cases to run.
+    #[inline]
+    pub fn config(mut self, config: Config) -> Self {
+        self.config = config;
+
+        self
+    }
+
+    /// Returns the ensemble which is in the same state as when it was passed to [`Fuzzer::new`].
+    #[inline]
+    pub fn ensemble(&self) -> &ContractEnsemble {
+        &self.ensemble
+    }
+
+    /// Runs the configured number of random sequences and returns the
+    /// shrunk sequence of the first one that failed.
+    ///
+    /// # Panics
+    ///
+    /// If no actors or message generators were added.
+    pub fn run(&mut self) -> Result<(), FuzzFailure> {
+        assert!(!self.actors.is_empty(), "Fuzzer: at least one actor is required.");
+        assert!(!self.generators.is_empty(), "Fuzzer: at least one message generator is required.");
+
+        let strategy = vec(
+            (select(self.actors.clone()), Union::new(self.generators.clone()))
+                .prop_map(|(sender, call)| FuzzStep {
+                    sender,
+                    contract: call.contract,
+                    msg: call.msg,
+                    funds: call.funds,
+                    error: None
+                }),
+            self.steps.clone()
+        );
+
+        let snapshot = self.ensemble.snapshot();
+        let mut runner = TestRunner::new(self.config.clone());
+
+        let result = {
+            let ensemble = RefCell::new(&mut self.ensemble);
+            let invariants = &self.invariants;
+
+            runner.run(&strategy, |mut steps| {
+                let mut ensemble = ensemble.borrow_mut();
+                ensemble.restore(&snapshot);
+
+                run_steps(&mut ensemble, invariants, &mut steps)
+                    .map_err(TestCaseError::fail)
+            })
+        };
+
+        self.ensemble.restore(&snapshot);
+
+        match result {
+            Ok(()) => Ok(()),
+            Err(TestError::Fail(reason, steps)) => {
+                let steps = self.remove_redundant_steps(steps);
+
+                // Replay the minimal sequence in order to record the result of each step.
+                match self.replay(steps.clone()) {
+                    Err(failure) => Err(failure),
+                    Ok(steps) => Err(FuzzFailure {
+                        reason: reason.message().to_string(),
+                        steps
+                    })
+                }
+            },
+            Err(TestError::Abort(reason)) => Err(FuzzFailure {
+                reason: reason.message().to_string(),
+                steps: vec![]
+            })
+        }
+    }
+
+    /// Executes the given steps in order and checks the invariants, the same
+    /// way that [`Fuzzer::run`] does. Useful for turning a reproduction into
+    /// a regression test. Returns the steps with their errors populated if
+    /// all invariants hold. The ensemble is restored to its initial state
+    /// afterwards in either case.
+    pub fn replay(&mut self, mut steps: Vec<FuzzStep>) -> Result<Vec<FuzzStep>, FuzzFailure> {
+        let snapshot = self.ensemble.snapshot();
+
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            run_steps(&mut self.ensemble, &self.invariants, &mut steps)
+        }));
+
+        self.ensemble.restore(&snapshot);
+
+        let reason = match result {
+            Ok(Ok(())) => return Ok(steps),
+            Ok(Err(reason)) => reason,
+            Err(payload) => match payload.downcast::<String>() {
+                Ok(msg) => *msg,
+                Err(payload) => payload.downcast_ref::<&str>()
+                    .map(|x| x.to_string())
+                    .unwrap_or_else(|| "Panicked".into())
+            }
+        };
+
+        Err(FuzzFailure { reason, steps })
+    }
+
+    /// Proptest doesn't retry removing steps after it has shrunk the
+    /// individual ones, which can leave steps that were only needed
+    /// before shrinking e.g. a transfer to the actor that is later
+    /// replaced by the one that already had the funds.
+    fn remove_redundant_steps(&mut self, mut steps: Vec<FuzzStep>) -> Vec<FuzzStep> {
+        let mut i = 0;
+
+        while i < steps.len() && steps.len() > self.steps.start {
+            let mut candidate = steps.clone();
+            candidate.remove(i);
+
+            if self.replay(candidate.clone()).is_err() {
+                steps = candidate;
+            } else {
+                i += 1;
+            }
+        }
+
+        steps
+    }
+
+    /// Same as [`Fuzzer::run`] but panics with the minimal reproduction
+    /// if a failing sequence was found.
+    #[inline]
+    pub fn check(&mut self) {
+        if let Err(failure) = self.run() {
+            panic!("Fuzzer: found a failing sequence.\n{}", failure);
+        }
+    }
+}
+
+impl Display for FuzzFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.reason)?;
+        writeln!(f, "Minimal reproduction ({} steps):", self.steps.len())?;
+
+        for (i, step) in self.steps.iter().enumerate() {
+            write!(f, "  {}. {} -> {}: {}", i + 1, step.sender, step.contract, decode(&step.msg))?;
+
+            if !step.funds.is_empty() {
+                write!(f, " [{}]", format_coins(&step.funds))?;
+            }
+
+            writeln!(f)?;
+
+            if let Some(err) = &step.error {
+                writeln!(f, "     error: {}", err)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn run_steps(
+    ensemble: &mut ContractEnsemble,
+    invariants: &[(String, Invariant)],
+    steps: &mut [FuzzStep]
+) -> Result<(), String> {
+    check_invariants(ensemble, invariants)
+        .map_err(|err| format!("{} before the first step", err))?;
+
+    for (i, step) in steps.iter_mut().enumerate() {
+        let env = MockEnv::new(&step.sender, &step.contract)
+            .sent_funds(step.funds.clone());
+
+        if let Err(err) = ensemble.execute_binary(step.msg.clone(), env) {
+            step.error = Some(err.to_string());
+        }
+
+        check_invariants(ensemble, invariants)
+            .map_err(|err| format!("{} after step {}", err, i + 1))?;
+    }
+
+    Ok(())
+}
+
+fn check_invariants(
+    ensemble: &ContractEnsemble,
+    invariants: &[(String, Invariant)]
+) -> Result<(), String> {
+    for (name, check) in invariants {
+        if let Err(err) = check(ensemble) {
+            return Err(format!("Invariant \"{}\" violated: {}", name, err));
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,358 @@
+use std::{
+    cell::Cell,
+    panic::{self, AssertUnwindSafe}
+};
+
+use fadroma::cosmwasm_std::{Storage, Record, Order};
+
+use super::{
+    ensemble::EnsembleResult,
+    error::EnsembleError
+};
+
+/// The gas costs that the ensemble charges for each operation when gas
+/// metering is enabled. The storage defaults are based on the Cosmos SDK
+/// `KVStore` gas configuration and the address costs on the `wasmd` ones.
+///
+/// Address validation is charged wherever the ensemble validates the
+/// addresses in messages. Calls that contracts make to `deps.api`, which
+/// includes signature verification, are not metered because
+/// `secret-cosmwasm-std` doesn't export all of the types needed to
+/// implement the [`Api`] trait.
+///
+/// [`Api`]: fadroma::cosmwasm_std::Api
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct GasCosts {
+    /// Flat cost of reading a key from storage.
+    pub storage_read: u64,
+    /// Cost of each byte read from storage (key and value).
+    pub storage_read_per_byte: u64,
+    /// Flat cost of writing a key to storage.
+    pub storage_write: u64,
+    /// Cost of each byte written to storage (key and value).
+    pub storage_write_per_byte: u64,
+    /// Cost of removing a key from storage.
+    pub storage_remove: u64,
+    /// Cost of each item returned when iterating over storage.
+    pub storage_iter_next: u64,
+    /// Cost of converting an address to its canonical form.
+    pub addr_canonicalize: u64,
+    /// Cost of converting a canonical address back to its human readable form.
+    /// Validating an address does both.
+    pub addr_humanize: u64,
+    /// Cost of each query that a contract makes.
+    pub query: u64,
+    /// Cost of dispatching a message or a reply.
+    pub sub_msg: u64
+}
+
+/// The amount of gas used by a single message, broken down by category.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct GasReport {
+    /// Gas used for reading from and writing to storage.
+    pub storage: u64,
+    /// Gas used for validating addresses.
+    pub api: u64,
+    /// Gas used for queries made by the contract.
+    pub queries: u64,
+    /// Gas used for dispatching the message.
+    pub sub_msg: u64
+}
+
+#[derive(Debug)]
+pub(crate) struct GasMeter {
+    pub costs: Option<GasCosts>,
+    pub limit: Option<u64>,
+    used: Cell<u64>,
+    report: Cell<GasReport>,
+    /// Whether a transaction is being executed.
+    in_tx: Cell<bool>,
+    /// The number of contract entry points currently being called.
+    calls: Cell<u32>
+}
+
+#[derive(Clone, Copy)]
+enum GasKind {
+    Storage,
+    Api,
+    Query,
+    SubMsg
+}
+
+/// The panic payload used to abort a contract that ran out of gas.
+pub(crate) struct OutOfGas;
+
+/// Wraps a contract's storage and charges gas for each access.
+pub(crate) struct MeteredStorage<'a> {
+    storage: &'a mut dyn Storage,
+    meter: &'a GasMeter
+}
+
+/// Wraps the storage of a contract that is being queried.
+pub(crate) struct MeteredStorageRef<'a> {
+    storage: &'a dyn Storage,
+    meter: &'a GasMeter
+}
+
+impl GasCosts {
+    /// Returns a configuration where every operation is free.
+    /// Useful for only charging gas for specific operations.
+    pub fn free() -> Self {
+        Self {
+            storage_read: 0,
+            storage_read_per_byte: 0,
+            storage_write: 0,
+            storage_write_per_byte: 0,
+            storage_remove: 0,
+            storage_iter_next: 0,
+            addr_canonicalize: 0,
+            addr_humanize: 0,
+            query: 0,
+            sub_msg: 0
+        }
+    }
+}
+
+impl Default for GasCosts {
+    fn default() -> Self {
+        Self {
+            storage_read: 1000,
+            storage_read_per_byte: 3,
+            storage_write: 2000,
+            storage_write_per_byte: 30,
+            storage_remove: 1000,
+            storage_iter_next: 30,
+            addr_canonicalize: 4,
+            addr_humanize: 5,
+            query: 1000,
+            sub_msg: 2000
+        }
+    }
+}
+
+impl GasReport {
+    /// The total amount of gas used.
+    #[inline]
+    pub fn total(&self) -> u64 {
+        self.storage + self.api + self.queries + self.sub_msg
+    }
+}
+
+impl GasMeter {
+    pub fn new() -> Self {
+        Self {
+            costs: None,
+            limit: None,
+            used: Cell::new(0),
+            report: Cell::new(GasReport::default()),
+            in_tx: Cell::new(false),
+            calls: Cell::new(0)
+        }
+    }
+
+    /// The gas used by the current transaction so far.
+    #[inline]
+    pub fn used(&self) -> u64 {
+        self.used.get()
+    }
+
+    /// The gas used by the current message so far.
+    #[inline]
+    pub fn report(&self) -> GasReport {
+        self.report.get()
+    }
+
+    /// Resets the gas used. The limit is only enforced
+    /// between this and [`GasMeter::end_tx`].
+    #[inline]
+    pub fn start_tx(&self) {
+        self.used.set(0);
+        self.in_tx.set(true);
+    }
+
+    #[inline]
+    pub fn end_tx(&self) {
+        self.in_tx.set(false);
+    }
+
+    /// Resets the report for the next message and charges for dispatching it.
+    #[inline]
+    pub fn start_msg(&self) {
+        self.report.set(GasReport::default());
+        self.charge(GasKind::SubMsg, |x| x.sub_msg);
+    }
+
+    pub fn check_limit(&self) -> EnsembleResult<()> {
+        match self.limit {
+            Some(limit) if self.in_tx.get() && self.used() > limit => Err(EnsembleError::OutOfGas {
+                limit,
+                used: self.used()
+            }),
+            _ => Ok(())
+        }
+    }
+
+    /// Calls a contract entry point. If the limit is exceeded while it runs,
+    /// the contract is aborted at that point instead of running to completion
+    /// and [`EnsembleError::OutOfGas`] is returned.
+    pub fn call<T>(&self, call: impl FnOnce() -> EnsembleResult<T>) -> EnsembleResult<T> {
+        self.calls.set(self.calls.get() + 1);
+        let result = panic::catch_unwind(AssertUnwindSafe(call));
+        self.calls.set(self.calls.get() - 1);
+
+        match result {
+            Ok(result) => {
+                // The contract may have turned running out of gas into an error,
+                // as the VM does, so it doesn't get to succeed or be replied to.
+                self.check_limit()?;
+
+                result
+            },
+            // Only raised once the limit is exceeded.
+            Err(payload) if payload.is::<OutOfGas>() => Err(self.check_limit().unwrap_err()),
+            Err(payload) => panic::resume_unwind(payload)
+        }
+    }
+
+    #[inline]
+    pub fn charge_query(&self) {
+        self.charge(GasKind::Query, |x| x.query);
+    }
+
+    /// Charges for converting the address to its canonical form and back.
+    #[inline]
+    pub fn charge_addr_validate(&self) {
+        self.charge(GasKind::Api, |x| x.addr_canonicalize + x.addr_humanize);
+    }
+
+    fn charge(&self, kind: GasKind, cost: impl FnOnce(&GasCosts) -> u64) {
+        let Some(costs) = &self.costs else {
+            return;
+        };
+
+        let amount = cost(costs);
+        let mut report = self.report.get();
+
+        let field = match kind {
+            GasKind::Storage => &mut report.storage,
+            GasKind::Api => &mut report.api,
+            GasKind::Query => &mut report.queries,
+            GasKind::SubMsg => &mut report.sub_msg
+        };
+
+        *field = field.saturating_add(amount);
+
+        self.report.set(report);
+        self.used.set(self.used.get().saturating_add(amount));
+
+        // Doesn't invoke the panic hook so nothing is printed. Outside of
+        // contract calls the limit is checked after the message instead.
+        if self.calls.get() > 0 && self.check_limit().is_err() {
+            panic::resume_unwind(Box::new(OutOfGas));
+        }
+    }
+
+    fn charge_read(&self, key: &[u8], value: Option<&Vec<u8>>) {
+        let len = (key.len() + value.map(|x| x.len()).unwrap_or_default()) as u64;
+
+        self.charge(GasKind::Storage, |x| x.storage_read + x.storage_read_per_byte * len);
+    }
+
+    fn charge_iter_next(&self, record: &Record) {
+        let len = (record.0.len() + record.1.len()) as u64;
+
+        self.charge(GasKind::Storage, |x| x.storage_iter_next + x.storage_read_per_byte * len);
+    }
+
+    fn range<'a>(
+        &'a self,
+        storage: &'a dyn Storage,
+        start: Option<&[u8]>,
+        end: Option<&[u8]>,
+        order: Order
+    ) -> Box<dyn Iterator<Item = Record> + 'a> {
+        Box::new(storage.range(start, end, order).inspect(|x| self.charge_iter_next(x)))
+    }
+}
+
+/// Runs `f` and returns [`None`] if it ran out of gas instead of unwinding
+/// further. Used where the abort can't unwind through, like VM host functions.
+#[cfg(feature = "vm")]
+pub(crate) fn catch_out_of_gas<T>(f: impl FnOnce() -> T) -> Option<T> {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(result) => Some(result),
+        Err(payload) if payload.is::<OutOfGas>() => None,
+        Err(payload) => panic::resume_unwind(payload)
+    }
+}
+
+impl<'a> MeteredStorage<'a> {
+    #[inline]
+    pub fn new(storage: &'a mut dyn Storage, meter: &'a GasMeter) -> Self {
+        Self { storage, meter }
+    }
+}
+
+impl<'a> Storage for MeteredStorage<'a> {
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        let result = self.storage.get(key);
+        self.meter.charge_read(key, result.as_ref());
+
+        result
+    }
+
+    fn range<'b>(
+        &'b self,
+        start: Option<&[u8]>,
+        end: Option<&[u8]>,
+        order: Order
+    ) -> Box<dyn Iterator<Item = Record> + 'b> {
+        self.meter.range(self.storage, start, end, order)
+    }
+
+    fn set(&mut self, key: &[u8], value: &[u8]) {
+        let len = (key.len() + value.len()) as u64;
+        self.meter.charge(GasKind::Storage, |x| x.storage_write + x.storage_write_per_byte * len);
+
+        self.storage.set(key, value);
+    }
+
+    fn remove(&mut self, key: &[u8]) {
+        self.meter.charge(GasKind::Storage, |x| x.storage_remove);
+
+        self.storage.remove(key);
+    }
+}
+
+impl<'a> MeteredStorageRef<'a> {
+    #[inline]
+    pub fn new(storage: &'a dyn Storage, meter: &'a GasMeter) -> Self {
+        Self { storage, meter }
+    }
+}
+
+impl<'a> Storage for MeteredStorageRef<'a> {
+    fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+        let result = self.storage.get(key);
+        self.meter.charge_read(key, result.as_ref());
+
+        result
+    }
+
+    fn range<'b>(
+        &'b self,
+        start: Option<&[u8]>,
+        end: Option<&[u8]>,
+        order: Order
+    ) -> Box<dyn Iterator<Item = Record> + 'b> {
+        self.meter.range(self.storage, start, end, order)
+    }
+
+    fn set(&mut self, _key: &[u8], _value: &[u8]) {
+        panic!("Storage is read-only in queries.")
+    }
+
+    fn remove(&mut self, _key: &[u8]) {
+        panic!("Storage is read-only in queries.")
+    }
+}
@@ -0,0 +1,881 @@
+use std::collections::HashMap;
+
+use serde::{Serialize, Deserialize};
+use sha2::{Sha256, Digest};
+use fadroma::{
+    prelude::ContractLink,
+    cosmwasm_std::{
+        DepsMut, Empty, Api, Querier, QuerierWrapper, Response, Addr, Binary,
+        Uint128, Coin, Timestamp, IbcMsg, IbcChannel, IbcEndpoint, IbcOrder,
+        IbcPacket, IbcTimeout, IbcAcknowledgement, IbcChannelOpenMsg,
+        IbcChannelConnectMsg, IbcChannelCloseMsg, IbcPacketReceiveMsg,
+        IbcPacketAckMsg, IbcPacketTimeoutMsg, IbcBasicResponse, to_binary,
+        from_binary, testing::MockApi
+    }
+};
+
+use super::{
+    ContractEnsemble, EnsembleResult, EnsembleError,
+    ensemble::{Context, SubMsgExecuteResult},
+    block::Block,
+    querier::EnsembleQuerier,
+    response::{ResponseVariants, IbcResponse, IbcMsgResponse},
+    execution_state::ExecutionState,
+    gas::MeteredStorage,
+    event::ProcessedEvents,
+    trace::decode
+};
+
+/// The port that the ICS-20 token transfer module is bound to.
+pub const TRANSFER_PORT: &str = "transfer";
+
+/// The only channel version that the ICS-20 token transfer module supports.
+pub const ICS20_VERSION: &str = "ics20-1";
+
+/// The message that is passed to one of the IBC entry points of a contract.
+/// Opening a channel is not included since it can't dispatch any messages.
+#[derive(Clone, PartialEq, Debug)]
+#[non_exhaustive]
+pub enum IbcEntryPoint {
+    ChannelConnect(IbcChannelConnectMsg),
+    ChannelClose(IbcChannelCloseMsg),
+    PacketReceive(IbcPacketReceiveMsg),
+    PacketAck(IbcPacketAckMsg),
+    PacketTimeout(IbcPacketTimeoutMsg)
+}
+
+/// The packet data used by ICS-20 token transfers.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct FungibleTokenPacketData {
+    /// The full denomination trace, i.e `transfer/channel-0/uatom`
+    /// for tokens that have already been transferred once.
+    pub denom: String,
+    pub amount: Uint128,
+    pub sender: String,
+    pub receiver: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub memo: String
+}
+
+/// A packet that was delivered by [`Relayer::relay`].
+#[derive(Clone, PartialEq, Debug)]
+pub struct RelayedPacket {
+    pub packet: IbcPacket,
+    pub outcome: PacketOutcome
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum PacketOutcome {
+    /// The packet was received and the acknowledgement was delivered back to the sender.
+    Acknowledged(IbcAcknowledgement),
+    /// The packet timed out or its channel was closed before it could be received.
+    TimedOut
+}
+
+/// Simulates an IBC relayer between two ensembles. Each ensemble represents a different
+/// chain so they must have different chain ids (see [`ContractEnsemble::set_chain_id`]).
+///
+/// Contracts are bound to the `wasm.<address>` port (see [`contract_port`]) and receive
+/// the IBC callbacks through the IBC methods of [`crate::ContractHarness`]. The ICS-20 token
+/// transfer module is bound to the [`TRANSFER_PORT`] and supports [`IbcMsg::Transfer`]
+/// using the bank module of each ensemble. Transferred tokens are escrowed on the source chain
+/// and minted as `ibc/<hash>` vouchers on the destination chain.
+///
+/// Packets and channel closes are not delivered until [`Relayer::relay`] is called.
+/// If a contract returns an error from `ibc_packet_receive`, its state changes
+/// are reverted and an error acknowledgement (`{"error":"<message>"}`) is sent back.
+pub struct Relayer {
+    address: Addr
+}
+
+#[derive(Default, Debug)]
+pub(crate) struct IbcState {
+    channels: Vec<Channel>,
+    /// Packets that were sent and are waiting to be relayed.
+    packets: Vec<IbcPacket>,
+    /// The chain id of the counterparty of each connection.
+    connections: Vec<String>,
+    /// Maps `ibc/<hash>` voucher denominations to their full trace.
+    denom_traces: HashMap<String, String>
+}
+
+#[derive(Clone, Debug)]
+struct Channel {
+    channel: IbcChannel,
+    counterparty_chain_id: String,
+    state: ChannelState,
+    next_sequence: u64
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum ChannelState {
+    Open,
+    /// Closed on this end but not yet relayed to the counterparty.
+    Closing,
+    Closed
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Acknowledgement {
+    Result(Binary),
+    Error(String)
+}
+
+/// Returns the IBC port that the contract with the given address is bound to.
+#[inline]
+pub fn contract_port(address: impl AsRef<str>) -> String {
+    format!("wasm.{}", address.as_ref())
+}
+
+/// Returns the address of the account that holds the tokens escrowed
+/// by ICS-20 transfers that were sent through the given channel.
+#[inline]
+pub fn escrow_address(channel_id: impl AsRef<str>) -> String {
+    format!("ibc-escrow-{}", channel_id.as_ref())
+}
+
+/// Returns the denomination of vouchers with the given denomination
+/// trace, i.e `transfer/channel-0/uatom` => `ibc/27394FB...`.
+pub fn ibc_denom(trace: impl AsRef<str>) -> String {
+    let hash = Sha256::digest(trace.as_ref().as_bytes());
+    let hex: String = hash.iter().map(|x| format!("{:02X}", x)).collect();
+
+    format!("ibc/{}", hex)
+}
+
+impl ContractEnsemble {
+    /// Returns all open channels of this ensemble.
+    pub fn ibc_channels(&self) -> Vec<IbcChannel> {
+        self.ctx.state.ibc.open_channels(None)
+    }
+
+    /// Returns all packets that were sent from this ensemble
+    /// and haven't been relayed yet.
+    #[inline]
+    pub fn pending_packets(&self) -> &[IbcPacket] {
+        &self.ctx.state.ibc.packets
+    }
+
+    /// Returns the full denomination trace of the given `ibc/<hash>` voucher
+    /// denomination if it was received by this ensemble.
+    #[inline]
+    pub fn denom_trace(&self, denom: impl AsRef<str>) -> Option<&str> {
+        self.ctx.state.ibc.denom_traces.get(denom.as_ref()).map(|x| x.as_str())
+    }
+}
+
+impl Relayer {
+    /// Creates a new relayer. The `address` is passed to contracts
+    /// as the relayer of packets and acknowledgements.
+    pub fn new(address: impl Into<String>) -> Self {
+        Self {
+            address: Addr::unchecked(address)
+        }
+    }
+
+    /// Performs the channel opening handshake between `a_port` on ensemble `a` and
+    /// `b_port` on ensemble `b` and returns the channel from the perspective of `a`.
+    /// Contracts can change the version in `ibc_channel_open`.
+    pub fn open_channel(
+        &self,
+        a: &mut ContractEnsemble,
+        a_port: impl Into<String>,
+        b: &mut ContractEnsemble,
+        b_port: impl Into<String>,
+        order: IbcOrder,
+        version: impl Into<String>
+    ) -> EnsembleResult<IbcChannel> {
+        let a_port = a_port.into();
+        let b_port = b_port.into();
+        let version = version.into();
+
+        if a.ctx.chain_id == b.ctx.chain_id {
+            return Err(EnsembleError::Ibc(
+                format!("Both ensembles have the same chain id: {}", a.ctx.chain_id)
+            ));
+        }
+
+        let a_owner = port_owner(&a.ctx, &a_port)?;
+        let b_owner = port_owner(&b.ctx, &b_port)?;
+
+        let is_transfer = a_owner.is_none() || b_owner.is_none();
+
+        if is_transfer && (version != ICS20_VERSION || order != IbcOrder::Unordered) {
+            return Err(EnsembleError::Ibc(format!(
+                "The transfer module only supports unordered channels with version {}",
+                ICS20_VERSION
+            )));
+        }
+
+        let a_id = a.ctx.state.ibc.next_channel_id();
+        let b_id = b.ctx.state.ibc.next_channel_id();
+
+        let mut a_channel = IbcChannel::new(
+            IbcEndpoint { port_id: a_port.clone(), channel_id: a_id },
+            IbcEndpoint { port_id: b_port.clone(), channel_id: String::new() },
+            order.clone(),
+            version.clone(),
+            a.ctx.state.ibc.connection_id(&b.ctx.chain_id)
+        );
+
+        // OpenInit
+        let a_version = match &a_owner {
+            Some(address) => a.ctx.ibc_channel_open(
+                address,
+                IbcChannelOpenMsg::new_init(a_channel.clone())
+            )?.unwrap_or(version),
+            None => version
+        };
+
+        let mut b_channel = IbcChannel::new(
+            IbcEndpoint { port_id: b_port, channel_id: b_id.clone() },
+            a_channel.endpoint.clone(),
+            order,
+            a_version.clone(),
+            b.ctx.state.ibc.connection_id(&a.ctx.chain_id)
+        );
+
+        // OpenTry
+        let b_version = match &b_owner {
+            Some(address) => b.ctx.ibc_channel_open(
+                address,
+                IbcChannelOpenMsg::new_try(b_channel.clone(), a_version.clone())
+            )?.unwrap_or_else(|| a_version.clone()),
+            None => a_version.clone()
+        };
+
+        a_channel.counterparty_endpoint.channel_id = b_id;
+        a_channel.version = b_version.clone();
+        b_channel.version = b_version.clone();
+
+        // OpenAck
+        a.ctx.state.ibc.add_channel(a_channel.clone(), b.ctx.chain_id.clone());
+
+        if let Some(address) = a_owner {
+            let msg = IbcChannelConnectMsg::new_ack(a_channel.clone(), b_version);
+
+            if let Err(err) = a.ctx.execute_ibc(address, IbcEntryPoint::ChannelConnect(msg)) {
+                a.ctx.state.ibc.channels.pop();
+
+                return Err(err);
+            }
+        }
+
+        // OpenConfirm
+        b.ctx.state.ibc.add_channel(b_channel.clone(), a.ctx.chain_id.clone());
+
+        if let Some(address) = b_owner {
+            let msg = IbcChannelConnectMsg::new_confirm(b_channel);
+
+            if let Err(err) = b.ctx.execute_ibc(address, IbcEntryPoint::ChannelConnect(msg)) {
+                b.ctx.state.ibc.channels.pop();
+
+                return Err(err);
+            }
+        }
+
+        Ok(a_channel)
+    }
+
+    /// Relays all pending channel closes, packets and acknowledgements between
+    /// the two ensembles in both directions until there is nothing left to relay.
+    /// Returns all packets that were delivered in the order that they were relayed.
+    ///
+    /// A packet times out if the destination ensemble's block height or time
+    /// has reached the packet's timeout by the time it is relayed.
+    pub fn relay(
+        &self,
+        a: &mut ContractEnsemble,
+        b: &mut ContractEnsemble
+    ) -> EnsembleResult<Vec<RelayedPacket>> {
+        let mut result = vec![];
+
+        loop {
+            let closed = self.relay_closes(&mut a.ctx, &mut b.ctx)? |
+                self.relay_closes(&mut b.ctx, &mut a.ctx)?;
+
+            let relayed = self.relay_packets(&mut a.ctx, &mut b.ctx, &mut result)? |
+                self.relay_packets(&mut b.ctx, &mut a.ctx, &mut result)?;
+
+            if !closed && !relayed {
+                break;
+            }
+        }
+
+        Ok(result)
+    }
+
+    fn relay_closes(&self, src: &mut Context, dest: &mut Context) -> EnsembleResult<bool> {
+        let closing: Vec<IbcChannel> = src.state.ibc.channels.iter()
+            .filter(|x| x.state == ChannelState::Closing && x.counterparty_chain_id == dest.chain_id)
+            .map(|x| x.channel.clone())
+            .collect();
+
+        for channel in &closing {
+            src.state.ibc.channel_mut(&channel.endpoint.channel_id)?.state = ChannelState::Closed;
+
+            if let Some(address) = port_owner(src, &channel.endpoint.port_id)? {
+                let msg = IbcChannelCloseMsg::new_init(channel.clone());
+                src.execute_ibc(address, IbcEntryPoint::ChannelClose(msg))?;
+            }
+
+            let counterparty = dest.state.ibc.channel_mut(&channel.counterparty_endpoint.channel_id)?;
+            counterparty.state = ChannelState::Closed;
+
+            let counterparty = counterparty.channel.clone();
+
+            if let Some(address) = port_owner(dest, &counterparty.endpoint.port_id)? {
+                let msg = IbcChannelCloseMsg::new_confirm(counterparty);
+                dest.execute_ibc(address, IbcEntryPoint::ChannelClose(msg))?;
+            }
+        }
+
+        Ok(!closing.is_empty())
+    }
+
+    fn relay_packets(
+        &self,
+        src: &mut Context,
+        dest: &mut Context,
+        result: &mut Vec<RelayedPacket>
+    ) -> EnsembleResult<bool> {
+        let packets = src.state.ibc.take_packets(&dest.chain_id);
+        let any = !packets.is_empty();
+
+        for packet in packets {
+            let dest_channel = dest.state.ibc.channel(&packet.dest.channel_id)?;
+
+            if dest_channel.state != ChannelState::Open || is_timed_out(&packet.timeout, &dest.block) {
+                match port_owner(src, &packet.src.port_id)? {
+                    Some(address) => {
+                        let msg = IbcPacketTimeoutMsg::new(packet.clone(), self.address.clone());
+                        src.execute_ibc(address, IbcEntryPoint::PacketTimeout(msg))?;
+                    },
+                    None => src.transfer_refund(&packet)?
+                }
+
+                result.push(RelayedPacket {
+                    packet,
+                    outcome: PacketOutcome::TimedOut
+                });
+
+                continue;
+            }
+
+            let ack = match port_owner(dest, &packet.dest.port_id)? {
+                Some(address) => {
+                    let msg = IbcPacketReceiveMsg::new(packet.clone(), self.address.clone());
+
+                    match dest.execute_ibc(address, IbcEntryPoint::PacketReceive(msg)) {
+                        Ok(resp) => IbcAcknowledgement::new(resp.acknowledgement.unwrap_or_default()),
+                        Err(err) if err.is_contract_error() => ack_error(err.to_string()),
+                        Err(err) => return Err(err)
+                    }
+                },
+                None => dest.transfer_receive(&packet)
+            };
+
+            match port_owner(src, &packet.src.port_id)? {
+                Some(address) => {
+                    let msg = IbcPacketAckMsg::new(ack.clone(), packet.clone(), self.address.clone());
+                    src.execute_ibc(address, IbcEntryPoint::PacketAck(msg))?;
+                },
+                None => if is_ack_error(&ack) {
+                    src.transfer_refund(&packet)?;
+                }
+            }
+
+            result.push(RelayedPacket {
+                packet,
+                outcome: PacketOutcome::Acknowledged(ack)
+            });
+        }
+
+        Ok(any)
+    }
+}
+
+impl IbcEntryPoint {
+    /// The name of the entry point.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::ChannelConnect(_) => "ibc_channel_connect",
+            Self::ChannelClose(_) => "ibc_channel_close",
+            Self::PacketReceive(_) => "ibc_packet_receive",
+            Self::PacketAck(_) => "ibc_packet_ack",
+            Self::PacketTimeout(_) => "ibc_packet_timeout"
+        }
+    }
+
+    /// The port on the other end of the channel.
+    pub fn counterparty_port(&self) -> &str {
+        match self {
+            Self::ChannelConnect(msg) => &msg.channel().counterparty_endpoint.port_id,
+            Self::ChannelClose(msg) => &msg.channel().counterparty_endpoint.port_id,
+            Self::PacketReceive(msg) => &msg.packet.src.port_id,
+            Self::PacketAck(msg) => &msg.original_packet.dest.port_id,
+            Self::PacketTimeout(msg) => &msg.packet.dest.port_id
+        }
+    }
+
+    /// A short description of the message, used by [`crate::Trace`].
+    pub(crate) fn summary(&self) -> String {
+        match self {
+            Self::ChannelConnect(msg) => msg.channel().endpoint.channel_id.clone(),
+            Self::ChannelClose(msg) => msg.channel().endpoint.channel_id.clone(),
+            Self::PacketReceive(msg) => decode(&msg.packet.data),
+            Self::PacketAck(msg) => decode(&msg.acknowledgement.data),
+            Self::PacketTimeout(msg) => format!("sequence {}", msg.packet.sequence)
+        }
+    }
+}
+
+impl IbcState {
+    fn channel(&self, id: &str) -> EnsembleResult<&Channel> {
+        self.channels.iter()
+            .find(|x| x.channel.endpoint.channel_id == id)
+            .ok_or_else(|| EnsembleError::Ibc(format!("Channel {} not found", id)))
+    }
+
+    pub fn open_channels(&self, port_id: Option<&str>) -> Vec<IbcChannel> {
+        self.channels.iter()
+            .filter(|x| x.state == ChannelState::Open)
+            .filter(|x| port_id.map(|port| x.channel.endpoint.port_id == port).unwrap_or(true))
+            .map(|x| x.channel.clone())
+            .collect()
+    }
+
+    /// Creates the packet that will be sent through the given channel
+    /// and increments its sequence. Fails if the channel isn't open.
+    pub fn create_packet(
+        &mut self,
+        channel_id: &str,
+        data: Binary,
+        timeout: IbcTimeout
+    ) -> EnsembleResult<IbcPacket> {
+        let channel = self.channel_mut(channel_id)?;
+
+        if channel.state != ChannelState::Open {
+            return Err(EnsembleError::Ibc(format!("Channel {} is closed", channel_id)));
+        }
+
+        let packet = IbcPacket::new(
+            data,
+            channel.channel.endpoint.clone(),
+            channel.channel.counterparty_endpoint.clone(),
+            channel.next_sequence,
+            timeout
+        );
+
+        channel.next_sequence += 1;
+
+        Ok(packet)
+    }
+
+    #[inline]
+    pub fn push_packet(&mut self, packet: IbcPacket) {
+        self.packets.push(packet);
+    }
+
+    /// Removes the packet created by `create_packet`.
+    pub fn remove_packet(&mut self, channel_id: &str, sequence: u64) {
+        self.packets.retain(|x| !(x.src.channel_id == channel_id && x.sequence == sequence));
+
+        if let Ok(channel) = self.channel_mut(channel_id) {
+            channel.next_sequence = channel.next_sequence.min(sequence);
+        }
+    }
+
+    /// Closes the channel on this end. Returns `false` if it was already closed.
+    pub fn close_channel(&mut self, channel_id: &str) -> EnsembleResult<bool> {
+        let channel = self.channel_mut(channel_id)?;
+
+        if channel.state != ChannelState::Open {
+            return Ok(false);
+        }
+
+        channel.state = ChannelState::Closing;
+
+        Ok(true)
+    }
+
+    pub fn reopen_channel(&mut self, channel_id: &str) {
+        if let Ok(channel) = self.channel_mut(channel_id) {
+            channel.state = ChannelState::Open;
+        }
+    }
+
+    fn channel_mut(&mut self, id: &str) -> EnsembleResult<&mut Channel> {
+        self.channels.iter_mut()
+            .find(|x| x.channel.endpoint.channel_id == id)
+            .ok_or_else(|| EnsembleError::Ibc(format!("Channel {} not found", id)))
+    }
+
+    #[inline]
+    fn next_channel_id(&self) -> String {
+        format!("channel-{}", self.channels.len())
+    }
+
+    fn connection_id(&mut self, counterparty_chain_id: &str) -> String {
+        let index = match self.connections.iter().position(|x| x == counterparty_chain_id) {
+            Some(index) => index,
+            None => {
+                self.connections.push(counterparty_chain_id.into());
+
+                self.connections.len() - 1
+            }
+        };
+
+        format!("connection-{}", index)
+    }
+
+    fn add_channel(&mut self, channel: IbcChannel, counterparty_chain_id: String) {
+        self.channels.push(Channel {
+            channel,
+            counterparty_chain_id,
+            state: ChannelState::Open,
+            next_sequence: 1
+        });
+    }
+
+    fn take_packets(&mut self, counterparty_chain_id: &str) -> Vec<IbcPacket> {
+        let channels = &self.channels;
+        let (result, rest) = self.packets.drain(..).partition(|packet| {
+            channels.iter().any(|x|
+                x.channel.endpoint.channel_id == packet.src.channel_id &&
+                x.counterparty_chain_id == counterparty_chain_id
+            )
+        });
+
+        self.packets = rest;
+
+        result
+    }
+
+    /// Returns the local denomination of the given denomination trace.
+    fn local_denom(&mut self, trace: &str) -> String {
+        if trace.contains('/') {
+            let denom = ibc_denom(trace);
+            self.denom_traces.insert(denom.clone(), trace.into());
+
+            denom
+        } else {
+            trace.into()
+        }
+    }
+}
+
+impl Context {
+    pub(crate) fn ibc_channel_open(
+        &mut self,
+        address: &str,
+        msg: IbcChannelOpenMsg
+    ) -> EnsembleResult<Option<String>> {
+        let instance = self.state.instance(address)?;
+        let contract = &self.contracts[instance.index];
+
+        let env = self.create_env(ContractLink {
+            address: Addr::unchecked(address),
+            code_hash: contract.code_hash.clone()
+        });
+
+        let querier = EnsembleQuerier::new(self);
+        let gas = &self.gas;
+
+        self.state.push_scope();
+
+        let result = self.state.borrow_storage_mut(address, |storage| gas.call(|| {
+            let deps = DepsMut::<Empty> {
+                storage: &mut MeteredStorage::new(storage, gas),
+                api: &MockApi::default() as &dyn Api,
+                querier: QuerierWrapper::new(&querier as &dyn Querier)
+            };
+
+            let result = contract.code.ibc_channel_open(deps, env, msg)?;
+
+            Ok(result)
+        }));
+
+        match result {
+            Ok(resp) => {
+                self.state.commit();
+
+                Ok(resp.map(|x| x.version))
+            },
+            Err(err) => {
+                self.state.revert();
+
+                Err(err)
+            }
+        }
+    }
+
+    /// Calls the given IBC entry point of the contract in a new transaction
+    /// and executes any messages that it returns.
+    pub(crate) fn execute_ibc(
+        &mut self,
+        address: String,
+        msg: IbcEntryPoint
+    ) -> EnsembleResult<IbcResponse> {
+        match self.execute_state(ExecutionState::new_ibc(address, msg))? {
+            ResponseVariants::Ibc(resp) => Ok(resp),
+            _ => unreachable!()
+        }
+    }
+
+    pub(crate) fn ibc_entry_point(&mut self, address: String, msg: IbcEntryPoint) -> SubMsgExecuteResult {
+        let index = self.state.instance(&address)?.index;
+        let contract = &self.contracts[index];
+
+        let env = self.create_env(ContractLink {
+            address: Addr::unchecked(address.clone()),
+            code_hash: contract.code_hash.clone()
+        });
+
+        let querier = EnsembleQuerier::new(self);
+        let gas = &self.gas;
+
+        let (response, acknowledgement) = self.state.borrow_storage_mut(&address, |storage| gas.call(|| {
+            let deps = DepsMut::<Empty> {
+                storage: &mut MeteredStorage::new(storage, gas),
+                api: &MockApi::default() as &dyn Api,
+                querier: QuerierWrapper::new(&querier as &dyn Querier)
+            };
+
+            let result = match msg.clone() {
+                IbcEntryPoint::ChannelConnect(msg) =>
+                    (into_response(contract.code.ibc_channel_connect(deps, env, msg)?), None),
+                IbcEntryPoint::ChannelClose(msg) =>
+                    (into_response(contract.code.ibc_channel_close(deps, env, msg)?), None),
+                IbcEntryPoint::PacketReceive(msg) => {
+                    let resp = contract.code.ibc_packet_receive(deps, env, msg)?;
+
+                    let response = Response::new()
+                        .add_submessages(resp.messages)
+                        .add_attributes(resp.attributes)
+                        .add_events(resp.events);
+
+                    (response, Some(resp.acknowledgement))
+                },
+                IbcEntryPoint::PacketAck(msg) =>
+                    (into_response(contract.code.ibc_packet_ack(deps, env, msg)?), None),
+                IbcEntryPoint::PacketTimeout(msg) =>
+                    (into_response(contract.code.ibc_packet_timeout(deps, env, msg)?), None)
+            };
+
+            Ok(result)
+        }))?;
+
+        let resp = IbcResponse {
+            sent: Vec::with_capacity(response.messages.len()),
+            address,
+            msg,
+            acknowledgement,
+            response,
+            gas: self.gas.report(),
+            events: vec![]
+        };
+
+        let events = ProcessedEvents::try_from(&resp)?;
+
+        Ok((resp.into(), events))
+    }
+
+    pub(crate) fn execute_ibc_msg(&mut self, msg: IbcMsg, sender: String) -> SubMsgExecuteResult {
+        let packet = match &msg {
+            IbcMsg::SendPacket { channel_id, data, timeout } => {
+                let channel = self.state.ibc.channel(channel_id)?;
+                assert_port_owner(channel, &contract_port(&sender))?;
+
+                let packet = self.state.ibc.create_packet(channel_id, data.clone(), timeout.clone())?;
+                self.state.send_packet(packet.clone());
+
+                Some(packet)
+            },
+            IbcMsg::Transfer { channel_id, to_address, amount, timeout, memo } => {
+                let channel = self.state.ibc.channel(channel_id)?;
+                assert_port_owner(channel, TRANSFER_PORT)?;
+
+                let trace = self.state.ibc.denom_traces.get(&amount.denom)
+                    .cloned()
+                    .unwrap_or_else(|| amount.denom.clone());
+
+                // Vouchers that are sent back to their source are burned, everything else is escrowed.
+                if trace.starts_with(&format!("{}/{}/", TRANSFER_PORT, channel_id)) {
+                    self.state.burn(&sender, vec![amount.clone()])?;
+                } else {
+                    self.state.transfer_funds(&sender, escrow_address(channel_id), vec![amount.clone()])?;
+                }
+
+                let data = to_binary(&FungibleTokenPacketData {
+                    denom: trace,
+                    amount: amount.amount,
+                    sender: sender.clone(),
+                    receiver: to_address.clone(),
+                    memo: memo.clone()
+                })?;
+
+                let packet = self.state.ibc.create_packet(channel_id, data, timeout.clone())?;
+                self.state.send_packet(packet.clone());
+
+                Some(packet)
+            },
+            IbcMsg::CloseChannel { channel_id } => {
+                let channel = self.state.ibc.channel(channel_id)?;
+                assert_port_owner(channel, &contract_port(&sender))?;
+
+                self.state.close_channel(channel_id)?;
+
+                None
+            },
+            _ => panic!("Ensemble: Unsupported message: {:?}", msg)
+        };
+
+        let resp = IbcMsgResponse {
+            sender,
+            msg,
+            packet
+        };
+
+        let events = ProcessedEvents::from(&resp);
+
+        Ok((resp.into(), events))
+    }
+
+    /// Handles an ICS-20 packet received by the transfer module.
+    fn transfer_receive(&mut self, packet: &IbcPacket) -> IbcAcknowledgement {
+        self.state.push_scope();
+
+        let result = from_binary::<FungibleTokenPacketData>(&packet.data)
+            .map_err(EnsembleError::from)
+            .and_then(|data| {
+                let prefix = format!("{}/{}/", packet.src.port_id, packet.src.channel_id);
+
+                // The tokens are returning to their source if they were
+                // previously sent through the counterparty's channel.
+                match data.denom.strip_prefix(&prefix) {
+                    Some(trace) => {
+                        let denom = self.state.ibc.local_denom(trace);
+
+                        self.state.transfer_funds(
+                            escrow_address(&packet.dest.channel_id),
+                            &data.receiver,
+                            vec![Coin::new(data.amount.u128(), denom)]
+                        )?;
+                    },
+                    None => {
+                        let trace = format!("{}/{}/{}", packet.dest.port_id, packet.dest.channel_id, data.denom);
+                        let denom = self.state.ibc.local_denom(&trace);
+
+                        self.state.mint(&data.receiver, vec![Coin::new(data.amount.u128(), denom)]);
+                    }
+                }
+
+                Ok(())
+            });
+
+        match result {
+            Ok(()) => {
+                self.state.commit();
+
+                IbcAcknowledgement::new(to_binary(&Acknowledgement::Result(Binary::from(&[1]))).unwrap())
+            },
+            Err(err) => {
+                self.state.revert();
+
+                ack_error(err.to_string())
+            }
+        }
+    }
+
+    /// Returns the tokens of a failed ICS-20 transfer to the sender.
+    fn transfer_refund(&mut self, packet: &IbcPacket) -> EnsembleResult<()> {
+        let data: FungibleTokenPacketData = from_binary(&packet.data)?;
+        let prefix = format!("{}/{}/", packet.src.port_id, packet.src.channel_id);
+
+        self.state.push_scope();
+
+        let result = if data.denom.starts_with(&prefix) {
+            // The voucher was burned when it was sent.
+            let denom = self.state.ibc.local_denom(&data.denom);
+            self.state.mint(&data.sender, vec![Coin::new(data.amount.u128(), denom)]);
+
+            Ok(())
+        } else {
+            let denom = self.state.ibc.local_denom(&data.denom);
+
+            self.state.transfer_funds(
+                escrow_address(&packet.src.channel_id),
+                &data.sender,
+                vec![Coin::new(data.amount.u128(), denom)]
+            ).map(|_| ())
+        };
+
+        match result {
+            Ok(()) => {
+                self.state.commit();
+
+                Ok(())
+            },
+            Err(err) => {
+                self.state.revert();
+
+                Err(err)
+            }
+        }
+    }
+}
+
+fn port_owner(ctx: &Context, port: &str) -> EnsembleResult<Option<String>> {
+    if port == TRANSFER_PORT {
+        return Ok(None);
+    }
+
+    match port.strip_prefix("wasm.") {
+        Some(address) if ctx.state.instance(address).is_ok() => Ok(Some(address.into())),
+        _ => Err(EnsembleError::Ibc(format!("Port {} is not bound", port)))
+    }
+}
+
+fn assert_port_owner(channel: &Channel, port: &str) -> EnsembleResult<()> {
+    if channel.channel.endpoint.port_id != port {
+        return Err(EnsembleError::Ibc(format!(
+            "Channel {} is not bound to port {}",
+            channel.channel.endpoint.channel_id,
+            port
+        )));
+    }
+
+    Ok(())
+}
+
+fn is_timed_out(timeout: &IbcTimeout, block: &Block) -> bool {
+    let height = timeout.block()
+        .map(|x| x.height > 0 && block.height >= x.height)
+        .unwrap_or_default();
+
+    let time = timeout.timestamp()
+        .map(|x| Timestamp::from_seconds(block.time) >= x)
+        .unwrap_or_default();
+
+    height || time
+}
+
+#[inline]
+fn into_response(resp: IbcBasicResponse) -> Response {
+    Response::new()
+        .add_submessages(resp.messages)
+        .add_attributes(resp.attributes)
+        .add_events(resp.events)
+}
+
+#[inline]
+fn ack_error(err: String) -> IbcAcknowledgement {
+    IbcAcknowledgement::new(to_binary(&Acknowledgement::Error(err)).unwrap())
+}
+
+#[inline]
+fn is_ack_error(ack: &IbcAcknowledgement) -> bool {
+    matches!(from_binary(&ack.data), Ok(Acknowledgement::Error(_)))
+}
@@ -8,12 +8,28 @@ mod querier;
 mod storage;
 mod block;
 mod response;
-#[cfg(feature = "ensemble-staking")]
+#[cfg(feature = "staking")]
 mod staking;
 mod state;
 mod execution_state;
 mod error;
 mod event;
+mod event_log;
+mod gas;
+mod snapshot;
+mod trace;
+mod module;
+mod address;
+mod mock;
+mod schedule;
+mod failure;
+mod coverage;
+#[cfg(feature = "ibc")]
+mod ibc;
+#[cfg(feature = "vm")]
+mod vm;
+#[cfg(feature = "fuzz")]
+mod fuzz;
 
 #[cfg(test)]
 mod tests;
@@ -21,18 +37,38 @@ mod tests;
 pub use ensemble::*;
 pub use env::*;
 pub use querier::*;
-pub use block::Block;
+pub use block::{Block, BlockMut};
 pub use response::*;
 pub use error::*;
+pub use gas::{GasCosts, GasReport};
+pub use event_log::{EventLog, EventMatcher};
+pub use snapshot::{Snapshot, SNAPSHOT_VERSION};
+pub use trace::{Trace, TraceStep, TraceKind, StorageWrite};
+pub use module::{Module, ModuleDeps, ModuleDepsMut, ModuleResponse};
+pub use address::Bech32Addresses;
+pub use mock::{ContractMock, MockTarget, MockId, MockCall, MockCallKind};
+pub use schedule::{Schedule, HookId};
+pub use failure::{Failure, FailureId};
+pub use coverage::{CoverageReport, ContractCoverage, VariantCoverage};
+#[cfg(feature = "staking")]
+pub use staking::{Unbonding, DEFAULT_UNBONDING_PERIOD};
+#[cfg(feature = "ibc")]
+pub use ibc::*;
+#[cfg(feature = "vm")]
+pub use vm::*;
+#[cfg(feature = "fuzz")]
+pub use fuzz::*;
 pub use anyhow;
+#[cfg(feature = "fuzz")]
+pub use proptest;
 
 pub use fadroma::prelude::cosmwasm_std;
 
 /// Generate a struct and implement [`ContractHarness`] for the given struct identifier,
 /// using the provided entry point functions.
 /// 
-/// Supports `init`, `execute` and `query` **or**
-/// `init`, `execute`, `query` and `reply`.
+/// Supports `init`, `execute` and `query` with
+/// optional `reply` and `migrate` (in that order).
 /// 
 /// # Examples
 /// 
@@ -130,6 +166,18 @@ macro_rules! contract_harness {
         }
     };
 
+    (@migrate $migrate:path) => {
+        fn migrate(
+            &self,
+            deps: $crate::cosmwasm_std::DepsMut,
+            env:  $crate::cosmwasm_std::Env,
+            msg:  $crate::cosmwasm_std::Binary
+        ) -> $crate::AnyResult<$crate::cosmwasm_std::Response> {
+            let result = $migrate(deps, env, $crate::cosmwasm_std::from_binary(&msg)?)?;
+            Ok(result)
+        }
+    };
+
     (@trait_impl $visibility:vis $name:ident, $($contents:tt)*) => {
         $visibility struct $name;
 
@@ -138,28 +186,13 @@ macro_rules! contract_harness {
         }
     };
 
-    (
-        $visibility:vis $name:ident,
-        init: $init:path,
-        execute: $execute:path,
-        query: $query:path,
-        reply: $reply:path
-    ) => {
-        $crate::contract_harness! {
-            @trait_impl
-            $visibility $name,
-            $crate::contract_harness!(@init $init);
-            $crate::contract_harness!(@execute $execute);
-            $crate::contract_harness!(@query $query);
-            $crate::contract_harness!(@reply $reply);
-        }
-    };
-
     (
         $visibility:vis $name:ident,
         init: $init:path,
         execute: $execute:path,
         query: $query:path
+        $(, reply: $reply:path)?
+        $(, migrate: $migrate:path)?
     ) => {
         $crate::contract_harness! {
             @trait_impl
@@ -167,6 +200,8 @@ macro_rules! contract_harness {
             $crate::contract_harness!(@init $init);
             $crate::contract_harness!(@execute $execute);
             $crate::contract_harness!(@query $query);
+            $($crate::contract_harness!(@reply $reply);)?
+            $($crate::contract_harness!(@migrate $migrate);)?
         }
     };
 }
@@ -0,0 +1,205 @@
+use std::{cell::RefCell, fmt::Debug};
+
+use serde::{Serialize, de::DeserializeOwned};
+use fadroma::cosmwasm_std::{Binary, Coin, Response, StdResult, from_binary, to_binary};
+
+use super::{AnyResult, anyhow::anyhow};
+
+type ExecuteHandler = Box<dyn Fn(&MockCall) -> AnyResult<Response>>;
+type QueryHandler = Box<dyn Fn(&MockCall) -> AnyResult<Binary>>;
+
+/// Intercepts execute messages and queries sent to a contract address or
+/// to any instance of a code id and responds with a canned result instead.
+/// The address doesn't need to belong to a contract instance, so a mock
+/// can stand in for an oracle or any other external contract without
+/// implementing a [`crate::ContractHarness`] for it.
+///
+/// Only the kinds of calls that a response was configured for are
+/// intercepted, the rest go through to the contract as usual. Add a mock
+/// with [`crate::ContractEnsemble::add_mock`] and inspect the calls that
+/// it received with [`crate::ContractEnsemble::mock_calls`].
+///
+/// # Examples
+///
+/// ```
+/// # use fadroma_ensemble::{ContractEnsemble, ContractMock, MockEnv};
+/// # use fadroma::cosmwasm_std::Response;
+/// let mut ensemble = ContractEnsemble::new();
+///
+/// let oracle = ensemble.add_mock(ContractMock::address("oracle")
+///     .query_returns(&100u64)
+///     .execute_returns(Response::default()));
+///
+/// let price: u64 = ensemble.query("oracle", &"price").unwrap();
+/// assert_eq!(price, 100);
+///
+/// ensemble.execute(&"update", MockEnv::new("keeper", "oracle")).unwrap();
+///
+/// let calls = ensemble.mock_calls(oracle);
+/// assert_eq!(calls.len(), 2);
+/// assert_eq!(calls[1].decode::<String>().unwrap(), "update");
+/// ```
+pub struct ContractMock {
+    pub(crate) target: MockTarget,
+    execute: Option<ExecuteHandler>,
+    query: Option<QueryHandler>,
+    pub(crate) calls: RefCell<Vec<MockCall>>
+}
+
+/// What a [`ContractMock`] intercepts.
+#[derive(Clone, PartialEq, Debug)]
+pub enum MockTarget {
+    /// Calls to the contract with this address.
+    Address(String),
+    /// Calls to any instance of the contract with this code id.
+    CodeId(u64)
+}
+
+/// Identifies a [`ContractMock`] that was added to the ensemble.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct MockId(pub(crate) usize);
+
+/// A call that was intercepted by a [`ContractMock`].
+#[derive(Clone, PartialEq, Debug)]
+pub struct MockCall {
+    pub kind: MockCallKind,
+    /// [`None`] for queries.
+    pub sender: Option<String>,
+    /// The address of the contract that was called.
+    pub address: String,
+    pub msg: Binary,
+    pub funds: Vec<Coin>
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MockCallKind {
+    Execute,
+    Query
+}
+
+impl ContractMock {
+    /// Creates a mock for the contract with the given address.
+    #[inline]
+    pub fn address(address: impl Into<String>) -> Self {
+        Self::new(MockTarget::Address(address.into()))
+    }
+
+    /// Creates a mock for all instances of the contract with the given code id.
+    #[inline]
+    pub fn code_id(code_id: u64) -> Self {
+        Self::new(MockTarget::CodeId(code_id))
+    }
+
+    /// Responds to execute messages with the given response. Any messages
+    /// in the response are executed as if the contract had sent them, but
+    /// they must not request a reply since the mock can't handle it.
+    pub fn execute_returns(self, response: Response) -> Self {
+        self.execute_with(move |_| Ok(response.clone()))
+    }
+
+    /// Fails all execute messages with the given error.
+    pub fn execute_fails(self, error: impl Into<String>) -> Self {
+        let error = error.into();
+
+        self.execute_with(move |_| Err(anyhow!(error.clone())))
+    }
+
+    /// Responds to execute messages using the given function.
+    pub fn execute_with(
+        mut self,
+        handler: impl Fn(&MockCall) -> AnyResult<Response> + 'static
+    ) -> Self {
+        self.execute = Some(Box::new(handler));
+
+        self
+    }
+
+    /// Responds to queries with the given value serialized as JSON.
+    ///
+    /// # Panics
+    ///
+    /// If the value can't be serialized.
+    pub fn query_returns<T: Serialize + ?Sized>(self, value: &T) -> Self {
+        let value = to_binary(value).unwrap();
+
+        self.query_with(move |_| Ok(value.clone()))
+    }
+
+    /// Fails all queries with the given error.
+    pub fn query_fails(self, error: impl Into<String>) -> Self {
+        let error = error.into();
+
+        self.query_with(move |_| Err(anyhow!(error.clone())))
+    }
+
+    /// Responds to queries using the given function.
+    pub fn query_with(
+        mut self,
+        handler: impl Fn(&MockCall) -> AnyResult<Binary> + 'static
+    ) -> Self {
+        self.query = Some(Box::new(handler));
+
+        self
+    }
+
+    fn new(target: MockTarget) -> Self {
+        Self {
+            target,
+            execute: None,
+            query: None,
+            calls: RefCell::new(vec![])
+        }
+    }
+
+    /// Returns `true` if the mock intercepts calls of the given kind
+    /// to a contract with the given address and code id, if any.
+    pub(crate) fn intercepts(
+        &self,
+        kind: MockCallKind,
+        address: &str,
+        code_id: Option<u64>
+    ) -> bool {
+        let handled = match kind {
+            MockCallKind::Execute => self.execute.is_some(),
+            MockCallKind::Query => self.query.is_some()
+        };
+
+        handled && match &self.target {
+            MockTarget::Address(x) => x == address,
+            MockTarget::CodeId(x) => code_id == Some(*x)
+        }
+    }
+
+    pub(crate) fn execute(&self, call: MockCall) -> AnyResult<Response> {
+        let result = (self.execute.as_ref().unwrap())(&call);
+        self.calls.borrow_mut().push(call);
+
+        result
+    }
+
+    pub(crate) fn query(&self, call: MockCall) -> AnyResult<Binary> {
+        let result = (self.query.as_ref().unwrap())(&call);
+        self.calls.borrow_mut().push(call);
+
+        result
+    }
+}
+
+impl MockCall {
+    /// Deserializes the message of the call.
+    #[inline]
+    pub fn decode<T: DeserializeOwned>(&self) -> StdResult<T> {
+        from_binary(&self.msg)
+    }
+}
+
+impl Debug for ContractMock {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ContractMock")
+            .field("target", &self.target)
+            .field("execute", &self.execute.is_some())
+            .field("query", &self.query.is_some())
+            .field("calls", &self.calls.borrow().len())
+            .finish()
+    }
+}
@@ -0,0 +1,234 @@
+use fadroma::cosmwasm_std::{
+    Storage, CosmosMsg, QueryRequest, Empty, Binary, Event, Coin, Uint128
+};
+
+use super::{
+    EnsembleResult, AnyResult,
+    anyhow::anyhow,
+    block::Block,
+    state::State
+};
+
+/// Extends the ensemble with support for messages and queries that it
+/// doesn't implement natively, such as governance votes, Stargate messages
+/// or chain specific custom queries. Register modules with
+/// [`crate::ContractEnsemble::add_module`].
+///
+/// Any message or query that the ensemble can't handle is offered to each
+/// module in the order that they were added until one of them handles it.
+/// If none does, messages panic and queries fall back to the default
+/// mock querier. Returning an error from [`Module::execute`] is treated
+/// the same way as a contract error, so it can be handled by a reply.
+///
+/// Modules get their own storage which is reverted together with the rest
+/// of the chain state, so they should keep any state there rather than in `self`.
+///
+/// # Examples
+///
+/// ```
+/// use fadroma::cosmwasm_std::{CosmosMsg, Empty, Event};
+/// use fadroma_ensemble::{Module, ModuleDepsMut, ModuleResponse, AnyResult};
+///
+/// struct Votes;
+///
+/// impl Module for Votes {
+///     fn execute(
+///         &self,
+///         mut deps: ModuleDepsMut,
+///         sender: &str,
+///         msg: &CosmosMsg
+///     ) -> AnyResult<Option<ModuleResponse>> {
+///         match msg {
+///             CosmosMsg::Custom(Empty {}) => {
+///                 deps.storage_mut().set(sender.as_bytes(), b"voted");
+///
+///                 Ok(Some(ModuleResponse::new().add_event(Event::new("vote"))))
+///             },
+///             _ => Ok(None)
+///         }
+///     }
+/// }
+/// ```
+pub trait Module {
+    /// Executes `msg` which was sent by `sender`. Returns [`None`]
+    /// if the module doesn't handle this kind of message.
+    fn execute(
+        &self,
+        _deps: ModuleDepsMut,
+        _sender: &str,
+        _msg: &CosmosMsg
+    ) -> AnyResult<Option<ModuleResponse>> {
+        Ok(None)
+    }
+
+    /// Answers the given query. Returns [`None`] if the
+    /// module doesn't handle this kind of query.
+    fn query(
+        &self,
+        _deps: ModuleDeps,
+        _request: &QueryRequest<Empty>
+    ) -> AnyResult<Option<Binary>> {
+        Ok(None)
+    }
+}
+
+/// The result of a message that was handled by a [`Module`].
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct ModuleResponse {
+    /// Events are emitted as is, without a `wasm-` prefix.
+    pub events: Vec<Event>,
+    pub data: Option<Binary>
+}
+
+/// Read-only access to the chain state, passed to [`Module::query`].
+pub struct ModuleDeps<'a> {
+    pub(crate) state: &'a State,
+    pub(crate) block: &'a Block,
+    pub(crate) chain_id: &'a str,
+    pub(crate) name: &'a str
+}
+
+/// Access to the chain state, passed to [`Module::execute`].
+/// Any changes are reverted if the transaction fails.
+pub struct ModuleDepsMut<'a> {
+    pub(crate) state: &'a mut State,
+    pub(crate) block: &'a Block,
+    pub(crate) chain_id: &'a str,
+    pub(crate) name: &'a str
+}
+
+impl ModuleResponse {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn add_event(mut self, event: Event) -> Self {
+        self.events.push(event);
+
+        self
+    }
+
+    #[inline]
+    pub fn set_data(mut self, data: impl Into<Binary>) -> Self {
+        self.data = Some(data.into());
+
+        self
+    }
+}
+
+impl<'a> ModuleDeps<'a> {
+    /// The storage of this module.
+    #[inline]
+    pub fn storage(&self) -> &dyn Storage {
+        self.state.module_storage(self.name)
+    }
+
+    #[inline]
+    pub fn block(&self) -> &Block {
+        self.block
+    }
+
+    #[inline]
+    pub fn chain_id(&self) -> &str {
+        self.chain_id
+    }
+
+    /// Returns the balance of `address` in the given `denom`.
+    #[inline]
+    pub fn balance(&self, address: impl AsRef<str>, denom: impl AsRef<str>) -> Uint128 {
+        balance(self.state, address.as_ref(), denom.as_ref())
+    }
+
+    /// Returns the total supply of the given `denom`.
+    #[inline]
+    pub fn supply(&self, denom: impl AsRef<str>) -> Uint128 {
+        self.state.bank.supply(denom.as_ref())
+    }
+
+    /// Returns the admin of the given token factory `denom`.
+    #[inline]
+    pub fn denom_admin(&self, denom: impl AsRef<str>) -> Option<&str> {
+        self.state.bank.denoms.get(denom.as_ref()).map(|x| x.as_str())
+    }
+}
+
+impl<'a> ModuleDepsMut<'a> {
+    /// The storage of this module.
+    #[inline]
+    pub fn storage(&self) -> &dyn Storage {
+        self.state.module_storage(self.name)
+    }
+
+    /// The storage of this module.
+    #[inline]
+    pub fn storage_mut(&mut self) -> &mut dyn Storage {
+        self.state.module_storage_mut(self.name)
+    }
+
+    #[inline]
+    pub fn block(&self) -> &Block {
+        self.block
+    }
+
+    #[inline]
+    pub fn chain_id(&self) -> &str {
+        self.chain_id
+    }
+
+    /// Returns the balance of `address` in the given `denom`.
+    #[inline]
+    pub fn balance(&self, address: impl AsRef<str>, denom: impl AsRef<str>) -> Uint128 {
+        balance(self.state, address.as_ref(), denom.as_ref())
+    }
+
+    /// Returns the total supply of the given `denom`.
+    #[inline]
+    pub fn supply(&self, denom: impl AsRef<str>) -> Uint128 {
+        self.state.bank.supply(denom.as_ref())
+    }
+
+    /// Returns the admin of the given token factory `denom`. Modules
+    /// that implement minting can use it to authorize the sender.
+    #[inline]
+    pub fn denom_admin(&self, denom: impl AsRef<str>) -> Option<&str> {
+        self.state.bank.denoms.get(denom.as_ref()).map(|x| x.as_str())
+    }
+
+    /// Mints the given funds, increasing the total supply.
+    #[inline]
+    pub fn add_funds(&mut self, address: impl Into<String>, coins: Vec<Coin>) {
+        self.state.mint(address, coins)
+    }
+
+    /// Burns the given funds, decreasing the total supply.
+    /// Returns an error if `address` has insufficient funds.
+    #[inline]
+    pub fn remove_funds(&mut self, address: impl Into<String>, coins: Vec<Coin>) -> AnyResult<()> {
+        into_any(self.state.burn(address, coins))
+    }
+
+    /// Returns an error if `from` has insufficient funds.
+    #[inline]
+    pub fn transfer_funds(
+        &mut self,
+        from: impl Into<String>,
+        to: impl Into<String>,
+        coins: Vec<Coin>
+    ) -> AnyResult<()> {
+        into_any(self.state.transfer_funds(from, to, coins).map(|_| ()))
+    }
+}
+
+fn balance(state: &State, address: &str, denom: &str) -> Uint128 {
+    state.bank.accounts.get(address)
+        .and_then(|x| x.get(denom))
+        .copied()
+        .unwrap_or_default()
+}
+
+#[inline]
+fn into_any(result: EnsembleResult<()>) -> AnyResult<()> {
+    result.map_err(|err| anyhow!("{}", err))
+}
@@ -1,15 +1,69 @@
-use super::ensemble::Context;
+use serde::{Serialize, Deserialize};
+
+use super::{
+    ensemble::Context, mock::MockCallKind,
+    error::{EnsembleError, RegistryError}
+};
 use fadroma::cosmwasm_std::{
     Querier, QueryRequest, WasmQuery, BankQuery, QuerierResult, SystemResult,
-    SystemError, ContractResult, Empty, AllBalanceResponse, BalanceResponse,
+    SystemError, ContractResult, Empty, AllBalanceResponse, BalanceResponse, Storage,
     from_slice, to_binary, testing::MockQuerier
 };
-#[cfg(feature = "ensemble-staking")]
+#[cfg(feature = "ibc")]
+use crate::cosmwasm_std::{IbcQuery, ListChannelsResponse, ChannelResponse};
+#[cfg(feature = "supply")]
+use crate::cosmwasm_std::{Coin, coin};
+#[cfg(feature = "staking")]
 use crate::cosmwasm_std::{
     ValidatorResponse, AllValidatorsResponse, AllDelegationsResponse,
     BondedDenomResponse, StakingQuery
 };
 
+/// Serializes to a superset of `ContractInfoResponse` from both
+/// `secret-cosmwasm-std` and vanilla CosmWasm, so that it
+/// can be deserialized into either one. Also includes the
+/// code hash, which `WasmContract` needs to convert the messages
+/// of vanilla contracts to Secret ones.
+#[derive(Serialize)]
+struct ContractInfo<'a> {
+    code_id: u64,
+    code_hash: &'a str,
+    creator: &'a str,
+    admin: Option<&'a str>,
+    pinned: bool,
+    ibc_port: Option<&'a str>,
+    label: &'a str
+}
+
+/// `WasmQuery::CodeInfo` only exists in vanilla CosmWasm.
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum CodeInfoRequest {
+    Wasm(CodeInfoQuery)
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum CodeInfoQuery {
+    CodeInfo { code_id: u64 }
+}
+
+/// `CodeInfoResponse` from vanilla CosmWasm. Uploads
+/// don't have a creator in the ensemble so it's empty.
+#[derive(Serialize)]
+struct CodeInfo<'a> {
+    code_id: u64,
+    creator: &'a str,
+    checksum: &'a str
+}
+
+/// `SupplyResponse` is non-exhaustive so it can't be constructed.
+#[cfg(feature = "supply")]
+#[derive(Serialize)]
+struct SupplyResponse {
+    amount: Coin
+}
+
 pub struct EnsembleQuerier {
     ctx: *const Context,
     base: MockQuerier
@@ -39,24 +93,33 @@ macro_rules! querier_result {
 
 impl Querier for EnsembleQuerier {
     fn raw_query(&self, bin_request: &[u8]) -> QuerierResult {
+        let ctx = unsafe { &*(self.ctx) };
+
         let request: QueryRequest<Empty> = match from_slice(bin_request) {
             Ok(v) => v,
-            Err(e) => {
-                return SystemResult::Err(SystemError::InvalidRequest {
+            Err(e) => match from_slice(bin_request) {
+                Ok(CodeInfoRequest::Wasm(CodeInfoQuery::CodeInfo { code_id })) => {
+                    ctx.gas.charge_query();
+
+                    return query_code_info(ctx, code_id);
+                }
+                Err(_) => return SystemResult::Err(SystemError::InvalidRequest {
                     error: format!("Parsing query request: {}", e),
                     request: bin_request.into(),
                 })
             }
         };
 
-        let ctx = unsafe { &*(self.ctx) };
+        ctx.gas.charge_query();
 
-        match request {
+        match request.clone() {
             QueryRequest::Wasm(query) => match query {
                 WasmQuery::Smart {
                     contract_addr, msg, ..
                 } => {
-                    if ctx.state.instance(&contract_addr).is_err() {
+                    if ctx.state.instance(&contract_addr).is_err() &&
+                        ctx.find_mock(MockCallKind::Query, &contract_addr).is_none()
+                    {
                         return SystemResult::Err(SystemError::NoSuchContract {
                             addr: contract_addr
                         });
@@ -64,20 +127,38 @@ impl Querier for EnsembleQuerier {
 
                     querier_result!(ctx.query(&contract_addr, msg))
                 }
-                WasmQuery::Raw { contract_addr, .. } => {
-                    if cfg!(feature = "scrt") {
-                        panic!("Raw queries are unsupported in Secret Network - keys and values in raw storage are encrypted and must be queried through a smart query.");
-                    } else {
-                        if ctx.state.instance(&contract_addr).is_err() {
-                            return SystemResult::Err(SystemError::NoSuchContract {
-                                addr: contract_addr
-                            });
-                        }
-    
-                        todo!()
-                    }
+                WasmQuery::Raw { contract_addr, key } => {
+                    let instance = match ctx.state.instance(&contract_addr) {
+                        Ok(instance) => instance,
+                        Err(_) => return SystemResult::Err(SystemError::NoSuchContract {
+                            addr: contract_addr
+                        })
+                    };
+
+                    // Missing keys are returned as empty data, same as wasmd.
+                    let value = instance.storage.get(key.as_slice()).unwrap_or_default();
+
+                    SystemResult::Ok(ContractResult::Ok(value.into()))
                 }
-                _ => unimplemented!(),
+                WasmQuery::ContractInfo { contract_addr } => {
+                    let instance = match ctx.state.instance(&contract_addr) {
+                        Ok(instance) => instance,
+                        Err(_) => return SystemResult::Err(SystemError::NoSuchContract {
+                            addr: contract_addr
+                        })
+                    };
+
+                    querier_result!(to_binary(&ContractInfo {
+                        code_id: instance.index as u64,
+                        code_hash: &ctx.contracts[instance.index].code_hash,
+                        creator: &instance.creator,
+                        admin: instance.admin.as_deref(),
+                        pinned: false,
+                        ibc_port: None,
+                        label: &instance.label
+                    }))
+                }
+                _ => self.query_modules(ctx, &request),
             },
             QueryRequest::Bank(query) => match query {
                 BankQuery::AllBalances { address } => {
@@ -92,9 +173,17 @@ impl Querier for EnsembleQuerier {
                         amount: amount.into_iter().next().unwrap()
                     }))
                 }
-                _ => unimplemented!(),
+                #[cfg(feature = "supply")]
+                BankQuery::Supply { denom } => {
+                    let amount = ctx.state.bank.supply(&denom);
+
+                    querier_result!(to_binary(&SupplyResponse {
+                        amount: coin(amount.u128(), denom)
+                    }))
+                }
+                _ => self.query_modules(ctx, &request),
             },
-            #[cfg(feature = "ensemble-staking")]
+            #[cfg(feature = "staking")]
             QueryRequest::Staking(query) => match query {
                 StakingQuery::AllDelegations { delegator } => {
                     let delegations = ctx.delegations.all_delegations(&delegator);
@@ -117,11 +206,9 @@ impl Querier for EnsembleQuerier {
                     querier_result!(to_binary(&delegation))
                 }
                 StakingQuery::AllValidators {} => {
-                    let validators = ctx.delegations.validators();
+                    let validators = ctx.delegations.active_validators();
 
-                    querier_result!(to_binary(&AllValidatorsResponse {
-                        validators: validators.to_vec(),
-                    }))
+                    querier_result!(to_binary(&AllValidatorsResponse { validators }))
                 }
                 StakingQuery::Validator { address } => {
                     let validator = ctx
@@ -134,9 +221,47 @@ impl Querier for EnsembleQuerier {
 
                     querier_result!(to_binary(&ValidatorResponse { validator }))
                 }
-                _ => unimplemented!(),
+                _ => self.query_modules(ctx, &request),
             },
-            _ => self.base.handle_query(&request)
+            #[cfg(feature = "ibc")]
+            QueryRequest::Ibc(query) => match query {
+                IbcQuery::ListChannels { port_id } => {
+                    let channels = ctx.state.ibc.open_channels(port_id.as_deref());
+
+                    querier_result!(to_binary(&ListChannelsResponse { channels }))
+                }
+                IbcQuery::Channel { channel_id, port_id } => {
+                    let channel = ctx.state.ibc.open_channels(port_id.as_deref())
+                        .into_iter()
+                        .find(|x| x.endpoint.channel_id == channel_id);
+
+                    querier_result!(to_binary(&ChannelResponse { channel }))
+                }
+                _ => self.query_modules(ctx, &request),
+            },
+            _ => self.query_modules(ctx, &request)
+        }
+    }
+}
+
+impl EnsembleQuerier {
+    fn query_modules(&self, ctx: &Context, request: &QueryRequest<Empty>) -> QuerierResult {
+        match ctx.query_modules(request) {
+            Some(result) => querier_result!(result),
+            None => self.base.handle_query(request)
         }
     }
 }
+
+fn query_code_info(ctx: &Context, code_id: u64) -> QuerierResult {
+    let result = match ctx.contracts.get(code_id as usize) {
+        Some(contract) => to_binary(&CodeInfo {
+            code_id,
+            creator: "",
+            checksum: &contract.code_hash
+        }).map_err(EnsembleError::from),
+        None => Err(EnsembleError::registry(RegistryError::IdNotFound(code_id)))
+    };
+
+    querier_result!(result)
+}
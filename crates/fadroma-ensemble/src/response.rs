@@ -2,20 +2,41 @@ use std::iter::Iterator;
 
 use fadroma::{
     prelude::ContractLink,
-    cosmwasm_std::{Addr, Binary, Response, Coin, Reply, SubMsg}
+    cosmwasm_std::{Addr, Binary, Response, Coin, Reply, SubMsg, CosmosMsg, Event}
 };
 
+use super::{
+    gas::GasReport,
+    module::ModuleResponse,
+    event::ProcessedEvents,
+    event_log::EventLog
+};
+#[cfg(feature = "staking")]
+use fadroma::cosmwasm_std::Timestamp;
+#[cfg(feature = "ibc")]
+use fadroma::cosmwasm_std::{IbcMsg, IbcPacket};
+#[cfg(feature = "ibc")]
+use super::ibc::IbcEntryPoint;
+
 #[derive(Clone, PartialEq, Debug)]
 #[non_exhaustive]
 pub enum ResponseVariants {
     Instantiate(InstantiateResponse),
     Execute(ExecuteResponse),
     Reply(ReplyResponse),
+    Migrate(MigrateResponse),
+    Admin(AdminResponse),
     Bank(BankResponse),
-    #[cfg(feature = "ensemble-staking")]
+    Burn(BurnResponse),
+    Module(ModuleMsgResponse),
+    #[cfg(feature = "staking")]
     Staking(StakingResponse),
-    #[cfg(feature = "ensemble-staking")]
-    Distribution(DistributionResponse)
+    #[cfg(feature = "staking")]
+    Distribution(DistributionResponse),
+    #[cfg(feature = "ibc")]
+    Ibc(IbcResponse),
+    #[cfg(feature = "ibc")]
+    IbcMsg(IbcMsgResponse)
 }
 
 #[derive(Clone, PartialEq, Debug)]
@@ -30,6 +51,12 @@ pub struct InstantiateResponse {
     pub msg: Binary,
     /// The init response returned by the contract.
     pub response: Response,
+    /// The gas used by this message, excluding any messages that it initiated.
+    /// All zeroes unless gas metering is enabled.
+    pub gas: GasReport,
+    /// The events emitted by this message, excluding any messages that it initiated.
+    /// Use `event_log` to get the events of the initiated messages as well.
+    pub events: Vec<Event>,
     /// The responses for any messages that the instantiated contract initiated.
     pub sent: Vec<ResponseVariants>
 }
@@ -44,6 +71,12 @@ pub struct ExecuteResponse {
     pub msg: Binary,
     /// The execute response returned by the contract.
     pub response: Response,
+    /// The gas used by this message, excluding any messages that it initiated.
+    /// All zeroes unless gas metering is enabled.
+    pub gas: GasReport,
+    /// The events emitted by this message, excluding any messages that it initiated.
+    /// Use `event_log` to get the events of the initiated messages as well.
+    pub events: Vec<Event>,
     /// The responses for any messages that the executed contract initiated.
     pub sent: Vec<ResponseVariants>
 }
@@ -56,10 +89,48 @@ pub struct ReplyResponse {
     pub reply: Reply,
     /// The execute response returned by the contract.
     pub response: Response,
+    /// The gas used by this message, excluding any messages that it initiated.
+    /// All zeroes unless gas metering is enabled.
+    pub gas: GasReport,
+    /// The events emitted by this message, excluding any messages that it initiated.
+    /// Use `event_log` to get the events of the initiated messages as well.
+    pub events: Vec<Event>,
     /// The responses for any messages that the executed contract initiated.
     pub sent: Vec<ResponseVariants>
 }
 
+#[derive(Clone, PartialEq, Debug)]
+pub struct MigrateResponse {
+    /// The address that triggered the migration.
+    pub sender: String,
+    /// The contract that was migrated.
+    pub address: String,
+    /// Code ID that the contract was migrated to.
+    pub code_id: u64,
+    /// The migrate message that was sent.
+    pub msg: Binary,
+    /// The migrate response returned by the contract.
+    pub response: Response,
+    /// The gas used by this message, excluding any messages that it initiated.
+    /// All zeroes unless gas metering is enabled.
+    pub gas: GasReport,
+    /// The events emitted by this message, excluding any messages that it initiated.
+    /// Use `event_log` to get the events of the initiated messages as well.
+    pub events: Vec<Event>,
+    /// The responses for any messages that the migrated contract initiated.
+    pub sent: Vec<ResponseVariants>
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct AdminResponse {
+    /// The address that changed the admin.
+    pub sender: String,
+    /// The contract whose admin was changed.
+    pub address: String,
+    /// The new admin of the contract. [`None`] if it was cleared.
+    pub admin: Option<String>
+}
+
 #[derive(Clone, PartialEq, Debug)]
 pub struct BankResponse {
     /// The address that sent the funds.
@@ -70,7 +141,27 @@ pub struct BankResponse {
     pub coins: Vec<Coin>
 }
 
-#[cfg(feature = "ensemble-staking")]
+#[derive(Clone, PartialEq, Debug)]
+pub struct BurnResponse {
+    /// The address whose funds were burned.
+    pub sender: String,
+    /// The funds that were burned.
+    pub coins: Vec<Coin>
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct ModuleMsgResponse {
+    /// The name of the module that handled the message.
+    pub module: String,
+    /// The address that sent the message.
+    pub sender: String,
+    /// The message that was sent.
+    pub msg: CosmosMsg,
+    /// The response returned by the module.
+    pub response: ModuleResponse
+}
+
+#[cfg(feature = "staking")]
 #[derive(Clone, PartialEq, Debug)]
 pub struct StakingResponse {
     /// The address that delegated the funds.
@@ -81,7 +172,7 @@ pub struct StakingResponse {
     pub kind: StakingOp
 }
 
-#[cfg(feature = "ensemble-staking")]
+#[cfg(feature = "staking")]
 #[derive(Clone, PartialEq, Debug)]
 #[non_exhaustive]
 pub enum StakingOp {
@@ -91,7 +182,9 @@ pub enum StakingOp {
     },
     Undelegate {
         /// The address of the validator where the funds were sent.
-        validator: String
+        validator: String,
+        /// The time at which the funds will be returned to the delegator.
+        completion_time: Timestamp
     },
     Redelegate {
         /// The address of the validator that the funds were redelegated from.
@@ -101,7 +194,7 @@ pub enum StakingOp {
     }
 }
 
-#[cfg(feature = "ensemble-staking")]
+#[cfg(feature = "staking")]
 #[derive(Clone, PartialEq, Debug)]
 pub struct DistributionResponse {
     /// The address that delegated the funds.
@@ -110,7 +203,7 @@ pub struct DistributionResponse {
     pub kind: DistributionOp
 }
 
-#[cfg(feature = "ensemble-staking")]
+#[cfg(feature = "staking")]
 #[derive(Clone, PartialEq, Debug)]
 #[non_exhaustive]
 pub enum DistributionOp {
@@ -126,6 +219,40 @@ pub enum DistributionOp {
     }
 }
 
+#[cfg(feature = "ibc")]
+#[derive(Clone, PartialEq, Debug)]
+pub struct IbcResponse {
+    /// The contract that was called.
+    pub address: String,
+    /// The message that was passed to the IBC entry point.
+    pub msg: IbcEntryPoint,
+    /// The acknowledgement returned by `ibc_packet_receive`.
+    /// [`None`] for all other entry points.
+    pub acknowledgement: Option<Binary>,
+    /// The response returned by the contract.
+    pub response: Response,
+    /// The gas used by this message, excluding any messages that it initiated.
+    /// All zeroes unless gas metering is enabled.
+    pub gas: GasReport,
+    /// The events emitted by this message, excluding any messages that it initiated.
+    /// Use `event_log` to get the events of the initiated messages as well.
+    pub events: Vec<Event>,
+    /// The responses for any messages that the contract initiated.
+    pub sent: Vec<ResponseVariants>
+}
+
+#[cfg(feature = "ibc")]
+#[derive(Clone, PartialEq, Debug)]
+pub struct IbcMsgResponse {
+    /// The contract that sent the message.
+    pub sender: String,
+    /// The IBC message that was sent.
+    pub msg: IbcMsg,
+    /// The packet that is waiting to be relayed.
+    /// [`None`] if the message closed a channel.
+    pub packet: Option<IbcPacket>
+}
+
 /// Iterator that iterates over all responses returned by
 /// the various modules in **order of execution**.
 pub struct Iter<'a> {
@@ -140,6 +267,18 @@ impl InstantiateResponse {
     pub fn iter(&self) -> Iter<'_> {
         Iter::new(&self.sent)
     }
+
+    /// Returns the total gas used by this message and all of its child messages.
+    pub fn gas_used(&self) -> u64 {
+        self.iter().fold(self.gas.total(), |acc, x| acc + x.gas().total())
+    }
+
+    /// Returns all events emitted by this message and the messages
+    /// that it initiated, in the order that the chain emits them.
+    #[inline]
+    pub fn event_log(&self) -> EventLog {
+        event_log(&self.events, &self.sent)
+    }
 }
 
 impl ExecuteResponse {
@@ -148,6 +287,58 @@ impl ExecuteResponse {
     pub fn iter(&self) -> Iter<'_> {
         Iter::new(&self.sent)
     }
+
+    /// Returns the total gas used by this message and all of its child messages.
+    pub fn gas_used(&self) -> u64 {
+        self.iter().fold(self.gas.total(), |acc, x| acc + x.gas().total())
+    }
+
+    /// Returns all events emitted by this message and the messages
+    /// that it initiated, in the order that the chain emits them.
+    #[inline]
+    pub fn event_log(&self) -> EventLog {
+        event_log(&self.events, &self.sent)
+    }
+}
+
+impl MigrateResponse {
+    /// Returns an iterator that iterates over this instance's child responses.
+    /// Iteration follows the message execution order.
+    pub fn iter(&self) -> Iter<'_> {
+        Iter::new(&self.sent)
+    }
+
+    /// Returns all events emitted by this message and the messages
+    /// that it initiated, in the order that the chain emits them.
+    #[inline]
+    pub fn event_log(&self) -> EventLog {
+        event_log(&self.events, &self.sent)
+    }
+}
+
+impl ReplyResponse {
+    /// Returns all events emitted by this message and the messages
+    /// that it initiated, in the order that the chain emits them.
+    #[inline]
+    pub fn event_log(&self) -> EventLog {
+        event_log(&self.events, &self.sent)
+    }
+}
+
+#[cfg(feature = "ibc")]
+impl IbcResponse {
+    /// Returns an iterator that iterates over this instance's child responses.
+    /// Iteration follows the message execution order.
+    pub fn iter(&self) -> Iter<'_> {
+        Iter::new(&self.sent)
+    }
+
+    /// Returns all events emitted by this message and the messages
+    /// that it initiated, in the order that the chain emits them.
+    #[inline]
+    pub fn event_log(&self) -> EventLog {
+        event_log(&self.events, &self.sent)
+    }
 }
 
 impl ResponseVariants {
@@ -166,36 +357,77 @@ impl ResponseVariants {
         matches!(&self, Self::Reply(_))
     }
 
+    #[inline]
+    pub fn is_migrate(&self) -> bool {
+        matches!(&self, Self::Migrate(_))
+    }
+
+    #[inline]
+    pub fn is_admin(&self) -> bool {
+        matches!(&self, Self::Admin(_))
+    }
+
     #[inline]
     pub fn is_bank(&self) -> bool {
         matches!(&self, Self::Bank(_))
     }
 
     #[inline]
-    #[cfg(feature = "ensemble-staking")]
+    pub fn is_burn(&self) -> bool {
+        matches!(&self, Self::Burn(_))
+    }
+
+    #[inline]
+    pub fn is_module(&self) -> bool {
+        matches!(&self, Self::Module(_))
+    }
+
+    #[inline]
+    #[cfg(feature = "staking")]
     pub fn is_staking(&self) -> bool {
         matches!(&self, Self::Staking(_))
     }
 
     #[inline]
-    #[cfg(feature = "ensemble-staking")]
+    #[cfg(feature = "staking")]
     pub fn is_distribution(&self) -> bool {
         matches!(&self, Self::Distribution(_))
     }
 
+    #[inline]
+    #[cfg(feature = "ibc")]
+    pub fn is_ibc(&self) -> bool {
+        matches!(&self, Self::Ibc(_))
+    }
+
+    #[inline]
+    #[cfg(feature = "ibc")]
+    pub fn is_ibc_msg(&self) -> bool {
+        matches!(&self, Self::IbcMsg(_))
+    }
+
     /// Returns the messages that were created by this response.
-    /// Only instantiate, execute and reply can return a non-empty slice.
+    /// Only instantiate, execute, reply, migrate and IBC entry point
+    /// responses can return a non-empty slice.
     #[inline]
     pub fn messages(&self) -> &[SubMsg] {
         match self {
             Self::Instantiate(resp) => &resp.response.messages,
             Self::Execute(resp) => &resp.response.messages,
             Self::Reply(resp) => &resp.response.messages,
+            Self::Migrate(resp) => &resp.response.messages,
+            Self::Admin(_) => &[],
             Self::Bank(_) => &[],
-            #[cfg(feature = "ensemble-staking")]
+            Self::Burn(_) => &[],
+            Self::Module(_) => &[],
+            #[cfg(feature = "staking")]
             Self::Staking(_) => &[],
-            #[cfg(feature = "ensemble-staking")]
-            Self::Distribution(_) => &[]
+            #[cfg(feature = "staking")]
+            Self::Distribution(_) => &[],
+            #[cfg(feature = "ibc")]
+            Self::Ibc(resp) => &resp.response.messages,
+            #[cfg(feature = "ibc")]
+            Self::IbcMsg(_) => &[]
         }
     }
 
@@ -205,11 +437,92 @@ impl ResponseVariants {
             Self::Instantiate(resp) => resp.sent.extend(responses),
             Self::Execute(resp) => resp.sent.extend(responses),
             Self::Reply(resp) => resp.sent.extend(responses),
+            Self::Migrate(resp) => resp.sent.extend(responses),
+            Self::Admin(_) => panic!("Trying to add a child response to an AdminResponse."),
             Self::Bank(_) => panic!("Trying to add a child response to a BankResponse."),
-            #[cfg(feature = "ensemble-staking")]
+            Self::Burn(_) => panic!("Trying to add a child response to a BurnResponse."),
+            Self::Module(_) => panic!("Trying to add a child response to a ModuleMsgResponse."),
+            #[cfg(feature = "staking")]
             Self::Staking(_) => panic!("Trying to add a child response to a StakingResponse."),
-            #[cfg(feature = "ensemble-staking")]
+            #[cfg(feature = "staking")]
             Self::Distribution(_) => panic!("Trying to add a child response to a DistributionResponse."),
+            #[cfg(feature = "ibc")]
+            Self::Ibc(resp) => resp.sent.extend(responses),
+            #[cfg(feature = "ibc")]
+            Self::IbcMsg(_) => panic!("Trying to add a child response to an IbcMsgResponse."),
+        }
+    }
+
+    /// Returns the gas used by this response. Only instantiate, execute,
+    /// reply and migrate have a report, the rest return an empty one.
+    #[inline]
+    pub fn gas(&self) -> GasReport {
+        match self {
+            Self::Instantiate(resp) => resp.gas,
+            Self::Execute(resp) => resp.gas,
+            Self::Reply(resp) => resp.gas,
+            Self::Migrate(resp) => resp.gas,
+            #[cfg(feature = "ibc")]
+            Self::Ibc(resp) => resp.gas,
+            _ => GasReport::default()
+        }
+    }
+
+    /// Returns the events emitted by this message, excluding
+    /// any messages that it initiated.
+    pub fn events(&self) -> Vec<Event> {
+        match self {
+            Self::Instantiate(resp) => resp.events.clone(),
+            Self::Execute(resp) => resp.events.clone(),
+            Self::Reply(resp) => resp.events.clone(),
+            Self::Migrate(resp) => resp.events.clone(),
+            Self::Admin(resp) => ProcessedEvents::from(resp).take(),
+            Self::Bank(resp) => ProcessedEvents::from(resp).take(),
+            Self::Burn(resp) => ProcessedEvents::from(resp).take(),
+            Self::Module(resp) => ProcessedEvents::from(resp).take(),
+            #[cfg(feature = "staking")]
+            Self::Staking(resp) => ProcessedEvents::from(resp).take(),
+            #[cfg(feature = "staking")]
+            Self::Distribution(resp) => ProcessedEvents::from(resp).take(),
+            #[cfg(feature = "ibc")]
+            Self::Ibc(resp) => resp.events.clone(),
+            #[cfg(feature = "ibc")]
+            Self::IbcMsg(resp) => ProcessedEvents::from(resp).take()
+        }
+    }
+
+    /// Returns all events emitted by this message and the messages
+    /// that it initiated, in the order that the chain emits them.
+    pub fn event_log(&self) -> EventLog {
+        let mut events = self.events();
+        events.extend(self.iter_children().flat_map(|x| x.events()));
+
+        EventLog::new(events)
+    }
+
+    pub(crate) fn set_events(&mut self, events: &[Event]) {
+        let target = match self {
+            Self::Instantiate(resp) => &mut resp.events,
+            Self::Execute(resp) => &mut resp.events,
+            Self::Reply(resp) => &mut resp.events,
+            Self::Migrate(resp) => &mut resp.events,
+            #[cfg(feature = "ibc")]
+            Self::Ibc(resp) => &mut resp.events,
+            _ => return
+        };
+
+        *target = events.to_vec();
+    }
+
+    fn iter_children(&self) -> Iter<'_> {
+        match self {
+            Self::Instantiate(resp) => resp.iter(),
+            Self::Execute(resp) => resp.iter(),
+            Self::Reply(resp) => Iter::new(&resp.sent),
+            Self::Migrate(resp) => resp.iter(),
+            #[cfg(feature = "ibc")]
+            Self::Ibc(resp) => resp.iter(),
+            _ => Iter::new(&[])
         }
     }
 
@@ -218,6 +531,9 @@ impl ResponseVariants {
             Self::Instantiate(resp) => Some(&resp.response),
             Self::Execute(resp) => Some(&resp.response),
             Self::Reply(resp) => Some(&resp.response),
+            Self::Migrate(resp) => Some(&resp.response),
+            #[cfg(feature = "ibc")]
+            Self::Ibc(resp) => Some(&resp.response),
             _ => None
         }
     }
@@ -244,6 +560,20 @@ impl From<ReplyResponse> for ResponseVariants {
     }
 }
 
+impl From<MigrateResponse> for ResponseVariants {
+    #[inline]
+    fn from(value: MigrateResponse) -> Self {
+        Self::Migrate(value)
+    }
+}
+
+impl From<AdminResponse> for ResponseVariants {
+    #[inline]
+    fn from(value: AdminResponse) -> Self {
+        Self::Admin(value)
+    }
+}
+
 impl From<BankResponse> for ResponseVariants {
     #[inline]
     fn from(value: BankResponse) -> Self {
@@ -251,7 +581,21 @@ impl From<BankResponse> for ResponseVariants {
     }
 }
 
-#[cfg(feature = "ensemble-staking")]
+impl From<BurnResponse> for ResponseVariants {
+    #[inline]
+    fn from(value: BurnResponse) -> Self {
+        Self::Burn(value)
+    }
+}
+
+impl From<ModuleMsgResponse> for ResponseVariants {
+    #[inline]
+    fn from(value: ModuleMsgResponse) -> Self {
+        Self::Module(value)
+    }
+}
+
+#[cfg(feature = "staking")]
 impl From<StakingResponse> for ResponseVariants {
     #[inline]
     fn from(value: StakingResponse) -> Self {
@@ -259,7 +603,7 @@ impl From<StakingResponse> for ResponseVariants {
     }
 }
 
-#[cfg(feature = "ensemble-staking")]
+#[cfg(feature = "staking")]
 impl From<DistributionResponse> for ResponseVariants {
     #[inline]
     fn from(value: DistributionResponse) -> Self {
@@ -267,8 +611,32 @@ impl From<DistributionResponse> for ResponseVariants {
     }
 }
 
+#[cfg(feature = "ibc")]
+impl From<IbcResponse> for ResponseVariants {
+    #[inline]
+    fn from(value: IbcResponse) -> Self {
+        Self::Ibc(value)
+    }
+}
+
+#[cfg(feature = "ibc")]
+impl From<IbcMsgResponse> for ResponseVariants {
+    #[inline]
+    fn from(value: IbcMsgResponse) -> Self {
+        Self::IbcMsg(value)
+    }
+}
+
+fn event_log(events: &[Event], sent: &[ResponseVariants]) -> EventLog {
+    let mut events = events.to_vec();
+    events.extend(Iter::new(sent).flat_map(|x| x.events()));
+
+    EventLog::new(events)
+}
+
 impl<'a> Iter<'a> {
-    /// Yields all responses that were initiated by the given `sender`. Reply responses are not included.
+    /// Yields all responses that were initiated by the given `sender`.
+    /// Reply and IBC entry point responses are not included.
     pub fn by_sender(self, sender: impl Into<String>) -> impl Iterator<Item = &'a ResponseVariants> {
         let sender = sender.into();
 
@@ -276,11 +644,19 @@ impl<'a> Iter<'a> {
             ResponseVariants::Instantiate(resp) => resp.sender == sender,
             ResponseVariants::Execute(resp) => resp.sender == sender,
             ResponseVariants::Reply(_) => false,
+            ResponseVariants::Migrate(resp) => resp.sender == sender,
+            ResponseVariants::Admin(resp) => resp.sender == sender,
             ResponseVariants::Bank(resp) => resp.sender == sender,
-            #[cfg(feature = "ensemble-staking")]
+            ResponseVariants::Burn(resp) => resp.sender == sender,
+            ResponseVariants::Module(resp) => resp.sender == sender,
+            #[cfg(feature = "staking")]
             ResponseVariants::Staking(resp) => resp.sender == sender,
-            #[cfg(feature = "ensemble-staking")]
+            #[cfg(feature = "staking")]
             ResponseVariants::Distribution(resp) => resp.sender == sender,
+            #[cfg(feature = "ibc")]
+            ResponseVariants::Ibc(_) => false,
+            #[cfg(feature = "ibc")]
+            ResponseVariants::IbcMsg(resp) => resp.sender == sender
         })
     }
 
@@ -300,11 +676,21 @@ impl<'a> Iter<'a> {
                 self.stack.extend(resp.sent.iter().rev()),
             ResponseVariants::Instantiate(resp) =>
                 self.stack.extend(resp.sent.iter().rev()),
+            ResponseVariants::Migrate(resp) =>
+                self.stack.extend(resp.sent.iter().rev()),
+            ResponseVariants::Admin(_) => { },
             ResponseVariants::Bank(_) => { },
-            #[cfg(feature = "ensemble-staking")]
+            ResponseVariants::Burn(_) => { },
+            ResponseVariants::Module(_) => { },
+            #[cfg(feature = "staking")]
             ResponseVariants::Staking(_) => { },
-            #[cfg(feature = "ensemble-staking")]
-            ResponseVariants::Distribution(_) => { }
+            #[cfg(feature = "staking")]
+            ResponseVariants::Distribution(_) => { },
+            #[cfg(feature = "ibc")]
+            ResponseVariants::Ibc(resp) =>
+                self.stack.extend(resp.sent.iter().rev()),
+            #[cfg(feature = "ibc")]
+            ResponseVariants::IbcMsg(_) => { }
         }
     }
 }
@@ -518,6 +904,8 @@ mod tests {
             address: address.into(),
             msg: Binary::from(format!("message_{}", index).as_bytes()),
             response: Response::default(),
+            gas: GasReport::default(),
+            events: vec![],
             sent: vec![]
         };
 
@@ -538,6 +926,8 @@ mod tests {
             code_id: 0,
             msg: Binary::from(format!("message_{}", index).as_bytes()),
             response: Response::default(),
+            gas: GasReport::default(),
+            events: vec![],
             sent: vec![]
         };
 
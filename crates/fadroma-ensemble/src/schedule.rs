@@ -0,0 +1,96 @@
+use std::fmt::Debug;
+
+use fadroma::cosmwasm_std::Timestamp;
+
+use super::{
+    ensemble::{ContractEnsemble, EnsembleResult},
+    block::Block
+};
+
+pub(crate) type HookAction = Box<dyn FnMut(&mut ContractEnsemble) -> EnsembleResult<()>>;
+
+/// When a hook added with [`ContractEnsemble::schedule`] runs. Hooks run
+/// on the first block at or after the given height or time that is reached
+/// by [`ContractEnsemble::fast_forward`], the other `fast_forward_*` methods
+/// or by executing a transaction.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Schedule {
+    /// Runs once at the given block height.
+    AtHeight(u64),
+    /// Runs once at the given block time.
+    AtTime(Timestamp),
+    /// Runs every time that the given number of blocks have passed,
+    /// counting from the block at which the hook was scheduled.
+    EveryBlocks(u64),
+    /// Runs every time that the given number of seconds have passed,
+    /// counting from the block at which the hook was scheduled.
+    EverySeconds(u64)
+}
+
+/// Identifies a hook that was added with [`ContractEnsemble::schedule`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct HookId(pub(crate) usize);
+
+pub(crate) struct Hook {
+    schedule: Schedule,
+    /// The height or the time in seconds, depending on the
+    /// schedule, at which the hook is due to run next.
+    next: u64,
+    pub action: HookAction
+}
+
+impl Hook {
+    pub fn new(schedule: Schedule, block: &Block, action: HookAction) -> Self {
+        let next = match schedule {
+            Schedule::AtHeight(height) => height,
+            Schedule::AtTime(time) => time.seconds(),
+            Schedule::EveryBlocks(interval) => {
+                assert!(interval > 0, "Ensemble: The block interval of a hook must be bigger than 0.");
+
+                block.height + interval
+            },
+            Schedule::EverySeconds(interval) => {
+                assert!(interval > 0, "Ensemble: The time interval of a hook must be bigger than 0.");
+
+                block.time + interval
+            }
+        };
+
+        Self { schedule, next, action }
+    }
+
+    #[inline]
+    pub fn is_due(&self, block: &Block) -> bool {
+        match self.schedule {
+            Schedule::AtHeight(_) | Schedule::EveryBlocks(_) => block.height >= self.next,
+            Schedule::AtTime(_) | Schedule::EverySeconds(_) => block.time >= self.next
+        }
+    }
+
+    /// Moves a repeating hook to the next interval after the current
+    /// block. Returns `false` if the hook only runs once.
+    pub fn reschedule(&mut self, block: &Block) -> bool {
+        let (interval, current) = match self.schedule {
+            Schedule::AtHeight(_) | Schedule::AtTime(_) => return false,
+            Schedule::EveryBlocks(interval) => (interval, block.height),
+            Schedule::EverySeconds(interval) => (interval, block.time)
+        };
+
+        // Blocks can span more than one interval, in which case
+        // the hook runs only once for all of them.
+        while self.next <= current {
+            self.next += interval;
+        }
+
+        true
+    }
+}
+
+impl Debug for Hook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Hook")
+            .field("schedule", &self.schedule)
+            .field("next", &self.next)
+            .finish()
+    }
+}
@@ -0,0 +1,264 @@
+use std::{
+    fs,
+    path::Path,
+    collections::{HashMap, BTreeMap}
+};
+
+use serde::{Serialize, Deserialize};
+use fadroma::cosmwasm_std::{Binary, Uint128};
+
+use super::{
+    EnsembleResult, EnsembleError,
+    bank::Bank,
+    block::Block,
+    storage::TestStorage,
+    state::{ContractInstance, module_address}
+};
+
+#[cfg(feature = "staking")]
+use super::staking::Delegations;
+
+/// The version of the on-disk format produced by [`Snapshot::to_bytes`].
+/// Increment this whenever the format changes in a way that
+/// isn't backwards compatible.
+pub const SNAPSHOT_VERSION: u32 = 3;
+
+/// A copy of the whole chain state at a given point in time, created
+/// by [`crate::ContractEnsemble::snapshot`]. It includes the storage of
+/// all contract instances, their admins, bank balances, the total supply,
+/// token factory denoms, delegations, the storage of any [`crate::Module`]s,
+/// the current block and the chain id.
+/// Restoring it with [`crate::ContractEnsemble::restore`] doesn't consume it,
+/// so the same snapshot can be restored any number of times.
+///
+/// Registered contract code is not part of the snapshot since it is
+/// never removed from the ensemble.
+///
+/// Snapshots can be written to disk with [`Snapshot::save`] and loaded
+/// back with [`Snapshot::load`]. The file is versioned JSON where storage
+/// keys and values are encoded as base64. Since the contract code is not
+/// included, it can only be restored into an ensemble that has the same
+/// contracts registered in the same order.
+#[derive(Clone, Debug)]
+pub struct Snapshot {
+    pub(crate) instances: HashMap<String, ContractInstance>,
+    pub(crate) bank: Bank,
+    pub(crate) modules: HashMap<String, TestStorage>,
+    /// [`None`] if the snapshot was created without the `staking` feature.
+    #[cfg(feature = "staking")]
+    pub(crate) delegations: Option<Delegations>,
+    pub(crate) block: Block,
+    pub(crate) chain_id: String,
+    /// The number of contracts that were registered at the time.
+    pub(crate) code_count: usize
+}
+
+#[derive(Deserialize)]
+struct Version {
+    version: u32
+}
+
+#[derive(Serialize, Deserialize)]
+struct SnapshotFile {
+    version: u32,
+    chain_id: String,
+    block: Block,
+    code_count: usize,
+    instances: BTreeMap<String, InstanceFile>,
+    bank: BTreeMap<String, BTreeMap<String, Uint128>>,
+    /// The admins of token factory denoms.
+    denoms: BTreeMap<String, String>,
+    supply: BTreeMap<String, Uint128>,
+    modules: BTreeMap<String, BTreeMap<Binary, Binary>>,
+    /// Always present so that files can be loaded regardless of whether
+    /// the `staking` feature is enabled. It's `null` if it wasn't when saving
+    /// and it's ignored if it isn't when loading.
+    delegations: Option<DelegationsFile>
+}
+
+#[cfg(feature = "staking")]
+type DelegationsFile = Delegations;
+
+#[cfg(not(feature = "staking"))]
+type DelegationsFile = serde_json::Value;
+
+#[derive(Serialize, Deserialize)]
+struct InstanceFile {
+    code_id: u64,
+    creator: String,
+    admin: Option<String>,
+    label: String,
+    storage: BTreeMap<Binary, Binary>
+}
+
+impl Snapshot {
+    /// Returns the addresses of all contract instances in the snapshot.
+    pub fn instances(&self) -> impl Iterator<Item = &str> {
+        self.instances.keys().map(|x| x.as_str())
+    }
+
+    /// Returns the block height at the time the snapshot was taken.
+    #[inline]
+    pub fn block_height(&self) -> u64 {
+        self.block.height
+    }
+
+    /// Returns the chain id at the time the snapshot was taken.
+    #[inline]
+    pub fn chain_id(&self) -> &str {
+        &self.chain_id
+    }
+
+    /// Writes the snapshot to the file at `path`, replacing it if it exists.
+    pub fn save(&self, path: impl AsRef<Path>) -> EnsembleResult<()> {
+        let path = path.as_ref();
+
+        fs::write(path, self.to_bytes()).map_err(|err|
+            EnsembleError::Snapshot(format!("Couldn't write {}: {}", path.display(), err))
+        )
+    }
+
+    /// Reads a snapshot from the file at `path` that was created
+    /// using [`Snapshot::save`].
+    pub fn load(path: impl AsRef<Path>) -> EnsembleResult<Self> {
+        let path = path.as_ref();
+
+        let bytes = fs::read(path).map_err(|err|
+            EnsembleError::Snapshot(format!("Couldn't read {}: {}", path.display(), err))
+        )?;
+
+        Self::from_bytes(&bytes)
+    }
+
+    /// Serializes the snapshot into the versioned file format.
+    /// The output is deterministic so that it can be checked in
+    /// and diffed as a test fixture.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let instances = self.instances.iter().map(|(address, instance)| {
+            (address.clone(), InstanceFile {
+                code_id: instance.index as u64,
+                creator: instance.creator.clone(),
+                admin: instance.admin.clone(),
+                label: instance.label.clone(),
+                storage: encode_storage(&instance.storage)
+            })
+        }).collect();
+
+        let bank = self.bank.accounts.iter()
+            .map(|(address, balances)| (
+                address.clone(),
+                balances.iter().map(|(denom, amount)| (denom.clone(), *amount)).collect()
+            ))
+            .collect();
+
+        let modules = self.modules.iter()
+            .filter(|(_, storage)| !storage.backing.is_empty())
+            .map(|(name, storage)| (name.clone(), encode_storage(storage)))
+            .collect();
+
+        let file = SnapshotFile {
+            version: SNAPSHOT_VERSION,
+            chain_id: self.chain_id.clone(),
+            block: self.block.clone(),
+            code_count: self.code_count,
+            instances,
+            bank,
+            denoms: self.bank.denoms.clone(),
+            supply: self.bank.supply.clone(),
+            modules,
+            #[cfg(feature = "staking")]
+            delegations: self.delegations.clone(),
+            #[cfg(not(feature = "staking"))]
+            delegations: None
+        };
+
+        serde_json::to_vec_pretty(&file).unwrap()
+    }
+
+    /// Deserializes a snapshot created using [`Snapshot::to_bytes`].
+    /// Returns an error if the data is malformed or was
+    /// written by an incompatible version.
+    pub fn from_bytes(bytes: &[u8]) -> EnsembleResult<Self> {
+        let Version { version } = serde_json::from_slice(bytes).map_err(invalid)?;
+
+        if version != SNAPSHOT_VERSION {
+            return Err(EnsembleError::Snapshot(format!(
+                "Unsupported snapshot version {}, expected {}",
+                version,
+                SNAPSHOT_VERSION
+            )));
+        }
+
+        let file: SnapshotFile = serde_json::from_slice(bytes).map_err(invalid)?;
+
+        let mut instances = HashMap::with_capacity(file.instances.len());
+
+        for (address, instance) in file.instances {
+            if instance.code_id >= file.code_count as u64 {
+                return Err(EnsembleError::Snapshot(format!(
+                    "Instance {} has code id {} but only {} contracts are registered",
+                    address,
+                    instance.code_id,
+                    file.code_count
+                )));
+            }
+
+            let storage = decode_storage(&address, instance.storage);
+
+            instances.insert(address, ContractInstance {
+                storage,
+                index: instance.code_id as usize,
+                creator: instance.creator,
+                admin: instance.admin,
+                label: instance.label
+            });
+        }
+
+        let bank = file.bank.into_iter()
+            .map(|(address, balances)| (address, balances.into_iter().collect()))
+            .collect();
+
+        let modules = file.modules.into_iter()
+            .map(|(name, storage)| {
+                let storage = decode_storage(module_address(&name), storage);
+
+                (name, storage)
+            })
+            .collect();
+
+        Ok(Self {
+            instances,
+            bank: Bank {
+                accounts: bank,
+                denoms: file.denoms,
+                supply: file.supply
+            },
+            modules,
+            #[cfg(feature = "staking")]
+            delegations: file.delegations,
+            block: file.block,
+            chain_id: file.chain_id,
+            code_count: file.code_count
+        })
+    }
+}
+
+fn encode_storage(storage: &TestStorage) -> BTreeMap<Binary, Binary> {
+    storage.backing.iter()
+        .map(|(k, v)| (Binary(k.clone()), Binary(v.clone())))
+        .collect()
+}
+
+fn decode_storage(address: impl Into<String>, storage: BTreeMap<Binary, Binary>) -> TestStorage {
+    let mut result = TestStorage::new(address);
+    result.backing = storage.into_iter()
+        .map(|(k, v)| (k.0, v.0))
+        .collect();
+
+    result
+}
+
+#[inline]
+fn invalid(err: serde_json::Error) -> EnsembleError {
+    EnsembleError::Snapshot(format!("Invalid snapshot: {}", err))
+}
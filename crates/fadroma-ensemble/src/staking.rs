@@ -1,6 +1,8 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet};
 
-use crate::prelude::*;
+use serde::{Serialize, Deserialize};
+
+use fadroma::prelude::*;
 use super::{
     EnsembleResult, EnsembleError,
     response::{
@@ -9,26 +11,46 @@ use super::{
     }
 };
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Serialize, Deserialize, Debug)]
 pub(crate) struct DelegationWithUnbonding {
     delegator: String,
     validator: String,
     amount: Coin,
-    unbonding_amount: Coin,
     can_redelegate: Coin,
     accumulated_rewards: Coin,
 }
 
-pub(crate) type Delegator = HashMap<String, DelegationWithUnbonding>;
+/// Funds that are being undelegated and will be returned
+/// to the delegator once the block time reaches `completion_time`.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Unbonding {
+    pub delegator: Addr,
+    pub validator: String,
+    pub amount: Coin,
+    pub completion_time: Timestamp
+}
+
+/// 21 days, same as the Cosmos Hub and Secret Network.
+pub const DEFAULT_UNBONDING_PERIOD: u64 = 21 * 24 * 60 * 60;
 
-#[derive(Debug)]
+pub(crate) type Delegator = BTreeMap<String, DelegationWithUnbonding>;
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
 pub(crate) struct Delegations {
     /// Denom for bonded currency
     bonded_denom: String,
     /// List of all valid validators
     validators: Vec<Validator>,
-    /// Doubly hashed array of delegations for easy access
-    delegators: HashMap<String, Delegator>,
+    /// Delegations keyed by delegator and then by validator
+    delegators: BTreeMap<String, Delegator>,
+    /// Pending unbondings in the order that they were created
+    unbondings: Vec<Unbonding>,
+    /// Time in seconds that it takes for undelegated funds to be returned
+    unbonding_period: u64,
+    /// Validators that are currently jailed
+    jailed: BTreeSet<String>,
+    /// Commission earned by each validator from distributed rewards
+    commission: BTreeMap<String, Uint128>,
 }
 
 impl Into<Delegation> for DelegationWithUnbonding {
@@ -59,6 +81,10 @@ impl Delegations {
             bonded_denom,
             validators: Default::default(),
             delegators: Default::default(),
+            unbondings: Default::default(),
+            unbonding_period: DEFAULT_UNBONDING_PERIOD,
+            jailed: Default::default(),
+            commission: Default::default(),
         }
     }
 
@@ -79,24 +105,145 @@ impl Delegations {
         }
     }
 
-    pub fn fast_forward_waits(&mut self) -> Vec<Delegation> {
-        let mut unbondings = vec![];
-        for delegator in self.delegators.iter_mut() {
-            for delegation_pair in delegator.1 {
-                let delegation = delegation_pair.1;
-                delegation.can_redelegate = delegation.amount.clone();
-                if delegation.unbonding_amount.amount > Uint128::zero() {
-                    unbondings.push(Delegation {
-                        delegator: Addr::unchecked(delegation.delegator.clone()),
-                        validator: delegation.validator.to_string(),
-                        amount: delegation.unbonding_amount.clone()
-                    });
+    /// Distributes `amount` across all delegations to validators that
+    /// aren't jailed, proportionally to their stake. Each validator keeps
+    /// its commission rate of the rewards of its delegators.
+    pub fn distribute_weighted_rewards(&mut self, amount: Uint128) {
+        let total_stake: Uint128 = self.delegators.values()
+            .flat_map(|delegator| delegator.values())
+            .filter(|delegation| !self.jailed.contains(&delegation.validator))
+            .map(|delegation| delegation.amount.amount)
+            .sum();
+
+        if total_stake.is_zero() {
+            return;
+        }
+
+        for delegator in self.delegators.values_mut() {
+            for delegation in delegator.values_mut() {
+                if self.jailed.contains(&delegation.validator) {
+                    continue;
                 }
+
+                let rate = self.validators.iter()
+                    .find(|x| x.address == delegation.validator)
+                    .map(|x| x.commission)
+                    .unwrap_or_default();
+
+                let reward = amount.multiply_ratio(delegation.amount.amount, total_stake);
+                let commission = reward * rate;
+
+                delegation.accumulated_rewards.amount += reward - commission;
+                *self.commission.entry(delegation.validator.clone()).or_default() += commission;
             }
         }
-        unbondings
     }
-    
+
+    /// Slashes all delegations to and unbondings from the given validator
+    /// by `fraction`. Returns the total amount that was slashed.
+    pub fn slash(&mut self, validator: &str, fraction: Decimal) -> EnsembleResult<Uint128> {
+        if fraction > Decimal::one() {
+            return Err(EnsembleError::Staking("Slash fraction must not be greater than 1".into()));
+        }
+        if !self.validate_validator(validator) {
+            return Err(EnsembleError::Staking("Validator not found".into()));
+        }
+
+        let mut slashed = Uint128::zero();
+
+        for delegator in self.delegators.values_mut() {
+            if let Some(delegation) = delegator.get_mut(validator) {
+                let amount = delegation.amount.amount * fraction;
+
+                delegation.amount.amount -= amount;
+                delegation.can_redelegate.amount = delegation.can_redelegate.amount
+                    .min(delegation.amount.amount);
+
+                slashed += amount;
+            }
+        }
+
+        for unbonding in self.unbondings.iter_mut().filter(|x| x.validator == validator) {
+            let amount = unbonding.amount.amount * fraction;
+
+            unbonding.amount.amount -= amount;
+            slashed += amount;
+        }
+
+        Ok(slashed)
+    }
+
+    pub fn jail(&mut self, validator: &str) -> EnsembleResult<()> {
+        if !self.validate_validator(validator) {
+            return Err(EnsembleError::Staking("Validator not found".into()));
+        }
+        if !self.jailed.insert(validator.to_string()) {
+            return Err(EnsembleError::Staking("Validator is already jailed".into()));
+        }
+
+        Ok(())
+    }
+
+    pub fn unjail(&mut self, validator: &str) -> EnsembleResult<()> {
+        if !self.jailed.remove(validator) {
+            return Err(EnsembleError::Staking("Validator is not jailed".into()));
+        }
+
+        Ok(())
+    }
+
+    pub fn set_commission(&mut self, validator: &str, rate: Decimal) -> EnsembleResult<()> {
+        match self.validators.iter_mut().find(|x| x.address == validator) {
+            Some(validator) => {
+                if rate > validator.max_commission {
+                    return Err(EnsembleError::Staking("Commission rate exceeds the maximum".into()));
+                }
+                validator.commission = rate;
+
+                Ok(())
+            },
+            None => Err(EnsembleError::Staking("Validator not found".into()))
+        }
+    }
+
+    /// Allows redelegating all delegations again and completes
+    /// all unbondings, regardless of their completion time.
+    pub fn fast_forward_waits(&mut self) -> Vec<Unbonding> {
+        for delegator in self.delegators.values_mut() {
+            for delegation in delegator.values_mut() {
+                delegation.can_redelegate = delegation.amount.clone();
+            }
+        }
+
+        std::mem::take(&mut self.unbondings)
+    }
+
+    /// Removes and returns the unbondings that have completed at `time`.
+    pub fn complete_unbondings(&mut self, time: Timestamp) -> Vec<Unbonding> {
+        let (completed, pending) = std::mem::take(&mut self.unbondings)
+            .into_iter()
+            .partition(|x| x.completion_time <= time);
+
+        self.unbondings = pending;
+
+        completed
+    }
+
+    pub fn set_unbonding_period(&mut self, seconds: u64) {
+        self.unbonding_period = seconds;
+    }
+
+    pub fn unbonding_period(&self) -> u64 {
+        self.unbonding_period
+    }
+
+    pub fn unbondings(&self, delegator: &str) -> Vec<Unbonding> {
+        self.unbondings.iter()
+            .filter(|x| x.delegator == delegator)
+            .cloned()
+            .collect()
+    }
+
     // Validator queries
     pub fn bonded_denom(&self) -> &str {
         &self.bonded_denom
@@ -130,6 +277,22 @@ impl Delegations {
         &self.validators
     }
 
+    /// Validators that are not jailed.
+    pub fn active_validators(&self) -> Vec<Validator> {
+        self.validators.iter()
+            .filter(|x| !self.jailed.contains(&x.address))
+            .cloned()
+            .collect()
+    }
+
+    pub fn is_jailed(&self, validator: &str) -> bool {
+        self.jailed.contains(validator)
+    }
+
+    pub fn validator_commission(&self, validator: &str) -> Uint128 {
+        self.commission.get(validator).copied().unwrap_or_default()
+    }
+
     // Validator transaction messages 
     pub fn delegate(
         &mut self, 
@@ -143,15 +306,14 @@ impl Delegations {
         if !self.validate_validator(&validator) {
             return Err(EnsembleError::Staking("Validator not found".into()));
         }
+        if self.jailed.contains(&validator) {
+            return Err(EnsembleError::Staking("Validator is jailed".into()));
+        }
        
         let mut new_delegation = DelegationWithUnbonding {
             delegator: delegator.clone(),
             validator: validator.clone(),
             amount: amount.clone(),
-            unbonding_amount: Coin {
-                denom: self.bonded_denom.clone(),
-                amount: Uint128::zero(),
-            },
             can_redelegate: amount.clone(),
             accumulated_rewards: Coin {
                 denom: self.bonded_denom.clone(),
@@ -169,7 +331,6 @@ impl Delegations {
                             denom: self.bonded_denom.clone(),
                             amount: old_delegation.amount.amount + amount.amount,
                         };
-                        new_delegation.unbonding_amount = old_delegation.unbonding_amount;
                         new_delegation.can_redelegate = Coin {
                             denom: self.bonded_denom.clone(),
                             amount: old_delegation.can_redelegate.amount + amount.amount,
@@ -197,7 +358,8 @@ impl Delegations {
         &mut self,
         delegator: String,
         validator: String,
-        amount: Coin
+        amount: Coin,
+        time: Timestamp
     ) -> EnsembleResult<StakingResponse> {
         if amount.denom != self.bonded_denom {
             return Err(EnsembleError::Staking("Incorrect coin denom".into()));
@@ -221,21 +383,26 @@ impl Delegations {
                         denom: self.bonded_denom.clone(),
                         amount: delegation.amount.amount - amount.amount,
                     },
-                    unbonding_amount: Coin {
-                        denom: self.bonded_denom.clone(),
-                        amount: delegation.unbonding_amount.amount + amount.amount,
-                    },
                     can_redelegate: new_can_redelegate,
                     accumulated_rewards: delegation.accumulated_rewards,
                 };
 
                 self.insert_delegation(delegator.clone(), validator.clone(), new_delegation);
+
+                let completion_time = time.plus_seconds(self.unbonding_period);
+                self.unbondings.push(Unbonding {
+                    delegator: Addr::unchecked(&delegator),
+                    validator: validator.clone(),
+                    amount: amount.clone(),
+                    completion_time
+                });
                 
                 Ok(StakingResponse {
                     sender: delegator,
                     amount,
                     kind: StakingOp::Undelegate {
-                        validator
+                        validator,
+                        completion_time
                     }
                 })
             },
@@ -254,7 +421,6 @@ impl Delegations {
                     delegator: delegator.clone(),
                     validator: validator.clone(),
                     amount: delegation.amount,
-                    unbonding_amount: delegation.unbonding_amount,
                     can_redelegate: delegation.can_redelegate,
                     accumulated_rewards: Coin {
                         denom: self.bonded_denom.clone(),
@@ -300,6 +466,10 @@ impl Delegations {
                     return Err(EnsembleError::Staking("Destination validator does not exist".into()));
                 }
 
+                if self.jailed.contains(&dst_validator) {
+                    return Err(EnsembleError::Staking("Destination validator is jailed".into()));
+                }
+
                 let new_src_delegation = DelegationWithUnbonding {
                     delegator: delegator.clone(),
                     validator: src_validator.clone(),
@@ -307,7 +477,6 @@ impl Delegations {
                         denom: self.bonded_denom.clone(),
                         amount: delegation.amount.amount - amount.amount,
                     },
-                    unbonding_amount: delegation.unbonding_amount,
                     can_redelegate: Coin {
                         denom: self.bonded_denom.clone(),
                         amount: delegation.can_redelegate.amount - amount.amount,
@@ -330,10 +499,6 @@ impl Delegations {
                                 denom: self.bonded_denom.clone(),
                                 amount: amount.amount,
                             },
-                            unbonding_amount: Coin {
-                                denom: self.bonded_denom.clone(),
-                                amount: Uint128::zero(),
-                            },
                             can_redelegate: Coin {
                                 denom: self.bonded_denom.clone(),
                                 amount: Uint128::zero(),
@@ -1,6 +1,8 @@
 use std::collections::HashMap;
 
 use fadroma::cosmwasm_std::{Coin, Storage};
+#[cfg(feature = "ibc")]
+use fadroma::cosmwasm_std::IbcPacket;
 
 use super::{
     EnsembleResult,
@@ -9,18 +11,28 @@ use super::{
     response::BankResponse,
     error::{EnsembleError, RegistryError}
 };
+#[cfg(feature = "ibc")]
+use super::ibc::IbcState;
 
 #[derive(Default, Debug)]
 pub(crate) struct State {
     pub instances: HashMap<String, ContractInstance>,
     pub bank: Bank,
+    /// The storage of each [`crate::Module`], by name.
+    pub modules: HashMap<String, TestStorage>,
+    #[cfg(feature = "ibc")]
+    pub ibc: IbcState,
     scopes: Vec<Scope>
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub(crate) struct ContractInstance {
     pub storage: TestStorage,
-    pub index: usize
+    pub index: usize,
+    /// The address that instantiated the contract.
+    pub creator: String,
+    pub admin: Option<String>,
+    pub label: String
 }
 
 #[derive(Clone, Debug)]
@@ -28,13 +40,24 @@ pub enum Op {
     CreateInstance {
         address: String
     },
+    MigrateInstance {
+        address: String,
+        old_index: usize
+    },
+    UpdateAdmin {
+        address: String,
+        old: Option<String>
+    },
     StorageWrite {
         address: String,
         key: Vec<u8>,
         old: Option<Vec<u8>>
     },
-    #[allow(dead_code)]
-    BankAddFunds {
+    BankMint {
+        address: String,
+        coin: Coin
+    },
+    BankBurn {
         address: String,
         coin: Coin
     },
@@ -47,17 +70,33 @@ pub enum Op {
         from: String,
         to: String,
         coin: Coin
+    },
+    #[cfg(feature = "ibc")]
+    IbcSendPacket {
+        channel_id: String,
+        sequence: u64
+    },
+    #[cfg(feature = "ibc")]
+    IbcCloseChannel {
+        channel_id: String
     }
 }
 
 #[derive(Default, Debug)]
 struct Scope(Vec<Op>);
 
+/// Prefixes the address of module storage in storage ops
+/// so that it can't be confused with a contract instance.
+const MODULE_PREFIX: &str = "module:";
+
 impl State {
     pub fn new() -> Self {
         Self {
             instances: HashMap::new(),
             bank: Bank::default(),
+            modules: HashMap::new(),
+            #[cfg(feature = "ibc")]
+            ibc: IbcState::default(),
             scopes: vec![]
         }
     }
@@ -65,7 +104,10 @@ impl State {
     pub fn create_contract_instance(
         &mut self,
         address: impl Into<String>,
-        index: usize
+        index: usize,
+        creator: String,
+        admin: Option<String>,
+        label: String
     ) -> EnsembleResult<()> {
         assert!(self.scopes.len() > 0);
         let address = address.into();
@@ -77,7 +119,7 @@ impl State {
         let storage = TestStorage::new(address.clone());
         self.instances.insert(
             address.clone(),
-            ContractInstance { index, storage }
+            ContractInstance { index, storage, creator, admin, label }
         );
 
         let scope = self.current_scope_mut();
@@ -94,6 +136,40 @@ impl State {
         }
     }
 
+    /// Points the instance at `address` to the code with the given `index`.
+    pub fn migrate_instance(&mut self, address: &str, index: usize) -> EnsembleResult<()> {
+        assert!(self.scopes.len() > 0);
+
+        let instance = self.instance_mut(address)?;
+        let old_index = instance.index;
+        instance.index = index;
+
+        let scope = self.current_scope_mut();
+        scope.0.push(Op::MigrateInstance {
+            address: address.to_string(),
+            old_index
+        });
+
+        Ok(())
+    }
+
+    /// Sets or clears (if `admin` is [`None`]) the admin of the instance at `address`.
+    pub fn set_admin(&mut self, address: &str, admin: Option<String>) -> EnsembleResult<()> {
+        assert!(self.scopes.len() > 0);
+
+        let instance = self.instance_mut(address)?;
+        let old = instance.admin.take();
+        instance.admin = admin;
+
+        let scope = self.current_scope_mut();
+        scope.0.push(Op::UpdateAdmin {
+            address: address.to_string(),
+            old
+        });
+
+        Ok(())
+    }
+
     pub fn borrow_storage_mut<F, T>(&mut self, address: &str, borrow: F) -> EnsembleResult<T>
         where F: FnOnce(&mut dyn Storage) -> EnsembleResult<T>
     {
@@ -109,6 +185,52 @@ impl State {
         }
     }
 
+    /// Creates the storage of the module with the given `name` if it doesn't exist.
+    pub fn add_module(&mut self, name: &str) {
+        if !self.modules.contains_key(name) {
+            self.modules.insert(name.to_string(), TestStorage::new(module_address(name)));
+        }
+    }
+
+    #[inline]
+    pub fn module_storage(&self, name: &str) -> &TestStorage {
+        &self.modules[name]
+    }
+
+    #[inline]
+    pub fn module_storage_mut(&mut self, name: &str) -> &mut TestStorage {
+        self.modules.get_mut(name).unwrap()
+    }
+
+    /// Records the storage writes of the module with the given `name` in the current scope.
+    pub fn flush_module_storage(&mut self, name: &str) {
+        let ops = self.module_storage_mut(name).ops();
+        self.push_ops(ops);
+    }
+
+    /// Returns the storage that the given storage op refers to.
+    pub fn storage(&self, address: &str) -> Option<&TestStorage> {
+        match address.strip_prefix(MODULE_PREFIX) {
+            Some(name) => self.modules.get(name),
+            None => self.instances.get(address).map(|x| &x.storage)
+        }
+    }
+
+    /// Replaces all instances, balances and module storage. Must not be called during execution.
+    pub fn replace(
+        &mut self,
+        instances: HashMap<String, ContractInstance>,
+        bank: Bank,
+        modules: HashMap<String, TestStorage>
+    ) {
+        // Discard any changes left over by a transaction that panicked.
+        self.scopes.clear();
+
+        self.instances = instances;
+        self.bank = bank;
+        self.modules = modules;
+    }
+
     #[inline]
     pub fn commit(&mut self) {
         self.scopes.clear();
@@ -136,17 +258,35 @@ impl State {
                 Op::CreateInstance { address } => {
                     self.instances.remove(&address);
                 }
-                Op::StorageWrite { address, key, old } => {
+                Op::MigrateInstance { address, old_index } => {
+                    if let Some(instance) = self.instances.get_mut(&address) {
+                        instance.index = old_index;
+                    }
+                }
+                Op::UpdateAdmin { address, old } => {
                     if let Some(instance) = self.instances.get_mut(&address) {
+                        instance.admin = old;
+                    }
+                }
+                Op::StorageWrite { address, key, old } => {
+                    let storage = match address.strip_prefix(MODULE_PREFIX) {
+                        Some(name) => self.modules.get_mut(name),
+                        None => self.instances.get_mut(&address).map(|x| &mut x.storage)
+                    };
+
+                    if let Some(storage) = storage {
                         if let Some(old) = old {
-                            instance.storage.backing.insert(key, old);
+                            storage.backing.insert(key, old);
                         } else {
-                            instance.storage.backing.remove(&key);
+                            storage.backing.remove(&key);
                         }
                     }
                 }
-                Op::BankAddFunds { address, coin } => {
-                    self.bank.remove_funds(&address, coin).unwrap();
+                Op::BankMint { address, coin } => {
+                    self.bank.burn(&address, coin).unwrap();
+                }
+                Op::BankBurn { address, coin } => {
+                    self.bank.mint(&address, coin);
                 }
                 Op::BankRemoveFunds { address, coin } => {
                     self.bank.add_funds(&address, coin);
@@ -154,12 +294,20 @@ impl State {
                 Op::BankTransferFunds { from, to, coin } => {
                     self.bank.transfer(&to, &from, coin).unwrap();
                 }
+                #[cfg(feature = "ibc")]
+                Op::IbcSendPacket { channel_id, sequence } => {
+                    self.ibc.remove_packet(&channel_id, sequence);
+                }
+                #[cfg(feature = "ibc")]
+                Op::IbcCloseChannel { channel_id } => {
+                    self.ibc.reopen_channel(&channel_id);
+                }
             }
         }
     }
 
-    #[allow(dead_code)]
-    pub fn add_funds(
+    /// Adds the given funds to `address`, increasing the total supply.
+    pub fn mint(
         &mut self,
         address: impl Into<String>, 
         coins: Vec<Coin>
@@ -172,35 +320,63 @@ impl State {
         scope.0.reserve_exact(coins.len());
 
         for coin in coins {
-            self.bank.add_funds(&address, coin.clone());
+            self.bank.mint(&address, coin.clone());
 
-            scope.0.push(Op::BankAddFunds {
+            scope.0.push(Op::BankMint {
                 address: address.clone(),
                 coin
             });
         }
     }
 
+    /// Removes the given funds from `address`, decreasing the total supply.
+    pub fn burn(
+        &mut self,
+        address: impl Into<String>, 
+        coins: Vec<Coin>
+    ) -> EnsembleResult<()> {
+        self.debit(address.into(), coins, true)
+    }
+
+    /// Removes the given funds from `address` without changing the total
+    /// supply since they continue to exist elsewhere, i.e when they are bonded.
     #[allow(dead_code)]
     pub fn remove_funds(
         &mut self,
         address: impl Into<String>, 
         coins: Vec<Coin>
+    ) -> EnsembleResult<()> {
+        self.debit(address.into(), coins, false)
+    }
+
+    fn debit(
+        &mut self,
+        address: String,
+        coins: Vec<Coin>,
+        burn: bool
     ) -> EnsembleResult<()> {
         assert!(self.scopes.len() > 0);
 
-        let address: String = address.into();
         self.push_scope();
 
         let temp = self.scopes.last_mut().unwrap();
         temp.0.reserve_exact(coins.len());
 
         for coin in coins {
-            match self.bank.remove_funds(&address, coin.clone()) {
+            let result = if burn {
+                self.bank.burn(&address, coin.clone())
+            } else {
+                self.bank.remove_funds(&address, coin.clone())
+            };
+
+            match result {
                 Ok(()) => {
-                    temp.0.push(Op::BankRemoveFunds {
-                        address: address.clone(),
-                        coin
+                    let address = address.clone();
+
+                    temp.0.push(if burn {
+                        Op::BankBurn { address, coin }
+                    } else {
+                        Op::BankRemoveFunds { address, coin }
                     });
                 },
                 Err(err) => {
@@ -262,11 +438,51 @@ impl State {
         Ok(res)
     }
 
+    /// Queues a packet created by [`IbcState::create_packet`] for relaying.
+    #[cfg(feature = "ibc")]
+    pub fn send_packet(&mut self, packet: IbcPacket) {
+        let scope = self.current_scope_mut();
+        scope.0.push(Op::IbcSendPacket {
+            channel_id: packet.src.channel_id.clone(),
+            sequence: packet.sequence
+        });
+
+        self.ibc.push_packet(packet);
+    }
+
+    #[cfg(feature = "ibc")]
+    pub fn close_channel(&mut self, channel_id: &str) -> EnsembleResult<()> {
+        assert!(self.scopes.len() > 0);
+
+        if self.ibc.close_channel(channel_id)? {
+            let scope = self.current_scope_mut();
+            scope.0.push(Op::IbcCloseChannel {
+                channel_id: channel_id.to_string()
+            });
+        }
+
+        Ok(())
+    }
+
+    #[inline]
+    fn instance_mut(&mut self, address: &str) -> EnsembleResult<&mut ContractInstance> {
+        match self.instances.get_mut(address) {
+            Some(instance) => Ok(instance),
+            None => Err(EnsembleError::registry(RegistryError::NotFound(address.to_string())))
+        }
+    }
+
     fn push_ops(&mut self, ops: Vec<Op>) {
         let scope = self.current_scope_mut();
         scope.0.extend(ops);
     }
 
+    /// The operations that were performed since the last call to `push_scope`.
+    #[inline]
+    pub fn current_scope_ops(&self) -> &[Op] {
+        self.scopes.last().map(|x| x.0.as_slice()).unwrap_or_default()
+    }
+
     #[inline]
     fn current_scope_mut(&mut self) -> &mut Scope {
         assert!(self.scopes.len() > 0);
@@ -275,6 +491,11 @@ impl State {
     }
 }
 
+#[inline]
+pub(crate) fn module_address(name: &str) -> String {
+    format!("{}{}", MODULE_PREFIX, name)
+}
+
 #[cfg(test)]
 mod tests {
     use crate::cosmwasm_std::Storage;
@@ -367,11 +588,11 @@ mod tests {
     #[test]
     fn reverts_bank_add_remove_funds() {
         let mut state = State::new();
-        state.bank.add_funds(CONTRACTS[0], Coin::new(100, "uscrt"));
+        state.bank.mint(CONTRACTS[0], Coin::new(100, "uscrt"));
 
         state.push_scope();
 
-        state.add_funds(CONTRACTS[0], vec![Coin::new(100, "uscrt")]);
+        state.mint(CONTRACTS[0], vec![Coin::new(100, "uscrt")]);
         assert_eq!(state.scopes.last().unwrap().0.len(), 1);
 
         state.remove_funds(CONTRACTS[1], vec![Coin::new(100, "uscrt")]).unwrap_err();
@@ -379,10 +600,19 @@ mod tests {
 
         assert_eq!(check_balance(&state, CONTRACTS[1]), 0);
         assert_eq!(check_balance(&state, CONTRACTS[0]), 200);
+        assert_eq!(state.bank.supply("uscrt").u128(), 200);
+
+        state.burn(CONTRACTS[0], vec![Coin::new(50, "uscrt")]).unwrap();
+        state.remove_funds(CONTRACTS[0], vec![Coin::new(50, "uscrt")]).unwrap();
+        assert_eq!(state.scopes.last().unwrap().0.len(), 3);
+
+        assert_eq!(check_balance(&state, CONTRACTS[0]), 100);
+        assert_eq!(state.bank.supply("uscrt").u128(), 150);
 
         state.revert();
 
         assert_eq!(check_balance(&state, CONTRACTS[0]), 100);
+        assert_eq!(state.bank.supply("uscrt").u128(), 100);
     }
 
     #[test]
@@ -391,7 +621,7 @@ mod tests {
         state.bank.add_funds(CONTRACTS[0], Coin::new(100, "uscrt"));
 
         state.push_scope();
-        state.add_funds(CONTRACTS[0], vec![Coin::new(100, "uscrt")]);
+        state.mint(CONTRACTS[0], vec![Coin::new(100, "uscrt")]);
 
         assert_eq!(check_balance(&state, CONTRACTS[0]), 200);
 
@@ -426,6 +656,27 @@ mod tests {
         assert_eq!(check_balance(&state, CONTRACTS[0]), 100);
     }
 
+    #[test]
+    fn reverts_migrate_and_admin_updates() {
+        let mut state = setup_storage();
+
+        state.push_scope();
+        state.set_admin(CONTRACTS[0], Some("admin".into())).unwrap();
+        state.commit();
+
+        state.push_scope();
+        state.migrate_instance(CONTRACTS[0], 2).unwrap();
+        state.set_admin(CONTRACTS[0], None).unwrap();
+
+        assert_eq!(state.instance(CONTRACTS[0]).unwrap().index, 2);
+        assert_eq!(state.instance(CONTRACTS[0]).unwrap().admin, None);
+
+        state.revert();
+
+        assert_eq!(state.instance(CONTRACTS[0]).unwrap().index, 0);
+        assert_eq!(state.instance(CONTRACTS[0]).unwrap().admin.as_deref(), Some("admin"));
+    }
+
     fn check_balance(state: &State, address: &str) -> u128 {
         let mut balances = state.bank.query_balances(address, Some("uscrt".into()));
         assert_eq!(balances.len(), 1);
@@ -442,9 +693,9 @@ mod tests {
 
         state.push_scope();
 
-        state.create_contract_instance(CONTRACTS[0], 0).unwrap();
-        state.create_contract_instance(CONTRACTS[1], 1).unwrap();
-        state.create_contract_instance(CONTRACTS[2], 2).unwrap();
+        state.create_contract_instance(CONTRACTS[0], 0, "creator".into(), None, "label".into()).unwrap();
+        state.create_contract_instance(CONTRACTS[1], 1, "creator".into(), None, "label".into()).unwrap();
+        state.create_contract_instance(CONTRACTS[2], 2, "creator".into(), None, "label".into()).unwrap();
 
         state.commit();
 
@@ -0,0 +1,269 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ContractEnsemble, ContractHarness, MockEnv, AnyResult, Bech32Addresses, EnsembleError,
+    RegistryError
+};
+use fadroma::prelude::*;
+
+const PREFIX: &str = "secret";
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ExecuteMsg {
+    Send { recipient: String },
+    Instantiate { code_id: u64, code_hash: String, label: String }
+}
+
+struct Contract;
+
+impl ContractHarness for Contract {
+    fn instantiate(&self, _deps: DepsMut, _env: Env, _info: MessageInfo, _msg: Binary) -> AnyResult<Response> {
+        Ok(Response::default())
+    }
+
+    fn execute(&self, _deps: DepsMut, env: Env, _info: MessageInfo, msg: Binary) -> AnyResult<Response> {
+        let msg: CosmosMsg = match from_binary(&msg)? {
+            ExecuteMsg::Send { recipient } => BankMsg::Send {
+                to_address: recipient,
+                amount: vec![coin(100, "uscrt")]
+            }.into(),
+            ExecuteMsg::Instantiate { code_id, code_hash, label } => WasmMsg::Instantiate {
+                code_id,
+                code_hash,
+                msg: to_binary(&())?,
+                funds: vec![],
+                label,
+                admin: Some(env.contract.address.into_string())
+            }.into()
+        };
+
+        Ok(Response::default().add_message(msg))
+    }
+
+    fn query(&self, _deps: Deps, env: Env, _msg: Binary) -> AnyResult<Binary> {
+        Ok(to_binary(&env.contract.address)?)
+    }
+}
+
+fn init() -> (ContractEnsemble, ContractCode) {
+    let mut ensemble = ContractEnsemble::new();
+    ensemble.set_bech32_prefix(Some(PREFIX));
+
+    let contract = ensemble.register(Box::new(Contract));
+
+    (ensemble, contract)
+}
+
+#[test]
+fn canonicalizes_bech32_addresses() {
+    let addresses = Bech32Addresses::new(PREFIX);
+    let alice = addresses.account("alice");
+
+    assert!(alice.starts_with("secret1"));
+    assert_eq!(alice, addresses.account("alice"));
+    assert_ne!(alice, addresses.account("bob"));
+
+    let canonical = addresses.addr_canonicalize(&alice).unwrap();
+    assert_eq!(canonical.len(), Bech32Addresses::ADDRESS_LEN);
+    assert_eq!(addresses.addr_humanize(&canonical).unwrap(), alice);
+    assert_eq!(addresses.addr_validate(&alice).unwrap(), alice);
+
+    // Upper case is a valid encoding but not normalized.
+    addresses.addr_canonicalize(&alice.to_uppercase()).unwrap();
+    addresses.addr_validate(&alice.to_uppercase()).unwrap_err();
+
+    let cosmos = Bech32Addresses::new("cosmos").account("alice");
+    let err = addresses.addr_validate(&cosmos).unwrap_err();
+    assert!(err.to_string().contains("expected prefix \"secret\", got \"cosmos\""));
+
+    // Invalid checksum
+    let mut invalid = alice.clone();
+    invalid.pop();
+    invalid.push(if alice.ends_with('q') { 'p' } else { 'q' });
+    addresses.addr_validate(&invalid).unwrap_err();
+
+    addresses.addr_validate("alice").unwrap_err();
+}
+
+#[test]
+#[should_panic(expected = "is not a valid bech32 prefix")]
+fn rejects_invalid_prefix() {
+    Bech32Addresses::new("Secret");
+}
+
+#[test]
+fn derives_addresses_like_wasmd() {
+    // The address of the first contract on any wasmd chain, which is 32 bytes long.
+    let wasm = Bech32Addresses::new("wasm");
+    let expected = wasm.addr_canonicalize(
+        "wasm14hj2tavq8fpesdwxxcu44rty3hh90vhujrvcmstl4zr3txmfvw9s0phg4d"
+    ).unwrap();
+
+    let address = wasm.contract_address(1, 1);
+    assert_eq!(
+        wasm.addr_canonicalize(&address).unwrap().as_slice(),
+        &expected.as_slice()[..Bech32Addresses::ADDRESS_LEN]
+    );
+
+    // Test vector from cosmwasm-std
+    let checksum = [
+        0x13, 0xa1, 0xfc, 0x99, 0x4c, 0xc6, 0xd1, 0xc8, 0x1b, 0x74, 0x6e, 0xe0, 0xc0, 0xff, 0x6f, 0x90,
+        0x04, 0x38, 0x75, 0xe0, 0xbf, 0x1d, 0x9b, 0xe6, 0xb7, 0xd7, 0x79, 0xfc, 0x97, 0x8d, 0xc2, 0xa5
+    ];
+    let creator = wasm.addr_humanize(&Binary::from([
+        0x99, 0x99, 0x99, 0x99, 0x99, 0xaa, 0xaa, 0xaa, 0xaa, 0xaa,
+        0xbb, 0xbb, 0xbb, 0xbb, 0xbb, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc
+    ].as_slice()).into()).unwrap();
+
+    let address = wasm.predictable_address(&checksum, creator.as_str(), b"a").unwrap();
+    assert_eq!(
+        wasm.addr_canonicalize(&address).unwrap().as_slice(),
+        &[
+            0x5e, 0x86, 0x5d, 0x3e, 0x45, 0xad, 0x3e, 0x96, 0x1f, 0x77,
+            0xfd, 0x77, 0xd4, 0x65, 0x43, 0x41, 0x7c, 0xed, 0x44, 0xd9
+        ]
+    );
+
+    wasm.predictable_address(&checksum, creator.as_str(), b"").unwrap_err();
+    wasm.predictable_address(&checksum, "alice", b"a").unwrap_err();
+}
+
+#[test]
+fn instances_get_derived_addresses() {
+    let (mut ensemble, contract) = init();
+    let sender = ensemble.addresses().unwrap().account("sender");
+
+    let first = ensemble.instantiate(contract.id, &(), MockEnv::new(&sender, "first")).unwrap();
+    let address = first.instance.address.to_string();
+
+    assert_eq!(address, ensemble.addresses().unwrap().contract_address(0, 1));
+
+    let queried: Addr = ensemble.query(&address, &()).unwrap();
+    assert_eq!(queried, address);
+
+    // Instances created by contracts get the next address in the sequence.
+    let resp = ensemble.execute(&ExecuteMsg::Instantiate {
+        code_id: contract.id,
+        code_hash: contract.code_hash.clone(),
+        label: "second".into()
+    }, MockEnv::new(&sender, &address)).unwrap();
+
+    let second = resp.iter().find_map(|x| match x {
+        crate::ResponseVariants::Instantiate(resp) => Some(resp.instance.address.to_string()),
+        _ => None
+    }).unwrap();
+
+    assert_eq!(second, ensemble.addresses().unwrap().contract_address(0, 2));
+    assert_eq!(ensemble.contract_admin(&second).unwrap(), Some(address.as_str()));
+
+    // Labels keep working when bech32 addresses are disabled.
+    ensemble.set_bech32_prefix(None);
+    let third = ensemble.instantiate(contract.id, &(), MockEnv::new(&sender, "third")).unwrap();
+    assert_eq!(third.instance.address, "third");
+}
+
+#[test]
+fn instantiate2_uses_predictable_address() {
+    let (mut ensemble, contract) = init();
+    let sender = ensemble.addresses().unwrap().account("sender");
+
+    let expected = ensemble.addresses().unwrap().predictable_address(
+        &ensemble.code_checksum(contract.id).unwrap(),
+        &sender,
+        b"salt"
+    ).unwrap();
+
+    let resp = ensemble.instantiate2(
+        contract.id,
+        &(),
+        MockEnv::new(&sender, "label"),
+        b"salt".as_slice()
+    ).unwrap();
+
+    assert_eq!(resp.instance.address, expected);
+
+    let err = ensemble.instantiate2(
+        contract.id,
+        &(),
+        MockEnv::new(&sender, "label"),
+        b"salt".as_slice()
+    ).unwrap_err();
+
+    assert!(matches!(
+        err,
+        EnsembleError::ContractRegistry(RegistryError::DuplicateAddress(address)) if address == expected
+    ));
+
+    // The salt is not reused by the next instantiation.
+    let resp = ensemble.instantiate(contract.id, &(), MockEnv::new(&sender, "label")).unwrap();
+    assert_ne!(resp.instance.address, expected);
+}
+
+#[test]
+#[should_panic(expected = "instantiate2 requires bech32 addresses to be enabled")]
+fn instantiate2_requires_bech32() {
+    let mut ensemble = ContractEnsemble::new();
+    let contract = ensemble.register(Box::new(Contract));
+
+    let _ = ensemble.instantiate2(contract.id, &(), MockEnv::new("sender", "label"), b"salt".as_slice());
+}
+
+#[test]
+fn validates_recipients_and_admins() {
+    let (mut ensemble, contract) = init();
+
+    let addresses = ensemble.addresses().unwrap().clone();
+    let sender = addresses.account("sender");
+
+    let err = ensemble.instantiate_with_admin(
+        contract.id,
+        &(),
+        MockEnv::new(&sender, "contract"),
+        "admin"
+    ).unwrap_err();
+
+    assert!(matches!(err, EnsembleError::Std(_)));
+
+    let resp = ensemble.instantiate_with_admin(
+        contract.id,
+        &(),
+        MockEnv::new(&sender, "contract"),
+        &sender
+    ).unwrap();
+
+    let address = resp.instance.address.into_string();
+    ensemble.add_funds(&address, vec![coin(100, "uscrt")]);
+
+    let err = ensemble.execute(
+        &ExecuteMsg::Send { recipient: Bech32Addresses::new("cosmos").account("bob") },
+        MockEnv::new(&sender, &address)
+    ).unwrap_err();
+
+    assert!(err.to_string().contains("expected prefix"));
+
+    let bob = addresses.account("bob");
+    ensemble.execute(
+        &ExecuteMsg::Send { recipient: bob.clone() },
+        MockEnv::new(&sender, &address)
+    ).unwrap();
+
+    assert_eq!(ensemble.balances(&bob).unwrap()["uscrt"].u128(), 100);
+
+    ensemble.update_admin("bob", MockEnv::new(&sender, &address)).unwrap_err();
+    ensemble.update_admin(&bob, MockEnv::new(&sender, &address)).unwrap();
+}
+
+#[test]
+fn contract_info_returns_label() {
+    let (mut ensemble, contract) = init();
+    let sender = ensemble.addresses().unwrap().account("sender");
+
+    let resp = ensemble.instantiate(contract.id, &(), MockEnv::new(&sender, "my contract")).unwrap();
+    let address = resp.instance.address.into_string();
+
+    assert_eq!(ensemble.ctx.state.instance(&address).unwrap().label, "my contract");
+
+    let err = ensemble.query::<_, Binary>("my contract", &()).unwrap_err();
+    assert!(err.to_string().contains("not found"));
+}
@@ -0,0 +1,172 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{ContractEnsemble, ContractHarness, MockEnv, AnyResult, Snapshot, EventMatcher, TraceKind};
+use fadroma::prelude::*;
+
+const SENDER: &str = "sender";
+const BURNER: &str = "burner";
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ExecuteMsg {
+    /// Burns the given amount and then sends the same amount
+    /// to the sender, which fails if it was all burned.
+    Burn { amount: u64, denom: String, send_back: bool }
+}
+
+struct Burner;
+
+impl ContractHarness for Burner {
+    fn instantiate(&self, _deps: DepsMut, _env: Env, _info: MessageInfo, _msg: Binary) -> AnyResult<Response> {
+        Ok(Response::default())
+    }
+
+    fn execute(&self, _deps: DepsMut, _env: Env, info: MessageInfo, msg: Binary) -> AnyResult<Response> {
+        let ExecuteMsg::Burn { amount, denom, send_back } = from_binary(&msg)?;
+        let coins = vec![coin(amount.into(), denom)];
+
+        let mut resp = Response::default().add_message(BankMsg::Burn { amount: coins.clone() });
+
+        if send_back {
+            resp = resp.add_message(BankMsg::Send {
+                to_address: info.sender.into_string(),
+                amount: coins
+            });
+        }
+
+        Ok(resp)
+    }
+
+    #[cfg(feature = "supply")]
+    fn query(&self, deps: Deps, _env: Env, msg: Binary) -> AnyResult<Binary> {
+        let denom: String = from_binary(&msg)?;
+        let resp: SupplyResponse = deps.querier.query(&BankQuery::Supply { denom }.into())?;
+
+        Ok(to_binary(&resp.amount.amount)?)
+    }
+
+    #[cfg(not(feature = "supply"))]
+    fn query(&self, _deps: Deps, _env: Env, _msg: Binary) -> AnyResult<Binary> {
+        unimplemented!()
+    }
+}
+
+fn init() -> ContractEnsemble {
+    let mut ensemble = ContractEnsemble::new();
+    ensemble.set_tracing(true);
+
+    let burner = ensemble.register(Box::new(Burner));
+    ensemble.instantiate(burner.id, &(), MockEnv::new(SENDER, BURNER)).unwrap();
+
+    ensemble.add_funds(BURNER, vec![coin(100, "uscrt")]);
+    ensemble.add_funds(SENDER, vec![coin(50, "uscrt")]);
+
+    ensemble
+}
+
+fn burn(amount: u64, denom: &str, send_back: bool) -> ExecuteMsg {
+    ExecuteMsg::Burn { amount, denom: denom.into(), send_back }
+}
+
+#[test]
+fn burn_decreases_supply() {
+    let mut ensemble = init();
+    assert_eq!(ensemble.supply("uscrt").u128(), 150);
+
+    let resp = ensemble.execute(&burn(40, "uscrt", false), MockEnv::new(SENDER, BURNER)).unwrap();
+
+    assert_eq!(ensemble.balances(BURNER).unwrap()["uscrt"].u128(), 60);
+    assert_eq!(ensemble.supply("uscrt").u128(), 110);
+
+    assert!(resp.sent[0].is_burn());
+    resp.event_log().assert(EventMatcher::new("burn")
+        .attr("burner", BURNER)
+        .attr("amount", "40uscrt"));
+
+    let step = &ensemble.trace().unwrap().steps[1];
+    assert_eq!(step.kind, TraceKind::Burn);
+    assert_eq!(step.funds, vec![coin(40, "uscrt")]);
+}
+
+#[test]
+fn failed_burn_is_reverted() {
+    let mut ensemble = init();
+
+    let err = ensemble.execute(&burn(101, "uscrt", false), MockEnv::new(SENDER, BURNER)).unwrap_err();
+    assert!(err.to_string().contains("Insufficient balance"));
+
+    // The burn succeeds but sending the burned funds back fails.
+    ensemble.execute(&burn(100, "uscrt", true), MockEnv::new(SENDER, BURNER)).unwrap_err();
+
+    assert_eq!(ensemble.balances(BURNER).unwrap()["uscrt"].u128(), 100);
+    assert_eq!(ensemble.supply("uscrt").u128(), 150);
+
+    ensemble.execute(&burn(50, "uscrt", true), MockEnv::new(SENDER, BURNER)).unwrap();
+
+    assert_eq!(ensemble.balances(BURNER).unwrap()["uscrt"].u128(), 0);
+    assert_eq!(ensemble.balances(SENDER).unwrap()["uscrt"].u128(), 100);
+    assert_eq!(ensemble.supply("uscrt").u128(), 100);
+}
+
+#[test]
+fn token_factory_denoms() {
+    let mut ensemble = init();
+
+    let denom = ensemble.create_denom(BURNER, "ufoo").unwrap();
+    assert_eq!(denom, "factory/burner/ufoo");
+    assert_eq!(ensemble.denom_admin(&denom), Some(BURNER));
+    assert_eq!(ensemble.supply(&denom).u128(), 0);
+
+    // Subdenoms only need to be unique per creator.
+    assert_eq!(ensemble.create_denom(SENDER, "ufoo").unwrap(), "factory/sender/ufoo");
+
+    let err = ensemble.create_denom(BURNER, "ufoo").unwrap_err();
+    assert_eq!(err.to_string(), "Ensemble error - Bank: Denom factory/burner/ufoo already exists");
+
+    ensemble.create_denom(BURNER, "").unwrap_err();
+    ensemble.create_denom(BURNER, "u foo").unwrap_err();
+    ensemble.create_denom(BURNER, "u".repeat(45)).unwrap_err();
+
+    ensemble.mint(BURNER, vec![coin(30, &denom), coin(5, "uscrt")]).unwrap();
+    assert_eq!(ensemble.supply(&denom).u128(), 30);
+    assert_eq!(ensemble.supply("uscrt").u128(), 155);
+
+    let err = ensemble.mint(BURNER, vec![coin(5, "uscrt"), coin(30, "factory/burner/ubar")]).unwrap_err();
+    assert_eq!(err.to_string(), "Ensemble error - Bank: Denom factory/burner/ubar does not exist");
+    assert_eq!(ensemble.supply("uscrt").u128(), 155);
+
+    ensemble.execute(&burn(10, &denom, false), MockEnv::new(SENDER, BURNER)).unwrap();
+    assert_eq!(ensemble.supply(&denom).u128(), 20);
+}
+
+#[test]
+fn denoms_are_included_in_snapshots() {
+    let mut ensemble = init();
+    let snapshot = ensemble.snapshot();
+
+    let denom = ensemble.create_denom(BURNER, "ufoo").unwrap();
+    ensemble.mint(SENDER, vec![coin(10, &denom)]).unwrap();
+
+    let saved = Snapshot::from_bytes(&ensemble.snapshot().to_bytes()).unwrap();
+
+    ensemble.restore(&snapshot);
+    assert_eq!(ensemble.denom_admin(&denom), None);
+    assert_eq!(ensemble.supply(&denom).u128(), 0);
+
+    ensemble.restore(&saved);
+    assert_eq!(ensemble.denom_admin(&denom), Some(BURNER));
+    assert_eq!(ensemble.supply(&denom).u128(), 10);
+}
+
+#[test]
+#[cfg(feature = "supply")]
+fn contracts_can_query_supply() {
+    let mut ensemble = init();
+    ensemble.execute(&burn(25, "uscrt", false), MockEnv::new(SENDER, BURNER)).unwrap();
+
+    let supply: Uint128 = ensemble.query(BURNER, &"uscrt").unwrap();
+    assert_eq!(supply.u128(), 125);
+
+    let supply: Uint128 = ensemble.query(BURNER, &"uatom").unwrap();
+    assert!(supply.is_zero());
+}
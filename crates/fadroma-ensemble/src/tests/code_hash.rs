@@ -0,0 +1,91 @@
+use crate::{
+    ContractEnsemble, ContractHarness, MockEnv, AnyResult, EnsembleError, RegistryError,
+    code_hash_of
+};
+use fadroma::prelude::*;
+
+const SENDER: &str = "sender";
+const CONTRACT: &str = "contract";
+const CALLER: &str = "caller";
+const HASH: &str = "2a6a3de1b6dfcdc5ac8de5d8de6a4cf4e1b5a26b64b8a44cd6a3b0ee76d4f2bb";
+
+struct Contract;
+
+impl ContractHarness for Contract {
+    fn instantiate(&self, _deps: DepsMut, _env: Env, _info: MessageInfo, _msg: Binary) -> AnyResult<Response> {
+        Ok(Response::default())
+    }
+
+    /// Calls the contract at `CONTRACT` with the code hash given in the message, if any.
+    fn execute(&self, _deps: DepsMut, env: Env, _info: MessageInfo, msg: Binary) -> AnyResult<Response> {
+        let resp = match from_binary::<Option<String>>(&msg)? {
+            Some(code_hash) => Response::default().add_message(WasmMsg::Execute {
+                contract_addr: CONTRACT.into(),
+                code_hash,
+                msg: to_binary(&None::<String>)?,
+                funds: vec![]
+            }),
+            None => Response::default().set_data(to_binary(&env.contract.code_hash)?)
+        };
+
+        Ok(resp)
+    }
+
+    fn query(&self, _deps: Deps, _env: Env, _msg: Binary) -> AnyResult<Binary> {
+        Ok(to_binary(&())?)
+    }
+}
+
+#[test]
+fn code_hash_of_is_sha256_hex() {
+    assert_eq!(
+        code_hash_of("abc"),
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+    );
+}
+
+#[test]
+fn register_with_code_hashes() {
+    let mut ensemble = ContractEnsemble::new();
+
+    let placeholder = ensemble.register(Box::new(Contract));
+    let named = ensemble.register_named(Box::new(Contract), "token");
+    let explicit = ensemble.register_with_code_hash(Box::new(Contract), HASH.to_uppercase());
+
+    assert_eq!(placeholder.code_hash, "test_contract_0");
+    assert_eq!(named.code_hash, code_hash_of("token"));
+    assert_eq!(ensemble.register_named(Box::new(Contract), "token").code_hash, named.code_hash);
+    assert_eq!(explicit.code_hash, HASH);
+
+    assert_eq!(ensemble.code_by_hash(&named.code_hash).unwrap().id, named.id);
+    assert_eq!(ensemble.code_by_hash(HASH.to_uppercase()).unwrap().id, explicit.id);
+    assert_eq!(ensemble.code_by_hash("test_contract_0").unwrap().id, placeholder.id);
+    assert!(ensemble.code_by_hash(code_hash_of("other")).is_none());
+
+    ensemble.instantiate(explicit.id, &(), MockEnv::new(SENDER, CONTRACT)).unwrap();
+
+    let resp = ensemble.execute(&None::<String>, MockEnv::new(SENDER, CONTRACT)).unwrap();
+    let code_hash: String = from_binary(&resp.response.data.unwrap()).unwrap();
+    assert_eq!(code_hash, HASH);
+}
+
+#[test]
+fn code_hash_is_case_insensitive() {
+    let mut ensemble = ContractEnsemble::new();
+    let contract = ensemble.register_with_code_hash(Box::new(Contract), HASH);
+
+    ensemble.instantiate(contract.id, &(), MockEnv::new(SENDER, CONTRACT)).unwrap();
+    ensemble.instantiate(contract.id, &(), MockEnv::new(SENDER, CALLER)).unwrap();
+
+    ensemble.execute(&Some(HASH.to_uppercase()), MockEnv::new(SENDER, CALLER)).unwrap();
+
+    let err = ensemble.execute(&Some(code_hash_of("other")), MockEnv::new(SENDER, CALLER)).unwrap_err();
+    assert!(matches!(err, EnsembleError::ContractRegistry(RegistryError::InvalidCodeHash(_))));
+}
+
+#[test]
+#[should_panic(expected = "is not a valid code hash")]
+fn rejects_invalid_code_hash() {
+    let mut ensemble = ContractEnsemble::new();
+    ensemble.register_with_code_hash(Box::new(Contract), "test_contract_0");
+}
@@ -0,0 +1,195 @@
+use std::fs;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{ContractEnsemble, ContractHarness, MockEnv, AnyResult, CoverageReport, anyhow::bail};
+use fadroma::{prelude::*, schemars};
+
+const ADMIN: &str = "admin";
+const VAULT: &str = "vault";
+const PROXY: &str = "proxy";
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+enum ExecuteMsg {
+    Deposit { amount: u64 },
+    Withdraw { amount: u64 },
+    Pause
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+enum QueryMsg {
+    Balance {},
+    Config {}
+}
+
+struct Vault;
+
+impl ContractHarness for Vault {
+    fn instantiate(&self, _deps: DepsMut, _env: Env, _info: MessageInfo, _msg: Binary) -> AnyResult<Response> {
+        Ok(Response::default())
+    }
+
+    fn execute(&self, _deps: DepsMut, _env: Env, info: MessageInfo, msg: Binary) -> AnyResult<Response> {
+        match from_binary(&msg)? {
+            ExecuteMsg::Deposit { amount: 0 } => bail!("Nothing to deposit"),
+            ExecuteMsg::Withdraw { .. } if info.sender != ADMIN => bail!("Unauthorized"),
+            _ => Ok(Response::default())
+        }
+    }
+
+    fn query(&self, _deps: Deps, _env: Env, msg: Binary) -> AnyResult<Binary> {
+        match from_binary(&msg)? {
+            QueryMsg::Balance {} => Ok(to_binary(&100u64)?),
+            QueryMsg::Config {} => bail!("Not configured")
+        }
+    }
+}
+
+/// Forwards everything to the vault.
+struct Proxy;
+
+impl ContractHarness for Proxy {
+    fn instantiate(&self, _deps: DepsMut, _env: Env, _info: MessageInfo, _msg: Binary) -> AnyResult<Response> {
+        Ok(Response::default())
+    }
+
+    fn execute(&self, _deps: DepsMut, _env: Env, _info: MessageInfo, msg: Binary) -> AnyResult<Response> {
+        Ok(Response::default().add_message(WasmMsg::Execute {
+            contract_addr: VAULT.into(),
+            code_hash: "test_contract_0".into(),
+            msg,
+            funds: vec![]
+        }))
+    }
+
+    fn query(&self, deps: Deps, _env: Env, msg: Binary) -> AnyResult<Binary> {
+        let msg: QueryMsg = from_binary(&msg)?;
+        let result: u64 = deps.querier.query_wasm_smart("test_contract_0", VAULT, &msg)?;
+
+        Ok(to_binary(&result)?)
+    }
+}
+
+fn init() -> ContractEnsemble {
+    let mut ensemble = ContractEnsemble::new();
+
+    let vault = ensemble.register(Box::new(Vault));
+    let proxy = ensemble.register(Box::new(Proxy));
+
+    ensemble.track_coverage::<ExecuteMsg, QueryMsg>("vault", vault.id);
+
+    ensemble.instantiate(vault.id, &(), MockEnv::new(ADMIN, VAULT)).unwrap();
+    ensemble.instantiate(proxy.id, &(), MockEnv::new(ADMIN, PROXY)).unwrap();
+
+    ensemble
+}
+
+#[test]
+fn records_variants_and_errors() {
+    let mut ensemble = init();
+
+    let report = ensemble.coverage();
+    let vault = &report.contracts["vault"];
+
+    assert_eq!(vault.execute.keys().collect::<Vec<_>>(), ["deposit", "pause", "withdraw"]);
+    assert_eq!(vault.query.keys().collect::<Vec<_>>(), ["balance", "config"]);
+    assert_eq!(vault.percentage(), 0.);
+
+    ensemble.execute(&ExecuteMsg::Deposit { amount: 10 }, MockEnv::new(ADMIN, VAULT)).unwrap();
+    ensemble.execute(&ExecuteMsg::Deposit { amount: 0 }, MockEnv::new(ADMIN, VAULT)).unwrap_err();
+    ensemble.execute(&ExecuteMsg::Pause, MockEnv::new(ADMIN, VAULT)).unwrap();
+
+    // Calls by other contracts and in reverted transactions are recorded too.
+    ensemble.execute(&ExecuteMsg::Withdraw { amount: 10 }, MockEnv::new(ADMIN, PROXY)).unwrap_err();
+    ensemble.query::<_, u64>(PROXY, &QueryMsg::Balance {}).unwrap();
+    ensemble.query::<_, u64>(VAULT, &QueryMsg::Config {}).unwrap_err();
+
+    let report = ensemble.coverage();
+    let vault = &report.contracts["vault"];
+
+    assert_eq!(vault.execute["deposit"].calls, 2);
+    assert_eq!(vault.execute["deposit"].errors["Nothing to deposit"], 1);
+    assert_eq!(vault.execute["pause"].calls, 1);
+    assert!(vault.execute["pause"].errors.is_empty());
+    assert_eq!(vault.execute["withdraw"].errors["Unauthorized"], 1);
+    assert_eq!(vault.query["balance"].calls, 1);
+    assert_eq!(vault.query["config"].errors["Not configured"], 1);
+    assert_eq!(vault.percentage(), 100.);
+
+    // Only tracked contracts are included.
+    assert_eq!(report.contracts.len(), 1);
+}
+
+#[test]
+fn lists_uncovered_variants() {
+    let mut ensemble = init();
+
+    ensemble.execute(&ExecuteMsg::Deposit { amount: 10 }, MockEnv::new(ADMIN, VAULT)).unwrap();
+    ensemble.query::<_, u64>(VAULT, &QueryMsg::Balance {}).unwrap();
+
+    let report = ensemble.coverage();
+    let vault = &report.contracts["vault"];
+
+    assert_eq!(vault.uncovered_execute().collect::<Vec<_>>(), ["pause", "withdraw"]);
+    assert_eq!(vault.uncovered_query().collect::<Vec<_>>(), ["config"]);
+    assert_eq!(vault.percentage(), 40.);
+
+    assert_eq!(report.to_string(), "\
+vault (40.0%)
+  execute: 1/3 variants
+    [x] deposit   1 call(s)
+    [ ] pause     never called
+    [ ] withdraw  never called
+  query: 1/2 variants
+    [x] balance  1 call(s)
+    [ ] config   never called
+");
+}
+
+#[test]
+fn reports_are_merged() {
+    let mut first = init();
+    first.execute(&ExecuteMsg::Deposit { amount: 0 }, MockEnv::new(ADMIN, VAULT)).unwrap_err();
+
+    let mut second = init();
+    second.execute(&ExecuteMsg::Deposit { amount: 0 }, MockEnv::new(ADMIN, VAULT)).unwrap_err();
+    second.execute(&ExecuteMsg::Withdraw { amount: 1 }, MockEnv::new(ADMIN, VAULT)).unwrap();
+
+    let path = std::env::temp_dir().join(format!("fadroma-ensemble-coverage-{}.json", std::process::id()));
+    let _ = fs::remove_file(&path);
+
+    first.coverage().merge_into_file(&path).unwrap();
+    let report = second.coverage().merge_into_file(&path).unwrap();
+
+    assert_eq!(CoverageReport::load(&path).unwrap(), report);
+    fs::remove_file(&path).unwrap();
+
+    let vault = &report.contracts["vault"];
+
+    assert_eq!(vault.execute["deposit"].calls, 2);
+    assert_eq!(vault.execute["deposit"].errors["Nothing to deposit"], 2);
+    assert_eq!(vault.execute["withdraw"].calls, 1);
+    assert_eq!(vault.uncovered_execute().collect::<Vec<_>>(), ["pause"]);
+
+    let json = report.to_json();
+    assert!(json.contains("\"Nothing to deposit\": 2"));
+    assert_eq!(CoverageReport::from_json(&json).unwrap(), report);
+}
+
+#[test]
+fn invalid_report_is_an_error() {
+    let err = CoverageReport::from_json("{\"contracts\": []}").unwrap_err();
+
+    assert!(err.to_string().starts_with("Ensemble error - Coverage: Invalid report"));
+}
+
+#[test]
+#[should_panic(expected = "Contract with id 1 not found")]
+fn cannot_track_unregistered_code() {
+    let mut ensemble = ContractEnsemble::new();
+    ensemble.register(Box::new(Vault));
+
+    ensemble.track_coverage::<ExecuteMsg, QueryMsg>("vault", 1);
+}
@@ -0,0 +1,169 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{ContractEnsemble, ContractHarness, MockEnv, AnyResult, EventMatcher};
+use fadroma::prelude::*;
+
+const SENDER: &str = "sender";
+const ROUTER: &str = "router";
+const VAULT: &str = "vault";
+const RECIPIENT: &str = "recipient";
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ExecuteMsg {
+    Route { amount: u64 },
+    Deposit { amount: u64 }
+}
+
+struct Router;
+
+impl ContractHarness for Router {
+    fn instantiate(&self, _deps: DepsMut, _env: Env, _info: MessageInfo, _msg: Binary) -> AnyResult<Response> {
+        Ok(Response::default())
+    }
+
+    fn execute(&self, _deps: DepsMut, _env: Env, _info: MessageInfo, msg: Binary) -> AnyResult<Response> {
+        let ExecuteMsg::Route { amount } = from_binary(&msg)? else {
+            unreachable!()
+        };
+
+        let deposit = WasmMsg::Execute {
+            contract_addr: VAULT.into(),
+            code_hash: "test_contract_1".into(),
+            msg: to_binary(&ExecuteMsg::Deposit { amount })?,
+            funds: vec![coin(amount.into(), "uscrt")]
+        };
+
+        Ok(Response::default()
+            .add_attribute("action", "route")
+            .add_event(Event::new("routed").add_attribute("amount", amount.to_string()))
+            .add_submessage(SubMsg::reply_on_success(deposit, 1)))
+    }
+
+    fn query(&self, _deps: Deps, _env: Env, _msg: Binary) -> AnyResult<Binary> {
+        unimplemented!()
+    }
+
+    fn reply(&self, _deps: DepsMut, _env: Env, reply: Reply) -> AnyResult<Response> {
+        Ok(Response::default().add_event(Event::new("replied").add_attribute("id", reply.id.to_string())))
+    }
+}
+
+struct Vault;
+
+impl ContractHarness for Vault {
+    fn instantiate(&self, _deps: DepsMut, _env: Env, _info: MessageInfo, _msg: Binary) -> AnyResult<Response> {
+        Ok(Response::default())
+    }
+
+    fn execute(&self, _deps: DepsMut, _env: Env, _info: MessageInfo, msg: Binary) -> AnyResult<Response> {
+        let ExecuteMsg::Deposit { amount } = from_binary(&msg)? else {
+            unreachable!()
+        };
+
+        Ok(Response::default()
+            .add_attribute("action", "deposit")
+            .add_attribute("amount", amount.to_string())
+            .add_message(BankMsg::Send {
+                to_address: RECIPIENT.into(),
+                amount: vec![coin(1, "uscrt")]
+            }))
+    }
+
+    fn query(&self, _deps: Deps, _env: Env, _msg: Binary) -> AnyResult<Binary> {
+        unimplemented!()
+    }
+}
+
+fn route(amount: u64) -> (ContractEnsemble, crate::ExecuteResponse) {
+    let mut ensemble = ContractEnsemble::new();
+    ensemble.set_tracing(true);
+
+    let router = ensemble.register(Box::new(Router));
+    let vault = ensemble.register(Box::new(Vault));
+
+    ensemble.instantiate(router.id, &(), MockEnv::new(SENDER, ROUTER)).unwrap();
+    ensemble.instantiate(vault.id, &(), MockEnv::new(SENDER, VAULT)).unwrap();
+    ensemble.add_funds(ROUTER, vec![coin(1000, "uscrt")]);
+
+    let resp = ensemble.execute(
+        &ExecuteMsg::Route { amount },
+        MockEnv::new(SENDER, ROUTER)
+    ).unwrap();
+
+    (ensemble, resp)
+}
+
+#[test]
+fn event_log_follows_execution_order() {
+    let (ensemble, resp) = route(100);
+    let log = resp.event_log();
+
+    let traced: Vec<Event> = ensemble.trace().unwrap().steps.iter()
+        .flat_map(|x| x.events.clone())
+        .collect();
+
+    assert_eq!(*log, traced);
+
+    // Funds sent with a message come before its events.
+    assert_eq!(resp.sent[0].events()[0].ty, "coin_spent");
+    assert_eq!(resp.events.len(), 3);
+
+    log.assert_order([
+        EventMatcher::new("execute").contract(ROUTER),
+        EventMatcher::custom("routed").attr("amount", "100"),
+        EventMatcher::new("transfer").attr("recipient", VAULT),
+        EventMatcher::wasm().contract(VAULT).attr("action", "deposit"),
+        EventMatcher::new("transfer").attr("recipient", RECIPIENT),
+        EventMatcher::new("reply").contract(ROUTER),
+        EventMatcher::custom("replied").attr("id", "1")
+    ]);
+}
+
+#[test]
+fn matches_attributes() {
+    let (_, resp) = route(250);
+    let log = resp.event_log();
+
+    let event = log.assert(EventMatcher::wasm()
+        .contract(VAULT)
+        .attr_regex("amount", r"^\d+$")
+        .attr_with("amount", |x| x.parse::<u64>().unwrap() > 200)
+        .has_attr("action"));
+
+    assert_eq!(event.attributes.len(), 3);
+
+    log.assert_count(EventMatcher::new("transfer"), 2);
+    log.assert_count(EventMatcher::any().contract(ROUTER), 5);
+    log.assert_none(EventMatcher::wasm().contract(RECIPIENT));
+
+    assert!(log.find(&EventMatcher::wasm().attr("action", "withdraw")).is_none());
+    assert_eq!(log.filter(&EventMatcher::wasm()).count(), 2);
+}
+
+#[test]
+#[should_panic(expected = "amount: expected =~ /^\\d{4}$/, got \"100\"")]
+fn failed_assertion_shows_diff() {
+    let (_, resp) = route(100);
+
+    resp.event_log().assert(EventMatcher::wasm()
+        .contract(VAULT)
+        .attr_regex("amount", r"^\d{4}$"));
+}
+
+#[test]
+#[should_panic(expected = "No event after #12 matched matcher #1 (it matched #2, which comes before): wasm-routed")]
+fn failed_order_assertion() {
+    let (_, resp) = route(100);
+
+    resp.event_log().assert_order([
+        EventMatcher::custom("replied"),
+        EventMatcher::custom("routed")
+    ]);
+}
+
+#[test]
+#[should_panic(expected = "Invalid regular expression")]
+fn invalid_regex_panics() {
+    EventMatcher::wasm().attr_regex("amount", "(");
+}
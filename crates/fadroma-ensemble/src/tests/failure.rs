@@ -0,0 +1,222 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{ContractEnsemble, ContractHarness, MockEnv, AnyResult, Failure, anyhow::bail};
+use fadroma::prelude::*;
+
+const SENDER: &str = "sender";
+const CALLER: &str = "caller";
+const TARGET: &str = "target";
+const TREASURY: &str = "treasury";
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ExecuteMsg {
+    Pay { recipient: String, reply: bool },
+    Call { reply: bool }
+}
+
+struct Caller;
+
+impl ContractHarness for Caller {
+    fn instantiate(&self, _deps: DepsMut, _env: Env, _info: MessageInfo, _msg: Binary) -> AnyResult<Response> {
+        Ok(Response::default())
+    }
+
+    fn execute(&self, _deps: DepsMut, _env: Env, _info: MessageInfo, msg: Binary) -> AnyResult<Response> {
+        let (msg, reply): (CosmosMsg, bool) = match from_binary(&msg)? {
+            ExecuteMsg::Pay { recipient, reply } => (BankMsg::Send {
+                to_address: recipient,
+                amount: vec![coin(100, "uscrt")]
+            }.into(), reply),
+            ExecuteMsg::Call { reply } => (WasmMsg::Execute {
+                contract_addr: TARGET.into(),
+                code_hash: "test_contract_1".into(),
+                msg: to_binary(&())?,
+                funds: vec![coin(10, "uscrt")]
+            }.into(), reply)
+        };
+
+        let resp = if reply {
+            Response::default().add_submessage(SubMsg::reply_on_error(msg, 1))
+        } else {
+            Response::default().add_message(msg)
+        };
+
+        Ok(resp)
+    }
+
+    fn query(&self, deps: Deps, _env: Env, _msg: Binary) -> AnyResult<Binary> {
+        let value: u64 = deps.querier.query_wasm_smart("test_contract_1", TARGET, &())?;
+
+        Ok(to_binary(&value)?)
+    }
+
+    fn reply(&self, deps: DepsMut, _env: Env, reply: Reply) -> AnyResult<Response> {
+        match reply.result {
+            SubMsgResult::Err(err) => {
+                deps.storage.set(b"error", err.as_bytes());
+
+                Ok(Response::default())
+            },
+            SubMsgResult::Ok(_) => bail!("Expected an error")
+        }
+    }
+}
+
+struct Target;
+
+impl ContractHarness for Target {
+    fn instantiate(&self, _deps: DepsMut, _env: Env, _info: MessageInfo, _msg: Binary) -> AnyResult<Response> {
+        Ok(Response::default())
+    }
+
+    fn execute(&self, _deps: DepsMut, _env: Env, _info: MessageInfo, _msg: Binary) -> AnyResult<Response> {
+        Ok(Response::default())
+    }
+
+    fn query(&self, _deps: Deps, _env: Env, _msg: Binary) -> AnyResult<Binary> {
+        Ok(to_binary(&7u64)?)
+    }
+}
+
+fn init() -> ContractEnsemble {
+    let mut ensemble = ContractEnsemble::new();
+
+    let caller = ensemble.register(Box::new(Caller));
+    let target = ensemble.register(Box::new(Target));
+
+    ensemble.instantiate(caller.id, &(), MockEnv::new(SENDER, CALLER)).unwrap();
+    ensemble.instantiate(target.id, &(), MockEnv::new(SENDER, TARGET)).unwrap();
+    ensemble.add_funds(CALLER, vec![coin(1000, "uscrt")]);
+
+    ensemble
+}
+
+fn reply_error(ensemble: &ContractEnsemble) -> Option<String> {
+    ensemble.ctx.state.instance(CALLER).unwrap()
+        .storage.get(b"error")
+        .map(|x| String::from_utf8(x).unwrap())
+}
+
+fn caller_balance(ensemble: &ContractEnsemble) -> u128 {
+    ensemble.balances(CALLER).unwrap()["uscrt"].u128()
+}
+
+#[test]
+fn fails_bank_transfers() {
+    let mut ensemble = init();
+    let id = ensemble.inject_failure(Failure::bank_transfer_to(TREASURY));
+
+    let err = ensemble.execute(
+        &ExecuteMsg::Pay { recipient: TREASURY.into(), reply: false },
+        MockEnv::new(SENDER, CALLER)
+    ).unwrap_err();
+
+    assert_eq!(err.unwrap_contract_error().to_string(), "Injected failure: bank transfer to treasury");
+
+    ensemble.execute(
+        &ExecuteMsg::Pay { recipient: TREASURY.into(), reply: true },
+        MockEnv::new(SENDER, CALLER)
+    ).unwrap();
+
+    assert_eq!(reply_error(&ensemble).unwrap(), "Injected failure: bank transfer to treasury");
+    assert_eq!(caller_balance(&ensemble), 1000);
+
+    // Other recipients are not affected.
+    ensemble.execute(
+        &ExecuteMsg::Pay { recipient: "other".into(), reply: false },
+        MockEnv::new(SENDER, CALLER)
+    ).unwrap();
+
+    assert_eq!(caller_balance(&ensemble), 900);
+    assert_eq!(ensemble.failure_count(id), 2);
+
+    ensemble.remove_failure(id);
+    ensemble.execute(
+        &ExecuteMsg::Pay { recipient: TREASURY.into(), reply: false },
+        MockEnv::new(SENDER, CALLER)
+    ).unwrap();
+
+    assert_eq!(ensemble.balances(TREASURY).unwrap()["uscrt"].u128(), 100);
+}
+
+#[test]
+fn fails_funds_sent_with_messages() {
+    let mut ensemble = init();
+    ensemble.inject_failure(Failure::bank_transfer_to(TARGET).with_error("Target is frozen"));
+
+    let err = ensemble.execute(&ExecuteMsg::Call { reply: false }, MockEnv::new(SENDER, CALLER)).unwrap_err();
+    assert_eq!(err.unwrap_contract_error().to_string(), "Target is frozen");
+
+    ensemble.execute(&ExecuteMsg::Call { reply: true }, MockEnv::new(SENDER, CALLER)).unwrap();
+    assert_eq!(reply_error(&ensemble).unwrap(), "Target is frozen");
+    assert_eq!(caller_balance(&ensemble), 1000);
+
+    // Top level messages with funds as well.
+    ensemble.add_funds(SENDER, vec![coin(10, "uscrt")]);
+    ensemble.execute(&(), MockEnv::new(SENDER, TARGET).sent_funds(vec![coin(10, "uscrt")])).unwrap_err();
+}
+
+#[test]
+fn fails_nth_query() {
+    let mut ensemble = init();
+    let id = ensemble.inject_failure(Failure::nth_query(TARGET, 2));
+
+    // Queries from contracts and from the test are both counted.
+    assert_eq!(ensemble.query::<_, u64>(CALLER, &()).unwrap(), 7);
+
+    let err = ensemble.query::<_, u64>(TARGET, &()).unwrap_err();
+    assert_eq!(err.unwrap_contract_error().to_string(), "Injected failure: query #2 to target");
+
+    assert_eq!(ensemble.query::<_, u64>(CALLER, &()).unwrap(), 7);
+    assert_eq!(ensemble.failure_count(id), 1);
+
+    ensemble.remove_failure(id);
+    ensemble.inject_failure(Failure::query(TARGET));
+
+    let err = ensemble.query::<_, u64>(CALLER, &()).unwrap_err();
+    assert!(err.to_string().contains("Injected failure: query #1 to target"));
+
+    ensemble.query::<_, u64>(TARGET, &()).unwrap_err();
+    ensemble.query::<_, u64>(CALLER, &()).unwrap_err();
+}
+
+#[test]
+fn fails_matching_sub_messages() {
+    let mut ensemble = init();
+
+    let id = ensemble.inject_failure(Failure::sub_msg(|msg, sender|
+        sender == CALLER && matches!(msg, CosmosMsg::Wasm(WasmMsg::Execute { contract_addr, .. }) if contract_addr == TARGET)
+    ));
+
+    let err = ensemble.execute(&ExecuteMsg::Call { reply: false }, MockEnv::new(SENDER, CALLER)).unwrap_err();
+    assert_eq!(err.unwrap_contract_error().to_string(), "Injected failure: message sent by caller");
+
+    ensemble.execute(&ExecuteMsg::Call { reply: true }, MockEnv::new(SENDER, CALLER)).unwrap();
+    assert_eq!(reply_error(&ensemble).unwrap(), "Injected failure: message sent by caller");
+
+    // Messages sent by others are not affected.
+    ensemble.execute(&(), MockEnv::new(SENDER, TARGET)).unwrap();
+    ensemble.execute(
+        &ExecuteMsg::Pay { recipient: TREASURY.into(), reply: false },
+        MockEnv::new(SENDER, CALLER)
+    ).unwrap();
+
+    assert_eq!(ensemble.failure_count(id), 2);
+
+    // Top level messages are matched as well.
+    ensemble.remove_failure(id);
+    ensemble.inject_failure(Failure::sub_msg(|_, sender| sender == SENDER));
+
+    ensemble.execute(&(), MockEnv::new(SENDER, TARGET)).unwrap_err();
+}
+
+#[test]
+#[should_panic(expected = "The failure has already been removed")]
+fn cannot_remove_failure_twice() {
+    let mut ensemble = init();
+    let id = ensemble.inject_failure(Failure::query(TARGET));
+
+    ensemble.remove_failure(id);
+    ensemble.remove_failure(id);
+}
@@ -0,0 +1,239 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ContractEnsemble, ContractHarness, MockEnv, AnyResult, Fuzzer, FuzzStep, FuzzFailure,
+    anyhow::{bail, ensure},
+    proptest::{prelude::*, sample::select, test_runner::Config}
+};
+use fadroma::prelude::*;
+
+const ACTORS: [&str; 3] = ["alice", "bob", "carol"];
+const TOKEN: &str = "token";
+
+const SUPPLY_KEY: &[u8] = b"supply";
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "snake_case")]
+enum ExecuteMsg {
+    Transfer { recipient: String, amount: u64 },
+    Burn { amount: u64 },
+    Panic
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum QueryMsg {
+    Supply,
+    Balance { address: String }
+}
+
+/// Burning more than 100 tokens only reduces the supply when `buggy` is `true`.
+struct Token {
+    buggy: bool
+}
+
+impl ContractHarness for Token {
+    fn instantiate(&self, deps: DepsMut, _env: Env, info: MessageInfo, _msg: Binary) -> AnyResult<Response> {
+        set(deps.storage, &balance_key(info.sender.as_str()), 1000)?;
+        set(deps.storage, SUPPLY_KEY, 1000)?;
+
+        Ok(Response::default())
+    }
+
+    fn execute(&self, deps: DepsMut, _env: Env, info: MessageInfo, msg: Binary) -> AnyResult<Response> {
+        let sender = balance_key(info.sender.as_str());
+        let balance = get(deps.storage, &sender)?;
+
+        match from_binary(&msg)? {
+            ExecuteMsg::Transfer { recipient, amount } => {
+                ensure!(balance >= amount, "Insufficient balance");
+                set(deps.storage, &sender, balance - amount)?;
+
+                let recipient = balance_key(&recipient);
+                let recipient_balance = get(deps.storage, &recipient)?;
+                set(deps.storage, &recipient, recipient_balance + amount)?;
+            },
+            ExecuteMsg::Burn { amount } => {
+                ensure!(balance >= amount, "Insufficient balance");
+
+                if !self.buggy || amount <= 100 {
+                    set(deps.storage, &sender, balance - amount)?;
+                }
+
+                let supply = get(deps.storage, SUPPLY_KEY)?;
+                set(deps.storage, SUPPLY_KEY, supply - amount)?;
+            },
+            ExecuteMsg::Panic => panic!("Token panicked")
+        }
+
+        Ok(Response::default())
+    }
+
+    fn query(&self, deps: Deps, _env: Env, msg: Binary) -> AnyResult<Binary> {
+        let result = match from_binary(&msg)? {
+            QueryMsg::Supply => get(deps.storage, SUPPLY_KEY)?,
+            QueryMsg::Balance { address } => get(deps.storage, &balance_key(&address))?
+        };
+
+        Ok(to_binary(&result)?)
+    }
+}
+
+fn balance_key(address: &str) -> Vec<u8> {
+    format!("balance/{}", address).into_bytes()
+}
+
+fn get(storage: &dyn Storage, key: &[u8]) -> AnyResult<u64> {
+    match storage.get(key) {
+        Some(value) => Ok(from_slice(&value)?),
+        None => Ok(0)
+    }
+}
+
+fn set(storage: &mut dyn Storage, key: &[u8], value: u64) -> AnyResult<()> {
+    storage.set(key, &to_vec(&value)?);
+
+    Ok(())
+}
+
+fn supply_matches_balances(ensemble: &ContractEnsemble) -> AnyResult<()> {
+    let supply: u64 = ensemble.query(TOKEN, &QueryMsg::Supply)?;
+    let mut sum = 0;
+
+    for address in ACTORS {
+        let balance: u64 = ensemble.query(TOKEN, &QueryMsg::Balance { address: address.into() })?;
+        sum += balance;
+    }
+
+    if supply != sum {
+        bail!("supply is {} but the balances add up to {}", supply, sum);
+    }
+
+    Ok(())
+}
+
+fn token_msg() -> impl Strategy<Value = ExecuteMsg> {
+    prop_oneof![
+        (select(&ACTORS[..]), 0..500u64).prop_map(|(recipient, amount)| ExecuteMsg::Transfer {
+            recipient: recipient.into(),
+            amount
+        }),
+        (0..200u64).prop_map(|amount| ExecuteMsg::Burn { amount })
+    ]
+}
+
+fn fuzzer(buggy: bool) -> Fuzzer {
+    let mut ensemble = ContractEnsemble::new();
+    let token = ensemble.register(Box::new(Token { buggy }));
+    ensemble.instantiate(token.id, &(), MockEnv::new(ACTORS[0], TOKEN)).unwrap();
+
+    Fuzzer::new(ensemble)
+        .actors(ACTORS)
+        .message(TOKEN, token_msg())
+        .invariant("supply", supply_matches_balances)
+        .config(Config {
+            cases: 64,
+            failure_persistence: None,
+            ..Config::default()
+        })
+}
+
+#[test]
+fn passes_when_invariants_hold() {
+    let mut fuzzer = fuzzer(false);
+
+    assert!(fuzzer.run().is_ok());
+}
+
+#[test]
+fn finds_failing_sequence() {
+    let mut fuzzer = fuzzer(true);
+    let failure = fuzzer.run().unwrap_err();
+
+    assert!(failure.reason.starts_with("Invariant \"supply\" violated"));
+
+    // Proptest doesn't guarantee the smallest possible amount when there are multiple actors.
+    let last = failure.steps.last().unwrap();
+    assert!(matches!(from_binary(&last.msg).unwrap(), ExecuteMsg::Burn { amount } if amount > 100));
+    assert_eq!(last.error, None);
+
+    // The ensemble is left in its initial state.
+    let balance: u64 = fuzzer.ensemble().query(TOKEN, &QueryMsg::Balance { address: ACTORS[0].into() }).unwrap();
+    assert_eq!(balance, 1000);
+
+    assert!(fuzzer.replay(failure.steps).is_err());
+}
+
+#[test]
+fn shrinks_failing_sequence() {
+    let mut ensemble = ContractEnsemble::new();
+    let token = ensemble.register(Box::new(Token { buggy: true }));
+    ensemble.instantiate(token.id, &(), MockEnv::new(ACTORS[0], TOKEN)).unwrap();
+
+    let mut fuzzer = Fuzzer::new(ensemble)
+        .actors([ACTORS[0]])
+        .message(TOKEN, (0..200u64).prop_map(|amount| ExecuteMsg::Burn { amount }))
+        .invariant("supply", supply_matches_balances);
+
+    let failure = fuzzer.run().unwrap_err();
+
+    assert_eq!(failure, FuzzFailure {
+        reason: "Invariant \"supply\" violated: supply is 899 but the balances add up to 1000 after step 1".into(),
+        steps: vec![FuzzStep {
+            sender: ACTORS[0].into(),
+            contract: TOKEN.into(),
+            msg: to_binary(&ExecuteMsg::Burn { amount: 101 }).unwrap(),
+            funds: vec![],
+            error: None
+        }]
+    });
+}
+
+#[test]
+fn reports_panics() {
+    let mut fuzzer = fuzzer(false)
+        .message(TOKEN, Just(ExecuteMsg::Panic));
+
+    let failure = fuzzer.run().unwrap_err();
+
+    assert!(failure.reason.contains("Token panicked"));
+    assert_eq!(failure.steps.len(), 1);
+    assert_eq!(failure.steps[0].msg, to_binary(&ExecuteMsg::Panic).unwrap());
+}
+
+#[test]
+fn replays_steps() {
+    let mut fuzzer = fuzzer(true);
+
+    let step = |amount| FuzzStep {
+        sender: ACTORS[0].into(),
+        contract: TOKEN.into(),
+        msg: to_binary(&ExecuteMsg::Burn { amount }).unwrap(),
+        funds: vec![],
+        error: None
+    };
+
+    let steps = fuzzer.replay(vec![step(2000), step(100)]).unwrap();
+    assert_eq!(steps[0].error.as_deref(), Some("Insufficient balance"));
+    assert_eq!(steps[1].error, None);
+
+    let failure = fuzzer.replay(vec![step(2000), step(150)]).unwrap_err();
+    assert_eq!(
+        failure.to_string(),
+        "Invariant \"supply\" violated: supply is 850 but the balances add up to 1000 after step 2\n\
+        Minimal reproduction (2 steps):\n  \
+        1. alice -> token: {\"burn\":{\"amount\":2000}}\n     \
+        error: Insufficient balance\n  \
+        2. alice -> token: {\"burn\":{\"amount\":150}}\n"
+    );
+
+    // Replaying doesn't change the state.
+    let supply: u64 = fuzzer.ensemble().query(TOKEN, &QueryMsg::Supply).unwrap();
+    assert_eq!(supply, 1000);
+}
+
+#[test]
+#[should_panic(expected = "Minimal reproduction")]
+fn check_panics_with_reproduction() {
+    fuzzer(true).check();
+}
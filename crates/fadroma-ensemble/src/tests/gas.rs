@@ -0,0 +1,237 @@
+use std::cell::Cell;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ContractEnsemble, ContractHarness, MockEnv, AnyResult,
+    EnsembleError, GasCosts, GasReport, response::ResponseVariants
+};
+use fadroma::prelude::*;
+
+const SENDER: &str = "sender";
+const A_ADDR: &str = "a";
+const B_ADDR: &str = "b";
+
+const KEY: &[u8] = b"key";
+
+thread_local! {
+    /// The number of writes that `WriteMany` completed.
+    static WRITES: Cell<u8> = const { Cell::new(0) };
+}
+
+struct Contract;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ExecuteMsg {
+    Write(Vec<u8>),
+    WriteMany(u8),
+    Read,
+    Query(String),
+    RunMsg(SubMsg)
+}
+
+impl ContractHarness for Contract {
+    fn instantiate(&self, _deps: DepsMut, _env: Env, _info: MessageInfo, _msg: Binary) -> AnyResult<Response> {
+        Ok(Response::default())
+    }
+
+    fn execute(&self, deps: DepsMut, _env: Env, _info: MessageInfo, msg: Binary) -> AnyResult<Response> {
+        match from_binary(&msg)? {
+            ExecuteMsg::Write(value) => deps.storage.set(KEY, &value),
+            ExecuteMsg::WriteMany(count) => for i in 0..count {
+                deps.storage.set(&[i], &[i]);
+                WRITES.with(|x| x.set(x.get() + 1));
+            },
+            ExecuteMsg::Read => {
+                deps.storage.get(KEY);
+            },
+            ExecuteMsg::Query(address) => {
+                let _: Binary = deps.querier.query_wasm_smart("test_contract_0", address, &())?;
+            },
+            ExecuteMsg::RunMsg(msg) => return Ok(Response::default().add_submessage(msg))
+        }
+
+        Ok(Response::default())
+    }
+
+    fn query(&self, deps: Deps, _env: Env, _msg: Binary) -> AnyResult<Binary> {
+        let value = deps.storage.get(KEY).unwrap_or_default();
+
+        Ok(to_binary(&Binary::from(value))?)
+    }
+}
+
+fn costs() -> GasCosts {
+    GasCosts {
+        storage_read: 10,
+        storage_read_per_byte: 1,
+        storage_write: 100,
+        storage_write_per_byte: 2,
+        query: 1000,
+        sub_msg: 10000,
+        ..GasCosts::free()
+    }
+}
+
+fn init(costs: Option<GasCosts>) -> ContractEnsemble {
+    let mut ensemble = ContractEnsemble::new();
+    ensemble.set_gas_costs(costs);
+
+    let contract = ensemble.register(Box::new(Contract));
+
+    ensemble.instantiate(contract.id, &(), MockEnv::new(SENDER, A_ADDR)).unwrap();
+    ensemble.instantiate(contract.id, &(), MockEnv::new(SENDER, B_ADDR)).unwrap();
+
+    ensemble
+}
+
+fn write(value: &[u8]) -> ExecuteMsg {
+    ExecuteMsg::Write(value.to_vec())
+}
+
+#[test]
+fn gas_is_not_metered_by_default() {
+    let mut ensemble = init(None);
+
+    let resp = ensemble.execute(&write(b"value"), MockEnv::new(SENDER, A_ADDR)).unwrap();
+    assert_eq!(resp.gas, GasReport::default());
+    assert_eq!(resp.gas_used(), 0);
+}
+
+#[test]
+fn charges_storage_access() {
+    let mut ensemble = init(Some(costs()));
+
+    let resp = ensemble.execute(&write(b"value"), MockEnv::new(SENDER, A_ADDR)).unwrap();
+    assert_eq!(resp.gas, GasReport {
+        storage: 100 + 2 * 8,
+        api: 0,
+        queries: 0,
+        sub_msg: 10000
+    });
+
+    let resp = ensemble.execute(&ExecuteMsg::Read, MockEnv::new(SENDER, A_ADDR)).unwrap();
+    assert_eq!(resp.gas.storage, 10 + 8);
+    assert_eq!(resp.gas.total(), 10 + 8 + 10000);
+}
+
+#[test]
+fn queries_are_charged_to_the_caller() {
+    let mut ensemble = init(Some(costs()));
+    ensemble.execute(&write(b"value"), MockEnv::new(SENDER, B_ADDR)).unwrap();
+
+    let resp = ensemble.execute(
+        &ExecuteMsg::Query(B_ADDR.into()),
+        MockEnv::new(SENDER, A_ADDR)
+    ).unwrap();
+
+    assert_eq!(resp.gas.queries, 1000);
+    assert_eq!(resp.gas.storage, 10 + 8);
+}
+
+#[test]
+fn each_message_has_its_own_report() {
+    let mut ensemble = init(Some(costs()));
+
+    let msg = SubMsg::new(WasmMsg::Execute {
+        contract_addr: B_ADDR.into(),
+        code_hash: "test_contract_0".into(),
+        msg: to_binary(&write(b"value")).unwrap(),
+        funds: vec![]
+    });
+
+    let resp = ensemble.execute(&ExecuteMsg::RunMsg(msg), MockEnv::new(SENDER, A_ADDR)).unwrap();
+    assert_eq!(resp.gas.total(), 10000);
+
+    let ResponseVariants::Execute(child) = resp.iter().next().unwrap() else {
+        panic!("Expecting ResponseVariants::Execute");
+    };
+    assert_eq!(child.gas.total(), 10000 + 100 + 2 * 8);
+
+    assert_eq!(resp.gas_used(), 2 * 10000 + 100 + 2 * 8);
+}
+
+#[test]
+fn exceeding_the_limit_reverts_the_transaction() {
+    let mut ensemble = init(Some(costs()));
+    ensemble.set_gas_limit(Some(10100));
+
+    // 10000 + 100 + 2 * (3 + 2)
+    let err = ensemble.execute(&write(b"va"), MockEnv::new(SENDER, A_ADDR)).unwrap_err();
+    assert!(matches!(err, EnsembleError::OutOfGas { limit: 10100, used: 10110 }));
+
+    ensemble.contract_storage(A_ADDR, |storage| {
+        assert!(storage.get(KEY).is_none());
+    }).unwrap();
+
+    // Can't be handled by a reply either.
+    let msg = SubMsg::reply_on_error(WasmMsg::Execute {
+        contract_addr: B_ADDR.into(),
+        code_hash: "test_contract_0".into(),
+        msg: to_binary(&ExecuteMsg::Read).unwrap(),
+        funds: vec![]
+    }, 1);
+
+    let err = ensemble.execute(&ExecuteMsg::RunMsg(msg), MockEnv::new(SENDER, A_ADDR)).unwrap_err();
+    assert!(matches!(err, EnsembleError::OutOfGas { .. }));
+
+    ensemble.set_gas_limit(None);
+    ensemble.execute(&write(b"va"), MockEnv::new(SENDER, A_ADDR)).unwrap();
+}
+
+#[test]
+fn running_out_of_gas_aborts_the_contract_immediately() {
+    let mut ensemble = init(Some(costs()));
+
+    // Enough for dispatching the message and two writes of 100 + 2 * 2.
+    ensemble.set_gas_limit(Some(10000 + 2 * 104));
+
+    let err = ensemble.execute(&ExecuteMsg::WriteMany(5), MockEnv::new(SENDER, A_ADDR)).unwrap_err();
+    assert!(matches!(err, EnsembleError::OutOfGas { limit: 10208, used: 10312 }));
+
+    // The third write ran out of gas and the contract didn't continue after it.
+    assert_eq!(WRITES.with(|x| x.get()), 2);
+
+    ensemble.contract_storage(A_ADDR, |storage| {
+        assert!(storage.get(&[0]).is_none());
+    }).unwrap();
+
+    // Queries outside of transactions aren't limited.
+    ensemble.set_gas_limit(Some(0));
+    let _: Binary = ensemble.query(A_ADDR, &()).unwrap();
+}
+
+#[test]
+fn charges_address_validation() {
+    let mut ensemble = init(Some(GasCosts {
+        addr_canonicalize: 4,
+        addr_humanize: 5,
+        ..GasCosts::free()
+    }));
+
+    let contract = ensemble.register(Box::new(Contract));
+
+    let resp = ensemble.instantiate(contract.id, &(), MockEnv::new(SENDER, "c")).unwrap();
+    assert_eq!(resp.gas.api, 0);
+
+    let resp = ensemble.instantiate_with_admin(contract.id, &(), MockEnv::new(SENDER, "d"), SENDER).unwrap();
+    assert_eq!(resp.gas.api, 4 + 5);
+    assert_eq!(resp.gas.total(), 4 + 5);
+
+    // Sending funds validates the recipient.
+    ensemble.set_gas_limit(Some(8));
+    ensemble.add_funds(A_ADDR, vec![coin(100, "uscrt")]);
+
+    let msg = SubMsg::new(BankMsg::Send {
+        to_address: B_ADDR.into(),
+        amount: vec![coin(100, "uscrt")]
+    });
+
+    let err = ensemble.execute(&ExecuteMsg::RunMsg(msg.clone()), MockEnv::new(SENDER, A_ADDR)).unwrap_err();
+    assert!(matches!(err, EnsembleError::OutOfGas { limit: 8, used: 9 }));
+
+    ensemble.set_gas_limit(Some(9));
+    ensemble.execute(&ExecuteMsg::RunMsg(msg), MockEnv::new(SENDER, A_ADDR)).unwrap();
+    assert_eq!(ensemble.balances(B_ADDR).unwrap()["uscrt"].u128(), 100);
+}
@@ -0,0 +1,487 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ContractEnsemble, ContractHarness, MockEnv, AnyResult, EnsembleError,
+    Relayer, PacketOutcome, TRANSFER_PORT, ICS20_VERSION,
+    contract_port, escrow_address, ibc_denom, anyhow::bail
+};
+use fadroma::prelude::*;
+
+const SENDER: &str = "sender";
+const PINGER: &str = "pinger";
+const VERSION: &str = "ping-1";
+
+const STATS_KEY: &[u8] = b"stats";
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ExecuteMsg {
+    Send { channel_id: String, data: String, timeout_height: Option<u64> },
+    SendAndFail { channel_id: String },
+    Transfer { channel_id: String, to_address: String, amount: Coin, timeout_height: Option<u64> },
+    Close { channel_id: String },
+    Fail
+}
+
+#[derive(Serialize, Deserialize, Default, PartialEq, Debug)]
+#[serde(default)]
+struct Stats {
+    channel: Option<String>,
+    received: Vec<String>,
+    acks: Vec<Binary>,
+    timeouts: u32,
+    closed: bool
+}
+
+struct Pinger;
+
+impl ContractHarness for Pinger {
+    fn instantiate(&self, _deps: DepsMut, _env: Env, _info: MessageInfo, _msg: Binary) -> AnyResult<Response> {
+        Ok(Response::default())
+    }
+
+    fn execute(&self, _deps: DepsMut, env: Env, _info: MessageInfo, msg: Binary) -> AnyResult<Response> {
+        let msg = match from_binary(&msg)? {
+            ExecuteMsg::Send { channel_id, data, timeout_height } => IbcMsg::SendPacket {
+                channel_id,
+                data: Binary::from(data.as_bytes()),
+                timeout: timeout(&env, timeout_height)
+            },
+            ExecuteMsg::SendAndFail { channel_id } => {
+                let fail = WasmMsg::Execute {
+                    contract_addr: env.contract.address.to_string(),
+                    code_hash: env.contract.code_hash.clone(),
+                    msg: to_binary(&ExecuteMsg::Fail)?,
+                    funds: vec![]
+                };
+
+                return Ok(Response::new()
+                    .add_message(IbcMsg::SendPacket {
+                        channel_id,
+                        data: Binary::from(b"ping"),
+                        timeout: timeout(&env, None)
+                    })
+                    .add_message(fail));
+            },
+            ExecuteMsg::Transfer { channel_id, to_address, amount, timeout_height } => IbcMsg::Transfer {
+                channel_id,
+                to_address,
+                amount,
+                timeout: timeout(&env, timeout_height),
+                memo: String::new()
+            },
+            ExecuteMsg::Close { channel_id } => IbcMsg::CloseChannel { channel_id },
+            ExecuteMsg::Fail => bail!("Failed")
+        };
+
+        Ok(Response::new().add_message(msg))
+    }
+
+    fn query(&self, deps: Deps, _env: Env, _msg: Binary) -> AnyResult<Binary> {
+        let stats = deps.storage.get(STATS_KEY).unwrap_or_else(|| b"{}".to_vec());
+
+        Ok(Binary(stats))
+    }
+
+    fn ibc_channel_open(
+        &self,
+        _deps: DepsMut,
+        _env: Env,
+        msg: IbcChannelOpenMsg
+    ) -> AnyResult<Option<Ibc3ChannelOpenResponse>> {
+        if msg.channel().version != VERSION {
+            bail!("Unsupported version: {}", msg.channel().version);
+        }
+
+        Ok(None)
+    }
+
+    fn ibc_channel_connect(&self, deps: DepsMut, _env: Env, msg: IbcChannelConnectMsg) -> AnyResult<IbcBasicResponse> {
+        update(deps, |stats| stats.channel = Some(msg.channel().endpoint.channel_id.clone()))?;
+
+        Ok(IbcBasicResponse::new())
+    }
+
+    fn ibc_channel_close(&self, deps: DepsMut, _env: Env, _msg: IbcChannelCloseMsg) -> AnyResult<IbcBasicResponse> {
+        update(deps, |stats| stats.closed = true)?;
+
+        Ok(IbcBasicResponse::new())
+    }
+
+    fn ibc_packet_receive(&self, deps: DepsMut, _env: Env, msg: IbcPacketReceiveMsg) -> AnyResult<IbcReceiveResponse> {
+        let data = String::from_utf8(msg.packet.data.0)?;
+        update(deps, |stats| stats.received.push(data.clone()))?;
+
+        if data == "fail" {
+            bail!("Received fail");
+        }
+
+        Ok(IbcReceiveResponse::new()
+            .set_ack(Binary::from(b"pong"))
+            .add_attribute("received", data))
+    }
+
+    fn ibc_packet_ack(&self, deps: DepsMut, _env: Env, msg: IbcPacketAckMsg) -> AnyResult<IbcBasicResponse> {
+        update(deps, |stats| stats.acks.push(msg.acknowledgement.data))?;
+
+        Ok(IbcBasicResponse::new())
+    }
+
+    fn ibc_packet_timeout(&self, deps: DepsMut, _env: Env, _msg: IbcPacketTimeoutMsg) -> AnyResult<IbcBasicResponse> {
+        update(deps, |stats| stats.timeouts += 1)?;
+
+        Ok(IbcBasicResponse::new())
+    }
+}
+
+fn timeout(env: &Env, height: Option<u64>) -> IbcTimeout {
+    match height {
+        Some(height) => IbcTimeout::with_block(IbcTimeoutBlock { revision: 0, height }),
+        None => IbcTimeout::with_timestamp(env.block.time.plus_seconds(3600))
+    }
+}
+
+fn update(deps: DepsMut, f: impl FnOnce(&mut Stats)) -> StdResult<()> {
+    let mut stats: Stats = match deps.storage.get(STATS_KEY) {
+        Some(bytes) => from_slice(&bytes)?,
+        None => Stats::default()
+    };
+    f(&mut stats);
+
+    deps.storage.set(STATS_KEY, &to_vec(&stats)?);
+
+    Ok(())
+}
+
+fn init() -> (ContractEnsemble, ContractEnsemble) {
+    let mut a = ContractEnsemble::new();
+    a.set_chain_id("chain-a");
+
+    let mut b = ContractEnsemble::new();
+    b.set_chain_id("chain-b");
+
+    for ensemble in [&mut a, &mut b] {
+        let pinger = ensemble.register(Box::new(Pinger));
+        ensemble.instantiate(pinger.id, &(), MockEnv::new(SENDER, PINGER)).unwrap();
+    }
+
+    (a, b)
+}
+
+fn open(relayer: &Relayer, a: &mut ContractEnsemble, b: &mut ContractEnsemble) -> IbcChannel {
+    relayer.open_channel(
+        a,
+        contract_port(PINGER),
+        b,
+        contract_port(PINGER),
+        IbcOrder::Unordered,
+        VERSION
+    ).unwrap()
+}
+
+fn send(ensemble: &mut ContractEnsemble, msg: ExecuteMsg) -> Result<(), EnsembleError> {
+    ensemble.execute(&msg, MockEnv::new(SENDER, PINGER)).map(|_| ())
+}
+
+fn stats(ensemble: &ContractEnsemble) -> Stats {
+    ensemble.query(PINGER, &()).unwrap()
+}
+
+fn balance(ensemble: &ContractEnsemble, address: &str, denom: &str) -> u128 {
+    ensemble.balances(address)
+        .and_then(|x| x.get(denom))
+        .map(|x| x.u128())
+        .unwrap_or_default()
+}
+
+#[test]
+fn opens_channel() {
+    let (mut a, mut b) = init();
+    let relayer = Relayer::new("relayer");
+
+    let channel = open(&relayer, &mut a, &mut b);
+
+    assert_eq!(channel.endpoint.port_id, contract_port(PINGER));
+    assert_eq!(channel.endpoint.channel_id, "channel-0");
+    assert_eq!(channel.counterparty_endpoint.channel_id, "channel-0");
+    assert_eq!(channel.version, VERSION);
+    assert_eq!(channel.connection_id, "connection-0");
+
+    assert_eq!(a.ibc_channels(), vec![channel]);
+    assert_eq!(b.ibc_channels().len(), 1);
+    assert_eq!(stats(&a).channel.as_deref(), Some("channel-0"));
+    assert_eq!(stats(&b).channel.as_deref(), Some("channel-0"));
+
+    let err = relayer.open_channel(
+        &mut a,
+        contract_port(PINGER),
+        &mut b,
+        contract_port(PINGER),
+        IbcOrder::Unordered,
+        "ping-2"
+    ).unwrap_err();
+
+    assert_eq!(err.unwrap_contract_error().to_string(), "Unsupported version: ping-2");
+    assert_eq!(a.ibc_channels().len(), 1);
+
+    let err = relayer.open_channel(
+        &mut a,
+        contract_port("unknown"),
+        &mut b,
+        contract_port(PINGER),
+        IbcOrder::Unordered,
+        VERSION
+    ).unwrap_err();
+
+    assert_eq!(err.to_string(), "Ensemble error - IBC: Port wasm.unknown is not bound");
+
+    b.set_chain_id("chain-a");
+
+    let err = relayer.open_channel(
+        &mut a,
+        contract_port(PINGER),
+        &mut b,
+        contract_port(PINGER),
+        IbcOrder::Unordered,
+        VERSION
+    ).unwrap_err();
+
+    assert_eq!(err.to_string(), "Ensemble error - IBC: Both ensembles have the same chain id: chain-a");
+}
+
+#[test]
+fn relays_packets_and_acks() {
+    let (mut a, mut b) = init();
+    let relayer = Relayer::new("relayer");
+    let channel = open(&relayer, &mut a, &mut b);
+
+    let channel_id = channel.endpoint.channel_id;
+
+    send(&mut a, ExecuteMsg::Send { channel_id: channel_id.clone(), data: "ping".into(), timeout_height: None }).unwrap();
+    send(&mut b, ExecuteMsg::Send { channel_id: channel_id.clone(), data: "pong".into(), timeout_height: None }).unwrap();
+
+    assert_eq!(a.pending_packets().len(), 1);
+    assert_eq!(a.pending_packets()[0].sequence, 1);
+    assert!(stats(&b).received.is_empty());
+
+    let relayed = relayer.relay(&mut a, &mut b).unwrap();
+
+    assert_eq!(relayed.len(), 2);
+    assert_eq!(relayed[0].packet.data, Binary::from(b"ping"));
+    assert_eq!(relayed[0].outcome, PacketOutcome::Acknowledged(IbcAcknowledgement::new(b"pong")));
+    assert_eq!(relayed[1].packet.data, Binary::from(b"pong"));
+
+    assert!(a.pending_packets().is_empty());
+    assert!(b.pending_packets().is_empty());
+
+    assert_eq!(stats(&a).received, vec!["pong".to_string()]);
+    assert_eq!(stats(&a).acks, vec![Binary::from(b"pong")]);
+    assert_eq!(stats(&b).received, vec!["ping".to_string()]);
+    assert_eq!(stats(&b).acks, vec![Binary::from(b"pong")]);
+
+    assert!(relayer.relay(&mut a, &mut b).unwrap().is_empty());
+}
+
+#[test]
+fn receive_error_is_acknowledged_and_reverted() {
+    let (mut a, mut b) = init();
+    let relayer = Relayer::new("relayer");
+    let channel = open(&relayer, &mut a, &mut b);
+
+    send(&mut a, ExecuteMsg::Send {
+        channel_id: channel.endpoint.channel_id,
+        data: "fail".into(),
+        timeout_height: None
+    }).unwrap();
+
+    let relayed = relayer.relay(&mut a, &mut b).unwrap();
+    assert_eq!(relayed.len(), 1);
+
+    let expected = Binary::from(br#"{"error":"Received fail"}"#);
+
+    assert_eq!(relayed[0].outcome, PacketOutcome::Acknowledged(IbcAcknowledgement::new(expected.clone())));
+    assert!(stats(&b).received.is_empty());
+    assert_eq!(stats(&a).acks, vec![expected]);
+}
+
+#[test]
+fn packets_time_out() {
+    let (mut a, mut b) = init();
+    let relayer = Relayer::new("relayer");
+    let channel = open(&relayer, &mut a, &mut b);
+
+    let height = b.block().height + 5;
+
+    send(&mut a, ExecuteMsg::Send {
+        channel_id: channel.endpoint.channel_id,
+        data: "ping".into(),
+        timeout_height: Some(height)
+    }).unwrap();
+
+    b.block_mut().increment(5);
+
+    let relayed = relayer.relay(&mut a, &mut b).unwrap();
+
+    assert_eq!(relayed.len(), 1);
+    assert_eq!(relayed[0].outcome, PacketOutcome::TimedOut);
+    assert!(stats(&b).received.is_empty());
+    assert_eq!(stats(&a).timeouts, 1);
+}
+
+#[test]
+fn reverted_tx_discards_packets() {
+    let (mut a, mut b) = init();
+    let relayer = Relayer::new("relayer");
+    let channel = open(&relayer, &mut a, &mut b);
+
+    let channel_id = channel.endpoint.channel_id;
+
+    let err = send(&mut a, ExecuteMsg::SendAndFail { channel_id: channel_id.clone() }).unwrap_err();
+    assert_eq!(err.unwrap_contract_error().to_string(), "Failed");
+    assert!(a.pending_packets().is_empty());
+
+    send(&mut a, ExecuteMsg::Send { channel_id, data: "ping".into(), timeout_height: None }).unwrap();
+    assert_eq!(a.pending_packets()[0].sequence, 1);
+}
+
+#[test]
+fn closes_channel() {
+    let (mut a, mut b) = init();
+    let relayer = Relayer::new("relayer");
+    let channel = open(&relayer, &mut a, &mut b);
+
+    let channel_id = channel.endpoint.channel_id;
+
+    send(&mut b, ExecuteMsg::Send { channel_id: channel_id.clone(), data: "ping".into(), timeout_height: None }).unwrap();
+    send(&mut a, ExecuteMsg::Close { channel_id: channel_id.clone() }).unwrap();
+
+    assert!(a.ibc_channels().is_empty());
+    assert!(!stats(&a).closed);
+
+    let relayed = relayer.relay(&mut a, &mut b).unwrap();
+
+    // Packets in flight time out once the channel is closed.
+    assert_eq!(relayed.len(), 1);
+    assert_eq!(relayed[0].outcome, PacketOutcome::TimedOut);
+    assert_eq!(stats(&b).timeouts, 1);
+
+    assert!(stats(&a).closed);
+    assert!(stats(&b).closed);
+    assert!(b.ibc_channels().is_empty());
+
+    let err = send(&mut a, ExecuteMsg::Send { channel_id, data: "ping".into(), timeout_height: None }).unwrap_err();
+    assert_eq!(err.to_string(), "Ensemble error - IBC: Channel channel-0 is closed");
+}
+
+#[test]
+fn transfers_tokens() {
+    let (mut a, mut b) = init();
+    let relayer = Relayer::new("relayer");
+
+    // Occupy channel-0 on A so that the channel ids differ on each end.
+    open(&relayer, &mut a, &mut b);
+    let (mut c, _) = init();
+    c.set_chain_id("chain-c");
+    open(&relayer, &mut a, &mut c);
+
+    let channel = relayer.open_channel(
+        &mut a,
+        TRANSFER_PORT,
+        &mut b,
+        TRANSFER_PORT,
+        IbcOrder::Unordered,
+        ICS20_VERSION
+    ).unwrap();
+
+    assert_eq!(channel.endpoint.channel_id, "channel-2");
+    assert_eq!(channel.counterparty_endpoint.channel_id, "channel-1");
+
+    a.add_funds(PINGER, vec![coin(100, "uscrt")]);
+
+    send(&mut a, ExecuteMsg::Transfer {
+        channel_id: "channel-2".into(),
+        to_address: PINGER.into(),
+        amount: coin(40, "uscrt"),
+        timeout_height: None
+    }).unwrap();
+
+    assert_eq!(balance(&a, PINGER, "uscrt"), 60);
+    assert_eq!(balance(&a, &escrow_address("channel-2"), "uscrt"), 40);
+
+    let relayed = relayer.relay(&mut a, &mut b).unwrap();
+    assert_eq!(relayed.len(), 1);
+    assert_eq!(relayed[0].outcome, PacketOutcome::Acknowledged(IbcAcknowledgement::new(br#"{"result":"AQ=="}"#)));
+
+    let voucher = ibc_denom("transfer/channel-1/uscrt");
+    assert_eq!(b.denom_trace(&voucher), Some("transfer/channel-1/uscrt"));
+    assert_eq!(balance(&b, PINGER, &voucher), 40);
+
+    // Sending the voucher back burns it and releases the escrowed tokens.
+    send(&mut b, ExecuteMsg::Transfer {
+        channel_id: "channel-1".into(),
+        to_address: "alice".into(),
+        amount: coin(15, &voucher),
+        timeout_height: None
+    }).unwrap();
+
+    assert_eq!(balance(&b, PINGER, &voucher), 25);
+
+    relayer.relay(&mut a, &mut b).unwrap();
+
+    assert_eq!(balance(&a, "alice", "uscrt"), 15);
+    assert_eq!(balance(&a, &escrow_address("channel-2"), "uscrt"), 25);
+}
+
+#[test]
+fn failed_transfers_are_refunded() {
+    let (mut a, mut b) = init();
+    let relayer = Relayer::new("relayer");
+
+    let err = relayer.open_channel(
+        &mut a,
+        TRANSFER_PORT,
+        &mut b,
+        TRANSFER_PORT,
+        IbcOrder::Ordered,
+        ICS20_VERSION
+    ).unwrap_err();
+
+    assert_eq!(
+        err.to_string(),
+        "Ensemble error - IBC: The transfer module only supports unordered channels with version ics20-1"
+    );
+
+    let channel = relayer.open_channel(
+        &mut a,
+        TRANSFER_PORT,
+        &mut b,
+        TRANSFER_PORT,
+        IbcOrder::Unordered,
+        ICS20_VERSION
+    ).unwrap();
+
+    let channel_id = channel.endpoint.channel_id;
+    a.add_funds(PINGER, vec![coin(100, "uscrt")]);
+
+    let height = b.block().height + 1;
+
+    send(&mut a, ExecuteMsg::Transfer {
+        channel_id: channel_id.clone(),
+        to_address: PINGER.into(),
+        amount: coin(40, "uscrt"),
+        timeout_height: Some(height)
+    }).unwrap();
+
+    assert_eq!(balance(&a, PINGER, "uscrt"), 60);
+
+    b.block_mut().next();
+
+    let relayed = relayer.relay(&mut a, &mut b).unwrap();
+    assert_eq!(relayed[0].outcome, PacketOutcome::TimedOut);
+    assert_eq!(balance(&a, PINGER, "uscrt"), 100);
+    assert_eq!(balance(&a, &escrow_address(&channel_id), "uscrt"), 0);
+
+    // Only the transfer module can use transfer channels.
+    let err = send(&mut a, ExecuteMsg::Send { channel_id, data: "ping".into(), timeout_height: None }).unwrap_err();
+    assert_eq!(err.to_string(), "Ensemble error - IBC: Channel channel-0 is not bound to port wasm.pinger");
+}
@@ -0,0 +1,281 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ContractEnsemble, ContractHarness, MockEnv,
+    AnyResult, EnsembleError, RegistryError,
+    anyhow::bail,
+    response::ResponseVariants
+};
+use fadroma::prelude::*;
+
+const ADMIN: &str = "admin";
+const SENDER: &str = "sender";
+const CONTRACT: &str = "contract";
+const FACTORY: &str = "factory";
+
+struct V1;
+struct V2;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ExecuteMsg {
+    Increment,
+    RunMsg(CosmosMsg)
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+struct MigrateMsg {
+    fail: bool
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+struct StateResponse {
+    version: u8,
+    number: u32
+}
+
+impl ContractHarness for V1 {
+    fn instantiate(&self, _deps: DepsMut, _env: Env, _info: MessageInfo, _msg: Binary) -> AnyResult<Response> {
+        Ok(Response::default())
+    }
+
+    fn execute(&self, deps: DepsMut, _env: Env, _info: MessageInfo, msg: Binary) -> AnyResult<Response> {
+        match from_binary(&msg)? {
+            ExecuteMsg::Increment => {
+                let number: u32 = storage::load(deps.storage, b"num")?.unwrap_or_default();
+                storage::save(deps.storage, b"num", &(number + 1))?;
+
+                Ok(Response::default())
+            }
+            ExecuteMsg::RunMsg(msg) => Ok(Response::default().add_message(msg))
+        }
+    }
+
+    fn query(&self, deps: Deps, _env: Env, _msg: Binary) -> AnyResult<Binary> {
+        query_state(deps, 1)
+    }
+}
+
+impl ContractHarness for V2 {
+    fn instantiate(&self, _deps: DepsMut, _env: Env, _info: MessageInfo, _msg: Binary) -> AnyResult<Response> {
+        Ok(Response::default())
+    }
+
+    fn execute(&self, _deps: DepsMut, _env: Env, _info: MessageInfo, _msg: Binary) -> AnyResult<Response> {
+        bail!("V2 doesn't support execute.")
+    }
+
+    fn query(&self, deps: Deps, _env: Env, _msg: Binary) -> AnyResult<Binary> {
+        query_state(deps, 2)
+    }
+
+    fn migrate(&self, deps: DepsMut, env: Env, msg: Binary) -> AnyResult<Response> {
+        let msg: MigrateMsg = from_binary(&msg)?;
+
+        storage::save(deps.storage, b"migrated", &true)?;
+
+        if msg.fail {
+            bail!("Failed at migrate.");
+        }
+
+        Ok(Response::default().add_attribute("code_hash", env.contract.code_hash))
+    }
+}
+
+fn query_state(deps: Deps, version: u8) -> AnyResult<Binary> {
+    let number: u32 = storage::load(deps.storage, b"num")?.unwrap_or_default();
+    let result = to_binary(&StateResponse { version, number })?;
+
+    Ok(result)
+}
+
+fn init(admin: Option<&str>) -> (ContractEnsemble, ContractCode, ContractCode) {
+    let mut ensemble = ContractEnsemble::new();
+
+    let v1 = ensemble.register(Box::new(V1));
+    let v2 = ensemble.register(Box::new(V2));
+
+    let env = MockEnv::new(SENDER, CONTRACT);
+
+    match admin {
+        Some(admin) => ensemble.instantiate_with_admin(v1.id, &(), env, admin),
+        None => ensemble.instantiate(v1.id, &(), env)
+    }.unwrap();
+
+    ensemble.execute(&ExecuteMsg::Increment, MockEnv::new(SENDER, CONTRACT)).unwrap();
+
+    (ensemble, v1, v2)
+}
+
+#[test]
+fn admin_is_set_on_instantiate() {
+    let (ensemble, _, _) = init(Some(ADMIN));
+    assert_eq!(ensemble.contract_admin(CONTRACT).unwrap(), Some(ADMIN));
+
+    let (ensemble, _, _) = init(None);
+    assert_eq!(ensemble.contract_admin(CONTRACT).unwrap(), None);
+}
+
+#[test]
+fn admin_can_migrate() {
+    let (mut ensemble, _, v2) = init(Some(ADMIN));
+
+    let resp = ensemble.migrate(
+        v2.id,
+        &MigrateMsg { fail: false },
+        MockEnv::new(ADMIN, CONTRACT)
+    ).unwrap();
+
+    assert_eq!(resp.code_id, v2.id);
+    assert_eq!(resp.sender, ADMIN);
+    assert_eq!(resp.response.attributes[0].value, v2.code_hash);
+
+    let state: StateResponse = ensemble.query(CONTRACT, &()).unwrap();
+    assert_eq!(state, StateResponse { version: 2, number: 1 });
+
+    ensemble.contract_storage(CONTRACT, |storage| {
+        assert!(storage.get(b"migrated").is_some());
+    }).unwrap();
+
+    let err = ensemble.execute(
+        &ExecuteMsg::Increment,
+        MockEnv::new(SENDER, CONTRACT)
+    ).unwrap_err();
+
+    assert_eq!(err.to_string(), "V2 doesn't support execute.");
+}
+
+#[test]
+fn only_admin_can_migrate() {
+    let (mut ensemble, _, v2) = init(Some(ADMIN));
+
+    let err = ensemble.migrate(
+        v2.id,
+        &MigrateMsg { fail: false },
+        MockEnv::new(SENDER, CONTRACT)
+    ).unwrap_err();
+
+    assert!(matches!(
+        err,
+        EnsembleError::ContractRegistry(RegistryError::Unauthorized { .. })
+    ));
+
+    let (mut ensemble, _, v2) = init(None);
+
+    let err = ensemble.migrate(
+        v2.id,
+        &MigrateMsg { fail: false },
+        MockEnv::new(SENDER, CONTRACT)
+    ).unwrap_err();
+
+    assert!(matches!(
+        err,
+        EnsembleError::ContractRegistry(RegistryError::NoAdmin(_))
+    ));
+
+    let state: StateResponse = ensemble.query(CONTRACT, &()).unwrap();
+    assert_eq!(state, StateResponse { version: 1, number: 1 });
+}
+
+#[test]
+fn failed_migration_is_reverted() {
+    let (mut ensemble, _, v2) = init(Some(ADMIN));
+
+    let err = ensemble.migrate(
+        v2.id,
+        &MigrateMsg { fail: true },
+        MockEnv::new(ADMIN, CONTRACT)
+    ).unwrap_err();
+
+    assert_eq!(err.to_string(), "Failed at migrate.");
+
+    let state: StateResponse = ensemble.query(CONTRACT, &()).unwrap();
+    assert_eq!(state, StateResponse { version: 1, number: 1 });
+
+    ensemble.contract_storage(CONTRACT, |storage| {
+        assert!(storage.get(b"migrated").is_none());
+    }).unwrap();
+}
+
+#[test]
+fn update_and_clear_admin() {
+    let (mut ensemble, _, v2) = init(Some(ADMIN));
+
+    ensemble.update_admin(SENDER, MockEnv::new(SENDER, CONTRACT)).unwrap_err();
+
+    let resp = ensemble.update_admin(SENDER, MockEnv::new(ADMIN, CONTRACT)).unwrap();
+    assert_eq!(resp.admin.as_deref(), Some(SENDER));
+    assert_eq!(ensemble.contract_admin(CONTRACT).unwrap(), Some(SENDER));
+
+    ensemble.clear_admin(MockEnv::new(ADMIN, CONTRACT)).unwrap_err();
+
+    let resp = ensemble.clear_admin(MockEnv::new(SENDER, CONTRACT)).unwrap();
+    assert_eq!(resp.admin, None);
+    assert_eq!(ensemble.contract_admin(CONTRACT).unwrap(), None);
+
+    ensemble.migrate(
+        v2.id,
+        &MigrateMsg { fail: false },
+        MockEnv::new(SENDER, CONTRACT)
+    ).unwrap_err();
+}
+
+#[test]
+fn contract_admin_can_send_admin_messages() {
+    let (mut ensemble, v1, v2) = init(None);
+
+    ensemble.instantiate(v1.id, &(), MockEnv::new(SENDER, FACTORY)).unwrap();
+
+    let msg = ExecuteMsg::RunMsg(WasmMsg::Instantiate {
+        code_id: v1.id,
+        code_hash: v1.code_hash,
+        msg: to_binary(&()).unwrap(),
+        funds: vec![],
+        label: "product".into(),
+        admin: Some(FACTORY.into())
+    }.into());
+
+    ensemble.execute(&msg, MockEnv::new(SENDER, FACTORY)).unwrap();
+    assert_eq!(ensemble.contract_admin("product").unwrap(), Some(FACTORY));
+
+    let msg = ExecuteMsg::RunMsg(WasmMsg::Migrate {
+        contract_addr: "product".into(),
+        code_hash: v2.code_hash.clone(),
+        code_id: v2.id,
+        msg: to_binary(&MigrateMsg { fail: false }).unwrap()
+    }.into());
+
+    let resp = ensemble.execute(&msg, MockEnv::new(SENDER, FACTORY)).unwrap();
+
+    let ResponseVariants::Migrate(migrate) = resp.iter().next().unwrap() else {
+        panic!("Expecting ResponseVariants::Migrate");
+    };
+    assert_eq!(migrate.sender, FACTORY);
+    assert_eq!(migrate.address, "product");
+
+    let state: StateResponse = ensemble.query("product", &()).unwrap();
+    assert_eq!(state.version, 2);
+
+    let msg = ExecuteMsg::RunMsg(WasmMsg::Migrate {
+        contract_addr: "product".into(),
+        code_hash: "wrong_hash".into(),
+        code_id: v2.id,
+        msg: to_binary(&MigrateMsg { fail: false }).unwrap()
+    }.into());
+
+    let err = ensemble.execute(&msg, MockEnv::new(SENDER, FACTORY)).unwrap_err();
+    assert!(matches!(
+        err,
+        EnsembleError::ContractRegistry(RegistryError::InvalidCodeHash(_))
+    ));
+
+    let msg = ExecuteMsg::RunMsg(WasmMsg::ClearAdmin {
+        contract_addr: "product".into()
+    }.into());
+
+    let resp = ensemble.execute(&msg, MockEnv::new(SENDER, FACTORY)).unwrap();
+    assert!(resp.iter().next().unwrap().is_admin());
+    assert_eq!(ensemble.contract_admin("product").unwrap(), None);
+}
@@ -0,0 +1,259 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ContractEnsemble, ContractHarness, ContractMock, MockEnv, MockCall, MockCallKind,
+    AnyResult, anyhow::bail
+};
+use fadroma::prelude::*;
+
+const SENDER: &str = "sender";
+const CALLER: &str = "caller";
+const ORACLE: &str = "oracle";
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+enum ExecuteMsg {
+    Call { address: String, msg: OracleMsg },
+    TryCall { address: String, msg: OracleMsg }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+enum OracleMsg {
+    SetPrice { price: u64 },
+    Price { asset: String }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum QueryMsg {
+    Price { oracle: String, asset: String }
+}
+
+struct Caller;
+
+impl ContractHarness for Caller {
+    fn instantiate(&self, _deps: DepsMut, _env: Env, _info: MessageInfo, _msg: Binary) -> AnyResult<Response> {
+        Ok(Response::default())
+    }
+
+    fn execute(&self, _deps: DepsMut, _env: Env, _info: MessageInfo, msg: Binary) -> AnyResult<Response> {
+        let (address, msg, reply) = match from_binary(&msg)? {
+            ExecuteMsg::Call { address, msg } => (address, msg, false),
+            ExecuteMsg::TryCall { address, msg } => (address, msg, true)
+        };
+
+        let msg = WasmMsg::Execute {
+            contract_addr: address,
+            code_hash: String::new(),
+            msg: to_binary(&msg)?,
+            funds: vec![]
+        };
+
+        let resp = if reply {
+            Response::default().add_submessage(SubMsg::reply_on_error(msg, 1))
+        } else {
+            Response::default().add_message(msg)
+        };
+
+        Ok(resp)
+    }
+
+    fn query(&self, deps: Deps, _env: Env, msg: Binary) -> AnyResult<Binary> {
+        match from_binary(&msg)? {
+            QueryMsg::Price { oracle, asset } => {
+                let price: u64 = deps.querier.query_wasm_smart(
+                    "",
+                    oracle,
+                    &OracleMsg::Price { asset }
+                )?;
+
+                Ok(to_binary(&price)?)
+            }
+        }
+    }
+
+    fn reply(&self, _deps: DepsMut, _env: Env, reply: Reply) -> AnyResult<Response> {
+        match reply.result {
+            SubMsgResult::Err(err) => Ok(Response::default().set_data(to_binary(&err)?)),
+            SubMsgResult::Ok(_) => bail!("Expected an error")
+        }
+    }
+}
+
+/// Keeps the last price that it was sent.
+struct Oracle;
+
+impl ContractHarness for Oracle {
+    fn instantiate(&self, deps: DepsMut, _env: Env, _info: MessageInfo, _msg: Binary) -> AnyResult<Response> {
+        deps.storage.set(b"price", &to_vec(&1u64)?);
+
+        Ok(Response::default())
+    }
+
+    fn execute(&self, deps: DepsMut, _env: Env, _info: MessageInfo, msg: Binary) -> AnyResult<Response> {
+        match from_binary(&msg)? {
+            OracleMsg::SetPrice { price } => deps.storage.set(b"price", &to_vec(&price)?),
+            OracleMsg::Price { .. } => bail!("Not an execute message")
+        }
+
+        Ok(Response::default())
+    }
+
+    fn query(&self, deps: Deps, _env: Env, _msg: Binary) -> AnyResult<Binary> {
+        Ok(deps.storage.get(b"price").unwrap().into())
+    }
+}
+
+fn init() -> ContractEnsemble {
+    let mut ensemble = ContractEnsemble::new();
+    let caller = ensemble.register(Box::new(Caller));
+    ensemble.instantiate(caller.id, &(), MockEnv::new(SENDER, CALLER)).unwrap();
+
+    ensemble
+}
+
+fn query_price(ensemble: &ContractEnsemble) -> AnyResult<u64> {
+    let price = ensemble.query(CALLER, &QueryMsg::Price {
+        oracle: ORACLE.into(),
+        asset: "scrt".into()
+    })?;
+
+    Ok(price)
+}
+
+#[test]
+fn mocks_address_without_contract() {
+    let mut ensemble = init();
+
+    let oracle = ensemble.add_mock(ContractMock::address(ORACLE)
+        .query_returns(&100u64)
+        .execute_returns(Response::default().add_attribute("updated", "true")));
+
+    assert_eq!(query_price(&ensemble).unwrap(), 100);
+
+    let set_price = OracleMsg::SetPrice { price: 200 };
+    let resp = ensemble.execute(
+        &ExecuteMsg::Call { address: ORACLE.into(), msg: set_price.clone() },
+        MockEnv::new(SENDER, CALLER)
+    ).unwrap();
+
+    let mocked = resp.iter().find_map(|x| match x {
+        crate::ResponseVariants::Execute(resp) if resp.address == ORACLE => Some(resp),
+        _ => None
+    }).unwrap();
+
+    assert_eq!(mocked.sender, CALLER);
+    assert_eq!(mocked.response.attributes[0].key, "updated");
+
+    // Top level calls work too.
+    ensemble.execute(&set_price, MockEnv::new(SENDER, ORACLE)).unwrap();
+
+    let calls = ensemble.mock_calls(oracle);
+    assert_eq!(calls.len(), 3);
+
+    assert_eq!(calls[0], MockCall {
+        kind: MockCallKind::Query,
+        sender: None,
+        address: ORACLE.into(),
+        msg: to_binary(&OracleMsg::Price { asset: "scrt".into() }).unwrap(),
+        funds: vec![]
+    });
+
+    assert_eq!(calls[1].kind, MockCallKind::Execute);
+    assert_eq!(calls[1].sender.as_deref(), Some(CALLER));
+    assert_eq!(calls[1].decode::<OracleMsg>().unwrap(), set_price);
+    assert_eq!(calls[2].sender.as_deref(), Some(SENDER));
+}
+
+#[test]
+fn mock_errors_can_be_handled() {
+    let mut ensemble = init();
+
+    ensemble.add_mock(ContractMock::address(ORACLE)
+        .query_fails("Oracle is down")
+        .execute_fails("Paused"));
+
+    let err = query_price(&ensemble).unwrap_err();
+    assert!(err.to_string().contains("Oracle is down"));
+
+    let msg = OracleMsg::SetPrice { price: 1 };
+
+    let err = ensemble.execute(
+        &ExecuteMsg::Call { address: ORACLE.into(), msg: msg.clone() },
+        MockEnv::new(SENDER, CALLER)
+    ).unwrap_err();
+    assert_eq!(err.unwrap_contract_error().to_string(), "Paused");
+
+    let resp = ensemble.execute(
+        &ExecuteMsg::TryCall { address: ORACLE.into(), msg },
+        MockEnv::new(SENDER, CALLER)
+    ).unwrap();
+
+    assert!(resp.response.data.is_none());
+
+    let reply = resp.iter().find_map(|x| match x {
+        crate::ResponseVariants::Reply(reply) => reply.response.data.clone(),
+        _ => None
+    }).unwrap();
+
+    assert_eq!(from_binary::<String>(&reply).unwrap(), "Paused");
+}
+
+#[test]
+fn mocks_code_id() {
+    let mut ensemble = init();
+
+    let oracle = ensemble.register(Box::new(Oracle));
+    ensemble.instantiate(oracle.id, &(), MockEnv::new(SENDER, ORACLE)).unwrap();
+
+    assert_eq!(query_price(&ensemble).unwrap(), 1);
+
+    let mock = ensemble.add_mock(ContractMock::code_id(oracle.id)
+        .query_with(|call| {
+            let OracleMsg::Price { asset } = call.decode()? else {
+                bail!("Unexpected query");
+            };
+
+            Ok(to_binary(&(asset.len() as u64))?)
+        }));
+
+    assert_eq!(query_price(&ensemble).unwrap(), 4);
+
+    // Execute messages are not intercepted.
+    ensemble.execute(&OracleMsg::SetPrice { price: 5 }, MockEnv::new(SENDER, ORACLE)).unwrap();
+    assert_eq!(ensemble.mock_calls(mock).len(), 1);
+
+    ensemble.remove_mock(mock);
+    assert_eq!(query_price(&ensemble).unwrap(), 5);
+}
+
+#[test]
+fn mock_response_messages_are_executed() {
+    let mut ensemble = init();
+    ensemble.add_funds(ORACLE, vec![coin(100, "uscrt")]);
+
+    ensemble.add_mock(ContractMock::address(ORACLE)
+        .execute_returns(Response::default().add_message(BankMsg::Send {
+            to_address: "recipient".into(),
+            amount: vec![coin(40, "uscrt")]
+        })));
+
+    ensemble.execute(
+        &ExecuteMsg::Call { address: ORACLE.into(), msg: OracleMsg::SetPrice { price: 1 } },
+        MockEnv::new(SENDER, CALLER)
+    ).unwrap();
+
+    assert_eq!(ensemble.balances("recipient").unwrap()["uscrt"].u128(), 40);
+    assert_eq!(ensemble.balances(ORACLE).unwrap()["uscrt"].u128(), 60);
+}
+
+#[test]
+fn unmocked_address_is_not_found() {
+    let mut ensemble = init();
+    ensemble.add_mock(ContractMock::address(ORACLE).query_returns(&1u64));
+
+    // Only queries are mocked.
+    ensemble.execute(&(), MockEnv::new(SENDER, ORACLE)).unwrap_err();
+    ensemble.query::<_, u64>("other", &()).unwrap_err();
+}
@@ -1,4 +1,24 @@
 mod interactions;
-#[cfg(feature = "ensemble-staking")]
+mod bank;
+#[cfg(feature = "staking")]
 mod staking;
 mod submsg;
+mod migrate;
+mod gas;
+mod snapshot;
+mod trace;
+mod module;
+mod query;
+mod address;
+mod code_hash;
+mod mock;
+mod event_log;
+mod failure;
+mod coverage;
+mod schedule;
+#[cfg(feature = "fuzz")]
+mod fuzz;
+#[cfg(feature = "ibc")]
+mod ibc;
+#[cfg(feature = "vm")]
+mod vm;
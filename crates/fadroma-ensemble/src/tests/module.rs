@@ -0,0 +1,438 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ContractEnsemble, ContractHarness, MockEnv, AnyResult, TraceKind, ResponseVariants, Snapshot,
+    Module, ModuleDeps, ModuleDepsMut, ModuleResponse, anyhow::bail
+};
+use fadroma::prelude::*;
+
+const SENDER: &str = "sender";
+const CALLER: &str = "caller";
+const COUNTER: &str = "counter";
+
+const COUNT_KEY: &[u8] = b"count";
+const REPLY_KEY: &[u8] = b"reply";
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ExecuteMsg {
+    Send { msg: CosmosMsg },
+    SendWithReply { msg: CosmosMsg },
+    SendAndFail { msg: CosmosMsg },
+    Fail
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+enum QueryMsg {
+    Custom,
+    LastReply
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+struct LastReply {
+    data: Option<Binary>,
+    events: Vec<Event>,
+    error: Option<String>
+}
+
+struct Caller;
+
+impl ContractHarness for Caller {
+    fn instantiate(&self, _deps: DepsMut, _env: Env, _info: MessageInfo, _msg: Binary) -> AnyResult<Response> {
+        Ok(Response::default())
+    }
+
+    fn execute(&self, _deps: DepsMut, env: Env, _info: MessageInfo, msg: Binary) -> AnyResult<Response> {
+        let resp = match from_binary(&msg)? {
+            ExecuteMsg::Send { msg } => Response::new().add_message(msg),
+            ExecuteMsg::SendWithReply { msg } => Response::new()
+                .add_submessage(SubMsg::reply_always(msg, 1)),
+            ExecuteMsg::SendAndFail { msg } => Response::new()
+                .add_message(msg)
+                .add_message(WasmMsg::Execute {
+                    contract_addr: env.contract.address.to_string(),
+                    code_hash: env.contract.code_hash,
+                    msg: to_binary(&ExecuteMsg::Fail)?,
+                    funds: vec![]
+                }),
+            ExecuteMsg::Fail => bail!("Failed")
+        };
+
+        Ok(resp)
+    }
+
+    fn query(&self, deps: Deps, _env: Env, msg: Binary) -> AnyResult<Binary> {
+        match from_binary(&msg)? {
+            QueryMsg::Custom => {
+                let count: u64 = deps.querier.query(&QueryRequest::Custom(Empty {}))?;
+
+                Ok(to_binary(&count)?)
+            },
+            QueryMsg::LastReply => Ok(Binary(deps.storage.get(REPLY_KEY).unwrap()))
+        }
+    }
+
+    fn reply(&self, deps: DepsMut, _env: Env, reply: Reply) -> AnyResult<Response> {
+        let last = match reply.result {
+            SubMsgResult::Ok(resp) => LastReply {
+                data: resp.data,
+                events: resp.events,
+                error: None
+            },
+            SubMsgResult::Err(err) => LastReply {
+                data: None,
+                events: vec![],
+                error: Some(err)
+            }
+        };
+
+        deps.storage.set(REPLY_KEY, &to_vec(&last)?);
+
+        Ok(Response::default())
+    }
+}
+
+/// Counts the custom messages that it receives and fails when
+/// they are sent by an address that was added to its block list.
+struct Counter {
+    blocked: Vec<&'static str>
+}
+
+impl Module for Counter {
+    fn execute(
+        &self,
+        mut deps: ModuleDepsMut,
+        sender: &str,
+        msg: &CosmosMsg
+    ) -> AnyResult<Option<ModuleResponse>> {
+        if !matches!(msg, CosmosMsg::Custom(Empty {})) {
+            return Ok(None);
+        }
+
+        if self.blocked.contains(&sender) {
+            bail!("{} is blocked", sender);
+        }
+
+        let count = count(deps.storage()) + 1;
+        deps.storage_mut().set(COUNT_KEY, &to_vec(&count)?);
+
+        Ok(Some(ModuleResponse::new()
+            .add_event(Event::new("count").add_attribute("count", count.to_string()))
+            .set_data(to_binary(&count)?)
+        ))
+    }
+
+    fn query(
+        &self,
+        deps: ModuleDeps,
+        request: &QueryRequest<Empty>
+    ) -> AnyResult<Option<Binary>> {
+        match request {
+            QueryRequest::Custom(Empty {}) => Ok(Some(to_binary(&count(deps.storage()))?)),
+            _ => Ok(None)
+        }
+    }
+}
+
+fn count(storage: &dyn Storage) -> u64 {
+    storage.get(COUNT_KEY)
+        .map(|x| from_slice(&x).unwrap())
+        .unwrap_or_default()
+}
+
+fn module_count(ensemble: &ContractEnsemble) -> u64 {
+    let mut result = 0;
+    ensemble.module_storage(COUNTER, |storage| result = count(storage)).unwrap();
+
+    result
+}
+
+fn init(blocked: Vec<&'static str>) -> ContractEnsemble {
+    let mut ensemble = ContractEnsemble::new();
+    ensemble.add_module(COUNTER, Box::new(Counter { blocked }));
+
+    let caller = ensemble.register(Box::new(Caller));
+    ensemble.instantiate(caller.id, &(), MockEnv::new(SENDER, CALLER)).unwrap();
+
+    ensemble
+}
+
+fn custom() -> CosmosMsg {
+    CosmosMsg::Custom(Empty {})
+}
+
+#[test]
+fn module_handles_custom_messages() {
+    let mut ensemble = init(vec![]);
+    ensemble.set_tracing(true);
+
+    let resp = ensemble.execute(
+        &ExecuteMsg::Send { msg: custom() },
+        MockEnv::new(SENDER, CALLER)
+    ).unwrap();
+
+    assert_eq!(module_count(&ensemble), 1);
+    assert_eq!(resp.sent.len(), 1);
+
+    match &resp.sent[0] {
+        ResponseVariants::Module(resp) => {
+            assert_eq!(resp.module, COUNTER);
+            assert_eq!(resp.sender, CALLER);
+            assert_eq!(resp.msg, custom());
+            assert_eq!(resp.response.data, Some(to_binary(&1u64).unwrap()));
+        },
+        _ => panic!("Expected a module response.")
+    }
+
+    let step = &ensemble.trace().unwrap().steps[1];
+    assert_eq!(step.kind, TraceKind::Module);
+    assert_eq!(step.sender, CALLER);
+    assert_eq!(step.receiver, COUNTER);
+    assert_eq!(step.storage_writes.len(), 1);
+    assert_eq!(step.storage_writes[0].address, format!("module:{}", COUNTER));
+}
+
+#[test]
+fn module_data_and_events_are_passed_to_replies() {
+    let mut ensemble = init(vec![]);
+
+    ensemble.execute(
+        &ExecuteMsg::SendWithReply { msg: custom() },
+        MockEnv::new(SENDER, CALLER)
+    ).unwrap();
+
+    let reply: LastReply = ensemble.query(CALLER, &QueryMsg::LastReply).unwrap();
+
+    assert_eq!(reply, LastReply {
+        data: Some(to_binary(&1u64).unwrap()),
+        events: vec![Event::new("count").add_attribute("count", "1")],
+        error: None
+    });
+}
+
+#[test]
+fn module_errors_can_be_handled_by_replies() {
+    let mut ensemble = init(vec![CALLER]);
+
+    ensemble.execute(
+        &ExecuteMsg::SendWithReply { msg: custom() },
+        MockEnv::new(SENDER, CALLER)
+    ).unwrap();
+
+    let reply: LastReply = ensemble.query(CALLER, &QueryMsg::LastReply).unwrap();
+    assert_eq!(reply.error.unwrap(), format!("{} is blocked", CALLER));
+
+    let err = ensemble.execute(
+        &ExecuteMsg::Send { msg: custom() },
+        MockEnv::new(SENDER, CALLER)
+    ).unwrap_err();
+
+    assert!(err.is_contract_error());
+    assert_eq!(err.to_string(), format!("{} is blocked", CALLER));
+}
+
+#[test]
+fn module_storage_is_reverted_when_tx_fails() {
+    let mut ensemble = init(vec![]);
+
+    ensemble.execute(
+        &ExecuteMsg::Send { msg: custom() },
+        MockEnv::new(SENDER, CALLER)
+    ).unwrap();
+
+    ensemble.execute(
+        &ExecuteMsg::SendAndFail { msg: custom() },
+        MockEnv::new(SENDER, CALLER)
+    ).unwrap_err();
+
+    assert_eq!(module_count(&ensemble), 1);
+}
+
+#[test]
+fn module_storage_is_included_in_snapshots() {
+    let mut ensemble = init(vec![]);
+
+    ensemble.execute(
+        &ExecuteMsg::Send { msg: custom() },
+        MockEnv::new(SENDER, CALLER)
+    ).unwrap();
+
+    let snapshot = ensemble.snapshot();
+    let bytes = snapshot.to_bytes();
+
+    ensemble.execute(
+        &ExecuteMsg::Send { msg: custom() },
+        MockEnv::new(SENDER, CALLER)
+    ).unwrap();
+    assert_eq!(module_count(&ensemble), 2);
+
+    ensemble.restore(&snapshot);
+    assert_eq!(module_count(&ensemble), 1);
+
+    ensemble.execute(
+        &ExecuteMsg::Send { msg: custom() },
+        MockEnv::new(SENDER, CALLER)
+    ).unwrap();
+
+    ensemble.restore(&Snapshot::from_bytes(&bytes).unwrap());
+    assert_eq!(module_count(&ensemble), 1);
+}
+
+#[test]
+fn module_handles_custom_queries() {
+    let mut ensemble = init(vec![]);
+
+    let count: u64 = ensemble.query(CALLER, &QueryMsg::Custom).unwrap();
+    assert_eq!(count, 0);
+
+    ensemble.execute(
+        &ExecuteMsg::Send { msg: custom() },
+        MockEnv::new(SENDER, CALLER)
+    ).unwrap();
+
+    let count: u64 = ensemble.query(CALLER, &QueryMsg::Custom).unwrap();
+    assert_eq!(count, 1);
+}
+
+#[test]
+fn unknown_module_storage_returns_err() {
+    let ensemble = init(vec![]);
+
+    assert!(ensemble.module_storage("gov", |_| { }).is_err());
+}
+
+#[test]
+#[should_panic(expected = "Unsupported message")]
+fn unhandled_message_panics() {
+    let mut ensemble = ContractEnsemble::new();
+
+    let caller = ensemble.register(Box::new(Caller));
+    ensemble.instantiate(caller.id, &(), MockEnv::new(SENDER, CALLER)).unwrap();
+
+    let _ = ensemble.execute(
+        &ExecuteMsg::Send { msg: custom() },
+        MockEnv::new(SENDER, CALLER)
+    );
+}
+
+#[test]
+#[should_panic(expected = "already exists")]
+fn duplicate_module_name_panics() {
+    let mut ensemble = init(vec![]);
+    ensemble.add_module(COUNTER, Box::new(Counter { blocked: vec![] }));
+}
+
+#[cfg(feature = "stargate")]
+mod stargate {
+    use super::*;
+
+    const GOV: &str = "gov";
+    const BANK: &str = "bank";
+
+    /// Records votes and pays out a reward to each voter.
+    struct Gov;
+
+    impl Module for Gov {
+        fn execute(
+            &self,
+            mut deps: ModuleDepsMut,
+            sender: &str,
+            msg: &CosmosMsg
+        ) -> AnyResult<Option<ModuleResponse>> {
+            match msg {
+                CosmosMsg::Gov(GovMsg::Vote { proposal_id, vote }) => {
+                    let key = format!("{}/{}", proposal_id, sender);
+                    deps.storage_mut().set(key.as_bytes(), &to_vec(vote)?);
+                    deps.add_funds(sender, vec![coin(10, "uscrt")]);
+
+                    Ok(Some(ModuleResponse::new()
+                        .add_event(Event::new("proposal_vote")
+                            .add_attribute("proposal_id", proposal_id.to_string())
+                        )
+                    ))
+                },
+                _ => Ok(None)
+            }
+        }
+    }
+
+    /// Handles Stargate messages with a type URL of `/cosmos.bank.v1beta1.MsgBurn`.
+    struct Burner;
+
+    impl Module for Burner {
+        fn execute(
+            &self,
+            mut deps: ModuleDepsMut,
+            sender: &str,
+            msg: &CosmosMsg
+        ) -> AnyResult<Option<ModuleResponse>> {
+            match msg {
+                CosmosMsg::Stargate { type_url, value } if type_url == "/cosmos.bank.v1beta1.MsgBurn" => {
+                    let amount: Coin = from_binary(value)?;
+                    deps.remove_funds(sender, vec![amount])?;
+
+                    Ok(Some(ModuleResponse::new()))
+                },
+                _ => Ok(None)
+            }
+        }
+    }
+
+    fn init() -> ContractEnsemble {
+        let mut ensemble = super::init(vec![]);
+        ensemble.add_module(GOV, Box::new(Gov));
+        ensemble.add_module(BANK, Box::new(Burner));
+
+        ensemble
+    }
+
+    #[test]
+    fn gov_vote_is_handled_by_module() {
+        let mut ensemble = init();
+
+        ensemble.execute(
+            &ExecuteMsg::Send {
+                msg: CosmosMsg::Gov(GovMsg::Vote { proposal_id: 3, vote: VoteOption::Yes })
+            },
+            MockEnv::new(SENDER, CALLER)
+        ).unwrap();
+
+        let mut vote = None;
+        ensemble.module_storage(GOV, |storage| {
+            vote = storage.get(format!("3/{}", CALLER).as_bytes())
+                .map(|x| from_slice::<VoteOption>(&x).unwrap());
+        }).unwrap();
+
+        assert_eq!(vote, Some(VoteOption::Yes));
+        assert_eq!(ensemble.balances(CALLER).unwrap()["uscrt"].u128(), 10);
+    }
+
+    #[test]
+    fn stargate_msg_is_handled_by_module() {
+        let mut ensemble = init();
+        ensemble.add_funds(CALLER, vec![coin(100, "uscrt")]);
+
+        let burn = |amount| CosmosMsg::Stargate {
+            type_url: "/cosmos.bank.v1beta1.MsgBurn".into(),
+            value: to_binary(&coin(amount, "uscrt")).unwrap()
+        };
+
+        ensemble.execute(
+            &ExecuteMsg::Send { msg: burn(40) },
+            MockEnv::new(SENDER, CALLER)
+        ).unwrap();
+
+        assert_eq!(ensemble.balances(CALLER).unwrap()["uscrt"].u128(), 60);
+
+        // Bank errors are returned as contract errors so that replies can handle them.
+        ensemble.execute(
+            &ExecuteMsg::SendWithReply { msg: burn(100) },
+            MockEnv::new(SENDER, CALLER)
+        ).unwrap();
+
+        let reply: LastReply = ensemble.query(CALLER, &QueryMsg::LastReply).unwrap();
+        assert!(reply.error.is_some());
+        assert_eq!(ensemble.balances(CALLER).unwrap()["uscrt"].u128(), 60);
+    }
+}
@@ -0,0 +1,181 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{ContractEnsemble, ContractHarness, MockEnv, AnyResult, Snapshot, anyhow::bail};
+use fadroma::prelude::*;
+
+const CREATOR: &str = "creator";
+const ADMIN: &str = "admin";
+const STORE: &str = "store";
+const READER: &str = "reader";
+
+const VALUE_KEY: &[u8] = b"value";
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum QueryMsg {
+    Raw { contract: String, key: Binary },
+    Info { contract: String },
+    InfoRaw { contract: String }
+}
+
+#[derive(Deserialize, PartialEq, Debug)]
+struct InfoResponse {
+    code_id: u64,
+    creator: String,
+    admin: Option<String>,
+    label: String
+}
+
+struct Store;
+
+impl ContractHarness for Store {
+    fn instantiate(&self, deps: DepsMut, _env: Env, _info: MessageInfo, _msg: Binary) -> AnyResult<Response> {
+        deps.storage.set(VALUE_KEY, b"stored");
+
+        Ok(Response::default())
+    }
+
+    fn execute(&self, _deps: DepsMut, _env: Env, _info: MessageInfo, _msg: Binary) -> AnyResult<Response> {
+        bail!("Not implemented")
+    }
+
+    fn query(&self, _deps: Deps, _env: Env, _msg: Binary) -> AnyResult<Binary> {
+        bail!("Not implemented")
+    }
+}
+
+struct Reader;
+
+impl ContractHarness for Reader {
+    fn instantiate(&self, _deps: DepsMut, _env: Env, _info: MessageInfo, _msg: Binary) -> AnyResult<Response> {
+        Ok(Response::default())
+    }
+
+    fn execute(&self, _deps: DepsMut, _env: Env, _info: MessageInfo, _msg: Binary) -> AnyResult<Response> {
+        bail!("Not implemented")
+    }
+
+    fn query(&self, deps: Deps, _env: Env, msg: Binary) -> AnyResult<Binary> {
+        match from_binary(&msg)? {
+            QueryMsg::Raw { contract, key } => {
+                let request: QueryRequest<Empty> = WasmQuery::Raw {
+                    contract_addr: contract,
+                    key
+                }.into();
+
+                match deps.querier.raw_query(&to_vec(&request)?) {
+                    SystemResult::Ok(ContractResult::Ok(value)) => Ok(to_binary(&value)?),
+                    SystemResult::Ok(ContractResult::Err(err)) => bail!(err),
+                    SystemResult::Err(err) => bail!(err)
+                }
+            },
+            QueryMsg::Info { contract } => {
+                let info: ContractInfoResponse = deps.querier.query(
+                    &WasmQuery::ContractInfo { contract_addr: contract }.into()
+                )?;
+
+                Ok(to_binary(&(info.code_id, info.creator))?)
+            },
+            QueryMsg::InfoRaw { contract } => {
+                let request: QueryRequest<Empty> = WasmQuery::ContractInfo {
+                    contract_addr: contract
+                }.into();
+
+                match deps.querier.raw_query(&to_vec(&request)?) {
+                    SystemResult::Ok(ContractResult::Ok(value)) => Ok(value),
+                    SystemResult::Ok(ContractResult::Err(err)) => bail!(err),
+                    SystemResult::Err(err) => bail!(err)
+                }
+            }
+        }
+    }
+}
+
+fn init() -> ContractEnsemble {
+    let mut ensemble = ContractEnsemble::new();
+
+    let store = ensemble.register(Box::new(Store));
+    let reader = ensemble.register(Box::new(Reader));
+
+    ensemble.instantiate_with_admin(store.id, &(), MockEnv::new(CREATOR, STORE), ADMIN).unwrap();
+    ensemble.instantiate(reader.id, &(), MockEnv::new(CREATOR, READER)).unwrap();
+
+    ensemble
+}
+
+#[test]
+fn raw_query_reads_storage_of_other_contract() {
+    let ensemble = init();
+
+    let value: Binary = ensemble.query(READER, &QueryMsg::Raw {
+        contract: STORE.into(),
+        key: Binary::from(VALUE_KEY)
+    }).unwrap();
+
+    assert_eq!(value, Binary::from(b"stored"));
+
+    let value: Binary = ensemble.query(READER, &QueryMsg::Raw {
+        contract: STORE.into(),
+        key: Binary::from(b"missing")
+    }).unwrap();
+
+    assert!(value.is_empty());
+
+    let err = ensemble.query::<_, Binary>(READER, &QueryMsg::Raw {
+        contract: "nonexistent".into(),
+        key: Binary::from(VALUE_KEY)
+    }).unwrap_err();
+
+    assert!(err.to_string().contains("nonexistent"));
+}
+
+#[test]
+fn contract_info_query() {
+    let mut ensemble = init();
+
+    let (code_id, creator): (u64, String) = ensemble.query(READER, &QueryMsg::Info {
+        contract: STORE.into()
+    }).unwrap();
+
+    assert_eq!(code_id, 0);
+    assert_eq!(creator, CREATOR);
+
+    let info: InfoResponse = ensemble.query(READER, &QueryMsg::InfoRaw {
+        contract: STORE.into()
+    }).unwrap();
+
+    assert_eq!(info, InfoResponse {
+        code_id: 0,
+        creator: CREATOR.into(),
+        admin: Some(ADMIN.into()),
+        label: STORE.into()
+    });
+
+    ensemble.clear_admin(MockEnv::new(ADMIN, STORE)).unwrap();
+
+    let info: InfoResponse = ensemble.query(READER, &QueryMsg::InfoRaw {
+        contract: STORE.into()
+    }).unwrap();
+
+    assert_eq!(info.admin, None);
+
+    let err = ensemble.query::<_, InfoResponse>(READER, &QueryMsg::InfoRaw {
+        contract: "nonexistent".into()
+    }).unwrap_err();
+
+    assert!(err.to_string().contains("nonexistent"));
+}
+
+#[test]
+fn creator_is_included_in_snapshots() {
+    let mut ensemble = init();
+
+    let snapshot = Snapshot::from_bytes(&ensemble.snapshot().to_bytes()).unwrap();
+    ensemble.restore(&snapshot);
+
+    let info: InfoResponse = ensemble.query(READER, &QueryMsg::InfoRaw {
+        contract: STORE.into()
+    }).unwrap();
+
+    assert_eq!(info.creator, CREATOR);
+}
@@ -0,0 +1,250 @@
+use std::{rc::Rc, cell::RefCell};
+
+use crate::{ContractEnsemble, ContractHarness, MockEnv, AnyResult, Schedule, anyhow::anyhow};
+use fadroma::prelude::*;
+
+const SENDER: &str = "keeper";
+const CONTRACT: &str = "clock";
+
+/// Records the height and time of the last block that it was executed in.
+struct Clock;
+
+impl ContractHarness for Clock {
+    fn instantiate(&self, _deps: DepsMut, _env: Env, _info: MessageInfo, _msg: Binary) -> AnyResult<Response> {
+        Ok(Response::default())
+    }
+
+    fn execute(&self, deps: DepsMut, env: Env, _info: MessageInfo, _msg: Binary) -> AnyResult<Response> {
+        deps.storage.set(b"block", &to_vec(&(env.block.height, env.block.time.seconds()))?);
+
+        Ok(Response::default())
+    }
+
+    fn query(&self, deps: Deps, _env: Env, _msg: Binary) -> AnyResult<Binary> {
+        Ok(deps.storage.get(b"block").unwrap_or(to_vec(&(0u64, 0u64))?).into())
+    }
+}
+
+fn init() -> ContractEnsemble {
+    let mut ensemble = ContractEnsemble::new();
+    ensemble.block_mut().exact_increments(1, 5);
+
+    let clock = ensemble.register(Box::new(Clock));
+    ensemble.instantiate(clock.id, &(), MockEnv::new(SENDER, CONTRACT)).unwrap();
+
+    ensemble
+}
+
+fn tick(ensemble: &mut ContractEnsemble) -> crate::EnsembleResult<()> {
+    ensemble.execute(&(), MockEnv::new(SENDER, CONTRACT))?;
+
+    Ok(())
+}
+
+#[test]
+fn hook_runs_at_height() {
+    let mut ensemble = init();
+    let start = ensemble.block().height;
+
+    ensemble.schedule(Schedule::AtHeight(start + 10), tick);
+
+    ensemble.fast_forward_to_height(start + 9).unwrap();
+    let (height, _): (u64, u64) = ensemble.query(CONTRACT, &()).unwrap();
+    assert_eq!(height, 0);
+
+    ensemble.fast_forward_to_height(start + 20).unwrap();
+    let (height, time): (u64, u64) = ensemble.query(CONTRACT, &()).unwrap();
+
+    // The hook runs in the block it was scheduled for and doesn't advance it.
+    assert_eq!(height, start + 10);
+    assert_eq!(time, ensemble.block().time - 50);
+    assert_eq!(ensemble.block().height, start + 20);
+}
+
+#[test]
+fn hooks_run_in_order() {
+    let mut ensemble = init();
+    let start = ensemble.block().height;
+
+    let runs = Rc::new(RefCell::new(vec![]));
+
+    let every = {
+        let runs = runs.clone();
+        ensemble.schedule(Schedule::EveryBlocks(3), move |ensemble| {
+            runs.borrow_mut().push(("every", ensemble.block().height - start));
+
+            Ok(())
+        })
+    };
+
+    {
+        let runs = runs.clone();
+        ensemble.schedule(Schedule::AtHeight(start + 6), move |ensemble| {
+            runs.borrow_mut().push(("once", ensemble.block().height - start));
+
+            Ok(())
+        });
+    }
+
+    ensemble.fast_forward(7).unwrap();
+
+    assert_eq!(*runs.borrow(), vec![("every", 3), ("every", 6), ("once", 6)]);
+
+    assert!(ensemble.unschedule(every));
+    assert!(!ensemble.unschedule(every));
+
+    ensemble.fast_forward(10).unwrap();
+    assert_eq!(runs.borrow().len(), 3);
+}
+
+#[test]
+fn fast_forward_to_time() {
+    let mut ensemble = init();
+    ensemble.block_mut().set_time(Timestamp::from_seconds(1000));
+
+    let runs = Rc::new(RefCell::new(vec![]));
+
+    {
+        let runs = runs.clone();
+        ensemble.schedule(Schedule::EverySeconds(12), move |ensemble| {
+            runs.borrow_mut().push(ensemble.block().time);
+
+            Ok(())
+        });
+    }
+
+    ensemble.schedule(Schedule::AtTime(Timestamp::from_seconds(1023)), tick);
+
+    ensemble.fast_forward_to_time(Timestamp::from_seconds(1042)).unwrap();
+
+    // The last block is shortened to land on the exact time.
+    assert_eq!(ensemble.block().time, 1042);
+    // A hook runs once for blocks that span more than one interval.
+    assert_eq!(*runs.borrow(), vec![1015, 1025, 1040]);
+
+    let (_, time): (u64, u64) = ensemble.query(CONTRACT, &()).unwrap();
+    assert_eq!(time, 1025);
+}
+
+#[test]
+fn failing_hook_stops_fast_forward() {
+    let mut ensemble = init();
+    let start = ensemble.block().height;
+
+    let mut runs = 0;
+    ensemble.schedule(Schedule::EveryBlocks(4), move |_| {
+        runs += 1;
+
+        if runs % 2 == 0 {
+            return Err(anyhow!("Skipping a round").into());
+        }
+
+        Ok(())
+    });
+
+    let err = ensemble.fast_forward(20).unwrap_err();
+    assert_eq!(err.unwrap_contract_error().to_string(), "Skipping a round");
+    assert_eq!(ensemble.block().height, start + 8);
+
+    // Repeating hooks stay scheduled.
+    ensemble.fast_forward(8).unwrap_err();
+    assert_eq!(ensemble.block().height, start + 16);
+}
+
+#[test]
+fn hooks_run_when_transactions_advance_the_block() {
+    let mut ensemble = init();
+    let start = ensemble.block().height;
+
+    let runs = Rc::new(RefCell::new(vec![]));
+
+    let hook = {
+        let runs = runs.clone();
+
+        ensemble.schedule(Schedule::EveryBlocks(2), move |ensemble| {
+            runs.borrow_mut().push(ensemble.block().height);
+
+            // Transactions executed by hooks don't advance the block.
+            tick(ensemble)
+        })
+    };
+
+    for _ in 0..5 {
+        tick(&mut ensemble).unwrap();
+    }
+
+    assert_eq!(*runs.borrow(), vec![start + 2, start + 4]);
+    assert_eq!(ensemble.block().height, start + 5);
+
+    // Frozen blocks don't advance so no hooks are due.
+    ensemble.block_mut().freeze();
+    tick(&mut ensemble).unwrap();
+    tick(&mut ensemble).unwrap();
+    assert_eq!(runs.borrow().len(), 2);
+
+    ensemble.unschedule(hook);
+    ensemble.schedule(Schedule::AtHeight(start + 6), |_| Err(anyhow!("Failed").into()));
+    ensemble.block_mut().unfreeze();
+
+    // The transaction is committed even if a hook fails.
+    let err = tick(&mut ensemble).unwrap_err();
+    assert_eq!(err.unwrap_contract_error().to_string(), "Failed");
+
+    let (height, _): (u64, u64) = ensemble.query(CONTRACT, &()).unwrap();
+    assert_eq!(height, start + 5);
+    assert_eq!(ensemble.block().height, start + 6);
+}
+
+#[test]
+fn hooks_can_unschedule_themselves() {
+    let mut ensemble = init();
+
+    let id = Rc::new(RefCell::new(None));
+    let runs = Rc::new(RefCell::new(0));
+
+    let hook = {
+        let (id, runs) = (id.clone(), runs.clone());
+
+        ensemble.schedule(Schedule::EveryBlocks(1), move |ensemble| {
+            *runs.borrow_mut() += 1;
+
+            if *runs.borrow() == 2 {
+                ensemble.unschedule(id.borrow().unwrap());
+            }
+
+            Ok(())
+        })
+    };
+
+    *id.borrow_mut() = Some(hook);
+
+    ensemble.fast_forward(5).unwrap();
+    assert_eq!(*runs.borrow(), 2);
+}
+
+#[test]
+fn fast_forward_advances_frozen_blocks() {
+    let mut ensemble = init();
+    ensemble.block_mut().freeze();
+
+    let start = ensemble.block().clone();
+
+    ensemble.schedule(Schedule::EveryBlocks(2), tick);
+    ensemble.fast_forward(4).unwrap();
+
+    assert_eq!(ensemble.block().height, start.height + 4);
+    assert_eq!(ensemble.block().time, start.time + 20);
+    assert!(ensemble.block().is_frozen());
+
+    tick(&mut ensemble).unwrap();
+    assert_eq!(ensemble.block().height, start.height + 4);
+}
+
+#[test]
+#[should_panic(expected = "Can't fast forward to height")]
+fn cannot_fast_forward_to_past_height() {
+    let mut ensemble = init();
+    ensemble.fast_forward(2).unwrap();
+
+    let _ = ensemble.fast_forward_to_height(1);
+}
@@ -0,0 +1,313 @@
+use crate::{ContractEnsemble, ContractHarness, MockEnv, AnyResult, Snapshot, SNAPSHOT_VERSION};
+use fadroma::prelude::*;
+
+const SENDER: &str = "sender";
+const A_ADDR: &str = "a";
+const B_ADDR: &str = "b";
+
+const NUM_KEY: &[u8] = b"num";
+
+struct Counter;
+
+impl ContractHarness for Counter {
+    fn instantiate(&self, _deps: DepsMut, _env: Env, _info: MessageInfo, _msg: Binary) -> AnyResult<Response> {
+        Ok(Response::default())
+    }
+
+    fn execute(&self, deps: DepsMut, _env: Env, _info: MessageInfo, _msg: Binary) -> AnyResult<Response> {
+        let number: u32 = storage::load(deps.storage, NUM_KEY)?.unwrap_or_default();
+        storage::save(deps.storage, NUM_KEY, &(number + 1))?;
+
+        Ok(Response::default())
+    }
+
+    fn query(&self, deps: Deps, _env: Env, _msg: Binary) -> AnyResult<Binary> {
+        let number: u32 = storage::load(deps.storage, NUM_KEY)?.unwrap_or_default();
+
+        Ok(to_binary(&number)?)
+    }
+}
+
+fn balance(ensemble: &ContractEnsemble, address: &str) -> u128 {
+    ensemble.balances(address)
+        .and_then(|x| x.get("uscrt"))
+        .map(|x| x.u128())
+        .unwrap_or_default()
+}
+
+fn init() -> (ContractEnsemble, ContractCode) {
+    let mut ensemble = ContractEnsemble::new();
+    let counter = ensemble.register(Box::new(Counter));
+
+    ensemble.add_funds(SENDER, vec![coin(1000, "uscrt")]);
+    ensemble.instantiate_with_admin(counter.id, &(), MockEnv::new(SENDER, A_ADDR), SENDER).unwrap();
+    ensemble.execute(&(), MockEnv::new(SENDER, A_ADDR)).unwrap();
+
+    (ensemble, counter)
+}
+
+#[test]
+fn restores_the_whole_state() {
+    let (mut ensemble, counter) = init();
+
+    let height = ensemble.block().height;
+    let time = ensemble.block().time;
+
+    let snapshot = ensemble.snapshot();
+    assert_eq!(snapshot.instances().collect::<Vec<_>>(), vec![A_ADDR]);
+    assert_eq!(snapshot.block_height(), height);
+
+    ensemble.execute(
+        &(),
+        MockEnv::new(SENDER, A_ADDR).sent_funds(vec![coin(100, "uscrt")])
+    ).unwrap();
+    ensemble.instantiate(counter.id, &(), MockEnv::new(SENDER, B_ADDR)).unwrap();
+    ensemble.clear_admin(MockEnv::new(SENDER, A_ADDR)).unwrap();
+    ensemble.add_funds("wallet", vec![coin(100, "uscrt")]);
+    ensemble.block_mut().increment(10);
+
+    assert_eq!(ensemble.query::<_, u32>(A_ADDR, &()).unwrap(), 2);
+    assert_eq!(balance(&ensemble, A_ADDR), 100);
+
+    ensemble.restore(&snapshot);
+
+    assert_eq!(ensemble.query::<_, u32>(A_ADDR, &()).unwrap(), 1);
+    assert!(ensemble.query::<_, u32>(B_ADDR, &()).is_err());
+    assert_eq!(ensemble.contract_admin(A_ADDR).unwrap(), Some(SENDER));
+    assert_eq!(balance(&ensemble, SENDER), 1000);
+    assert_eq!(balance(&ensemble, A_ADDR), 0);
+    assert!(ensemble.balances("wallet").is_none());
+    assert_eq!(ensemble.block().height, height);
+    assert_eq!(ensemble.block().time, time);
+}
+
+#[test]
+fn snapshot_can_be_restored_multiple_times() {
+    let (mut ensemble, counter) = init();
+    let snapshot = ensemble.snapshot();
+
+    for _ in 0..3 {
+        ensemble.execute(&(), MockEnv::new(SENDER, A_ADDR)).unwrap();
+        ensemble.instantiate(counter.id, &(), MockEnv::new(SENDER, B_ADDR)).unwrap();
+
+        assert_eq!(ensemble.query::<_, u32>(A_ADDR, &()).unwrap(), 2);
+
+        ensemble.restore(&snapshot);
+    }
+
+    assert_eq!(ensemble.query::<_, u32>(A_ADDR, &()).unwrap(), 1);
+
+    // Snapshots are independent of each other.
+    ensemble.execute(&(), MockEnv::new(SENDER, A_ADDR)).unwrap();
+    let second = ensemble.snapshot();
+
+    ensemble.restore(&snapshot);
+    assert_eq!(ensemble.query::<_, u32>(A_ADDR, &()).unwrap(), 1);
+
+    ensemble.restore(&second);
+    assert_eq!(ensemble.query::<_, u32>(A_ADDR, &()).unwrap(), 2);
+}
+
+#[test]
+#[should_panic(expected = "Snapshot references contracts that are not registered in this ensemble.")]
+fn cannot_restore_into_ensemble_with_missing_code() {
+    let (ensemble, _) = init();
+    let snapshot = ensemble.snapshot();
+
+    ContractEnsemble::new().restore(&snapshot);
+}
+
+#[test]
+fn loads_saved_state_into_fresh_ensemble() {
+    let (mut ensemble, _) = init();
+    ensemble.set_chain_id("pulsar-3");
+    ensemble.block_mut().freeze();
+
+    let path = std::env::temp_dir().join(format!("ensemble-snapshot-{}.json", std::process::id()));
+    ensemble.snapshot().save(&path).unwrap();
+
+    let snapshot = Snapshot::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(snapshot.chain_id(), "pulsar-3");
+
+    let mut loaded = ContractEnsemble::new();
+    loaded.register(Box::new(Counter));
+    loaded.restore(&snapshot);
+
+    assert_eq!(loaded.query::<_, u32>(A_ADDR, &()).unwrap(), 1);
+    assert_eq!(loaded.contract_admin(A_ADDR).unwrap(), Some(SENDER));
+    assert_eq!(balance(&loaded, SENDER), 1000);
+    assert_eq!(loaded.block().height, ensemble.block().height);
+    assert_eq!(loaded.block().time, ensemble.block().time);
+
+    // Frozen blocks are preserved.
+    loaded.execute(&(), MockEnv::new(SENDER, A_ADDR)).unwrap();
+    assert_eq!(loaded.block().height, ensemble.block().height);
+    assert_eq!(loaded.query::<_, u32>(A_ADDR, &()).unwrap(), 2);
+
+    // Serialization is deterministic.
+    assert_eq!(loaded.snapshot().to_bytes(), {
+        ensemble.execute(&(), MockEnv::new(SENDER, A_ADDR)).unwrap();
+        ensemble.snapshot().to_bytes()
+    });
+}
+
+#[test]
+fn rejects_unsupported_snapshot_version() {
+    let (ensemble, _) = init();
+    let bytes = String::from_utf8(ensemble.snapshot().to_bytes()).unwrap().replacen(
+        &format!("\"version\": {}", SNAPSHOT_VERSION),
+        &format!("\"version\": {}", SNAPSHOT_VERSION + 1),
+        1
+    );
+
+    let err = Snapshot::from_bytes(bytes.as_bytes()).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "Ensemble error - Snapshot: Unsupported snapshot version {}, expected {}",
+            SNAPSHOT_VERSION + 1,
+            SNAPSHOT_VERSION
+        )
+    );
+
+    assert!(Snapshot::from_bytes(b"{}").is_err());
+}
+
+#[cfg(feature = "staking")]
+#[test]
+fn restores_delegations() {
+    const VALIDATOR: &str = "validator";
+
+    let (mut ensemble, _) = init();
+    ensemble.add_validator(Validator {
+        address: VALIDATOR.into(),
+        commission: Decimal::percent(5),
+        max_commission: Decimal::percent(10),
+        max_change_rate: Decimal::percent(1),
+    });
+
+    let bonded_denom = ensemble.ctx.delegations.bonded_denom().to_string();
+    ensemble.ctx.delegations.delegate(
+        SENDER.into(),
+        VALIDATOR.into(),
+        coin(300, &bonded_denom)
+    ).unwrap();
+
+    let expected = vec![Delegation {
+        delegator: Addr::unchecked(SENDER),
+        validator: VALIDATOR.into(),
+        amount: coin(300, &bonded_denom)
+    }];
+
+    let snapshot = ensemble.snapshot();
+
+    let time = Timestamp::from_seconds(ensemble.block().time);
+    ensemble.ctx.delegations.undelegate(
+        SENDER.into(),
+        VALIDATOR.into(),
+        coin(100, &bonded_denom),
+        time
+    ).unwrap();
+    assert_eq!(ensemble.delegations(SENDER)[0].amount, coin(200, &bonded_denom));
+
+    ensemble.restore(&snapshot);
+    assert_eq!(ensemble.delegations(SENDER), expected);
+
+    let mut loaded = ContractEnsemble::new();
+    loaded.register(Box::new(Counter));
+    loaded.restore(&Snapshot::from_bytes(&snapshot.to_bytes()).unwrap());
+
+    assert_eq!(loaded.delegations(SENDER), expected);
+}
+
+#[cfg(feature = "staking")]
+#[test]
+fn equal_snapshots_serialize_to_identical_bytes() {
+    const VALIDATORS: usize = 8;
+    const DELEGATORS: usize = 16;
+
+    // Apply the same operations in a different order so that
+    // any hash based collections would end up iterating differently.
+    let build = |reverse: bool| {
+        let (mut ensemble, _) = init();
+        let bonded_denom = ensemble.ctx.delegations.bonded_denom().to_string();
+
+        for i in 0..VALIDATORS {
+            ensemble.add_validator(Validator {
+                address: format!("validator{}", i),
+                commission: Decimal::percent(i as u64),
+                max_commission: Decimal::percent(50),
+                max_change_rate: Decimal::percent(1),
+            });
+        }
+
+        let mut pairs: Vec<(usize, usize)> = (0..DELEGATORS)
+            .flat_map(|d| (0..VALIDATORS).map(move |v| (d, v)))
+            .collect();
+
+        if reverse {
+            pairs.reverse();
+        }
+
+        for (d, v) in pairs {
+            ensemble.ctx.delegations.delegate(
+                format!("delegator{}", d),
+                format!("validator{}", v),
+                coin(100, &bonded_denom)
+            ).unwrap();
+        }
+
+        ensemble.distribute_rewards(10_000u128);
+
+        let mut jailed: Vec<usize> = (0..VALIDATORS).step_by(2).collect();
+
+        if reverse {
+            jailed.reverse();
+        }
+
+        for v in jailed {
+            ensemble.jail_validator(format!("validator{}", v)).unwrap();
+        }
+
+        ensemble
+    };
+
+    let a = build(false).snapshot().to_bytes();
+    let b = build(true).snapshot().to_bytes();
+
+    assert_eq!(String::from_utf8(a).unwrap(), String::from_utf8(b).unwrap());
+}
+
+#[test]
+fn loads_snapshots_regardless_of_staking_feature() {
+    let (ensemble, _) = init();
+
+    let mut file: serde_json::Value = serde_json::from_slice(&ensemble.snapshot().to_bytes()).unwrap();
+    assert!(file.get("delegations").is_some());
+
+    // Saved with the staking feature enabled.
+    file["delegations"] = serde_json::json!({
+        "bonded_denom": "uscrt",
+        "validators": [],
+        "delegators": {},
+        "unbondings": [],
+        "unbonding_period": 0,
+        "jailed": [],
+        "commission": {}
+    });
+    let with_staking = Snapshot::from_bytes(&serde_json::to_vec(&file).unwrap()).unwrap();
+
+    // Saved with the staking feature disabled.
+    file["delegations"] = serde_json::Value::Null;
+    let without_staking = Snapshot::from_bytes(&serde_json::to_vec(&file).unwrap()).unwrap();
+
+    for snapshot in [with_staking, without_staking] {
+        let mut loaded = ContractEnsemble::new();
+        loaded.register(Box::new(Counter));
+        loaded.restore(&snapshot);
+
+        assert_eq!(loaded.query::<_, u32>(A_ADDR, &()).unwrap(), 1);
+    }
+}
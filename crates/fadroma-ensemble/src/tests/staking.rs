@@ -1,4 +1,6 @@
-use crate::{ContractEnsemble, EnsembleError};
+use serde::{Deserialize, Serialize};
+
+use crate::{ContractEnsemble, ContractHarness, MockEnv, AnyResult, EnsembleError, EventMatcher, Unbonding};
 use fadroma::prelude::*;
 
 #[test]
@@ -9,7 +11,7 @@ fn staking() {
         "something".to_string()
     );
 
-    let mut ensemble = ContractEnsemble::new();
+    let mut ensemble = ContractEnsemble::new_with_denom("uscrt");
     assert_eq!(ensemble.ctx.delegations.bonded_denom(), "uscrt");
 
     let addr1 = "addr1";
@@ -146,6 +148,7 @@ fn staking() {
             addr1.to_string(),
             val_addr_1.to_string(),
             Coin::new(500u128, "uscrt"),
+            Timestamp::from_seconds(ensemble.block().time),
         )
         .unwrap();
     match ensemble.ctx.delegations.delegation(&addr1, &val_addr_1) {
@@ -165,6 +168,7 @@ fn staking() {
         addr1.to_string(),
         val_addr_2.to_string(),
         Coin::new(300u128, "uscrt"),
+        Timestamp::from_seconds(ensemble.block().time),
     ) {
         Err(error) => match error {
             EnsembleError::Staking(msg) => assert_eq!("Delegation not found", msg),
@@ -176,6 +180,7 @@ fn staking() {
         addr1.to_string(),
         val_addr_1.to_string(),
         Coin::new(600u128, "uscrt"),
+        Timestamp::from_seconds(ensemble.block().time),
     ) {
         Err(error) => match error {
             EnsembleError::Staking(msg) => assert_eq!("Insufficient funds", msg),
@@ -259,6 +264,7 @@ fn staking() {
             addr1.to_string(),
             val_addr_2.to_string(),
             Coin::new(325u128, "uscrt"),
+            Timestamp::from_seconds(ensemble.block().time),
         )
         .unwrap();
     match ensemble.ctx.delegations.delegation(&addr1, &val_addr_1) {
@@ -394,3 +400,294 @@ fn staking() {
         vec![Coin::new(875u128, "uscrt")], // 500 undelegate, 325 undelegate, 50 rewards
     );
 }
+
+const ALICE: &str = "alice";
+const BOB: &str = "bob";
+const STAKER: &str = "staker";
+const VALIDATOR_1: &str = "validator1";
+const VALIDATOR_2: &str = "validator2";
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ExecuteMsg {
+    Delegate { validator: String, amount: u64 },
+    Undelegate { validator: String, amount: u64 }
+}
+
+/// Returns the addresses of all validators when queried.
+struct Staker;
+
+impl ContractHarness for Staker {
+    fn instantiate(&self, _deps: DepsMut, _env: Env, _info: MessageInfo, _msg: Binary) -> AnyResult<Response> {
+        Ok(Response::default())
+    }
+
+    fn execute(&self, _deps: DepsMut, _env: Env, _info: MessageInfo, msg: Binary) -> AnyResult<Response> {
+        let msg = match from_binary(&msg)? {
+            ExecuteMsg::Delegate { validator, amount } => StakingMsg::Delegate {
+                validator,
+                amount: coin(amount.into(), "uscrt")
+            },
+            ExecuteMsg::Undelegate { validator, amount } => StakingMsg::Undelegate {
+                validator,
+                amount: coin(amount.into(), "uscrt")
+            }
+        };
+
+        Ok(Response::default().add_message(msg))
+    }
+
+    fn query(&self, deps: Deps, _env: Env, _msg: Binary) -> AnyResult<Binary> {
+        let validators: Vec<String> = deps.querier.query_all_validators()?
+            .into_iter()
+            .map(|x| x.address)
+            .collect();
+
+        Ok(to_binary(&validators)?)
+    }
+}
+
+fn init() -> ContractEnsemble {
+    let mut ensemble = ContractEnsemble::new_with_denom("uscrt");
+
+    ensemble.add_validator(Validator {
+        address: VALIDATOR_1.into(),
+        commission: Decimal::percent(10),
+        max_commission: Decimal::percent(20),
+        max_change_rate: Decimal::percent(1),
+    });
+    ensemble.add_validator(Validator {
+        address: VALIDATOR_2.into(),
+        commission: Decimal::zero(),
+        max_commission: Decimal::percent(10),
+        max_change_rate: Decimal::percent(1),
+    });
+
+    ensemble.ctx.delegations.delegate(ALICE.into(), VALIDATOR_1.into(), coin(600, "uscrt")).unwrap();
+    ensemble.ctx.delegations.delegate(BOB.into(), VALIDATOR_2.into(), coin(400, "uscrt")).unwrap();
+
+    ensemble
+}
+
+fn init_staker(ensemble: &mut ContractEnsemble) {
+    let staker = ensemble.register(Box::new(Staker));
+    ensemble.instantiate(staker.id, &(), MockEnv::new(ALICE, STAKER)).unwrap();
+    ensemble.add_funds(STAKER, vec![coin(100, "uscrt")]);
+}
+
+fn delegate(validator: &str, amount: u64) -> ExecuteMsg {
+    ExecuteMsg::Delegate { validator: validator.into(), amount }
+}
+
+fn undelegate(validator: &str, amount: u64) -> ExecuteMsg {
+    ExecuteMsg::Undelegate { validator: validator.into(), amount }
+}
+
+fn rewards(ensemble: &ContractEnsemble, delegator: &str, validator: &str) -> u128 {
+    ensemble.delegation(delegator, validator).unwrap().accumulated_rewards[0].amount.u128()
+}
+
+#[test]
+fn distributes_rewards_by_stake_minus_commission() {
+    let mut ensemble = init();
+    ensemble.distribute_rewards(1000u128);
+
+    assert_eq!(rewards(&ensemble, ALICE, VALIDATOR_1), 540);
+    assert_eq!(rewards(&ensemble, BOB, VALIDATOR_2), 400);
+    assert_eq!(ensemble.validator_commission(VALIDATOR_1).u128(), 60);
+    assert!(ensemble.validator_commission(VALIDATOR_2).is_zero());
+
+    // Jailed validators don't earn rewards.
+    ensemble.jail_validator(VALIDATOR_2).unwrap();
+    ensemble.set_validator_commission(VALIDATOR_1, Decimal::percent(20)).unwrap();
+    ensemble.distribute_rewards(100u128);
+
+    assert_eq!(rewards(&ensemble, ALICE, VALIDATOR_1), 620);
+    assert_eq!(rewards(&ensemble, BOB, VALIDATOR_2), 400);
+    assert_eq!(ensemble.validator_commission(VALIDATOR_1).u128(), 80);
+
+    let err = ensemble.set_validator_commission(VALIDATOR_2, Decimal::percent(11)).unwrap_err();
+    assert_eq!(err.to_string(), "Ensemble error - Staking: Commission rate exceeds the maximum");
+}
+
+#[test]
+fn slashing_reduces_delegations_and_unbondings() {
+    let mut ensemble = init();
+    ensemble.ctx.delegations.undelegate(ALICE.into(), VALIDATOR_1.into(), coin(200, "uscrt"), Timestamp::from_seconds(0)).unwrap();
+
+    let slashed = ensemble.slash_validator(VALIDATOR_1, Decimal::percent(10)).unwrap();
+    assert_eq!(slashed.u128(), 60);
+
+    let delegation = ensemble.delegation(ALICE, VALIDATOR_1).unwrap();
+    assert_eq!(delegation.amount, coin(360, "uscrt"));
+    assert_eq!(delegation.can_redelegate, coin(360, "uscrt"));
+    assert_eq!(ensemble.delegation(BOB, VALIDATOR_2).unwrap().amount, coin(400, "uscrt"));
+
+    // Only the slashed unbonding amount is paid out.
+    ensemble.fast_forward_delegation_waits();
+    assert_eq!(ensemble.balances(ALICE).unwrap()["uscrt"].u128(), 180);
+
+    let err = ensemble.slash_validator(VALIDATOR_1, Decimal::percent(101)).unwrap_err();
+    assert_eq!(err.to_string(), "Ensemble error - Staking: Slash fraction must not be greater than 1");
+
+    ensemble.slash_validator("validator3", Decimal::percent(1)).unwrap_err();
+}
+
+#[test]
+fn supply_only_changes_when_funds_are_minted_burned_or_slashed() {
+    let mut ensemble = ContractEnsemble::new_with_denom("uscrt");
+    ensemble.add_validator(Validator {
+        address: VALIDATOR_2.into(),
+        commission: Decimal::zero(),
+        max_commission: Decimal::percent(10),
+        max_change_rate: Decimal::percent(1),
+    });
+
+    init_staker(&mut ensemble);
+    assert_eq!(ensemble.supply("uscrt").u128(), 100);
+
+    ensemble.execute(&delegate(VALIDATOR_2, 80), MockEnv::new(ALICE, STAKER)).unwrap();
+    ensemble.execute(&undelegate(VALIDATOR_2, 30), MockEnv::new(ALICE, STAKER)).unwrap();
+    assert_eq!(ensemble.balances(STAKER).unwrap()["uscrt"].u128(), 20);
+    assert_eq!(ensemble.supply("uscrt").u128(), 100);
+
+    // 10% of the 50 that are still delegated and the 30 that are unbonding.
+    let slashed = ensemble.slash_validator(VALIDATOR_2, Decimal::percent(10)).unwrap();
+    assert_eq!(slashed.u128(), 8);
+    assert_eq!(ensemble.supply("uscrt").u128(), 92);
+
+    ensemble.fast_forward_delegation_waits();
+    assert_eq!(ensemble.balances(STAKER).unwrap()["uscrt"].u128(), 47);
+    assert_eq!(ensemble.delegation(STAKER, VALIDATOR_2).unwrap().amount, coin(45, "uscrt"));
+    assert_eq!(ensemble.supply("uscrt").u128(), 92);
+
+    ensemble.remove_funds(STAKER, coin(7, "uscrt")).unwrap();
+    assert_eq!(ensemble.supply("uscrt").u128(), 85);
+}
+
+#[test]
+fn jailed_validators_cannot_be_delegated_to() {
+    let mut ensemble = init();
+    init_staker(&mut ensemble);
+
+    ensemble.jail_validator(VALIDATOR_1).unwrap();
+    assert!(ensemble.is_jailed(VALIDATOR_1));
+
+    let err = ensemble.execute(&delegate(VALIDATOR_1, 50), MockEnv::new(ALICE, STAKER)).unwrap_err();
+    assert_eq!(err.to_string(), "Ensemble error - Staking: Validator is jailed");
+    assert_eq!(ensemble.balances(STAKER).unwrap()["uscrt"].u128(), 100);
+
+    let validators: Vec<String> = ensemble.query(STAKER, &()).unwrap();
+    assert_eq!(validators, [VALIDATOR_2]);
+
+    ensemble.ctx.delegations.redelegate(BOB.into(), VALIDATOR_2.into(), VALIDATOR_1.into(), coin(10, "uscrt")).unwrap_err();
+    ensemble.jail_validator(VALIDATOR_1).unwrap_err();
+
+    ensemble.unjail_validator(VALIDATOR_1).unwrap();
+    ensemble.unjail_validator(VALIDATOR_1).unwrap_err();
+
+    ensemble.execute(&delegate(VALIDATOR_1, 50), MockEnv::new(ALICE, STAKER)).unwrap();
+    assert_eq!(ensemble.delegation(STAKER, VALIDATOR_1).unwrap().amount, coin(50, "uscrt"));
+
+    let validators: Vec<String> = ensemble.query(STAKER, &()).unwrap();
+    assert_eq!(validators, [VALIDATOR_1, VALIDATOR_2]);
+}
+
+#[test]
+fn unbondings_complete_after_unbonding_period() {
+    let mut ensemble = init();
+    init_staker(&mut ensemble);
+
+    ensemble.set_unbonding_period(100);
+    ensemble.block_mut().set_time(Timestamp::from_seconds(1_600_000_000));
+    ensemble.block_mut().exact_increments(1, 10);
+
+    ensemble.execute(&delegate(VALIDATOR_1, 100), MockEnv::new(ALICE, STAKER)).unwrap();
+
+    let time = Timestamp::from_seconds(ensemble.block().time);
+    let resp = ensemble.execute(&undelegate(VALIDATOR_1, 40), MockEnv::new(ALICE, STAKER)).unwrap();
+
+    resp.event_log().assert(EventMatcher::new("unbond")
+        .attr("validator", VALIDATOR_1)
+        .attr("completion_time", "2020-09-13T12:28:30Z"));
+
+    ensemble.execute(&undelegate(VALIDATOR_1, 20), MockEnv::new(ALICE, STAKER)).unwrap();
+
+    assert_eq!(ensemble.unbondings(STAKER), vec![
+        Unbonding {
+            delegator: Addr::unchecked(STAKER),
+            validator: VALIDATOR_1.into(),
+            amount: coin(40, "uscrt"),
+            completion_time: time.plus_seconds(100)
+        },
+        Unbonding {
+            delegator: Addr::unchecked(STAKER),
+            validator: VALIDATOR_1.into(),
+            amount: coin(20, "uscrt"),
+            completion_time: time.plus_seconds(110)
+        }
+    ]);
+    assert!(ensemble.unbondings(ALICE).is_empty());
+
+    ensemble.fast_forward(7).unwrap();
+    assert_eq!(ensemble.unbondings(STAKER).len(), 2);
+    assert_eq!(ensemble.balances(STAKER).unwrap()["uscrt"].u128(), 0);
+
+    // The first unbonding completes at the block before the second one.
+    ensemble.fast_forward(1).unwrap();
+    assert_eq!(ensemble.unbondings(STAKER).len(), 1);
+    assert_eq!(ensemble.balances(STAKER).unwrap()["uscrt"].u128(), 40);
+
+    // Blocks advanced manually are taken into account by the next transaction.
+    ensemble.block_mut().increment(1);
+    ensemble.execute(&delegate(VALIDATOR_1, 10), MockEnv::new(ALICE, STAKER)).unwrap();
+
+    assert!(ensemble.unbondings(STAKER).is_empty());
+    assert_eq!(ensemble.balances(STAKER).unwrap()["uscrt"].u128(), 50);
+    assert_eq!(ensemble.delegation(STAKER, VALIDATOR_1).unwrap().amount, coin(50, "uscrt"));
+}
+
+#[test]
+fn unbondings_complete_when_block_is_advanced_manually() {
+    let mut ensemble = init();
+    init_staker(&mut ensemble);
+
+    ensemble.set_unbonding_period(100);
+    ensemble.execute(&delegate(VALIDATOR_1, 100), MockEnv::new(ALICE, STAKER)).unwrap();
+    ensemble.execute(&undelegate(VALIDATOR_1, 40), MockEnv::new(ALICE, STAKER)).unwrap();
+
+    let completion_time = ensemble.unbondings(STAKER)[0].completion_time;
+
+    ensemble.block_mut().set_time(completion_time.minus_seconds(1));
+    assert_eq!(ensemble.unbondings(STAKER).len(), 1);
+    assert!(ensemble.balances(STAKER).unwrap()["uscrt"].is_zero());
+
+    ensemble.block_mut().increment(1);
+    assert!(ensemble.unbondings(STAKER).is_empty());
+    assert_eq!(ensemble.balances(STAKER).unwrap()["uscrt"].u128(), 40);
+}
+
+#[test]
+fn unbondings_are_slashed_and_included_in_snapshots() {
+    let mut ensemble = init();
+    init_staker(&mut ensemble);
+
+    ensemble.execute(&delegate(VALIDATOR_1, 100), MockEnv::new(ALICE, STAKER)).unwrap();
+    ensemble.execute(&undelegate(VALIDATOR_1, 50), MockEnv::new(ALICE, STAKER)).unwrap();
+
+    let snapshot = ensemble.snapshot();
+    ensemble.slash_validator(VALIDATOR_1, Decimal::percent(50)).unwrap();
+
+    assert_eq!(ensemble.unbondings(STAKER)[0].amount, coin(25, "uscrt"));
+
+    ensemble.restore(&snapshot);
+    assert_eq!(ensemble.unbondings(STAKER)[0].amount, coin(50, "uscrt"));
+
+    let period = ensemble.unbonding_period();
+    assert_eq!(period, crate::DEFAULT_UNBONDING_PERIOD);
+
+    let completion_time = ensemble.unbondings(STAKER)[0].completion_time;
+    ensemble.fast_forward_to_time(completion_time).unwrap();
+
+    assert_eq!(ensemble.balances(STAKER).unwrap()["uscrt"].u128(), 50);
+}
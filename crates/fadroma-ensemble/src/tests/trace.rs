@@ -0,0 +1,244 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ContractEnsemble, ContractHarness, MockEnv, AnyResult,
+    TraceKind, anyhow::bail
+};
+use fadroma::prelude::*;
+
+const SENDER: &str = "sender";
+const FACTORY: &str = "factory";
+const PRODUCT: &str = "product";
+
+const INSTANTIATE_REPLY_ID: u64 = 1;
+const PRODUCTS_KEY: &[u8] = b"products";
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum FactoryMsg {
+    Create { code_id: u64, code_hash: String, fail: bool }
+}
+
+#[derive(Serialize, Deserialize)]
+struct ProductInit {
+    fail: bool
+}
+
+struct Factory;
+
+impl ContractHarness for Factory {
+    fn instantiate(&self, _deps: DepsMut, _env: Env, _info: MessageInfo, _msg: Binary) -> AnyResult<Response> {
+        Ok(Response::default())
+    }
+
+    fn execute(&self, _deps: DepsMut, _env: Env, _info: MessageInfo, msg: Binary) -> AnyResult<Response> {
+        let FactoryMsg::Create { code_id, code_hash, fail } = from_binary(&msg)?;
+
+        let msg = SubMsg::reply_always(WasmMsg::Instantiate {
+            code_id,
+            code_hash,
+            msg: to_binary(&ProductInit { fail })?,
+            funds: vec![],
+            label: PRODUCT.into(),
+            admin: None
+        }, INSTANTIATE_REPLY_ID);
+
+        Ok(Response::new().add_submessage(msg))
+    }
+
+    fn query(&self, _deps: Deps, _env: Env, _msg: Binary) -> AnyResult<Binary> {
+        bail!("Not implemented")
+    }
+
+    fn reply(&self, deps: DepsMut, _env: Env, reply: Reply) -> AnyResult<Response> {
+        let count: u32 = storage::load(deps.storage, PRODUCTS_KEY)?.unwrap_or_default();
+
+        if reply.result.is_ok() {
+            storage::save(deps.storage, PRODUCTS_KEY, &(count + 1))?;
+        }
+
+        Ok(Response::new().add_attribute("products", (count + 1).to_string()))
+    }
+}
+
+struct Product;
+
+impl ContractHarness for Product {
+    fn instantiate(&self, deps: DepsMut, _env: Env, info: MessageInfo, msg: Binary) -> AnyResult<Response> {
+        let msg: ProductInit = from_binary(&msg)?;
+
+        if msg.fail {
+            bail!("Product failed to instantiate");
+        }
+
+        deps.storage.set(b"factory", info.sender.as_bytes());
+
+        Ok(Response::new().add_message(BankMsg::Send {
+            to_address: info.sender.into_string(),
+            amount: vec![coin(10, "uscrt")]
+        }))
+    }
+
+    fn execute(&self, _deps: DepsMut, _env: Env, _info: MessageInfo, _msg: Binary) -> AnyResult<Response> {
+        bail!("Not implemented")
+    }
+
+    fn query(&self, _deps: Deps, _env: Env, _msg: Binary) -> AnyResult<Binary> {
+        bail!("Not implemented")
+    }
+}
+
+fn init() -> (ContractEnsemble, ContractCode) {
+    let mut ensemble = ContractEnsemble::new();
+
+    let factory = ensemble.register(Box::new(Factory));
+    let product = ensemble.register(Box::new(Product));
+
+    ensemble.instantiate(factory.id, &(), MockEnv::new(SENDER, FACTORY)).unwrap();
+    ensemble.add_funds(SENDER, vec![coin(100, "uscrt")]);
+    ensemble.add_funds(PRODUCT, vec![coin(100, "uscrt")]);
+
+    (ensemble, product)
+}
+
+fn create(product: &ContractCode, fail: bool) -> FactoryMsg {
+    FactoryMsg::Create {
+        code_id: product.id,
+        code_hash: product.code_hash.clone(),
+        fail
+    }
+}
+
+#[test]
+fn tracing_is_disabled_by_default() {
+    let (mut ensemble, product) = init();
+
+    ensemble.execute(&create(&product, false), MockEnv::new(SENDER, FACTORY)).unwrap();
+    assert!(ensemble.trace().is_none());
+}
+
+#[test]
+fn records_every_step() {
+    let (mut ensemble, product) = init();
+    ensemble.set_tracing(true);
+
+    ensemble.execute(
+        &create(&product, false),
+        MockEnv::new(SENDER, FACTORY).sent_funds(vec![coin(5, "uscrt")])
+    ).unwrap();
+
+    let trace = ensemble.trace().unwrap();
+    assert_eq!(trace.steps.len(), 4);
+
+    let execute = &trace.steps[0];
+    assert_eq!(execute.depth, 0);
+    assert_eq!(execute.kind, TraceKind::Execute);
+    assert_eq!(execute.sender, SENDER);
+    assert_eq!(execute.receiver, FACTORY);
+    assert_eq!(execute.funds, vec![coin(5, "uscrt")]);
+    assert!(execute.msg.as_ref().unwrap().starts_with("{\"create\":"));
+    assert!(execute.storage_writes.is_empty());
+
+    let instantiate = &trace.steps[1];
+    assert_eq!(instantiate.depth, 1);
+    assert_eq!(instantiate.kind, TraceKind::Instantiate { code_id: product.id });
+    assert_eq!(instantiate.sender, FACTORY);
+    assert_eq!(instantiate.receiver, PRODUCT);
+    assert_eq!(instantiate.msg.as_deref(), Some("{\"fail\":false}"));
+    assert_eq!(instantiate.storage_writes.len(), 1);
+    assert_eq!(instantiate.storage_writes[0].address, PRODUCT);
+    assert_eq!(instantiate.storage_writes[0].key, Binary::from(b"factory"));
+    assert_eq!(instantiate.storage_writes[0].value, Some(Binary::from(FACTORY.as_bytes())));
+
+    let send = &trace.steps[2];
+    assert_eq!(send.depth, 2);
+    assert_eq!(send.kind, TraceKind::Bank);
+    assert_eq!(send.sender, PRODUCT);
+    assert_eq!(send.receiver, FACTORY);
+    assert_eq!(send.funds, vec![coin(10, "uscrt")]);
+    assert!(send.events.iter().any(|x| x.ty == "transfer"));
+
+    let reply = &trace.steps[3];
+    assert_eq!(reply.depth, 1);
+    assert_eq!(reply.kind, TraceKind::Reply { id: INSTANTIATE_REPLY_ID, success: true });
+    assert_eq!(reply.sender, PRODUCT);
+    assert_eq!(reply.receiver, FACTORY);
+    assert_eq!(reply.storage_writes.len(), 1);
+    assert!(reply.events[1].attributes.iter().any(|x| x.key == "products" && x.value == "1"));
+    assert!(trace.steps.iter().all(|x| x.error.is_none()));
+}
+
+#[test]
+fn records_failed_steps() {
+    let (mut ensemble, product) = init();
+    ensemble.set_tracing(true);
+
+    ensemble.execute(&create(&product, true), MockEnv::new(SENDER, FACTORY)).unwrap();
+
+    let trace = ensemble.trace().unwrap();
+    assert_eq!(trace.steps.len(), 3);
+
+    assert!(trace.steps[1].error.as_ref().unwrap().contains("Product failed to instantiate"));
+    assert_eq!(trace.steps[2].kind, TraceKind::Reply { id: INSTANTIATE_REPLY_ID, success: false });
+    assert_eq!(trace.steps[2].msg.as_deref(), trace.steps[1].error.as_deref());
+
+    // Only the last transaction is kept.
+    ensemble.execute(&create(&product, false), MockEnv::new(SENDER, FACTORY)).unwrap();
+    assert_eq!(ensemble.trace().unwrap().steps.len(), 4);
+}
+
+#[test]
+fn renders_text_tree() {
+    let (mut ensemble, product) = init();
+    ensemble.set_tracing(true);
+
+    ensemble.execute(&create(&product, false), MockEnv::new(SENDER, FACTORY)).unwrap();
+
+    let text = ensemble.trace().unwrap().to_string();
+
+    assert_eq!(text, "\
+sender -> factory: execute {\"create\":{\"code_hash\":\"test_contract_1\",\"code_id\":1,\"fail\":false}}
+  event execute: contract_address=factory
+  factory -> product: instantiate (code 1) {\"fail\":false}
+    storage product: \"factory\" = \"factory\"
+    event instantiate: contract_address=product, code_id=1
+    product -> factory: send [10uscrt]
+      event coin_spent: amount=10uscrt, spender=product
+      event coin_received: amount=10uscrt, receiver=factory
+      event transfer: amount=10uscrt, recipient=factory, sender=product
+  product -> factory: reply (id 1, ok)
+    storage factory: \"products\" = AQE=
+    event reply: contract_address=factory
+    event wasm: products=1, contract_address=factory
+");
+}
+
+#[test]
+fn renders_sequence_diagrams() {
+    let (mut ensemble, product) = init();
+    ensemble.set_tracing(true);
+
+    ensemble.execute(&create(&product, true), MockEnv::new(SENDER, FACTORY)).unwrap();
+    let trace = ensemble.trace().unwrap();
+
+    let mermaid = trace.to_mermaid();
+    let lines = mermaid.lines().collect::<Vec<_>>();
+
+    assert_eq!(lines[0], "sequenceDiagram");
+    assert_eq!(lines[1], "    participant p0 as sender");
+    assert_eq!(lines[2], "    participant p1 as factory");
+    assert_eq!(lines[3], "    participant p2 as product");
+    assert!(lines[4].starts_with("    p0->>p1: execute {\"create\":"));
+    assert_eq!(lines[5], "    p1-xp2: instantiate (code 1) {\"fail\":true}");
+    assert!(lines[6].starts_with("    p2-->>p1: reply (id 1, error)"));
+
+    let plantuml = trace.to_plantuml();
+    let lines = plantuml.lines().collect::<Vec<_>>();
+
+    assert_eq!(lines[0], "@startuml");
+    assert_eq!(lines[1], "participant \"sender\" as p0");
+    assert!(lines[4].starts_with("p0 -> p1 : execute {\"create\":"));
+    assert_eq!(lines[5], "p1 ->x p2 : instantiate (code 1) {\"fail\":true}");
+    assert!(lines[6].starts_with("p2 --> p1 : reply (id 1, error)"));
+    assert_eq!(lines.last(), Some(&"@enduml"));
+}
@@ -0,0 +1,521 @@
+use serde::{Deserialize, Serialize};
+use cosmwasm_vm::{
+    Backend, Instance, InstanceOptions, Size, call_query_raw,
+    testing::{MockApi, MockQuerier, MockStorage, mock_env}
+};
+use wasm_encoder::{
+    CodeSection, ConstExpr, DataSection, EntityType, ExportKind, ExportSection,
+    Function, FunctionSection, ImportSection, Instruction, MemArg, MemoryType,
+    Module, TypeSection, ValType
+};
+use vanilla_cosmwasm_std as vanilla;
+
+use crate::{
+    ContractEnsemble, ContractHarness, MockEnv, AnyResult,
+    WasmContract, code_hash_of, anyhow::bail, response::ResponseVariants,
+    vm::secret
+};
+use fadroma::{prelude::*, cosmwasm_std::testing};
+
+const SCRT_NULL: &[u8] = include_bytes!("../../../../fixtures/scrt-null.wasm");
+const SCRT_STUB: &[u8] = include_bytes!("../../../../fixtures/scrt-stub.wasm");
+const CW_NULL: &[u8] = include_bytes!("../../../../fixtures/cw-null.wasm");
+const CW_HACKATOM: &[u8] = include_bytes!("../../../../fixtures/cw-hackatom.wasm");
+
+const SENDER: &str = "sender";
+const NULL: &str = "null_contract";
+const PROXY: &str = "proxy";
+const HACKATOM: &str = "hackatom";
+const VERIFIER: &str = "verifier";
+const BENEFICIARY: &str = "beneficiary";
+const RELAY: &str = "relay";
+
+struct Proxy;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ProxyMsg {
+    Execute { address: String, code_hash: String },
+    Query { address: String, code_hash: String }
+}
+
+impl ContractHarness for Proxy {
+    fn instantiate(&self, _deps: DepsMut, _env: Env, _info: MessageInfo, _msg: Binary) -> AnyResult<Response> {
+        Ok(Response::default())
+    }
+
+    fn execute(&self, _deps: DepsMut, _env: Env, _info: MessageInfo, msg: Binary) -> AnyResult<Response> {
+        let ProxyMsg::Execute { address, code_hash } = from_binary(&msg)? else {
+            panic!("Expecting ProxyMsg::Execute");
+        };
+
+        Ok(Response::default().add_message(WasmMsg::Execute {
+            contract_addr: address,
+            code_hash,
+            msg: to_binary(&())?,
+            funds: vec![]
+        }))
+    }
+
+    fn query(&self, deps: Deps, _env: Env, msg: Binary) -> AnyResult<Binary> {
+        let ProxyMsg::Query { address, code_hash } = from_binary(&msg)? else {
+            panic!("Expecting ProxyMsg::Query");
+        };
+
+        let request: QueryRequest<Empty> = WasmQuery::Smart {
+            contract_addr: address,
+            code_hash,
+            msg: to_binary(&())?
+        }.into();
+
+        match deps.querier.raw_query(&to_vec(&request)?) {
+            SystemResult::Ok(ContractResult::Ok(result)) => Ok(result),
+            result => bail!("Unexpected query result: {:?}", result)
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct HackatomInit {
+    verifier: String,
+    beneficiary: String
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+enum HackatomMsg {
+    Release {}
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+enum HackatomQuery {
+    Verifier {}
+}
+
+#[derive(Deserialize)]
+struct VerifierResponse {
+    verifier: String
+}
+
+#[test]
+fn runs_secret_contract() {
+    let mut ensemble = ContractEnsemble::new();
+    let null = ensemble.register(Box::new(WasmContract::new(SCRT_NULL)));
+
+    let resp = ensemble.instantiate(null.id, &(), MockEnv::new(SENDER, NULL)).unwrap();
+    assert_eq!(resp.instance.address.as_str(), NULL);
+
+    let resp = ensemble.execute(&(), MockEnv::new(SENDER, NULL)).unwrap();
+    assert_eq!(resp.address, NULL);
+    assert_eq!(resp.sender, SENDER);
+
+    let result = ensemble.query_raw(NULL, &()).unwrap();
+    assert!(result.is_empty());
+}
+
+#[test]
+fn register_wasm_uses_hash_of_code() {
+    let mut ensemble = ContractEnsemble::new();
+    let null = ensemble.register_wasm(WasmContract::new(SCRT_NULL));
+
+    assert_eq!(null.code_hash, code_hash_of(SCRT_NULL));
+    assert_eq!(ensemble.code_by_hash(&null.code_hash).unwrap().id, null.id);
+
+    ensemble.instantiate(null.id, &(), MockEnv::new(SENDER, NULL)).unwrap();
+}
+
+#[test]
+fn runs_vanilla_contract() {
+    let mut ensemble = ContractEnsemble::new();
+    let null = ensemble.register(Box::new(WasmContract::new(CW_NULL)));
+
+    ensemble.instantiate(null.id, &(), MockEnv::new(SENDER, NULL)).unwrap();
+    ensemble.execute(&(), MockEnv::new(SENDER, NULL)).unwrap();
+
+    let result: () = ensemble.query(NULL, &()).unwrap();
+    assert_eq!(result, ());
+}
+
+#[test]
+fn contract_errors_are_returned() {
+    let mut ensemble = ContractEnsemble::new();
+    let stub = ensemble.register(Box::new(WasmContract::new(SCRT_STUB)));
+
+    let err = ensemble.instantiate(stub.id, &(), MockEnv::new(SENDER, NULL)).unwrap_err();
+    assert!(err.to_string().contains("This contract is not available on this chain."));
+    assert!(ensemble.contract_storage(NULL, |_| ()).is_err());
+}
+
+#[test]
+fn invalid_code_is_an_error() {
+    let mut ensemble = ContractEnsemble::new();
+    let invalid = ensemble.register(Box::new(WasmContract::new(b"not wasm".as_slice())));
+
+    ensemble.instantiate(invalid.id, &(), MockEnv::new(SENDER, NULL)).unwrap_err();
+}
+
+#[test]
+fn interacts_with_harness_contracts() {
+    let mut ensemble = ContractEnsemble::new();
+
+    let null = ensemble.register(Box::new(WasmContract::new(SCRT_NULL)));
+    let proxy = ensemble.register(Box::new(Proxy));
+
+    ensemble.instantiate(null.id, &(), MockEnv::new(SENDER, NULL)).unwrap();
+    ensemble.instantiate(proxy.id, &(), MockEnv::new(SENDER, PROXY)).unwrap();
+
+    let resp = ensemble.execute(
+        &ProxyMsg::Execute { address: NULL.into(), code_hash: null.code_hash.clone() },
+        MockEnv::new(SENDER, PROXY)
+    ).unwrap();
+
+    let ResponseVariants::Execute(resp) = resp.iter().next().unwrap() else {
+        panic!("Expecting ResponseVariants::Execute");
+    };
+    assert_eq!(resp.address, NULL);
+    assert_eq!(resp.sender, PROXY);
+
+    let result = ensemble.query_raw(
+        PROXY,
+        &ProxyMsg::Query { address: NULL.into(), code_hash: null.code_hash }
+    ).unwrap();
+    assert!(result.is_empty());
+}
+
+#[test]
+fn contract_uses_storage_and_funds() {
+    let mut ensemble = ContractEnsemble::new();
+    let hackatom = ensemble.register_wasm(WasmContract::new(CW_HACKATOM));
+
+    ensemble.add_funds(SENDER, vec![coin(1000, "uscrt")]);
+
+    ensemble.instantiate(
+        hackatom.id,
+        &HackatomInit { verifier: VERIFIER.into(), beneficiary: BENEFICIARY.into() },
+        MockEnv::new(SENDER, HACKATOM).sent_funds(vec![coin(1000, "uscrt")])
+    ).unwrap();
+
+    assert_eq!(ensemble.balances(HACKATOM).unwrap()["uscrt"].u128(), 1000);
+
+    ensemble.contract_storage(HACKATOM, |storage| {
+        assert_eq!(storage.range(None, None, Order::Ascending).count(), 1);
+    }).unwrap();
+
+    let result: VerifierResponse = ensemble.query(HACKATOM, &HackatomQuery::Verifier {}).unwrap();
+    assert_eq!(result.verifier, VERIFIER);
+
+    let err = ensemble.execute(
+        &HackatomMsg::Release {},
+        MockEnv::new(SENDER, HACKATOM)
+    ).unwrap_err();
+    assert!(err.to_string().contains("Unauthorized"));
+
+    let resp = ensemble.execute(
+        &HackatomMsg::Release {},
+        MockEnv::new(VERIFIER, HACKATOM)
+    ).unwrap();
+
+    assert!(resp.iter().any(|x| matches!(x, ResponseVariants::Bank(_))));
+    assert_eq!(ensemble.balances(HACKATOM).unwrap()["uscrt"].u128(), 0);
+    assert_eq!(ensemble.balances(BENEFICIARY).unwrap()["uscrt"].u128(), 1000);
+}
+
+#[test]
+fn contract_is_compiled_once() {
+    let contract = WasmContract::new(CW_HACKATOM);
+    let harness: &dyn ContractHarness = &contract;
+
+    assert_eq!(contract.cache_hits(), None);
+
+    let mut deps = testing::mock_dependencies();
+    let msg = to_binary(&HackatomInit { verifier: VERIFIER.into(), beneficiary: BENEFICIARY.into() }).unwrap();
+    harness.instantiate(deps.as_mut(), testing::mock_env(), testing::mock_info(SENDER, &[]), msg).unwrap();
+
+    let msg = to_binary(&HackatomQuery::Verifier {}).unwrap();
+
+    for _ in 0..3 {
+        harness.query(deps.as_ref(), testing::mock_env(), msg.clone()).unwrap();
+    }
+
+    assert_eq!(contract.cache_hits(), Some(4));
+}
+
+#[test]
+fn secret_contract_queries_are_converted() {
+    let code = secret::rewrite(&secret_querier()).unwrap().unwrap();
+
+    let balances = [vanilla::coin(100, "uscrt")];
+    let querier = MockQuerier::<vanilla::Empty>::new(&[("wallet", &balances)])
+        .with_custom_handler(|_| vanilla::SystemResult::Ok(vanilla::ContractResult::Err("custom error".into())));
+    let backend = Backend { api: MockApi::default(), storage: MockStorage::default(), querier };
+
+    let mut instance = Instance::from_code(
+        &code,
+        backend,
+        InstanceOptions { gas_limit: u64::MAX },
+        Some(Size::mebi(16))
+    ).unwrap();
+
+    let env = vanilla::to_json_vec(&mock_env()).unwrap();
+
+    let mut query = |request: QueryRequest<Empty>| -> SystemResult<ContractResult<Binary>> {
+        let result = call_query_raw(&mut instance, &env, &to_vec(&request).unwrap()).unwrap();
+
+        from_slice(&result).unwrap()
+    };
+
+    let result = query(BankQuery::Balance { address: "wallet".into(), denom: "uscrt".into() }.into());
+    let SystemResult::Ok(ContractResult::Ok(balance)) = result else {
+        panic!("Expecting a balance, got: {:?}", result);
+    };
+    let balance: BalanceResponse = from_binary(&balance).unwrap();
+    assert_eq!(balance.amount, coin(100, "uscrt"));
+
+    let result = query(QueryRequest::Custom(Empty {}));
+    assert_eq!(result, SystemResult::Ok(ContractResult::Err("custom error".into())));
+
+    let result = query(WasmQuery::Smart {
+        contract_addr: "absent".into(),
+        code_hash: "hash".into(),
+        msg: to_binary(&()).unwrap()
+    }.into());
+    assert_eq!(result, SystemResult::Err(SystemError::NoSuchContract { addr: "absent".into() }));
+}
+
+#[test]
+fn vanilla_contract_messages_and_queries_are_converted() {
+    let mut ensemble = ContractEnsemble::new();
+    let hackatom = ensemble.register_wasm(WasmContract::new(CW_HACKATOM));
+    let relay = ensemble.register_wasm(WasmContract::new(vanilla_relay()));
+
+    ensemble.add_funds(RELAY, vec![coin(1000, "uscrt")]);
+
+    ensemble.instantiate(
+        hackatom.id,
+        &HackatomInit { verifier: RELAY.into(), beneficiary: BENEFICIARY.into() },
+        MockEnv::new(SENDER, HACKATOM)
+    ).unwrap();
+
+    let query = vanilla::QueryRequest::<vanilla::Empty>::Wasm(vanilla::WasmQuery::Smart {
+        contract_addr: HACKATOM.into(),
+        msg: vanilla::to_json_binary(&HackatomQuery::Verifier {}).unwrap()
+    });
+    ensemble.instantiate(relay.id, &query, MockEnv::new(SENDER, RELAY)).unwrap();
+
+    ensemble.contract_storage(RELAY, |storage| {
+        let result: vanilla::SystemResult<vanilla::ContractResult<vanilla::Binary>> =
+            vanilla::from_json(storage.get(b"result").unwrap()).unwrap();
+        let vanilla::SystemResult::Ok(vanilla::ContractResult::Ok(result)) = result else {
+            panic!("Expecting the verifier, got: {:?}", result);
+        };
+        let result: VerifierResponse = vanilla::from_json(result).unwrap();
+        assert_eq!(result.verifier, RELAY);
+    }).unwrap();
+
+    let response = vanilla::Response::<vanilla::Empty>::new()
+        .add_message(vanilla::WasmMsg::Execute {
+            contract_addr: HACKATOM.into(),
+            msg: vanilla::to_json_binary(&HackatomMsg::Release {}).unwrap(),
+            funds: vec![vanilla::coin(1000, "uscrt")]
+        })
+        .add_message(vanilla::WasmMsg::Instantiate {
+            admin: None,
+            code_id: hackatom.id,
+            msg: vanilla::to_json_binary(&HackatomInit {
+                verifier: VERIFIER.into(),
+                beneficiary: BENEFICIARY.into()
+            }).unwrap(),
+            funds: vec![],
+            label: "other_hackatom".into()
+        });
+
+    let resp = ensemble.execute(
+        &vanilla::ContractResult::Ok(response),
+        MockEnv::new(SENDER, RELAY)
+    ).unwrap();
+
+    assert!(resp.iter().any(|x| matches!(x, ResponseVariants::Instantiate(_))));
+    assert_eq!(ensemble.balances(RELAY).unwrap()["uscrt"].u128(), 0);
+    assert_eq!(ensemble.balances(BENEFICIARY).unwrap()["uscrt"].u128(), 1000);
+}
+
+/// A minimal Secret contract whose query entry point passes the message to `query_chain`
+/// and returns its raw result.
+fn secret_querier() -> Vec<u8> {
+    let mut query = Function::new([]);
+    for instruction in [
+        Instruction::Call(1),
+        Instruction::Drop,
+        Instruction::LocalGet(1),
+        Instruction::Call(0),
+        Instruction::End
+    ] {
+        query.instruction(&instruction);
+    }
+
+    contract_module(
+        &[("query_chain", 0), ("check_gas", 1)],
+        [("query", 4, query)],
+        &[]
+    )
+}
+
+/// A minimal vanilla contract which, when instantiated, passes the message to `query_chain`
+/// and stores the raw result under `result`. When executed, it returns the message as is.
+fn vanilla_relay() -> Vec<u8> {
+    const KEY: i32 = 16;
+    const EMPTY: i32 = 32;
+
+    let mut instantiate = Function::new([]);
+    for instruction in [
+        Instruction::I32Const(KEY),
+        Instruction::LocalGet(2),
+        Instruction::Call(0),
+        Instruction::Call(1),
+        Instruction::I32Const(EMPTY),
+        Instruction::End
+    ] {
+        instantiate.instruction(&instruction);
+    }
+
+    let mut execute = Function::new([]);
+    execute.instruction(&Instruction::LocalGet(2));
+    execute.instruction(&Instruction::End);
+
+    contract_module(
+        &[("query_chain", 0), ("db_write", 5)],
+        [("instantiate", 6, instantiate), ("execute", 6, execute)],
+        &[
+            (KEY, b"result"),
+            (EMPTY, br#"{"ok":{"messages":[],"attributes":[],"events":[],"data":null}}"#)
+        ]
+    )
+}
+
+/// Builds a contract with the given imports from `env` and entry points. Memory is allocated
+/// by bumping the pointer stored at 0 and each of `data` is stored in a region at its offset.
+fn contract_module<const N: usize>(
+    imports: &[(&str, u32)],
+    entry_points: [(&str, u32, Function); N],
+    data: &[(i32, &[u8])]
+) -> Vec<u8> {
+    const HEAP: i32 = 1024;
+
+    let word = MemArg { offset: 0, align: 2, memory_index: 0 };
+    let mut module = Module::new();
+
+    let mut types = TypeSection::new();
+    types.ty().function([ValType::I32], [ValType::I32]);
+    types.ty().function([], [ValType::I64]);
+    types.ty().function([], []);
+    types.ty().function([ValType::I32], []);
+    types.ty().function([ValType::I32, ValType::I32], [ValType::I32]);
+    types.ty().function([ValType::I32, ValType::I32], []);
+    types.ty().function([ValType::I32, ValType::I32, ValType::I32], [ValType::I32]);
+    module.section(&types);
+
+    let mut import_section = ImportSection::new();
+    for (name, ty) in imports {
+        import_section.import("env", name, EntityType::Function(*ty));
+    }
+    module.section(&import_section);
+
+    let mut functions = FunctionSection::new();
+    // interface_version_8, allocate, deallocate
+    for ty in [2, 0, 3] {
+        functions.function(ty);
+    }
+    for (_, ty, _) in &entry_points {
+        functions.function(*ty);
+    }
+    module.section(&functions);
+
+    let mut memory = wasm_encoder::MemorySection::new();
+    memory.memory(MemoryType {
+        minimum: 2,
+        maximum: None,
+        memory64: false,
+        shared: false,
+        page_size_log2: None
+    });
+    module.section(&memory);
+
+    let first = imports.len() as u32;
+
+    let mut exports = ExportSection::new();
+    exports.export("memory", ExportKind::Memory, 0);
+    exports.export("interface_version_8", ExportKind::Func, first);
+    exports.export("allocate", ExportKind::Func, first + 1);
+    exports.export("deallocate", ExportKind::Func, first + 2);
+    for (index, (name, _, _)) in entry_points.iter().enumerate() {
+        exports.export(name, ExportKind::Func, first + 3 + index as u32);
+    }
+    module.section(&exports);
+
+    let mut code = CodeSection::new();
+
+    let mut interface_version = Function::new([]);
+    interface_version.instruction(&Instruction::End);
+    code.function(&interface_version);
+
+    // The region is { offset, capacity, length } followed by the data.
+    let mut allocate = Function::new([(1, ValType::I32)]);
+    for instruction in [
+        Instruction::I32Const(0),
+        Instruction::I32Load(word),
+        Instruction::LocalSet(1),
+        Instruction::LocalGet(1),
+        Instruction::LocalGet(1),
+        Instruction::I32Const(12),
+        Instruction::I32Add,
+        Instruction::I32Store(word),
+        Instruction::LocalGet(1),
+        Instruction::LocalGet(0),
+        Instruction::I32Store(MemArg { offset: 4, ..word }),
+        Instruction::LocalGet(1),
+        Instruction::I32Const(0),
+        Instruction::I32Store(MemArg { offset: 8, ..word }),
+        Instruction::I32Const(0),
+        Instruction::LocalGet(1),
+        Instruction::I32Const(12),
+        Instruction::I32Add,
+        Instruction::LocalGet(0),
+        Instruction::I32Add,
+        Instruction::I32Store(word),
+        Instruction::LocalGet(1),
+        Instruction::End
+    ] {
+        allocate.instruction(&instruction);
+    }
+    code.function(&allocate);
+
+    let mut deallocate = Function::new([]);
+    deallocate.instruction(&Instruction::End);
+    code.function(&deallocate);
+
+    for (_, _, function) in &entry_points {
+        code.function(function);
+    }
+
+    module.section(&code);
+
+    let mut data_section = DataSection::new();
+
+    // The data of the regions is placed at the start of the heap.
+    let mut heap = HEAP;
+    for (offset, bytes) in data {
+        let len = bytes.len() as i32;
+        let region: Vec<u8> = [heap, len, len].iter().flat_map(|x| x.to_le_bytes()).collect();
+
+        data_section.active(0, &ConstExpr::i32_const(*offset), region);
+        data_section.active(0, &ConstExpr::i32_const(heap), bytes.iter().copied());
+
+        heap += len;
+    }
+
+    data_section.active(0, &ConstExpr::i32_const(0), heap.to_le_bytes());
+    module.section(&data_section);
+
+    module.finish()
+}
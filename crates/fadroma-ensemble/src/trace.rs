@@ -0,0 +1,442 @@
+use std::{
+    fmt::{self, Display, Write},
+    collections::BTreeSet
+};
+
+use fadroma::cosmwasm_std::{
+    SubMsg, CosmosMsg, WasmMsg, BankMsg, Binary, Coin, Event
+};
+
+#[cfg(feature = "staking")]
+use fadroma::cosmwasm_std::{StakingMsg, DistributionMsg};
+
+#[cfg(feature = "ibc")]
+use fadroma::cosmwasm_std::IbcMsg;
+
+use super::{
+    ensemble::SubMsgExecuteResult,
+    execution_state::MessageType,
+    state::{State, Op},
+    response::ResponseVariants
+};
+
+/// A record of every step that was executed in a single transaction,
+/// in order of execution. Enable it with [`crate::ContractEnsemble::set_tracing`]
+/// and get the trace of the last transaction with [`crate::ContractEnsemble::trace`].
+///
+/// Failed steps are included as well, even if their error was handled by a reply.
+///
+/// The [`Display`] implementation renders it as an indented text tree.
+/// It can also be rendered as a sequence diagram with [`Trace::to_mermaid`]
+/// and [`Trace::to_plantuml`].
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct Trace {
+    pub steps: Vec<TraceStep>
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct TraceStep {
+    /// How deep in the call tree this step is. The initial message is at 0.
+    /// Replies are at the same depth as the message that they reply to.
+    pub depth: usize,
+    pub kind: TraceKind,
+    /// The address that sent the message. For replies, this
+    /// is the contract whose sub-message is being replied to.
+    pub sender: String,
+    /// The address that received the message.
+    pub receiver: String,
+    /// The message that was sent, decoded as JSON if possible, base64 otherwise.
+    pub msg: Option<String>,
+    /// The funds that were sent along with the message.
+    pub funds: Vec<Coin>,
+    /// The storage keys that were written to during this step.
+    pub storage_writes: Vec<StorageWrite>,
+    /// The events that this step emitted.
+    pub events: Vec<Event>,
+    /// The error that this step failed with, if any.
+    pub error: Option<String>
+}
+
+#[derive(Clone, PartialEq, Debug)]
+#[non_exhaustive]
+pub enum TraceKind {
+    Instantiate {
+        code_id: u64
+    },
+    Execute,
+    Reply {
+        id: u64,
+        /// Whether the sub-message that is replied to succeeded.
+        success: bool
+    },
+    Migrate {
+        code_id: u64
+    },
+    UpdateAdmin,
+    ClearAdmin,
+    Bank,
+    /// Funds burned with `BankMsg::Burn`. The receiver is `bank`.
+    Burn,
+    /// A message handled by a [`crate::Module`]. The receiver is the name of the module.
+    Module,
+    #[cfg(feature = "staking")]
+    Staking,
+    #[cfg(feature = "staking")]
+    Distribution,
+    /// A call to one of the IBC entry points of a contract.
+    #[cfg(feature = "ibc")]
+    IbcEntryPoint {
+        name: &'static str
+    },
+    /// An IBC message sent by a contract. The receiver is the channel id.
+    #[cfg(feature = "ibc")]
+    Ibc
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct StorageWrite {
+    /// The contract whose storage was written to.
+    /// Prefixed with `module:` for the storage of a [`crate::Module`].
+    pub address: String,
+    pub key: Binary,
+    /// The value at the end of the step. [`None`] if the key was removed.
+    pub value: Option<Binary>
+}
+
+impl Trace {
+    /// Renders the trace as a Mermaid sequence diagram.
+    pub fn to_mermaid(&self) -> String {
+        let participants = self.participants();
+        let mut result = String::from("sequenceDiagram\n");
+
+        for (i, name) in participants.iter().enumerate() {
+            writeln!(result, "    participant p{} as {}", i, escape_mermaid(name)).unwrap();
+        }
+
+        for step in &self.steps {
+            let arrow = match (&step.kind, &step.error) {
+                (_, Some(_)) => "-x",
+                (TraceKind::Reply { .. }, None) => "-->>",
+                _ => "->>"
+            };
+
+            writeln!(
+                result,
+                "    p{}{}p{}: {}",
+                index_of(&participants, &step.sender),
+                arrow,
+                index_of(&participants, &step.receiver),
+                escape_mermaid(&step.label())
+            ).unwrap();
+        }
+
+        result
+    }
+
+    /// Renders the trace as a PlantUML sequence diagram.
+    pub fn to_plantuml(&self) -> String {
+        let participants = self.participants();
+        let mut result = String::from("@startuml\n");
+
+        for (i, name) in participants.iter().enumerate() {
+            writeln!(result, "participant \"{}\" as p{}", name.replace('"', "'"), i).unwrap();
+        }
+
+        for step in &self.steps {
+            let arrow = match (&step.kind, &step.error) {
+                (_, Some(_)) => "->x",
+                (TraceKind::Reply { .. }, None) => "-->",
+                _ => "->"
+            };
+
+            writeln!(
+                result,
+                "p{} {} p{} : {}",
+                index_of(&participants, &step.sender),
+                arrow,
+                index_of(&participants, &step.receiver),
+                step.label().replace('\n', " ")
+            ).unwrap();
+        }
+
+        result.push_str("@enduml\n");
+
+        result
+    }
+
+    /// All addresses that appear in the trace, in order of appearance.
+    fn participants(&self) -> Vec<&str> {
+        let mut result: Vec<&str> = vec![];
+
+        for step in &self.steps {
+            for address in [&step.sender, &step.receiver] {
+                if !result.contains(&address.as_str()) {
+                    result.push(address);
+                }
+            }
+        }
+
+        result
+    }
+
+    pub(crate) fn start_step(&mut self, depth: usize, msg: &MessageType) -> TraceStep {
+        let (kind, sender, receiver, msg, funds) = match msg {
+            MessageType::SubMsg { msg, sender } => {
+                let (kind, receiver, msg, funds) = sub_msg_info(msg);
+
+                (kind, sender.clone(), receiver, msg, funds)
+            },
+            MessageType::Reply { id, error, target } => {
+                // The reply is at the same depth as the message it replies to.
+                let sender = self.steps.iter()
+                    .rev()
+                    .find(|x| x.depth == depth && !matches!(x.kind, TraceKind::Reply { .. }))
+                    .map(|x| x.receiver.clone())
+                    .unwrap_or_else(|| target.clone());
+
+                let kind = TraceKind::Reply {
+                    id: *id,
+                    success: error.is_none()
+                };
+
+                (kind, sender, target.clone(), error.clone(), vec![])
+            }
+            #[cfg(feature = "ibc")]
+            MessageType::Ibc { address, msg } => {
+                let kind = TraceKind::IbcEntryPoint { name: msg.name() };
+
+                (kind, msg.counterparty_port().to_string(), address.clone(), Some(msg.summary()), vec![])
+            }
+        };
+
+        TraceStep {
+            depth,
+            kind,
+            sender,
+            receiver,
+            msg,
+            funds,
+            storage_writes: vec![],
+            events: vec![],
+            error: None
+        }
+    }
+
+    pub(crate) fn finish_step(
+        &mut self,
+        mut step: TraceStep,
+        result: &SubMsgExecuteResult,
+        state: &State
+    ) {
+        match result {
+            Ok((resp, events)) => {
+                match resp {
+                    ResponseVariants::Instantiate(resp) =>
+                        step.receiver = resp.instance.address.to_string(),
+                    ResponseVariants::Module(resp) =>
+                        step.receiver = resp.module.clone(),
+                    _ => { }
+                }
+
+                step.events = events.events().to_vec();
+            },
+            Err(err) => step.error = Some(err.to_string())
+        }
+
+        let mut written = BTreeSet::new();
+
+        for op in state.current_scope_ops() {
+            if let Op::StorageWrite { address, key, .. } = op {
+                if !written.insert((address.as_str(), key.as_slice())) {
+                    continue;
+                }
+
+                let value = state.storage(address)
+                    .and_then(|x| x.backing.get(key))
+                    .map(|x| Binary(x.clone()));
+
+                step.storage_writes.push(StorageWrite {
+                    address: address.clone(),
+                    key: Binary(key.clone()),
+                    value
+                });
+            }
+        }
+
+        self.steps.push(step);
+    }
+}
+
+impl TraceStep {
+    /// A short, single line description of the step.
+    fn label(&self) -> String {
+        let mut result = match &self.kind {
+            TraceKind::Instantiate { code_id } => format!("instantiate (code {})", code_id),
+            TraceKind::Execute => "execute".into(),
+            TraceKind::Reply { id, success } => format!(
+                "reply (id {}, {})",
+                id,
+                if *success { "ok" } else { "error" }
+            ),
+            TraceKind::Migrate { code_id } => format!("migrate (code {})", code_id),
+            TraceKind::UpdateAdmin => "update admin".into(),
+            TraceKind::ClearAdmin => "clear admin".into(),
+            TraceKind::Bank => "send".into(),
+            TraceKind::Burn => "burn".into(),
+            TraceKind::Module => "module".into(),
+            #[cfg(feature = "staking")]
+            TraceKind::Staking => "staking".into(),
+            #[cfg(feature = "staking")]
+            TraceKind::Distribution => "distribution".into(),
+            #[cfg(feature = "ibc")]
+            TraceKind::IbcEntryPoint { name } => name.replace('_', " "),
+            #[cfg(feature = "ibc")]
+            TraceKind::Ibc => "ibc".into()
+        };
+
+        if let Some(msg) = &self.msg {
+            write!(result, " {}", msg).unwrap();
+        }
+
+        if !self.funds.is_empty() {
+            write!(result, " [{}]", format_coins(&self.funds)).unwrap();
+        }
+
+        result
+    }
+}
+
+impl Display for Trace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for step in &self.steps {
+            let indent = "  ".repeat(step.depth);
+
+            writeln!(f, "{}{} -> {}: {}", indent, step.sender, step.receiver, step.label())?;
+
+            for write in &step.storage_writes {
+                match &write.value {
+                    Some(value) => writeln!(
+                        f,
+                        "{}  storage {}: {} = {}",
+                        indent,
+                        write.address,
+                        format_bytes(&write.key),
+                        format_bytes(value)
+                    )?,
+                    None => writeln!(
+                        f,
+                        "{}  storage {}: {} removed",
+                        indent,
+                        write.address,
+                        format_bytes(&write.key)
+                    )?
+                }
+            }
+
+            for event in &step.events {
+                let attrs = event.attributes.iter()
+                    .map(|x| format!("{}={}", x.key, x.value))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+
+                writeln!(f, "{}  event {}: {}", indent, event.ty, attrs)?;
+            }
+
+            if let Some(err) = &step.error {
+                writeln!(f, "{}  error: {}", indent, err)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn sub_msg_info(sub_msg: &SubMsg) -> (TraceKind, String, Option<String>, Vec<Coin>) {
+    match &sub_msg.msg {
+        CosmosMsg::Wasm(msg) => match msg {
+            WasmMsg::Execute { contract_addr, msg, funds, .. } =>
+                (TraceKind::Execute, contract_addr.clone(), Some(decode(msg)), funds.clone()),
+            WasmMsg::Instantiate { code_id, msg, funds, label, .. } =>
+                (TraceKind::Instantiate { code_id: *code_id }, label.clone(), Some(decode(msg)), funds.clone()),
+            WasmMsg::Migrate { contract_addr, code_id, msg, .. } =>
+                (TraceKind::Migrate { code_id: *code_id }, contract_addr.clone(), Some(decode(msg)), vec![]),
+            WasmMsg::UpdateAdmin { contract_addr, admin } =>
+                (TraceKind::UpdateAdmin, contract_addr.clone(), Some(admin.clone()), vec![]),
+            WasmMsg::ClearAdmin { contract_addr } =>
+                (TraceKind::ClearAdmin, contract_addr.clone(), None, vec![]),
+            _ => module_msg_info(&sub_msg.msg)
+        },
+        CosmosMsg::Bank(msg) => match msg {
+            BankMsg::Send { to_address, amount } =>
+                (TraceKind::Bank, to_address.clone(), None, amount.clone()),
+            BankMsg::Burn { amount } =>
+                (TraceKind::Burn, "bank".into(), None, amount.clone()),
+            _ => module_msg_info(&sub_msg.msg)
+        },
+        #[cfg(feature = "staking")]
+        CosmosMsg::Staking(msg) => match msg {
+            StakingMsg::Delegate { validator, amount } =>
+                (TraceKind::Staking, validator.clone(), Some("delegate".into()), vec![amount.clone()]),
+            StakingMsg::Undelegate { validator, amount } =>
+                (TraceKind::Staking, validator.clone(), Some("undelegate".into()), vec![amount.clone()]),
+            StakingMsg::Redelegate { dst_validator, amount, .. } =>
+                (TraceKind::Staking, dst_validator.clone(), Some("redelegate".into()), vec![amount.clone()]),
+            _ => module_msg_info(&sub_msg.msg)
+        },
+        #[cfg(feature = "staking")]
+        CosmosMsg::Distribution(msg) => match msg {
+            DistributionMsg::WithdrawDelegatorReward { validator } =>
+                (TraceKind::Distribution, validator.clone(), Some("withdraw rewards".into()), vec![]),
+            _ => module_msg_info(&sub_msg.msg)
+        },
+        #[cfg(feature = "ibc")]
+        CosmosMsg::Ibc(msg) => match msg {
+            IbcMsg::SendPacket { channel_id, data, .. } =>
+                (TraceKind::Ibc, channel_id.clone(), Some(format!("send packet {}", decode(data))), vec![]),
+            IbcMsg::Transfer { channel_id, to_address, amount, .. } =>
+                (TraceKind::Ibc, channel_id.clone(), Some(format!("transfer to {}", to_address)), vec![amount.clone()]),
+            IbcMsg::CloseChannel { channel_id } =>
+                (TraceKind::Ibc, channel_id.clone(), Some("close channel".into()), vec![]),
+            _ => module_msg_info(&sub_msg.msg)
+        },
+        msg => module_msg_info(msg)
+    }
+}
+
+#[inline]
+fn module_msg_info(msg: &CosmosMsg) -> (TraceKind, String, Option<String>, Vec<Coin>) {
+    // The receiver is set to the name of the module once one has handled the message.
+    (TraceKind::Module, "module".into(), Some(format!("{:?}", msg)), vec![])
+}
+
+pub(crate) fn decode(msg: &Binary) -> String {
+    match serde_json::from_slice::<serde_json::Value>(msg.as_slice()) {
+        Ok(value) => value.to_string(),
+        Err(_) => msg.to_base64()
+    }
+}
+
+fn format_bytes(bytes: &Binary) -> String {
+    match std::str::from_utf8(bytes.as_slice()) {
+        Ok(text) if text.chars().all(|x| !x.is_control()) => format!("{:?}", text),
+        _ => bytes.to_base64()
+    }
+}
+
+pub(crate) fn format_coins(coins: &[Coin]) -> String {
+    coins.iter()
+        .map(|x| format!("{}{}", x.amount, x.denom))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[inline]
+fn index_of(participants: &[&str], address: &str) -> usize {
+    participants.iter().position(|x| *x == address).unwrap()
+}
+
+fn escape_mermaid(text: &str) -> String {
+    text.replace('#', "#35;")
+        .replace(';', "#59;")
+        .replace('\n', " ")
+}
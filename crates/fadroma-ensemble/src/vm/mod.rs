@@ -0,0 +1,667 @@
+use std::{
+    fmt::Debug, fs, panic, process, thread, vec,
+    path::{Path, PathBuf},
+    sync::{
+        OnceLock, mpsc::{self, Sender},
+        atomic::{AtomicUsize, Ordering as AtomicOrdering}
+    }
+};
+
+use cosmwasm_vm::{
+    Backend, BackendApi, BackendError, BackendResult, Cache, CacheOptions,
+    GasInfo, Instance, InstanceOptions, Size, VmResult, capabilities_from_csv,
+    call_instantiate_raw, call_execute_raw, call_query_raw, call_reply_raw,
+    call_migrate_raw
+};
+use vanilla_cosmwasm_std as vanilla;
+use serde::{Serialize, Deserialize, de::DeserializeOwned};
+use fadroma::cosmwasm_std::{
+    Deps, DepsMut, Env, MessageInfo, Reply, Response, Binary, Storage,
+    Querier, Api, ContractResult, SystemResult, SystemError, Order, Record,
+    SubMsg, CosmosMsg, WasmMsg, QueryRequest, WasmQuery, Empty,
+    from_slice, to_vec, testing::MockApi
+};
+
+use fadroma::prelude::ContractCode;
+
+use super::{
+    AnyResult, ContractHarness, ContractEnsemble, code_hash_of,
+    gas::catch_out_of_gas
+};
+
+pub(crate) mod secret;
+
+/// The amount of gas that a single call into a [`WasmContract`] is allowed to use.
+pub const DEFAULT_GAS_LIMIT: u64 = 500_000_000_000_000;
+
+/// The maximum amount of memory that a [`WasmContract`] instance can allocate.
+pub const DEFAULT_MEMORY_LIMIT: Size = Size::mebi(32);
+
+/// The capabilities that contracts can require, as supported by the ensemble.
+const CAPABILITIES: &str = "iterator,staking";
+
+/// A [`ContractHarness`] implementation that executes a compiled `.wasm` contract
+/// inside an embedded CosmWasm VM. The contract runs against the same storage,
+/// bank and querier that the ensemble provides to every other harness, so
+/// it can freely interact with native Rust contracts and vice versa.
+///
+/// The contract is validated and compiled the first time that it is called
+/// and the compiled module is reused for every call after that.
+///
+/// Both Secret Network and vanilla CosmWasm contracts are supported. Secret
+/// contracts are rewritten before they are compiled to stub out the host functions
+/// that only exist on Secret Network and to convert the results of the queries that
+/// they make to the encoding they expect. `check_gas` and `gas_evaporate` always
+/// succeed while `secp256k1_sign` and `ed25519_sign` always return an error.
+///
+/// The messages and smart queries of vanilla contracts don't include the code hash
+/// of the contract that they target, so it is looked up using the querier and they
+/// are converted to their Secret counterparts before the ensemble handles them.
+///
+/// # Examples
+///
+/// ```ignore
+/// use fadroma_ensemble::{ContractEnsemble, WasmContract, MockEnv};
+///
+/// let mut ensemble = ContractEnsemble::new();
+///
+/// let snip20 = WasmContract::from_file("snip20.wasm").unwrap();
+/// let snip20 = ensemble.register_wasm(snip20);
+/// ```
+pub struct WasmContract {
+    code: Vec<u8>,
+    gas_limit: u64,
+    memory_limit: Size,
+    print_debug: bool,
+    compiled: OnceLock<Result<Compiled, String>>
+}
+
+/// The module of a contract, stored in a cache of its own.
+struct Compiled {
+    cache: Cache<VmApi, VmStorage, VmQuerier>,
+    checksum: vanilla::Checksum,
+    /// Whether this is a Secret Network contract, which was rewritten.
+    secret: bool,
+    // Declared last so that it is removed after the cache is dropped.
+    _dir: CacheDir
+}
+
+/// The directory of a [`Cache`], which is removed when dropped.
+struct CacheDir(PathBuf);
+
+type VmInstance = Instance<VmApi, VmStorage, VmQuerier>;
+
+impl WasmContract {
+    /// Creates a new instance from the bytes of a compiled contract.
+    pub fn new(code: impl Into<Vec<u8>>) -> Self {
+        Self {
+            code: code.into(),
+            gas_limit: DEFAULT_GAS_LIMIT,
+            memory_limit: DEFAULT_MEMORY_LIMIT,
+            print_debug: false,
+            compiled: OnceLock::new()
+        }
+    }
+
+    /// Reads the compiled contract from the given file path.
+    pub fn from_file(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let code = fs::read(path)?;
+
+        Ok(Self::new(code))
+    }
+
+    /// Sets the amount of VM gas that each call into the contract is allowed to use.
+    #[inline]
+    pub fn gas_limit(mut self, limit: u64) -> Self {
+        self.gas_limit = limit;
+
+        self
+    }
+
+    /// Sets the maximum amount of memory that the contract can allocate.
+    #[inline]
+    pub fn memory_limit(mut self, limit: Size) -> Self {
+        self.memory_limit = limit;
+
+        self
+    }
+
+    /// Print any messages that the contract emits using `deps.api.debug`.
+    #[inline]
+    pub fn print_debug(mut self, print: bool) -> Self {
+        self.print_debug = print;
+
+        self
+    }
+
+    /// Returns the bytes of the compiled contract.
+    #[inline]
+    pub fn code(&self) -> &[u8] {
+        &self.code
+    }
+
+    fn compiled(&self) -> AnyResult<&Compiled> {
+        self.compiled
+            .get_or_init(|| self.compile().map_err(|x| x.to_string()))
+            .as_ref()
+            .map_err(|x| anyhow::anyhow!("{x}"))
+    }
+
+    fn compile(&self) -> AnyResult<Compiled> {
+        static NEXT_DIR: AtomicUsize = AtomicUsize::new(0);
+
+        let dir = CacheDir(std::env::temp_dir().join(format!(
+            "fadroma-ensemble-vm-{}-{}",
+            process::id(),
+            NEXT_DIR.fetch_add(1, AtomicOrdering::Relaxed)
+        )));
+
+        let options = CacheOptions::new(
+            &dir.0,
+            capabilities_from_csv(CAPABILITIES),
+            Size::new(0),
+            self.memory_limit
+        );
+
+        // SAFETY: The directory is only used by this cache and is removed
+        // with it, so the modules in it can't have been tampered with.
+        let cache = unsafe { Cache::new(options)? };
+
+        let code = secret::rewrite(&self.code).map_err(anyhow::Error::msg)?;
+
+        // Validates the contract with the same checks as the chain.
+        let checksum = cache.store_code(code.as_deref().unwrap_or(&self.code), true, true)?;
+        cache.pin(&checksum)?;
+
+        Ok(Compiled { cache, checksum, secret: code.is_some(), _dir: dir })
+    }
+
+    /// Calls into the contract on a thread of its own. The VM requires its backend
+    /// to be `'static` while the storage and querier are only borrowed for the duration
+    /// of the call, so the contract sends its storage access and queries back to this
+    /// thread instead, which handles them until the contract returns.
+    fn call(
+        &self,
+        mut storage: HostStorage,
+        querier: &dyn Querier,
+        call: impl FnOnce(&mut VmInstance) -> VmResult<Vec<u8>> + Send
+    ) -> AnyResult<Vec<u8>> {
+        let compiled = self.compiled()?;
+
+        let (host, tasks) = mpsc::channel();
+        let options = InstanceOptions { gas_limit: self.gas_limit };
+        let print_debug = self.print_debug;
+        let vanilla = !compiled.secret;
+
+        thread::scope(|scope| {
+            let vm = scope.spawn(move || {
+                let backend = Backend {
+                    api: VmApi,
+                    storage: VmStorage {
+                        host: Host(host.clone()),
+                        iterators: vec![]
+                    },
+                    querier: VmQuerier { host: Host(host), vanilla }
+                };
+
+                let mut instance = compiled.cache.get_instance(
+                    &compiled.checksum,
+                    backend,
+                    options
+                )?;
+
+                if print_debug {
+                    instance.set_debug_handler(|msg, _| eprintln!("{msg}"));
+                }
+
+                call(&mut instance)
+            });
+
+            // Stops once the instance is dropped at the end of the call.
+            for task in tasks {
+                task(&mut storage, querier);
+            }
+
+            match vm.join() {
+                Ok(result) => Ok(result?),
+                Err(payload) => panic::resume_unwind(payload)
+            }
+        })
+    }
+
+    fn parse_response(&self, result: &[u8], querier: &dyn Querier) -> AnyResult<Response> {
+        if self.compiled()?.secret {
+            return from_wasm_result(result);
+        }
+
+        let response: vanilla::Response = from_wasm_result(result)?;
+
+        from_vanilla_response(response, querier)
+    }
+
+    /// Whether the contract was compiled and how many calls used the compiled module.
+    #[cfg(test)]
+    pub(crate) fn cache_hits(&self) -> Option<u32> {
+        let compiled = self.compiled.get()?.as_ref().ok()?;
+        let stats = compiled.cache.stats();
+
+        assert_eq!(stats.misses, 0);
+
+        Some(stats.hits_pinned_memory_cache)
+    }
+}
+
+impl ContractHarness for WasmContract {
+    fn instantiate(&self, deps: DepsMut, env: Env, info: MessageInfo, msg: Binary) -> AnyResult<Response> {
+        let env = to_vec(&env)?;
+        let info = to_vec(&info)?;
+
+        let result = self.call(HostStorage::Mutable(deps.storage), &*deps.querier, |instance|
+            call_instantiate_raw(instance, &env, &info, msg.as_slice())
+        )?;
+
+        self.parse_response(&result, &*deps.querier)
+    }
+
+    fn execute(&self, deps: DepsMut, env: Env, info: MessageInfo, msg: Binary) -> AnyResult<Response> {
+        let env = to_vec(&env)?;
+        let info = to_vec(&info)?;
+
+        let result = self.call(HostStorage::Mutable(deps.storage), &*deps.querier, |instance|
+            call_execute_raw(instance, &env, &info, msg.as_slice())
+        )?;
+
+        self.parse_response(&result, &*deps.querier)
+    }
+
+    fn query(&self, deps: Deps, env: Env, msg: Binary) -> AnyResult<Binary> {
+        let env = to_vec(&env)?;
+
+        let result = self.call(HostStorage::ReadOnly(deps.storage), &*deps.querier, |instance|
+            call_query_raw(instance, &env, msg.as_slice())
+        )?;
+
+        from_wasm_result(&result)
+    }
+
+    fn reply(&self, deps: DepsMut, env: Env, reply: Reply) -> AnyResult<Response> {
+        let env = to_vec(&env)?;
+        let reply = to_vec(&reply)?;
+
+        let result = self.call(HostStorage::Mutable(deps.storage), &*deps.querier, |instance|
+            call_reply_raw(instance, &env, &reply)
+        )?;
+
+        self.parse_response(&result, &*deps.querier)
+    }
+
+    fn migrate(&self, deps: DepsMut, env: Env, msg: Binary) -> AnyResult<Response> {
+        let env = to_vec(&env)?;
+
+        let result = self.call(HostStorage::Mutable(deps.storage), &*deps.querier, |instance|
+            call_migrate_raw(instance, &env, msg.as_slice())
+        )?;
+
+        self.parse_response(&result, &*deps.querier)
+    }
+}
+
+impl Debug for WasmContract {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WasmContract")
+            .field("code_len", &self.code.len())
+            .field("gas_limit", &self.gas_limit)
+            .field("memory_limit", &self.memory_limit)
+            .finish()
+    }
+}
+
+impl Drop for CacheDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+impl ContractEnsemble {
+    /// Registers a compiled contract, using the SHA-256 hash of its
+    /// code as the code hash, the same as on Secret Network.
+    pub fn register_wasm(&mut self, contract: WasmContract) -> ContractCode {
+        let code_hash = code_hash_of(contract.code());
+
+        self.register_impl(Box::new(contract), code_hash)
+    }
+}
+
+/// Secret contracts serialize their result as `{"Ok":..}`/`{"Err":..}` while
+/// vanilla CosmWasm ones use `{"ok":..}`/`{"error":..}`. Accept both.
+#[derive(Deserialize)]
+enum WasmResult<T> {
+    #[serde(alias = "ok")]
+    Ok(T),
+    #[serde(alias = "error")]
+    Err(String)
+}
+
+#[inline]
+fn from_wasm_result<T: DeserializeOwned>(result: &[u8]) -> AnyResult<T> {
+    match from_slice(result)? {
+        WasmResult::Ok(result) => Ok(result),
+        WasmResult::Err(err) => Err(anyhow::anyhow!(err))
+    }
+}
+
+/// Vanilla Wasm messages don't include the code hash of the contract that
+/// they target and name the funds that they send differently. The rest of
+/// the response is encoded the same as in Secret Network.
+fn from_vanilla_response(response: vanilla::Response, querier: &dyn Querier) -> AnyResult<Response> {
+    let messages = response.messages
+        .into_iter()
+        .map(|sub_msg| Ok(SubMsg {
+            id: sub_msg.id,
+            msg: from_vanilla_msg(sub_msg.msg, querier)?,
+            gas_limit: sub_msg.gas_limit,
+            reply_on: convert(&sub_msg.reply_on)?
+        }))
+        .collect::<AnyResult<Vec<_>>>()?;
+
+    let mut result = Response::new().add_submessages(messages);
+    result.attributes = convert(&response.attributes)?;
+    result.events = convert(&response.events)?;
+    result.data = response.data.map(|x| x.to_vec().into());
+
+    Ok(result)
+}
+
+fn from_vanilla_msg(msg: vanilla::CosmosMsg, querier: &dyn Querier) -> AnyResult<CosmosMsg> {
+    let msg = match msg {
+        vanilla::CosmosMsg::Wasm(vanilla::WasmMsg::Execute { contract_addr, msg, funds }) => WasmMsg::Execute {
+            code_hash: query_code_hash(querier, CodeHashQuery::ContractInfo { contract_addr: &contract_addr }),
+            contract_addr,
+            msg: msg.to_vec().into(),
+            funds: convert(&funds)?
+        },
+        vanilla::CosmosMsg::Wasm(vanilla::WasmMsg::Instantiate { admin, code_id, msg, funds, label }) => WasmMsg::Instantiate {
+            admin,
+            code_id,
+            code_hash: query_code_hash(querier, CodeHashQuery::CodeInfo { code_id }),
+            msg: msg.to_vec().into(),
+            funds: convert(&funds)?,
+            label
+        },
+        vanilla::CosmosMsg::Wasm(vanilla::WasmMsg::Migrate { contract_addr, new_code_id, msg }) => WasmMsg::Migrate {
+            contract_addr,
+            code_hash: query_code_hash(querier, CodeHashQuery::CodeInfo { code_id: new_code_id }),
+            code_id: new_code_id,
+            msg: msg.to_vec().into()
+        },
+        msg => return convert(&msg)
+    };
+
+    Ok(msg.into())
+}
+
+/// Vanilla smart queries don't include the code hash of the contract either.
+/// Every other query is passed to the ensemble as is.
+fn from_vanilla_query(request: Vec<u8>, querier: &dyn Querier) -> Vec<u8> {
+    let Ok(vanilla::QueryRequest::Wasm(vanilla::WasmQuery::Smart { contract_addr, msg })) =
+        vanilla::from_json::<vanilla::QueryRequest>(&request) else {
+        return request;
+    };
+
+    let query: QueryRequest<Empty> = WasmQuery::Smart {
+        code_hash: query_code_hash(querier, CodeHashQuery::ContractInfo { contract_addr: &contract_addr }),
+        contract_addr,
+        msg: msg.to_vec().into()
+    }.into();
+
+    to_vec(&query).unwrap_or(request)
+}
+
+/// Converts between types that are encoded the same way in both vanilla CosmWasm and Secret Network.
+#[inline]
+fn convert<T: Serialize, U: DeserializeOwned>(value: &T) -> AnyResult<U> {
+    Ok(from_slice(&to_vec(value)?)?)
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+enum CodeHashRequest<'a> {
+    Wasm(CodeHashQuery<'a>)
+}
+
+/// The ensemble's querier includes the code hash in the response to `ContractInfo`
+/// and answers vanilla `CodeInfo` queries, which return it as the checksum.
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+enum CodeHashQuery<'a> {
+    ContractInfo { contract_addr: &'a str },
+    CodeInfo { code_id: u64 }
+}
+
+#[derive(Deserialize)]
+struct CodeHash {
+    #[serde(alias = "checksum")]
+    code_hash: String
+}
+
+/// Returns an empty code hash if the contract or code doesn't exist,
+/// such as for mocks, so that the ensemble reports the actual error
+/// when it handles the message.
+fn query_code_hash(querier: &dyn Querier, query: CodeHashQuery) -> String {
+    to_vec(&CodeHashRequest::Wasm(query))
+        .ok()
+        .and_then(|request| match querier.raw_query(&request) {
+            SystemResult::Ok(ContractResult::Ok(result)) => from_slice::<CodeHash>(&result).ok(),
+            _ => None
+        })
+        .map(|x| x.code_hash)
+        .unwrap_or_default()
+}
+
+/// The storage of the contract being called, which stays on the ensemble's thread.
+enum HostStorage<'a> {
+    ReadOnly(&'a dyn Storage),
+    Mutable(&'a mut dyn Storage)
+}
+
+/// Work that the contract's thread sends to the ensemble's thread.
+type HostTask = Box<dyn FnOnce(&mut HostStorage, &dyn Querier) + Send>;
+
+/// Runs tasks on the ensemble's thread, see [`WasmContract::call`].
+struct Host(Sender<HostTask>);
+
+struct VmStorage {
+    host: Host,
+    iterators: Vec<vec::IntoIter<Record>>
+}
+
+struct VmQuerier {
+    host: Host,
+    /// Whether the queries of the contract need to be converted, see [`from_vanilla_query`].
+    vanilla: bool
+}
+
+#[derive(Clone, Copy)]
+struct VmApi;
+
+impl HostStorage<'_> {
+    #[inline]
+    fn storage(&self) -> &dyn Storage {
+        match self {
+            Self::ReadOnly(storage) => *storage,
+            Self::Mutable(storage) => *storage
+        }
+    }
+
+    #[inline]
+    fn storage_mut(&mut self) -> Result<&mut dyn Storage, BackendError> {
+        match self {
+            Self::ReadOnly(_) => Err(BackendError::user_err("Storage is read-only in queries.")),
+            Self::Mutable(storage) => Ok(*storage)
+        }
+    }
+}
+
+impl Host {
+    /// Runs the task on the ensemble's thread and waits for its result.
+    fn run<T: Send + 'static>(
+        &self,
+        task: impl FnOnce(&mut HostStorage, &dyn Querier) -> Result<T, BackendError> + Send + 'static
+    ) -> Result<T, BackendError> {
+        let (sender, result) = mpsc::sync_channel(1);
+
+        let task: HostTask = Box::new(move |storage, querier| {
+            // Running out of the ensemble's gas can't unwind through the VM, so the
+            // contract is aborted with an error which the ensemble then reports as such.
+            let result = catch_out_of_gas(|| task(storage, querier))
+                .unwrap_or_else(|| Err(BackendError::out_of_gas()));
+
+            let _ = sender.send(result);
+        });
+
+        // Only fails if the ensemble's thread panicked while handling a previous task.
+        self.0.send(task)
+            .ok()
+            .and_then(|_| result.recv().ok())
+            .unwrap_or_else(|| Err(BackendError::unknown("Ensemble: the contract's host has stopped.")))
+    }
+}
+
+impl cosmwasm_vm::Storage for VmStorage {
+    fn get(&self, key: &[u8]) -> BackendResult<Option<Vec<u8>>> {
+        let key = key.to_vec();
+        let result = self.host.run(move |storage, _| Ok(storage.storage().get(&key)));
+
+        (result, GasInfo::free())
+    }
+
+    fn scan(
+        &mut self,
+        start: Option<&[u8]>,
+        end: Option<&[u8]>,
+        order: vanilla::Order
+    ) -> BackendResult<u32> {
+        let order = match order {
+            vanilla::Order::Ascending => Order::Ascending,
+            vanilla::Order::Descending => Order::Descending
+        };
+
+        let start = start.map(|x| x.to_vec());
+        let end = end.map(|x| x.to_vec());
+
+        let result = self.host.run(move |storage, _| {
+            let records: Vec<Record> = storage.storage()
+                .range(start.as_deref(), end.as_deref(), order)
+                .collect();
+
+            Ok(records)
+        }).map(|records| {
+            self.iterators.push(records.into_iter());
+
+            self.iterators.len() as u32 - 1
+        });
+
+        (result, GasInfo::free())
+    }
+
+    fn next(&mut self, iterator_id: u32) -> BackendResult<Option<vanilla::Record>> {
+        let result = match self.iterators.get_mut(iterator_id as usize) {
+            Some(iter) => Ok(iter.next()),
+            None => Err(BackendError::iterator_does_not_exist(iterator_id))
+        };
+
+        (result, GasInfo::free())
+    }
+
+    fn set(&mut self, key: &[u8], value: &[u8]) -> BackendResult<()> {
+        let (key, value) = (key.to_vec(), value.to_vec());
+        let result = self.host.run(move |storage, _| {
+            storage.storage_mut().map(|x| x.set(&key, &value))
+        });
+
+        (result, GasInfo::free())
+    }
+
+    fn remove(&mut self, key: &[u8]) -> BackendResult<()> {
+        let key = key.to_vec();
+        let result = self.host.run(move |storage, _| {
+            storage.storage_mut().map(|x| x.remove(&key))
+        });
+
+        (result, GasInfo::free())
+    }
+}
+
+impl cosmwasm_vm::Querier for VmQuerier {
+    fn query_raw(
+        &self,
+        request: &[u8],
+        _gas_limit: u64
+    ) -> BackendResult<vanilla::SystemResult<vanilla::ContractResult<vanilla::Binary>>> {
+        let request = request.to_vec();
+        let vanilla = self.vanilla;
+
+        let result = self.host
+            .run(move |_, querier| {
+                let request = if vanilla {
+                    from_vanilla_query(request, querier)
+                } else {
+                    request
+                };
+
+                Ok(querier.raw_query(&request))
+            })
+            .map(|result| match result {
+                SystemResult::Ok(ContractResult::Ok(result)) =>
+                    vanilla::SystemResult::Ok(vanilla::ContractResult::Ok(result.0.into())),
+                SystemResult::Ok(ContractResult::Err(err)) =>
+                    vanilla::SystemResult::Ok(vanilla::ContractResult::Err(err)),
+                SystemResult::Err(err) => vanilla::SystemResult::Err(into_system_error(err))
+            });
+
+        (result, GasInfo::free())
+    }
+}
+
+fn into_system_error(err: SystemError) -> vanilla::SystemError {
+    match err {
+        SystemError::InvalidRequest { error, request } =>
+            vanilla::SystemError::InvalidRequest { error, request: request.0.into() },
+        SystemError::InvalidResponse { error, response } =>
+            vanilla::SystemError::InvalidResponse { error, response: response.0.into() },
+        SystemError::NoSuchContract { addr } =>
+            vanilla::SystemError::NoSuchContract { addr },
+        SystemError::UnsupportedRequest { kind } =>
+            vanilla::SystemError::UnsupportedRequest { kind },
+        _ => vanilla::SystemError::Unknown {}
+    }
+}
+
+impl BackendApi for VmApi {
+    fn addr_validate(&self, input: &str) -> BackendResult<()> {
+        let result = MockApi::default()
+            .addr_validate(input)
+            .map(|_| ())
+            .map_err(|x| BackendError::user_err(x.to_string()));
+
+        (result, GasInfo::free())
+    }
+
+    fn addr_canonicalize(&self, human: &str) -> BackendResult<Vec<u8>> {
+        let result = MockApi::default()
+            .addr_canonicalize(human)
+            .map(|x| x.0.0)
+            .map_err(|x| BackendError::user_err(x.to_string()));
+
+        (result, GasInfo::free())
+    }
+
+    fn addr_humanize(&self, canonical: &[u8]) -> BackendResult<String> {
+        let result = MockApi::default()
+            .addr_humanize(&Binary::from(canonical).into())
+            .map(|x| x.into_string())
+            .map_err(|x| BackendError::user_err(x.to_string()));
+
+        (result, GasInfo::free())
+    }
+}
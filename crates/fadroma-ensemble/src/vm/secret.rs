@@ -0,0 +1,249 @@
+//! Secret Network contracts import host functions which the standard
+//! VM doesn't provide and expect the results of the queries that they
+//! make to be encoded differently. Since the public API of the VM can't
+//! be extended with more imports, the contract is rewritten before it is
+//! compiled so that it implements these differences itself.
+
+use std::collections::HashSet;
+
+use wasm_encoder::{
+    reencode::{self, Reencode},
+    CodeSection, Function, FunctionSection, ImportSection, Instruction, MemArg, Module
+};
+use wasmparser::{Parser, Payload, TypeRef};
+
+/// Host functions that only exist on Secret Network.
+const SECRET_IMPORTS: &[&str] = &["secp256k1_sign", "ed25519_sign", "check_gas", "gas_evaporate"];
+
+/// The error code is in the high half of the result. Anything other
+/// than 0 (success) or 1000 (invalid key) is reported as unknown.
+const SIGNING_UNSUPPORTED: i64 = 1 << 32;
+
+/// Replaces a `"error"` key, the first 8 bytes of `{"error":`, with the
+/// `"Err"` one that Secret contracts expect, padded with whitespace.
+const ERR_KEY: i64 = i64::from_le_bytes(*b"{  \"Err\"");
+
+/// Rewrites the given Secret contract so that it can run in the standard VM.
+/// Returns [`None`] if the contract doesn't need to be rewritten because it
+/// doesn't import any of the host functions that are specific to Secret Network.
+pub(crate) fn rewrite(code: &[u8]) -> Result<Option<Vec<u8>>, String> {
+    let Some(mut rewriter) = Rewriter::new(code).map_err(|x| x.to_string())? else {
+        return Ok(None);
+    };
+
+    let mut module = Module::new();
+    rewriter.parse_core_module(&mut module, Parser::new(0), code)
+        .map_err(|x| x.to_string())?;
+
+    Ok(Some(module.finish()))
+}
+
+/// An imported function that is implemented by the contract after the rewrite.
+struct Import {
+    name: &'static str,
+    index: u32,
+    ty: u32
+}
+
+struct Rewriter {
+    /// The number of functions imported by the original contract.
+    imported: u32,
+    /// The number of functions defined by the original contract.
+    defined: u32,
+    /// The Secret specific imports which are replaced with stubs.
+    stubs: Vec<Import>,
+    /// The `query_chain` import whose results need to be converted.
+    query_chain: Option<Import>
+}
+
+impl Rewriter {
+    fn new(code: &[u8]) -> wasmparser::Result<Option<Self>> {
+        let mut rewriter = Self {
+            imported: 0,
+            defined: 0,
+            stubs: vec![],
+            query_chain: None
+        };
+
+        for payload in Parser::new(0).parse_all(code) {
+            match payload? {
+                Payload::ImportSection(section) => for import in section.into_imports() {
+                    let import = import?;
+
+                    let TypeRef::Func(ty) = import.ty else {
+                        continue;
+                    };
+
+                    let index = rewriter.imported;
+                    rewriter.imported += 1;
+
+                    if import.module != "env" {
+                        continue;
+                    }
+
+                    if let Some(name) = SECRET_IMPORTS.iter().find(|x| **x == import.name) {
+                        rewriter.stubs.push(Import { name, index, ty });
+                    } else if import.name == "query_chain" {
+                        rewriter.query_chain = Some(Import { name: "query_chain", index, ty });
+                    }
+                },
+                Payload::FunctionSection(section) => rewriter.defined = section.count(),
+                _ => {}
+            }
+        }
+
+        Ok((!rewriter.stubs.is_empty()).then_some(rewriter))
+    }
+
+    /// The index of an import that is kept, after the stubbed ones are removed.
+    fn kept_import_index(&self, index: u32) -> u32 {
+        index - self.stubs.iter().filter(|x| x.index < index).count() as u32
+    }
+
+    /// The index of the first function that is added by the rewrite.
+    fn added_index(&self) -> u32 {
+        self.imported - self.stubs.len() as u32 + self.defined
+    }
+
+    fn stub(&self, import: &Import) -> Function {
+        let mut func = Function::new([]);
+
+        func.instruction(&match import.name {
+            "secp256k1_sign" | "ed25519_sign" => Instruction::I64Const(SIGNING_UNSUPPORTED),
+            // Returning 0 here is treated as an error by the contract.
+            "check_gas" => Instruction::I64Const(1),
+            "gas_evaporate" => Instruction::I32Const(0),
+            _ => unreachable!()
+        });
+        func.instruction(&Instruction::End);
+
+        func
+    }
+
+    /// Calls the real `query_chain` and converts the JSON encoded result from
+    /// `{"ok":{"ok":..}}`, `{"ok":{"error":..}}` or `{"error":..}` to the
+    /// capitalized `Ok` and `Err` keys in place.
+    fn query_chain(&self, import: &Import) -> Function {
+        const DATA: u32 = 1;
+
+        let load = |offset| MemArg { offset, align: 0, memory_index: 0 };
+        let mut func = Function::new([(1, wasm_encoder::ValType::I32)]);
+
+        for instruction in [
+            Instruction::LocalGet(0),
+            Instruction::Call(self.kept_import_index(import.index)),
+            Instruction::LocalTee(0),
+            // The result is a region whose first field is the offset of the data.
+            Instruction::I32Load(MemArg { offset: 0, align: 2, memory_index: 0 }),
+            Instruction::LocalSet(DATA),
+
+            Instruction::LocalGet(DATA),
+            Instruction::I32Load8U(load(2)),
+            Instruction::I32Const(b'e' as i32),
+            Instruction::I32Eq,
+            Instruction::If(wasm_encoder::BlockType::Empty),
+                Instruction::LocalGet(DATA),
+                Instruction::I64Const(ERR_KEY),
+                Instruction::I64Store(load(0)),
+            Instruction::Else,
+                Instruction::LocalGet(DATA),
+                Instruction::I32Const(b'O' as i32),
+                Instruction::I32Store8(load(2)),
+
+                Instruction::LocalGet(DATA),
+                Instruction::I32Load8U(load(8)),
+                Instruction::I32Const(b'e' as i32),
+                Instruction::I32Eq,
+                Instruction::If(wasm_encoder::BlockType::Empty),
+                    Instruction::LocalGet(DATA),
+                    Instruction::I64Const(ERR_KEY),
+                    Instruction::I64Store(load(6)),
+                Instruction::Else,
+                    Instruction::LocalGet(DATA),
+                    Instruction::I32Const(b'O' as i32),
+                    Instruction::I32Store8(load(8)),
+                Instruction::End,
+            Instruction::End,
+
+            Instruction::LocalGet(0),
+            Instruction::End
+        ] {
+            func.instruction(&instruction);
+        }
+
+        func
+    }
+}
+
+impl Reencode for Rewriter {
+    type Error = std::convert::Infallible;
+
+    fn function_index(&mut self, func: u32) -> Result<u32, reencode::Error<Self::Error>> {
+        if func >= self.imported {
+            return Ok(func - self.stubs.len() as u32);
+        }
+
+        if let Some(i) = self.stubs.iter().position(|x| x.index == func) {
+            return Ok(self.added_index() + i as u32);
+        }
+
+        // Calls to query_chain go through the function which converts the result.
+        if self.query_chain.as_ref().is_some_and(|x| x.index == func) {
+            return Ok(self.added_index() + self.stubs.len() as u32);
+        }
+
+        Ok(self.kept_import_index(func))
+    }
+
+    fn parse_import_section(
+        &mut self,
+        imports: &mut ImportSection,
+        section: wasmparser::ImportSectionReader<'_>
+    ) -> Result<(), reencode::Error<Self::Error>> {
+        let stubs: HashSet<&str> = self.stubs.iter().map(|x| x.name).collect();
+
+        for import in section.into_imports() {
+            let import = import?;
+
+            if import.module == "env" && stubs.contains(import.name) {
+                continue;
+            }
+
+            self.parse_import(imports, import)?;
+        }
+
+        Ok(())
+    }
+
+    fn parse_function_section(
+        &mut self,
+        functions: &mut FunctionSection,
+        section: wasmparser::FunctionSectionReader<'_>
+    ) -> Result<(), reencode::Error<Self::Error>> {
+        reencode::utils::parse_function_section(self, functions, section)?;
+
+        for import in self.stubs.iter().chain(&self.query_chain) {
+            functions.function(import.ty);
+        }
+
+        Ok(())
+    }
+
+    fn parse_code_section(
+        &mut self,
+        code: &mut CodeSection,
+        section: wasmparser::CodeSectionReader<'_>
+    ) -> Result<(), reencode::Error<Self::Error>> {
+        reencode::utils::parse_code_section(self, code, section)?;
+
+        for import in &self.stubs {
+            code.function(&self.stub(import));
+        }
+
+        if let Some(import) = &self.query_chain {
+            code.function(&self.query_chain(import));
+        }
+
+        Ok(())
+    }
+}
@@ -6,6 +6,7 @@ pub use fadroma_proc_auth::*;
 use serde::{Serialize, Deserialize};
 
 use crate::{
+    self as fadroma,
     dsl::*,
     core::Canonize,
     storage::SingleItem,
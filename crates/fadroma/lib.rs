@@ -18,6 +18,7 @@ pub mod core;
 pub mod scrt;
 
 pub mod tokens;
+pub mod schema;
 pub use fadroma_dsl as dsl;
 pub mod killswitch;
 pub mod admin;
@@ -0,0 +1,30 @@
+//! Description of a contract's API in the
+//! [`cosmwasm-schema`](https://docs.rs/cosmwasm-schema) IDL format.
+//! Fadroma DSL generates an `api_schema` function which returns it for each `#[contract]`.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::schemars::schema::RootSchema;
+
+/// Version of the IDL format that [`ContractApi`] follows.
+pub const IDL_VERSION: &str = "1.0.0";
+
+/// The combined JSON schema of all messages that a contract accepts
+/// and the responses to each of its queries. Serializing it as JSON
+/// produces the same document as the `api.json` file generated by
+/// `cosmwasm-schema` and can be consumed by the same tooling.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct ContractApi {
+    pub contract_name: String,
+    pub contract_version: String,
+    pub idl_version: String,
+    pub instantiate: RootSchema,
+    pub execute: Option<RootSchema>,
+    pub query: Option<RootSchema>,
+    pub migrate: Option<RootSchema>,
+    pub sudo: Option<RootSchema>,
+    /// The response schema of each query, keyed by the name of the query message.
+    pub responses: Option<BTreeMap<String, RootSchema>>
+}
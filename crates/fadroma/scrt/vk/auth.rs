@@ -4,6 +4,7 @@
 use serde::{Deserialize, Serialize};
 
 use crate::{
+    self as fadroma,
     dsl::*,
     core::Canonize,
     storage::{ItemSpace, TypedKey},